@@ -0,0 +1,179 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use crate::{color::Palette, config::CloudConfig, prompt::Segment};
+
+/// A cloud account the shell's commands would act on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloudAccount {
+    /// `aws` or `gcp`
+    pub provider: &'static str,
+    /// The AWS profile or GCP project
+    pub name: String,
+    pub region: Option<String>,
+}
+
+impl CloudAccount {
+    /// Whether the name contains one of the production markers, ignoring case
+    pub fn is_production(&self, markers: &[String]) -> bool {
+        let name = self.name.to_lowercase();
+        markers
+            .iter()
+            .any(|marker| !marker.is_empty() && name.contains(&marker.to_lowercase()))
+    }
+
+    fn label(&self) -> String {
+        match &self.region {
+            Some(region) => format!("{}:{}({})", self.provider, self.name, region),
+            None => format!("{}:{}", self.provider, self.name),
+        }
+    }
+}
+
+/// The accounts selected by the shell's variables: the AWS profile, and the
+/// GCP project from `$CLOUDSDK_CORE_PROJECT` or gcloud's active configuration
+pub fn accounts(vars: &HashMap<String, String>) -> Vec<CloudAccount> {
+    let var = |name: &str| vars.get(name).filter(|value| !value.is_empty()).cloned();
+    let mut accounts = Vec::new();
+
+    if let Some(profile) = var("AWS_PROFILE").or_else(|| var("AWS_VAULT")) {
+        accounts.push(CloudAccount {
+            provider: "aws",
+            name: profile,
+            region: var("AWS_REGION").or_else(|| var("AWS_DEFAULT_REGION")),
+        });
+    }
+
+    let project = var("CLOUDSDK_CORE_PROJECT").or_else(|| gcloud_project(vars));
+    if let Some(project) = project {
+        accounts.push(CloudAccount {
+            provider: "gcp",
+            name: project,
+            region: var("CLOUDSDK_COMPUTE_REGION"),
+        });
+    }
+    accounts
+}
+
+/// The prompt segments for the active accounts, in the production color where it applies
+pub fn segments(
+    vars: &HashMap<String, String>,
+    config: &CloudConfig,
+    palette: &Palette,
+) -> Vec<Segment> {
+    if !config.show {
+        return Vec::new();
+    }
+    accounts(vars)
+        .into_iter()
+        .map(|account| {
+            let color = if account.is_production(&config.production) {
+                palette.cloud_production
+            } else {
+                palette.cloud
+            };
+            Segment::colored(account.label(), color)
+        })
+        .collect()
+}
+
+/// The `project` under `[core]` in gcloud's active configuration
+fn gcloud_project(vars: &HashMap<String, String>) -> Option<String> {
+    let dir = match vars.get("CLOUDSDK_CONFIG") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(vars.get("HOME")?)
+            .join(".config")
+            .join("gcloud"),
+    };
+    let active = match vars.get("CLOUDSDK_ACTIVE_CONFIG_NAME") {
+        Some(name) => name.clone(),
+        None => fs::read_to_string(dir.join("active_config"))
+            .ok()?
+            .trim()
+            .to_string(),
+    };
+    let config = fs::read_to_string(
+        dir.join("configurations")
+            .join(format!("config_{}", active)),
+    )
+    .ok()?;
+
+    let mut in_core = false;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_core = line == "[core]";
+        } else if let Some((key, value)) = line.split_once('=') {
+            if in_core && key.trim() == "project" {
+                return Some(value.trim().to_string()).filter(|p| !p.is_empty());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_aws_profile_and_region() {
+        let accounts = accounts(&vars(&[
+            ("AWS_PROFILE", "prod-admin"),
+            ("AWS_DEFAULT_REGION", "eu-west-1"),
+        ]));
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].label(), "aws:prod-admin(eu-west-1)");
+        assert!(accounts[0].is_production(&CloudConfig::default().production));
+
+        let accounts = super::accounts(&vars(&[("AWS_REGION", "us-east-1")]));
+        assert!(accounts.is_empty());
+    }
+
+    #[test]
+    fn test_gcloud_active_configuration() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("configurations")).unwrap();
+        fs::write(dir.path().join("active_config"), "work\n").unwrap();
+        fs::write(
+            dir.path().join("configurations").join("config_work"),
+            "[compute]\nproject = wrong\n\n[core]\naccount = me@example.com\nproject = staging-42\n",
+        )
+        .unwrap();
+
+        let config_dir = dir.path().to_str().unwrap();
+        let accounts = accounts(&vars(&[("CLOUDSDK_CONFIG", config_dir)]));
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].label(), "gcp:staging-42");
+        assert!(!accounts[0].is_production(&CloudConfig::default().production));
+
+        let accounts = super::accounts(&vars(&[
+            ("CLOUDSDK_CONFIG", config_dir),
+            ("CLOUDSDK_CORE_PROJECT", "shop-live"),
+        ]));
+        assert_eq!(accounts[0].name, "shop-live");
+    }
+
+    #[test]
+    fn test_segments_use_production_color() {
+        let palette = Palette::default();
+        let config = CloudConfig::default();
+        let production = segments(&vars(&[("AWS_PROFILE", "Production")]), &config, &palette);
+        assert_eq!(
+            production,
+            vec![Segment::colored("aws:Production", palette.cloud_production)]
+        );
+
+        let hidden = CloudConfig {
+            show: false,
+            ..CloudConfig::default()
+        };
+        assert!(segments(&vars(&[("AWS_PROFILE", "dev")]), &hidden, &palette).is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal},
+    str::FromStr,
+};
+
+use crate::core::error::ShellError;
+
+mod palette;
+mod terminal;
+mod theme;
+
+pub use palette::{Color, Palette};
+pub use terminal::ColorDepth;
+pub use theme::Themes;
+
+/// When to emit color, as set by `--color=WHEN` or the `color` config key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Decides whether output to stdout should be colored.
+    ///
+    /// `always` and `never` win outright. `auto` honours `CLICOLOR_FORCE`,
+    /// `NO_COLOR` and `CLICOLOR=0` from the shell's variables, in that order,
+    /// and otherwise colors only when stdout is a terminal.
+    pub fn enabled(self, vars: &HashMap<String, String>) -> bool {
+        self.resolve(vars, io::stdout().is_terminal())
+    }
+
+    /// Makes everything printed until the next call follow this choice
+    pub fn apply(self, vars: &HashMap<String, String>) {
+        colored::control::set_override(self.enabled(vars));
+    }
+
+    fn resolve(self, vars: &HashMap<String, String>, is_terminal: bool) -> bool {
+        let var = |name: &str| vars.get(name).map(String::as_str).unwrap_or_default();
+
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto if !var("CLICOLOR_FORCE").is_empty() && var("CLICOLOR_FORCE") != "0" => true,
+            Self::Auto if !var("NO_COLOR").is_empty() => false,
+            Self::Auto if var("CLICOLOR") == "0" => false,
+            Self::Auto => is_terminal,
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = ShellError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(ShellError::Builtin {
+                message: format!("invalid color choice '{}' (use auto, always or never)", s),
+                code: 2,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_explicit_choice_wins() {
+        let no_color = vars(&[("NO_COLOR", "1")]);
+        assert!(ColorChoice::Always.resolve(&no_color, false));
+        assert!(!ColorChoice::Never.resolve(&vars(&[]), true));
+    }
+
+    #[test]
+    fn test_auto_follows_environment_then_terminal() {
+        assert!(ColorChoice::Auto.resolve(&vars(&[]), true));
+        assert!(!ColorChoice::Auto.resolve(&vars(&[]), false));
+        assert!(!ColorChoice::Auto.resolve(&vars(&[("NO_COLOR", "1")]), true));
+        assert!(ColorChoice::Auto.resolve(&vars(&[("NO_COLOR", "")]), true));
+        assert!(!ColorChoice::Auto.resolve(&vars(&[("CLICOLOR", "0")]), true));
+        assert!(
+            ColorChoice::Auto.resolve(&vars(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")]), false)
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("never".parse::<ColorChoice>().unwrap(), ColorChoice::Never);
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+    }
+}
@@ -0,0 +1,350 @@
+use serde::{Deserialize, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+use super::terminal::ColorDepth;
+
+/// A color as the palette specifies it.
+///
+/// 256-color and RGB values are drawn as the closest color the terminal can
+/// show, so a palette written for truecolor still looks right on a basic one.
+/// In the config a color is a name (`red`, `bright-blue`), a 256-color index
+/// (`208`) or a hex RGB value (`#268bd2`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    Fixed(u8),
+    Rgb(u8, u8, u8),
+}
+
+const BASIC: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::White,
+    Color::BrightBlack,
+    Color::BrightRed,
+    Color::BrightGreen,
+    Color::BrightYellow,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+    Color::BrightWhite,
+];
+
+/// xterm's defaults for the basic colors, used to find the nearest one
+const BASIC_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Channel values of the 6x6x6 cube in the 256-color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Color {
+    /// Wraps `text` in this color, unless the color policy turned color off
+    pub fn paint(self, text: &str) -> String {
+        if !colored::control::SHOULD_COLORIZE.should_colorize() {
+            return text.to_string();
+        }
+        format!("\x1b[{}m{}\x1b[0m", self.sgr(ColorDepth::current()), text)
+    }
+
+    /// The SGR parameters selecting this color as the foreground
+    fn sgr(self, depth: ColorDepth) -> String {
+        match self.degrade(depth) {
+            Self::Fixed(index) => format!("38;5;{}", index),
+            Self::Rgb(r, g, b) => format!("38;2;{};{};{}", r, g, b),
+            basic => {
+                let index = BASIC.iter().position(|&c| c == basic).unwrap_or(7);
+                match index {
+                    0..=7 => (30 + index).to_string(),
+                    _ => (90 + index - 8).to_string(),
+                }
+            }
+        }
+    }
+
+    /// The closest color a terminal with `depth` can show
+    fn degrade(self, depth: ColorDepth) -> Self {
+        match (self, depth) {
+            (Self::Rgb(r, g, b), ColorDepth::Ansi256) => Self::Fixed(nearest_fixed((r, g, b))),
+            (Self::Rgb(r, g, b), ColorDepth::Basic) => BASIC[nearest_basic((r, g, b))],
+            (Self::Fixed(index), ColorDepth::Basic) if index < 16 => BASIC[index as usize],
+            (Self::Fixed(index), ColorDepth::Basic) => {
+                let (r, g, b) = fixed_to_rgb(index);
+                Self::Rgb(r, g, b).degrade(depth)
+            }
+            _ => self,
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const NAMES: [&str; 8] = [
+            "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+        ];
+
+        if let Some(hex) = s.strip_prefix('#') {
+            let channel = |i: usize| {
+                hex.get(i..i + 2)
+                    .and_then(|c| u8::from_str_radix(c, 16).ok())
+            };
+            return match (hex.len(), channel(0), channel(2), channel(4)) {
+                (6, Some(r), Some(g), Some(b)) => Ok(Self::Rgb(r, g, b)),
+                _ => Err(format!("invalid hex color '{}'", s)),
+            };
+        }
+        if let Ok(index) = s.parse::<u8>() {
+            return Ok(Self::Fixed(index));
+        }
+
+        let name = s.to_ascii_lowercase().replace(['-', '_'], "");
+        let (bright, base) = match name.strip_prefix("bright") {
+            Some(base) => (true, base),
+            None => (false, name.as_str()),
+        };
+        NAMES
+            .iter()
+            .position(|&n| n == base)
+            .map(|i| BASIC[if bright { i + 8 } else { i }])
+            .ok_or_else(|| format!("unknown color '{}'", s))
+    }
+}
+
+/// Writes the color the way the config spells it, so it parses back to itself
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const NAMES: [&str; 16] = [
+            "black",
+            "red",
+            "green",
+            "yellow",
+            "blue",
+            "magenta",
+            "cyan",
+            "white",
+            "bright-black",
+            "bright-red",
+            "bright-green",
+            "bright-yellow",
+            "bright-blue",
+            "bright-magenta",
+            "bright-cyan",
+            "bright-white",
+        ];
+
+        match self {
+            Self::Fixed(index) => write!(f, "{}", index),
+            Self::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            basic => {
+                let index = BASIC.iter().position(|c| c == basic).unwrap_or(7);
+                f.write_str(NAMES[index])
+            }
+        }
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn nearest_basic(rgb: (u8, u8, u8)) -> usize {
+    (0..BASIC_RGB.len())
+        .min_by_key(|&i| distance(rgb, BASIC_RGB[i]))
+        .unwrap_or(0)
+}
+
+/// Picks the closer of the nearest color cube entry and the nearest gray
+fn nearest_fixed(rgb: (u8, u8, u8)) -> u8 {
+    let level = |v: u8| match v {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (v - 35) / 40,
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = 16 + 36 * r + 6 * g + b;
+
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+
+    if distance(rgb, fixed_to_rgb(gray)) < distance(rgb, fixed_to_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+fn fixed_to_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => BASIC_RGB[index as usize],
+        16..=231 => {
+            let i = index - 16;
+            (
+                CUBE_LEVELS[(i / 36) as usize],
+                CUBE_LEVELS[(i / 6 % 6) as usize],
+                CUBE_LEVELS[(i % 6) as usize],
+            )
+        }
+        _ => {
+            let gray = 8 + 10 * (index - 232);
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// The color of each part of the shell's own output.
+///
+/// Themes in the config may leave out any of these to keep the default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Palette {
+    pub user: Color,
+    pub host: Color,
+    pub cwd: Color,
+    pub git_branch: Color,
+    pub git_status: Color,
+    pub directory: Color,
+    pub executable: Color,
+    pub error: Color,
+    /// The cloud account segment, and its color when the account looks like production
+    pub cloud: Color,
+    pub cloud_production: Color,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            user: Color::BrightGreen,
+            host: Color::Green,
+            cwd: Color::BrightBlue,
+            git_branch: Color::Green,
+            git_status: Color::Red,
+            directory: Color::BrightBlue,
+            executable: Color::Green,
+            error: Color::Red,
+            cloud: Color::Yellow,
+            cloud_production: Color::BrightRed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sgr_codes() {
+        assert_eq!(Color::Red.sgr(ColorDepth::TrueColor), "31");
+        assert_eq!(Color::BrightBlue.sgr(ColorDepth::Basic), "94");
+        assert_eq!(Color::Fixed(208).sgr(ColorDepth::Ansi256), "38;5;208");
+        assert_eq!(
+            Color::Rgb(38, 139, 210).sgr(ColorDepth::TrueColor),
+            "38;2;38;139;210"
+        );
+    }
+
+    #[test]
+    fn test_parse_colors() {
+        assert_eq!("red".parse::<Color>(), Ok(Color::Red));
+        assert_eq!("bright-blue".parse::<Color>(), Ok(Color::BrightBlue));
+        assert_eq!("BrightBlack".parse::<Color>(), Ok(Color::BrightBlack));
+        assert_eq!("208".parse::<Color>(), Ok(Color::Fixed(208)));
+        assert_eq!("#268bd2".parse::<Color>(), Ok(Color::Rgb(38, 139, 210)));
+
+        for color in [
+            Color::BrightBlue,
+            Color::Fixed(208),
+            Color::Rgb(38, 139, 210),
+        ] {
+            assert_eq!(color.to_string().parse::<Color>(), Ok(color));
+        }
+        assert!("#26".parse::<Color>().is_err());
+        assert!("purple".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn test_rgb_degrades_to_256() {
+        assert_eq!(
+            Color::Rgb(255, 135, 0).degrade(ColorDepth::Ansi256),
+            Color::Fixed(208)
+        );
+        assert_eq!(
+            Color::Rgb(128, 128, 128).degrade(ColorDepth::Ansi256),
+            Color::Fixed(244)
+        );
+        assert_eq!(
+            Color::Rgb(0, 0, 0).degrade(ColorDepth::Ansi256),
+            Color::Fixed(16)
+        );
+    }
+
+    #[test]
+    fn test_degrades_to_basic() {
+        assert_eq!(
+            Color::Rgb(250, 10, 10).degrade(ColorDepth::Basic),
+            Color::BrightRed
+        );
+        assert_eq!(
+            Color::Rgb(0, 0, 200).degrade(ColorDepth::Basic),
+            Color::Blue
+        );
+        assert_eq!(Color::Fixed(2).degrade(ColorDepth::Basic), Color::Green);
+        assert_eq!(
+            Color::Fixed(231).degrade(ColorDepth::Basic),
+            Color::BrightWhite
+        );
+        assert_eq!(Color::Cyan.degrade(ColorDepth::Basic), Color::Cyan);
+    }
+}
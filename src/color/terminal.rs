@@ -0,0 +1,200 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// How many colors the terminal can display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    /// The 8 standard colors and their bright variants
+    Basic,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// The depth of the terminal the shell was started in, detected once
+    pub fn current() -> Self {
+        static DEPTH: OnceLock<ColorDepth> = OnceLock::new();
+        *DEPTH.get_or_init(Self::detect)
+    }
+
+    fn detect() -> Self {
+        let colorterm = env::var("COLORTERM").ok();
+        let term = env::var("TERM").ok();
+        let max_colors = term.as_deref().and_then(terminfo_max_colors);
+        Self::from_capabilities(colorterm.as_deref(), term.as_deref(), max_colors)
+    }
+
+    /// `COLORTERM` is the only reliable truecolor signal; terminfo and the
+    /// `TERM` name cover 256-color terminals that do not set it
+    fn from_capabilities(
+        colorterm: Option<&str>,
+        term: Option<&str>,
+        max_colors: Option<i32>,
+    ) -> Self {
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            return Self::TrueColor;
+        }
+        match max_colors {
+            Some(colors) if colors >= 1 << 24 => return Self::TrueColor,
+            Some(colors) if colors >= 256 => return Self::Ansi256,
+            _ => {}
+        }
+        if term.is_some_and(|term| term.contains("256color")) {
+            return Self::Ansi256;
+        }
+        Self::Basic
+    }
+}
+
+/// Looks up the `colors` capability of `term` in the compiled terminfo database
+fn terminfo_max_colors(term: &str) -> Option<i32> {
+    let first = term.chars().next()?;
+    terminfo_dirs().into_iter().find_map(|dir| {
+        // Linux uses the first letter as the directory, macOS its hex code
+        [first.to_string(), format!("{:x}", first as u32)]
+            .iter()
+            .map(|sub| dir.join(sub).join(term))
+            .find(|path| path.is_file())
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|data| parse_max_colors(&data))
+    })
+}
+
+fn terminfo_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(dir) = env::var("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join(".terminfo"));
+    }
+    if let Ok(list) = env::var("TERMINFO_DIRS") {
+        dirs.extend(list.split(':').filter(|d| !d.is_empty()).map(PathBuf::from));
+    }
+    dirs.extend(
+        [
+            "/etc/terminfo",
+            "/lib/terminfo",
+            "/usr/share/terminfo",
+            "/usr/lib/terminfo",
+        ]
+        .iter()
+        .map(Path::new)
+        .map(Path::to_path_buf),
+    );
+    dirs
+}
+
+/// Reads `max_colors` from a compiled terminfo entry, in either the legacy
+/// format with 16-bit numbers or the extended one with 32-bit numbers
+fn parse_max_colors(data: &[u8]) -> Option<i32> {
+    const MAGIC_16BIT: u16 = 0o432;
+    const MAGIC_32BIT: u16 = 0o1036;
+    const MAX_COLORS: usize = 13;
+
+    let header = |i: usize| -> Option<usize> {
+        let bytes = data.get(i * 2..i * 2 + 2)?;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+    };
+
+    let number_size = match header(0)? as u16 {
+        MAGIC_16BIT => 2,
+        MAGIC_32BIT => 4,
+        _ => return None,
+    };
+    let (names_size, bools_count, numbers_count) = (header(1)?, header(2)?, header(3)?);
+    if MAX_COLORS >= numbers_count {
+        return None;
+    }
+
+    // Numbers start on an even offset after the names and booleans
+    let mut offset = 12 + names_size + bools_count;
+    offset += offset % 2;
+    let start = offset + MAX_COLORS * number_size;
+    let bytes = data.get(start..start + number_size)?;
+
+    let colors = match number_size {
+        2 => i16::from_le_bytes([bytes[0], bytes[1]]) as i32,
+        _ => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    };
+    (colors > 0).then_some(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(magic: u16, names: &[u8], bools: usize, numbers: &[i32]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [
+            magic,
+            names.len() as u16,
+            bools as u16,
+            numbers.len() as u16,
+            0,
+            0,
+        ] {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend(names);
+        data.extend(std::iter::repeat_n(1, bools));
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        for &number in numbers {
+            match magic {
+                0o432 => data.extend((number as i16).to_le_bytes()),
+                _ => data.extend(number.to_le_bytes()),
+            }
+        }
+        data
+    }
+
+    fn numbers_with_colors(colors: i32) -> Vec<i32> {
+        let mut numbers = vec![-1; 15];
+        numbers[13] = colors;
+        numbers
+    }
+
+    #[test]
+    fn test_parse_max_colors() {
+        let legacy = entry(0o432, b"xterm-256color\0", 3, &numbers_with_colors(256));
+        assert_eq!(parse_max_colors(&legacy), Some(256));
+
+        let extended = entry(0o1036, b"xterm-direct\0", 4, &numbers_with_colors(1 << 24));
+        assert_eq!(parse_max_colors(&extended), Some(1 << 24));
+
+        let absent = entry(0o432, b"dumb\0", 2, &numbers_with_colors(-1));
+        assert_eq!(parse_max_colors(&absent), None);
+        assert_eq!(parse_max_colors(b"garbage"), None);
+    }
+
+    #[test]
+    fn test_depth_from_capabilities() {
+        use ColorDepth::*;
+        assert_eq!(
+            ColorDepth::from_capabilities(Some("truecolor"), None, None),
+            TrueColor
+        );
+        assert_eq!(
+            ColorDepth::from_capabilities(None, Some("xterm"), Some(1 << 24)),
+            TrueColor
+        );
+        assert_eq!(
+            ColorDepth::from_capabilities(None, Some("xterm"), Some(256)),
+            Ansi256
+        );
+        assert_eq!(
+            ColorDepth::from_capabilities(None, Some("screen-256color"), None),
+            Ansi256
+        );
+        assert_eq!(
+            ColorDepth::from_capabilities(None, Some("xterm"), Some(8)),
+            Basic
+        );
+        assert_eq!(ColorDepth::from_capabilities(None, None, None), Basic);
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::palette::{Color, Palette};
+
+/// The built-in themes plus any defined in the config, and which one is active
+#[derive(Debug, Clone)]
+pub struct Themes {
+    themes: BTreeMap<String, Palette>,
+    current: String,
+}
+
+impl Default for Themes {
+    fn default() -> Self {
+        Self::new(&HashMap::new())
+    }
+}
+
+impl Themes {
+    pub const DEFAULT: &'static str = "default";
+
+    /// Config themes with the same name as a built-in replace it
+    pub fn new(custom: &HashMap<String, Palette>) -> Self {
+        let mut themes: BTreeMap<String, Palette> = builtin_themes()
+            .into_iter()
+            .map(|(name, palette)| (name.to_string(), palette))
+            .collect();
+        themes.extend(
+            custom
+                .iter()
+                .map(|(name, palette)| (name.clone(), *palette)),
+        );
+
+        Self {
+            themes,
+            current: Self::DEFAULT.to_string(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Palette> {
+        self.themes.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.themes.keys().map(String::as_str)
+    }
+
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    /// Makes `name` the active theme, returning its palette if it exists
+    pub fn select(&mut self, name: &str) -> Option<Palette> {
+        let palette = *self.themes.get(name)?;
+        self.current = name.to_string();
+        Some(palette)
+    }
+}
+
+fn builtin_themes() -> [(&'static str, Palette); 4] {
+    [
+        (Themes::DEFAULT, Palette::default()),
+        (
+            "solarized",
+            Palette {
+                user: Color::Rgb(133, 153, 0),
+                host: Color::Rgb(42, 161, 152),
+                cwd: Color::Rgb(38, 139, 210),
+                git_branch: Color::Rgb(108, 113, 196),
+                git_status: Color::Rgb(203, 75, 22),
+                directory: Color::Rgb(38, 139, 210),
+                executable: Color::Rgb(133, 153, 0),
+                error: Color::Rgb(220, 50, 47),
+                cloud: Color::Rgb(181, 137, 0),
+                cloud_production: Color::Rgb(220, 50, 47),
+            },
+        ),
+        (
+            "gruvbox",
+            Palette {
+                user: Color::Rgb(184, 187, 38),
+                host: Color::Rgb(142, 192, 124),
+                cwd: Color::Rgb(131, 165, 152),
+                git_branch: Color::Rgb(211, 134, 155),
+                git_status: Color::Rgb(254, 128, 25),
+                directory: Color::Rgb(131, 165, 152),
+                executable: Color::Rgb(184, 187, 38),
+                error: Color::Rgb(251, 73, 52),
+                cloud: Color::Rgb(250, 189, 47),
+                cloud_production: Color::Rgb(251, 73, 52),
+            },
+        ),
+        (
+            "mono",
+            Palette {
+                user: Color::BrightWhite,
+                host: Color::White,
+                cwd: Color::BrightWhite,
+                git_branch: Color::White,
+                git_status: Color::BrightBlack,
+                directory: Color::BrightWhite,
+                executable: Color::White,
+                error: Color::BrightWhite,
+                cloud: Color::White,
+                cloud_production: Color::BrightWhite,
+            },
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_builtin_and_custom() {
+        let custom = Palette {
+            cwd: Color::Fixed(208),
+            ..Palette::default()
+        };
+        let mut themes = Themes::new(&HashMap::from([("mine".to_string(), custom)]));
+        assert_eq!(themes.current(), "default");
+        assert_eq!(
+            themes.names().collect::<Vec<_>>(),
+            ["default", "gruvbox", "mine", "mono", "solarized"]
+        );
+
+        assert_eq!(themes.select("mine"), Some(custom));
+        assert_eq!(themes.current(), "mine");
+        assert_eq!(themes.select("nope"), None);
+        assert_eq!(themes.current(), "mine");
+    }
+}
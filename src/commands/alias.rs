@@ -0,0 +1,114 @@
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::{FlagSpec, Flags},
+};
+
+#[derive(Clone)]
+pub struct Alias;
+
+impl Command for Alias {
+    fn name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn description(&self) -> &'static str {
+        "Define or display aliases"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Define or display aliases.\n\n\
+         Without arguments, print all aliases. With a name, print that alias."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[NAME[=VALUE]...]"
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        if args.is_empty() {
+            let mut aliases: Vec<_> = context.aliases.iter().collect();
+            aliases.sort();
+            for (name, value) in aliases {
+                println!("alias {}='{}'", name, value);
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut status = ExitCode::SUCCESS;
+        for arg in args {
+            match arg.split_once('=') {
+                Some(("", _)) => {
+                    return Err(ShellError::builtin(format!(
+                        "alias: `{}`: invalid alias name",
+                        arg
+                    )));
+                }
+                Some((name, value)) => {
+                    context.aliases.insert(name.to_string(), value.to_string());
+                }
+                None => match context.aliases.get(*arg) {
+                    Some(value) => println!("alias {}='{}'", arg, value),
+                    None => {
+                        eprintln!("alias: {}: not found", arg);
+                        status = ExitCode::FAILURE;
+                    }
+                },
+            }
+        }
+
+        Ok(status)
+    }
+}
+
+#[derive(Clone)]
+pub struct Unalias;
+
+impl Command for Unalias {
+    fn name(&self) -> &'static str {
+        "unalias"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove aliases"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Remove aliases"
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[FlagSpec::short('a', "Remove all aliases")];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "NAME..."
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        if flags.has_flag('a') {
+            context.aliases.clear();
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut status = ExitCode::SUCCESS;
+        for name in args {
+            if context.aliases.remove(*name).is_none() {
+                eprintln!("unalias: {}: not found", name);
+                status = ExitCode::FAILURE;
+            }
+        }
+        Ok(status)
+    }
+}
@@ -0,0 +1,84 @@
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::Flags,
+};
+
+#[derive(Clone)]
+pub struct Builtin;
+
+impl Builtin {
+    /// Fails unless `builtin NAME` may run `name`: a registered builtin, or
+    /// one of the commands the shell handles itself
+    pub fn check(name: &str, context: &CommandContext) -> ShellResult<()> {
+        let special = matches!(name, "exit" | "return" | "source" | ".");
+        if special || context.is_builtin(name) {
+            Ok(())
+        } else {
+            Err(ShellError::builtin(format!(
+                "builtin: {}: not a shell builtin",
+                name
+            )))
+        }
+    }
+}
+
+impl Command for Builtin {
+    fn name(&self) -> &'static str {
+        "builtin"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run a shell builtin, ignoring aliases with the same name"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Run the shell builtin NAME with its arguments, even when an alias of the\n\
+         same name exists. Fails if NAME is not a builtin, so a program on PATH is\n\
+         never run by mistake."
+    }
+
+    fn usage(&self) -> &'static str {
+        "NAME [ARG...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        _flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        // The shell runs `builtin NAME ...` itself; alone it does nothing
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let mut context = CommandContext::default();
+        context.builtins = vec!["cd", "history"];
+        assert!(Builtin::check("cd", &context).is_ok());
+        assert!(Builtin::check("exit", &context).is_ok());
+        assert!(Builtin::check(".", &context).is_ok());
+
+        let error = Builtin::check("sh", &context).unwrap_err();
+        assert_eq!(error.to_string(), "builtin: sh: not a shell builtin");
+        assert_eq!(error.exit_code(), 1);
+
+        // Builtins outside POSIX are hidden in posix mode
+        context.options.posix = true;
+        assert!(Builtin::check("cd", &context).is_ok());
+        assert!(Builtin::check("history", &context).is_err());
+    }
+
+    #[test]
+    fn test_alone() {
+        let mut context = CommandContext::default();
+        let status = Builtin.execute(&[], &Flags::default(), &mut context);
+        assert_eq!(status.unwrap(), ExitCode::SUCCESS);
+    }
+}
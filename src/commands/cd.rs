@@ -1,7 +1,6 @@
-use std::{env, error::Error};
-
 use crate::core::{
-    command::{Command, CommandContext},
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
     flags::Flags,
 };
 
@@ -11,16 +10,31 @@ pub struct ChangeDirectory;
 impl Command for ChangeDirectory {
     fn execute(
         &self,
-        args: &[&str],
-        _flags: &Flags,
-        _context: &CommandContext,
-    ) -> Result<(), Box<dyn Error>> {
-        let new_dir = args.first().map_or_else(
-            || Ok::<String, Box<dyn Error>>(env::var("HOME")?),
-            |path| Ok::<String, Box<dyn Error>>(path.to_string()),
-        )?;
-        env::set_current_dir(new_dir)?;
-        Ok(())
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let new_dir = match flags.positionals().first() {
+            Some(path) => path.to_string(),
+            None => context
+                .var("HOME")
+                .map(String::from)
+                .ok_or_else(|| ShellError::builtin("cd: HOME not set"))?,
+        };
+
+        let target = context
+            .resolve_path(&new_dir)
+            .canonicalize()
+            .map_err(|e| ShellError::builtin(format!("cd: {}: {}", new_dir, e)))?;
+        if !target.is_dir() {
+            return Err(ShellError::builtin(format!(
+                "cd: {}: Not a directory",
+                new_dir
+            )));
+        }
+
+        context.set_cwd(target);
+        Ok(ExitCode::SUCCESS)
     }
 
     fn name(&self) -> &'static str {
@@ -34,4 +48,8 @@ impl Command for ChangeDirectory {
     fn extended_description(&self) -> &'static str {
         "Change the current working directory. If no directory is specified, change to the user's home directory."
     }
+
+    fn usage(&self) -> &'static str {
+        "[DIR]"
+    }
 }
@@ -0,0 +1,193 @@
+use std::{
+    fs::{self, File},
+    io,
+};
+
+use crate::{
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::{FlagSpec, Flags},
+    },
+    digest::{self, Digest, Md5, Sha256},
+};
+
+const FLAGS: &[FlagSpec] = &[FlagSpec::new(
+    'c',
+    "check",
+    "Read sums from the FILEs and check them",
+)];
+
+/// Hashes each file, or stdin for `-` and when there are none
+fn hash_files<D: Digest>(name: &str, flags: &Flags, context: &CommandContext) -> ExitCode {
+    let files = match flags.positionals() {
+        [] => &["-".to_string()][..],
+        files => files,
+    };
+
+    let mut status = ExitCode::SUCCESS;
+    for file in files {
+        match hash_file::<D>(file, context) {
+            Ok(sum) => println!("{}  {}", sum, file),
+            Err(e) => {
+                eprintln!("{}: {}: {}", name, file, e);
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
+}
+
+fn hash_file<D: Digest>(file: &str, context: &CommandContext) -> io::Result<String> {
+    if file == "-" {
+        digest::hash_reader::<D>(io::stdin().lock())
+    } else {
+        digest::hash_reader::<D>(File::open(context.resolve_path(file))?)
+    }
+}
+
+/// Checks the `SUM  FILE` lines of each sums file, as `sha256sum -c` does
+fn check_files<D: Digest>(name: &str, flags: &Flags, context: &CommandContext) -> ExitCode {
+    let mut status = ExitCode::SUCCESS;
+    let mut mismatched = 0;
+    let mut unreadable = 0;
+
+    for sums_file in flags.positionals() {
+        let sums = match fs::read_to_string(context.resolve_path(sums_file)) {
+            Ok(sums) => sums,
+            Err(e) => {
+                eprintln!("{}: {}: {}", name, sums_file, e);
+                status = ExitCode::FAILURE;
+                continue;
+            }
+        };
+
+        for line in sums.lines().filter(|line| !line.trim().is_empty()) {
+            // Binary mode marks the name with `*` instead of the second space
+            let Some((expected, file)) = line.split_once("  ").or_else(|| line.split_once(" *"))
+            else {
+                eprintln!("{}: {}: improperly formatted line", name, sums_file);
+                status = ExitCode::FAILURE;
+                continue;
+            };
+
+            match hash_file::<D>(file, context) {
+                Ok(sum) if sum.eq_ignore_ascii_case(expected.trim()) => println!("{}: OK", file),
+                Ok(_) => {
+                    println!("{}: FAILED", file);
+                    mismatched += 1;
+                }
+                Err(e) => {
+                    eprintln!("{}: {}: {}", name, file, e);
+                    println!("{}: FAILED open or read", file);
+                    unreadable += 1;
+                }
+            }
+        }
+    }
+
+    if unreadable > 0 {
+        eprintln!(
+            "{}: WARNING: {} listed files could not be read",
+            name, unreadable
+        );
+    }
+    if mismatched > 0 {
+        eprintln!(
+            "{}: WARNING: {} computed checksums did NOT match",
+            name, mismatched
+        );
+    }
+    if unreadable + mismatched > 0 {
+        status = ExitCode::FAILURE;
+    }
+    status
+}
+
+fn run<D: Digest>(name: &str, flags: &Flags, context: &CommandContext) -> ShellResult<ExitCode> {
+    if !flags.has_option('c', "check") {
+        return Ok(hash_files::<D>(name, flags, context));
+    }
+    if flags.positionals().is_empty() {
+        return Err(ShellError::Builtin {
+            message: format!("{}: -c needs a file of sums", name),
+            code: 2,
+        });
+    }
+    Ok(check_files::<D>(name, flags, context))
+}
+
+#[derive(Clone)]
+pub struct Sha256Sum;
+
+impl Command for Sha256Sum {
+    fn name(&self) -> &'static str {
+        "sha256sum"
+    }
+
+    fn description(&self) -> &'static str {
+        "Compute and check SHA-256 sums"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Print the SHA-256 sum of each FILE, or of standard input when there is none or\n\
+         FILE is -. Files are read a piece at a time, so size does not matter.\n\n\
+         With -c, read `SUM  FILE` lines, as printed without it, and report whether\n\
+         each file still matches."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-c] [FILE...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        run::<Sha256>(self.name(), flags, context)
+    }
+}
+
+#[derive(Clone)]
+pub struct Md5Sum;
+
+impl Command for Md5Sum {
+    fn name(&self) -> &'static str {
+        "md5sum"
+    }
+
+    fn description(&self) -> &'static str {
+        "Compute and check MD5 sums"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Print the MD5 sum of each FILE, or of standard input when there is none or\n\
+         FILE is -. MD5 is fine for spotting corruption but not tampering; prefer\n\
+         sha256sum where there is a choice.\n\n\
+         With -c, read `SUM  FILE` lines, as printed without it, and report whether\n\
+         each file still matches."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-c] [FILE...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        run::<Md5>(self.name(), flags, context)
+    }
+}
@@ -0,0 +1,191 @@
+use std::path::Path;
+
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::ShellResult,
+    flags::{FlagSpec, Flags},
+};
+
+#[derive(Clone)]
+pub struct CommandBuiltin;
+
+impl CommandBuiltin {
+    /// How `name` would run, as `command -v` prints it: a path, the name of a
+    /// builtin or function, or an alias definition that could be read back in
+    fn describe(name: &str, context: &CommandContext) -> Option<String> {
+        if let Some(value) = context.aliases.get(name) {
+            return Some(format!("alias {}='{}'", name, value));
+        }
+        if context.function(name).is_some() || context.is_builtin(name) {
+            return Some(name.to_string());
+        }
+        if name.contains('/') {
+            let path = context.resolve_path(name);
+            return path.is_file().then(|| name.to_string());
+        }
+        context
+            .path_cache
+            .lookup(name)
+            .map(Path::display)
+            .map(|path| path.to_string())
+    }
+
+    /// The longer form for `command -V`, worded like `type`
+    fn describe_verbose(name: &str, context: &CommandContext) -> Option<String> {
+        if let Some(value) = context.aliases.get(name) {
+            return Some(format!("{} is aliased to `{}'", name, value));
+        }
+        if context.function(name).is_some() {
+            return Some(format!("{} is a function", name));
+        }
+        if context.is_builtin(name) {
+            return Some(format!("{} is a shell builtin", name));
+        }
+        Self::describe(name, context).map(|path| format!("{} is {}", name, path))
+    }
+}
+
+impl Command for CommandBuiltin {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run a command without alias lookup, or tell how it would run"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Run NAME as a builtin or a program on PATH, ignoring any alias called NAME.\n\n\
+         With -v, print how each NAME would run instead: its path, its name for a\n\
+         builtin or function, or the alias definition. Nothing is printed for unknown names and\n\
+         the exit status is 1, so `command -v NAME` works as an existence test.\n\
+         -V prints the same in words."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[
+            FlagSpec::short('v', "Print how each NAME would run"),
+            FlagSpec::short('V', "Describe how each NAME would run"),
+        ];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-vV] NAME [ARG...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        // The shell runs `command NAME ...` itself, skipping the alias lookup,
+        // so only the options that describe commands end up here
+        let verbose = flags.has_flag('V');
+        if !verbose && !flags.has_flag('v') {
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut status = ExitCode::SUCCESS;
+        for name in flags.positionals() {
+            let description = if verbose {
+                Self::describe_verbose(name, context)
+            } else {
+                Self::describe(name, context)
+            };
+            match description {
+                Some(description) => println!("{}", description),
+                None => {
+                    if verbose {
+                        eprintln!("command: {}: not found", name);
+                    }
+                    status = ExitCode::FAILURE;
+                }
+            }
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::core::{
+        parser::{self, Function},
+        path_cache::PathCache,
+    };
+
+    fn function(name: &str) -> Rc<Function> {
+        Rc::new(parser::parse_function(&format!("{}() {{ true; }}", name)))
+    }
+
+    fn context() -> CommandContext {
+        let mut context = CommandContext::default();
+        context.builtins = vec!["cd", "echo"];
+        context.path_cache = PathCache::new("/bin:/usr/bin");
+        context
+            .aliases
+            .insert("ll".to_string(), "ls -l".to_string());
+        context
+            .functions
+            .insert("greet".to_string(), function("greet"));
+        context
+    }
+
+    #[test]
+    fn test_describe() {
+        let context = context();
+        let describe = |name| CommandBuiltin::describe(name, &context);
+        assert_eq!(describe("cd").as_deref(), Some("cd"));
+        assert_eq!(describe("ll").as_deref(), Some("alias ll='ls -l'"));
+        assert_eq!(describe("greet").as_deref(), Some("greet"));
+        assert!(describe("sh").is_some_and(|path| path.ends_with("/sh")));
+        assert_eq!(describe("/bin/sh").as_deref(), Some("/bin/sh"));
+        assert_eq!(describe("hermit-no-such-command"), None);
+        assert_eq!(describe("/hermit/no/such/command"), None);
+    }
+
+    #[test]
+    fn test_describe_verbose() {
+        let context = context();
+        let describe = |name| CommandBuiltin::describe_verbose(name, &context);
+        assert_eq!(describe("echo").as_deref(), Some("echo is a shell builtin"));
+        assert_eq!(describe("ll").as_deref(), Some("ll is aliased to `ls -l'"));
+        assert_eq!(describe("greet").as_deref(), Some("greet is a function"));
+        assert!(describe("sh").is_some_and(|text| text.starts_with("sh is /")));
+        assert_eq!(describe("hermit-no-such-command"), None);
+    }
+
+    #[test]
+    fn test_functions_named_after_builtins() {
+        let mut context = context();
+        context.functions.insert("cd".to_string(), function("cd"));
+        assert_eq!(
+            CommandBuiltin::describe_verbose("cd", &context).as_deref(),
+            Some("cd is a shell builtin")
+        );
+        context.options.funcfirst = true;
+        assert_eq!(
+            CommandBuiltin::describe_verbose("cd", &context).as_deref(),
+            Some("cd is a function")
+        );
+    }
+
+    #[test]
+    fn test_exit_status() {
+        let mut context = context();
+        let mut run = |args: &[&str]| {
+            let flags = Flags::new(args).unwrap();
+            CommandBuiltin.execute(args, &flags, &mut context).unwrap()
+        };
+        assert_eq!(run(&["-v", "cd", "greet"]), ExitCode::SUCCESS);
+        assert_eq!(
+            run(&["-v", "cd", "hermit-no-such-command"]),
+            ExitCode::FAILURE
+        );
+        assert_eq!(run(&["-V", "hermit-no-such-command"]), ExitCode::FAILURE);
+    }
+}
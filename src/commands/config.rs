@@ -0,0 +1,150 @@
+use std::{fs, io, process};
+
+use crate::{
+    config::{self, Config},
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::Flags,
+    },
+};
+
+#[derive(Clone)]
+pub struct ConfigCommand;
+
+impl ConfigCommand {
+    fn get(key: Option<&String>) -> ShellResult<ExitCode> {
+        let config = Config::load();
+        let value = match key {
+            Some(key) => config
+                .get(key)
+                .ok_or_else(|| ShellError::builtin(format!("config: unknown setting '{}'", key)))?,
+            None => toml::Value::try_from(&config)
+                .map_err(|e| ShellError::builtin(format!("config: {}", e)))?,
+        };
+
+        match value {
+            toml::Value::String(text) => println!("{}", text),
+            toml::Value::Table(table) => print!("{}", table),
+            value => println!("{}", value),
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+
+    fn set(
+        context: &mut CommandContext,
+        key: Option<&String>,
+        value: Option<&String>,
+    ) -> ShellResult<ExitCode> {
+        let (Some(key), Some(value)) = (key, value) else {
+            return Err(ShellError::builtin("config: set: expected KEY VALUE"));
+        };
+        let path =
+            Config::path().ok_or_else(|| ShellError::builtin("config: no config directory"))?;
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(ShellError::builtin(format!(
+                    "config: {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+
+        let updated = config::set_value(&contents, key, value)
+            .map_err(|e| ShellError::builtin(format!("config: {}: {}", key, e)))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, updated)
+            .map_err(|e| ShellError::builtin(format!("config: {}: {}", path.display(), e)))?;
+        context.config_changed = true;
+        Ok(ExitCode::SUCCESS)
+    }
+
+    fn edit(context: &mut CommandContext) -> ShellResult<ExitCode> {
+        let path =
+            Config::path().ok_or_else(|| ShellError::builtin("config: no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let editor = ["VISUAL", "EDITOR"]
+            .iter()
+            .find_map(|name| context.var(name).filter(|value| !value.is_empty()))
+            .unwrap_or("vi")
+            .to_string();
+        let mut parts = editor.split_whitespace();
+        let program = parts.next().unwrap_or("vi");
+        let status = process::Command::new(program)
+            .args(parts)
+            .arg(&path)
+            .current_dir(&context.cwd)
+            .env_clear()
+            .envs(&context.variables)
+            .status()
+            .map_err(|e| ShellError::builtin(format!("config: {}: {}", program, e)))?;
+        if !status.success() {
+            return Ok(status.into());
+        }
+
+        // Reload even when the file is broken, so the shell falls back to the defaults
+        context.config_changed = true;
+        let contents = fs::read_to_string(&path).unwrap_or_default();
+        Config::parse(&contents).map_err(|e| {
+            ShellError::builtin(format!("config: {}: {}", path.display(), e.message()))
+        })?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+impl Command for ConfigCommand {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show or change settings in the config file"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Show or change settings in the config file.\n\n\
+         config get [KEY]       Print a setting such as prompt.show_hostname, or every setting\n\
+         config set KEY VALUE   Write a setting to the config file\n\
+         config edit            Open the config file in $VISUAL or $EDITOR\n\
+         config reload          Read the config file again and apply it\n\n\
+         VALUE is read as TOML when it can be (5000, true, [\"a\", \"b\"]) and as a\n\
+         string otherwise. The shell also notices when the file is changed by another\n\
+         program, and applies it before the next prompt. Plugins are only loaded when\n\
+         the shell starts."
+    }
+
+    fn usage(&self) -> &'static str {
+        "get [KEY] | set KEY VALUE | edit | reload"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let args = flags.positionals();
+        match args.first().map(String::as_str) {
+            Some("get") => Self::get(args.get(1)),
+            Some("set") => Self::set(context, args.get(1), args.get(2)),
+            Some("edit") => Self::edit(context),
+            Some("reload") => {
+                context.config_changed = true;
+                Ok(ExitCode::SUCCESS)
+            }
+            Some(other) => Err(ShellError::builtin(format!(
+                "config: unknown subcommand '{}'",
+                other
+            ))),
+            None => Self::get(None),
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    color::Color,
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::{FlagSpec, Flags},
+    },
+    diff::{self, Line},
+};
+
+const DEFAULT_CONTEXT: usize = 3;
+
+/// What comparing two paths found
+#[derive(Default)]
+struct Outcome {
+    differ: bool,
+    /// Something could not be read, which diff(1) reports with status 2
+    trouble: bool,
+}
+
+#[derive(Clone)]
+pub struct Diff;
+
+impl Diff {
+    fn usage_error(message: impl Into<String>) -> ShellError {
+        ShellError::Builtin {
+            message: message.into(),
+            code: 2,
+        }
+    }
+
+    /// Prints a unified diff of two files, with a line naming them first when
+    /// they were found inside directories being compared, `command` being how
+    /// diff was run. The paths are shown
+    /// as given and read relative to `cwd`.
+    fn compare_files(
+        cwd: &Path,
+        a: &Path,
+        b: &Path,
+        context: usize,
+        command: Option<&str>,
+        out: &mut impl Write,
+        outcome: &mut Outcome,
+    ) -> io::Result<()> {
+        let (old, new) = match (fs::read(cwd.join(a)), fs::read(cwd.join(b))) {
+            (Ok(old), Ok(new)) => (old, new),
+            (Err(e), _) | (_, Err(e)) => {
+                let path = if fs::metadata(cwd.join(a)).is_err() {
+                    a
+                } else {
+                    b
+                };
+                eprintln!("diff: {}: {}", path.display(), e);
+                outcome.trouble = true;
+                return Ok(());
+            }
+        };
+        if old == new {
+            return Ok(());
+        }
+        outcome.differ = true;
+
+        if let Some(command) = command {
+            writeln!(out, "{} {} {}", command, a.display(), b.display())?;
+        }
+        if old.contains(&0) || new.contains(&0) {
+            writeln!(
+                out,
+                "Binary files {} and {} differ",
+                a.display(),
+                b.display()
+            )?;
+            return Ok(());
+        }
+
+        let (old, new) = (String::from_utf8_lossy(&old), String::from_utf8_lossy(&new));
+        let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+        let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+        writeln!(out, "--- {}", a.display())?;
+        writeln!(out, "+++ {}", b.display())?;
+        for hunk in diff::hunks(&old_lines, &new_lines, context) {
+            writeln!(out, "{}", Color::Cyan.paint(&hunk.header()))?;
+            for line in hunk.lines {
+                let (text, painted) = match line {
+                    Line::Context(text) => (text, format!(" {}", text.trim_end_matches('\n'))),
+                    Line::Removed(text) => (
+                        text,
+                        Color::Red.paint(&format!("-{}", text.trim_end_matches('\n'))),
+                    ),
+                    Line::Added(text) => (
+                        text,
+                        Color::Green.paint(&format!("+{}", text.trim_end_matches('\n'))),
+                    ),
+                };
+                writeln!(out, "{}", painted)?;
+                if !text.ends_with('\n') {
+                    writeln!(out, "\\ No newline at end of file")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Compares the entries two directories share and notes the ones only one
+    /// of them has, going into subdirectories when `recursive`
+    fn compare_dirs(
+        cwd: &Path,
+        a: &Path,
+        b: &Path,
+        context: usize,
+        recursive: bool,
+        out: &mut impl Write,
+        outcome: &mut Outcome,
+    ) -> io::Result<()> {
+        let names = |dir: &Path| -> io::Result<BTreeSet<_>> {
+            Ok(fs::read_dir(dir)?
+                .flatten()
+                .map(|entry| entry.file_name())
+                .collect())
+        };
+        let (old, new) = match (names(&cwd.join(a)), names(&cwd.join(b))) {
+            (Ok(old), Ok(new)) => (old, new),
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("diff: {}", e);
+                outcome.trouble = true;
+                return Ok(());
+            }
+        };
+
+        for name in old.union(&new) {
+            let (path_a, path_b) = (a.join(name), b.join(name));
+            if !new.contains(name) || !old.contains(name) {
+                let dir = if old.contains(name) { a } else { b };
+                writeln!(out, "Only in {}: {}", dir.display(), name.to_string_lossy())?;
+                outcome.differ = true;
+                continue;
+            }
+            match (cwd.join(&path_a).is_dir(), cwd.join(&path_b).is_dir()) {
+                (true, true) if recursive => {
+                    Self::compare_dirs(cwd, &path_a, &path_b, context, recursive, out, outcome)?
+                }
+                (true, true) => writeln!(
+                    out,
+                    "Common subdirectories: {} and {}",
+                    path_a.display(),
+                    path_b.display()
+                )?,
+                (false, false) => {
+                    let command = if recursive { "diff -r" } else { "diff" };
+                    Self::compare_files(
+                        cwd,
+                        &path_a,
+                        &path_b,
+                        context,
+                        Some(command),
+                        out,
+                        outcome,
+                    )?
+                }
+                (a_is_dir, _) => {
+                    let kind = |is_dir| if is_dir { "directory" } else { "regular file" };
+                    writeln!(
+                        out,
+                        "File {} is a {} while file {} is a {}",
+                        path_a.display(),
+                        kind(a_is_dir),
+                        path_b.display(),
+                        kind(!a_is_dir)
+                    )?;
+                    outcome.differ = true;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Command for Diff {
+    fn name(&self) -> &'static str {
+        "diff"
+    }
+
+    fn description(&self) -> &'static str {
+        "Compare files line by line"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Show how OLD differs from NEW as a colored unified diff, with N unchanged\n\
+         lines of context around each change (3 by default).\n\n\
+         Given two directories, compare the files they have in common and list the\n\
+         ones only one has; with -r, subdirectories are compared too. Given a file\n\
+         and a directory, compare the file with the one of the same name inside it.\n\n\
+         The exit status is 0 when the inputs are the same, 1 when they differ and\n\
+         2 when something could not be read."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[
+            FlagSpec::new('r', "recursive", "Compare subdirectories too"),
+            FlagSpec::new('U', "unified", "Show N lines of context").takes_value("N"),
+        ];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-r] [-U N] OLD NEW"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let [old, new] = flags.positionals() else {
+            return Err(Self::usage_error(format!(
+                "diff: usage: diff {}",
+                self.usage()
+            )));
+        };
+        let lines = match flags.option_value('U', "unified") {
+            Some(n) => n
+                .parse()
+                .map_err(|_| Self::usage_error(format!("diff: invalid context length '{}'", n)))?,
+            None => DEFAULT_CONTEXT,
+        };
+
+        let cwd = context.resolve_path("");
+        let (mut a, mut b) = (PathBuf::from(old), PathBuf::from(new));
+        for path in [&a, &b] {
+            if let Err(e) = fs::metadata(cwd.join(path)) {
+                return Err(Self::usage_error(format!(
+                    "diff: {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        }
+        // As in diff(1), a directory against a file means the file of that name in it
+        let inside = |dir: &Path, file: &Path| dir.join(file.file_name().unwrap_or_default());
+        match (cwd.join(&a).is_dir(), cwd.join(&b).is_dir()) {
+            (true, false) => a = inside(&a, &b),
+            (false, true) => b = inside(&b, &a),
+            _ => {}
+        }
+
+        let mut outcome = Outcome::default();
+        let mut stdout = io::stdout().lock();
+        if cwd.join(&a).is_dir() {
+            let recursive = flags.has_option('r', "recursive");
+            Self::compare_dirs(&cwd, &a, &b, lines, recursive, &mut stdout, &mut outcome)?;
+        } else {
+            Self::compare_files(&cwd, &a, &b, lines, None, &mut stdout, &mut outcome)?;
+        }
+        stdout.flush()?;
+
+        Ok(match outcome {
+            Outcome { trouble: true, .. } => ExitCode::new(2),
+            Outcome { differ: true, .. } => ExitCode::FAILURE,
+            _ => ExitCode::SUCCESS,
+        })
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::ShellResult,
+        flags::Flags,
+    },
+    doctor::{self, Status},
+};
+
+#[derive(Clone)]
+pub struct Doctor;
+
+impl Command for Doctor {
+    fn name(&self) -> &'static str {
+        "doctor"
+    }
+
+    fn description(&self) -> &'static str {
+        "Diagnose problems with the shell setup"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Check the config file, history file, PATH, terminal, git library and\n\
+         plugins, and suggest a fix for each problem found.\n\n\
+         Exits with status 1 if any check fails. Also available as 'hermit doctor'."
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let checks = doctor::run(&context.variables, &context.history_path);
+        for check in &checks {
+            println!("{}", check);
+        }
+
+        if checks.iter().any(|check| check.status == Status::Error) {
+            Ok(ExitCode::FAILURE)
+        } else {
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
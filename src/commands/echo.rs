@@ -1,8 +1,8 @@
 use crate::core::{
-    command::{Command, CommandContext},
+    command::{Command, CommandContext, ExitCode},
+    error::ShellResult,
     flags::Flags,
 };
-use std::error::Error;
 
 #[derive(Clone)]
 pub struct Echo;
@@ -20,13 +20,21 @@ impl Command for Echo {
         "Prints the given arguments"
     }
 
+    fn usage(&self) -> &'static str {
+        "[ARG...]"
+    }
+
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
     fn execute(
         &self,
         args: &[&str],
         _flags: &Flags,
-        _context: &CommandContext,
-    ) -> Result<(), Box<dyn Error>> {
+        _context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
         println!("{}", args.join(" "));
-        Ok(())
+        Ok(ExitCode::SUCCESS)
     }
 }
@@ -0,0 +1,177 @@
+use std::io::{self, BufRead, Read, Write};
+
+use crate::{
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::{FlagSpec, Flags},
+    },
+    encoding,
+};
+
+/// The arguments joined by spaces, or all of stdin when there are none
+fn input(name: &str, flags: &Flags) -> ShellResult<Vec<u8>> {
+    if !flags.positionals().is_empty() {
+        return Ok(flags.positionals().join(" ").into_bytes());
+    }
+    let mut data = Vec::new();
+    io::stdin()
+        .lock()
+        .read_to_end(&mut data)
+        .map_err(|e| ShellError::builtin(format!("{}: {}", name, e)))?;
+    Ok(data)
+}
+
+/// Applies `transform` to the joined arguments, or to each line of stdin
+fn per_line(name: &str, flags: &Flags, transform: impl Fn(&str) -> Vec<u8>) -> ShellResult<()> {
+    let mut stdout = io::stdout().lock();
+    if !flags.positionals().is_empty() {
+        stdout.write_all(&transform(&flags.positionals().join(" ")))?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|e| ShellError::builtin(format!("{}: {}", name, e)))?;
+        stdout.write_all(&transform(&line))?;
+        writeln!(stdout)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct Base64;
+
+impl Command for Base64 {
+    fn name(&self) -> &'static str {
+        "base64"
+    }
+
+    fn description(&self) -> &'static str {
+        "Encode or decode base64"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Encode the TEXT arguments, joined by spaces, or standard input when there are\n\
+         none. Encoded output is wrapped at 76 columns.\n\n\
+         With -d, decode instead; whitespace in the input is ignored."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[
+            FlagSpec::new('d', "decode", "Decode the input"),
+            FlagSpec::new('w', "wrap", "Wrap lines after COLS characters, 0 for never")
+                .takes_value("COLS"),
+        ];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-d] [-w COLS] [TEXT...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let data = input(self.name(), flags)?;
+        let mut stdout = io::stdout().lock();
+
+        if flags.has_option('d', "decode") {
+            let text = String::from_utf8_lossy(&data);
+            let decoded = encoding::base64_decode(&text)
+                .map_err(|e| ShellError::builtin(format!("base64: {}", e)))?;
+            stdout.write_all(&decoded)?;
+            stdout.flush()?;
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let wrap = match flags
+            .get_value('w')
+            .or_else(|| flags.get_long_value("wrap"))
+        {
+            Some(cols) => cols.parse().map_err(|_| ShellError::Builtin {
+                message: format!("base64: invalid wrap size '{}'", cols),
+                code: 2,
+            })?,
+            None => 76,
+        };
+        let encoded = encoding::base64_encode(&data);
+        if wrap == 0 || encoded.is_empty() {
+            writeln!(stdout, "{}", encoded)?;
+        } else {
+            for line in encoded.as_bytes().chunks(wrap) {
+                stdout.write_all(line)?;
+                writeln!(stdout)?;
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+#[derive(Clone)]
+pub struct UrlEncode;
+
+impl Command for UrlEncode {
+    fn name(&self) -> &'static str {
+        "urlencode"
+    }
+
+    fn description(&self) -> &'static str {
+        "Percent-encode text for use in a URL"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Percent-encode the TEXT arguments, joined by spaces, or each line of standard\n\
+         input when there are none. Letters, digits and -_.~ are left alone."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[TEXT...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        per_line(self.name(), flags, |text| {
+            encoding::url_encode(text).into_bytes()
+        })?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+#[derive(Clone)]
+pub struct UrlDecode;
+
+impl Command for UrlDecode {
+    fn name(&self) -> &'static str {
+        "urldecode"
+    }
+
+    fn description(&self) -> &'static str {
+        "Decode percent-encoded text"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Decode the %XX escapes in the TEXT arguments, joined by spaces, or in each line\n\
+         of standard input when there are none. A + stays a +."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[TEXT...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        per_line(self.name(), flags, encoding::url_decode)?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
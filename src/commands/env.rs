@@ -0,0 +1,168 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{
+    color::Color,
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::Flags,
+        records::{Table, Value, CSV_FLAG, JSON_FLAG},
+    },
+};
+
+/// How a variable differs from the snapshot
+#[derive(Debug, PartialEq, Eq)]
+enum Change<'a> {
+    Added(&'a str, &'a str),
+    Removed(&'a str, &'a str),
+    Changed(&'a str, &'a str, &'a str),
+}
+
+#[derive(Clone)]
+pub struct Env;
+
+impl Env {
+    /// The variables added, removed and changed between `before` and `after`, by name
+    fn changes<'a>(
+        before: &'a HashMap<String, String>,
+        after: &'a HashMap<String, String>,
+    ) -> Vec<Change<'a>> {
+        let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+        names
+            .into_iter()
+            .filter_map(|name| match (before.get(name), after.get(name)) {
+                (None, Some(value)) => Some(Change::Added(name, value)),
+                (Some(value), None) => Some(Change::Removed(name, value)),
+                (Some(old), Some(new)) if old != new => Some(Change::Changed(name, old, new)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Prints the changes since the snapshot
+    fn diff(context: &CommandContext) {
+        let environment = context.environment();
+        for change in Self::changes(&context.env_snapshot, &environment) {
+            let line = match change {
+                Change::Added(name, value) => Color::Green.paint(&format!("+{}={}", name, value)),
+                Change::Removed(name, value) => Color::Red.paint(&format!("-{}={}", name, value)),
+                Change::Changed(name, old, new) => {
+                    Color::Yellow.paint(&format!("~{}: {} -> {}", name, old, new))
+                }
+            };
+            println!("{}", line);
+        }
+    }
+
+    /// The exported variables as a table, sorted by name
+    fn table(context: &CommandContext) -> Table {
+        let mut table = Table::new(&["name", "value"]);
+        let mut variables: Vec<_> = context.environment().into_iter().collect();
+        variables.sort();
+        for (name, value) in variables {
+            table.rows.push(vec![Value::Text(name), Value::Text(value)]);
+        }
+        table
+    }
+}
+
+impl Command for Env {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn description(&self) -> &'static str {
+        "Compare exported variables against a snapshot"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "env snapshot  Remember the exported variables\n\
+         env diff      Show variables added (+), removed (-) and changed (~) since the\n\
+         \x20             last snapshot, or since the shell started\n\
+         env --json    Print the variables as JSON, or as CSV with --csv\n\n\
+         Anything else runs the env program, so `env NAME=VALUE COMMAND` still works."
+    }
+
+    fn usage(&self) -> &'static str {
+        "snapshot | diff | --json | --csv | [ARG...]"
+    }
+
+    // Everything but the two actions belongs to the env program
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        match args {
+            ["snapshot"] => context.env_snapshot = context.environment(),
+            ["diff"] => Self::diff(context),
+            ["--json"] | ["--csv"] => {
+                let flags = Flags::parse(args, &[JSON_FLAG, CSV_FLAG])?;
+                Self::table(context).print_data(&flags);
+            }
+            _ => {
+                return context
+                    .external_command()
+                    .execute("env", args)
+                    .map_err(|e| ShellError::from_spawn("env", e))
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_changes() {
+        let before = vars(&[("A", "1"), ("B", "2"), ("C", "3")]);
+        let after = vars(&[("A", "1"), ("B", "20"), ("D", "4")]);
+        assert_eq!(
+            Env::changes(&before, &after),
+            [
+                Change::Changed("B", "2", "20"),
+                Change::Removed("C", "3"),
+                Change::Added("D", "4"),
+            ]
+        );
+        assert!(Env::changes(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut context = CommandContext::default();
+        context.set_var("A", "1");
+        context.set_var("LOCAL", "1");
+        context.exported.insert("A".to_string());
+        let status = Env.execute(&["snapshot"], &Flags::default(), &mut context);
+        assert_eq!(status.unwrap(), ExitCode::SUCCESS);
+        assert!(!context.env_snapshot.contains_key("LOCAL"));
+
+        context.set_var("A", "2");
+        context.set_var("LOCAL", "2");
+        assert_eq!(
+            Env::changes(&context.env_snapshot, &context.environment()),
+            [Change::Changed("A", "1", "2")]
+        );
+        let names: Vec<_> = Env::table(&context)
+            .rows
+            .iter()
+            .map(|row| row[0].clone())
+            .collect();
+        assert_eq!(names, [Value::Text("A".to_string())]);
+    }
+}
@@ -1 +1,69 @@
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::Flags,
+};
 
+#[derive(Clone)]
+pub struct Export;
+
+impl Command for Export {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn description(&self) -> &'static str {
+        "Set environment variables"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Set environment variables for the shell and the commands it runs. A NAME\n\
+         without a value exports the shell variable of that name.\n\n\
+         Without arguments, print the exported variables."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[NAME[=VALUE]...]"
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        if args.is_empty() {
+            let mut variables: Vec<_> = context
+                .variables
+                .iter()
+                .filter(|(name, _)| context.exported.contains(*name))
+                .collect();
+            variables.sort();
+            for (name, value) in variables {
+                println!("{}={}", name, value);
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (*arg, None),
+            };
+
+            if name.is_empty() {
+                return Err(ShellError::builtin(format!(
+                    "export: `{}`: not a valid identifier",
+                    arg
+                )));
+            }
+
+            if let Some(value) = value {
+                context.set_var(name, value);
+            }
+            context.exported.insert(name.to_string());
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
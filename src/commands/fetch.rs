@@ -0,0 +1,182 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+};
+
+use url::Url;
+
+use super::json;
+use crate::{
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::Flags,
+    },
+    http::{Request, Response},
+};
+
+#[derive(Clone)]
+pub struct Fetch;
+
+impl Fetch {
+    fn usage_error(message: impl Into<String>) -> ShellError {
+        ShellError::Builtin {
+            message: message.into(),
+            code: 2,
+        }
+    }
+
+    /// Builds the request from the arguments; `-H` may be given any number of times
+    fn parse(args: &[&str]) -> ShellResult<(Request, Option<String>)> {
+        let mut url = None;
+        let mut method = None;
+        let mut headers = Vec::new();
+        let mut body = None;
+        let mut output = None;
+
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            let (option, inline) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => (option, Some(value)),
+                _ => (arg, None),
+            };
+            if !option.starts_with('-') {
+                if url.replace(arg).is_some() {
+                    return Err(Self::usage_error("fetch: only one URL may be given"));
+                }
+                continue;
+            }
+            let value = match inline.or_else(|| args.next().copied()) {
+                Some(value) => value,
+                None => {
+                    return Err(Self::usage_error(format!(
+                        "fetch: {}: expected a value",
+                        option
+                    )))
+                }
+            };
+            match option {
+                "-o" | "--output" => output = Some(value.to_string()),
+                "-X" | "--request" => method = Some(value),
+                "-d" | "--data" => body = Some(value.as_bytes().to_vec()),
+                "-H" | "--header" => {
+                    let (name, value) = value.split_once(':').ok_or_else(|| {
+                        Self::usage_error(format!(
+                            "fetch: {}: headers look like 'Name: value'",
+                            value
+                        ))
+                    })?;
+                    headers.push((name.trim().to_string(), value.trim().to_string()));
+                }
+                _ => {
+                    return Err(Self::usage_error(format!(
+                        "fetch: {}: unknown option",
+                        option
+                    )))
+                }
+            }
+        }
+
+        let url = url.ok_or_else(|| Self::usage_error("fetch: usage: fetch [OPTION...] URL"))?;
+        // Like curl, a bare host means plain HTTP
+        let url = if url.contains("://") {
+            Url::parse(url)
+        } else {
+            Url::parse(&format!("http://{}", url))
+        }
+        .map_err(|e| Self::usage_error(format!("fetch: {}: {}", url, e)))?;
+
+        let method = method.unwrap_or(if body.is_some() { "POST" } else { "GET" });
+        let mut request = Request::new(method, url);
+        request.headers = headers;
+        request.body = body;
+        Ok((request, output))
+    }
+
+    fn is_json(response: &Response) -> bool {
+        response.header("content-type").is_some_and(|value| {
+            let mime = value.split(';').next().unwrap_or_default().trim();
+            mime.eq_ignore_ascii_case("application/json") || mime.ends_with("+json")
+        })
+    }
+
+    /// Writes the body to stdout, indenting JSON so it can be read at a glance
+    fn print_body(response: &mut Response) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        if !Self::is_json(response) {
+            io::copy(&mut response.body, &mut stdout)?;
+            return stdout.flush();
+        }
+
+        let mut body = Vec::new();
+        response.body.read_to_end(&mut body)?;
+        match serde_json::from_slice::<serde_json::Value>(&body) {
+            Ok(value) => writeln!(stdout, "{}", json::render(&value, false))?,
+            // Servers do mislabel things; show what came back rather than nothing
+            Err(_) => stdout.write_all(&body)?,
+        }
+        stdout.flush()
+    }
+}
+
+impl Command for Fetch {
+    fn name(&self) -> &'static str {
+        "fetch"
+    }
+
+    fn description(&self) -> &'static str {
+        "Make an HTTP request and print the response"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Request URL and print the response body, following redirects. JSON responses\n\
+         are indented and colored. A URL without a scheme is fetched over http.\n\n\
+         Options:\n\
+         \x20 -o, --output FILE    Save the body to FILE instead of printing it\n\
+         \x20 -H, --header HEADER  Send 'Name: value' as well; may be repeated\n\
+         \x20 -X, --request METHOD Use METHOD instead of GET\n\
+         \x20 -d, --data DATA      Send DATA as the body, as a POST unless -X says otherwise\n\n\
+         Exits with 1 when the server answers with an error status."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-o FILE] [-H HEADER]... [-X METHOD] [-d DATA] URL"
+    }
+
+    // -H can be repeated, which the shared flag parser rejects
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let (request, output) = Self::parse(args)?;
+        let url = request.url.to_string();
+        let mut response = request
+            .send()
+            .map_err(|e| ShellError::builtin(format!("fetch: {}: {}", url, e)))?;
+
+        let written = match &output {
+            Some(path) => File::create(context.resolve_path(path))
+                .and_then(|mut file| io::copy(&mut response.body, &mut file).map(|_| ())),
+            None => Self::print_body(&mut response),
+        };
+        written.map_err(|e| {
+            ShellError::builtin(format!(
+                "fetch: {}: {}",
+                output.as_deref().unwrap_or(&url),
+                e
+            ))
+        })?;
+
+        if response.status >= 400 {
+            eprintln!("fetch: {}: {} {}", url, response.status, response.reason);
+            return Ok(ExitCode::FAILURE);
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,113 @@
+use std::io::{self, Write};
+
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::Flags,
+};
+
+/// Picks the job a `fg` or `bg` argument names; a bare number counts as `%N`
+fn find_job(name: &str, spec: Option<&String>, context: &CommandContext) -> ShellResult<usize> {
+    let spec = match spec {
+        Some(spec) if spec.starts_with('%') => spec.to_string(),
+        Some(spec) => format!("%{}", spec),
+        None => "%+".to_string(),
+    };
+    context
+        .jobs
+        .resolve(&spec)
+        .map(|job| job.id())
+        .map_err(|e| ShellError::builtin(format!("{}: {}", name, e)))
+}
+
+#[derive(Clone)]
+pub struct Foreground;
+
+impl Command for Foreground {
+    fn name(&self) -> &'static str {
+        "fg"
+    }
+
+    fn description(&self) -> &'static str {
+        "Wait for a background job, showing its output as it runs"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Bring JOB to the foreground: print what it has collected so far, then\n\
+         keep showing its output until it exits. The exit status is the job's.\n\n\
+         JOB is a job spec: %N for job N, %+ or %% for the current (newest) job,\n\
+         %- for the one before it, %str for the job whose command starts with str,\n\
+         or %?str for the one containing str. Without JOB, the current job is used."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[JOB]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let id = find_job("fg", flags.positionals().first(), context)?;
+        if let Some(job) = context.jobs.get(id) {
+            println!("{}", job.command());
+        }
+
+        let mut stdout = io::stdout();
+        let finished = context.jobs.wait(id, |output| {
+            stdout.write_all(output).ok();
+            stdout.flush().ok();
+        })?;
+        let Some(finished) = finished else {
+            return Err(ShellError::builtin(format!("fg: %{}: no such job", id)));
+        };
+        stdout.write_all(&finished.output)?;
+        stdout.flush()?;
+        Ok(finished.status.into())
+    }
+}
+
+#[derive(Clone)]
+pub struct Background;
+
+impl Command for Background {
+    fn name(&self) -> &'static str {
+        "bg"
+    }
+
+    fn description(&self) -> &'static str {
+        "Resume a stopped background job"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Let JOB carry on in the background after it was stopped, for example with\n\
+         kill -STOP. JOB is a job spec as for fg; without it, the current job is used."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[JOB]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let id = find_job("bg", flags.positionals().first(), context)?;
+        let Some(job) = context.jobs.get(id) else {
+            return Err(ShellError::builtin(format!("bg: %{}: no such job", id)));
+        };
+        if unsafe { libc::kill(job.pid() as libc::pid_t, libc::SIGCONT) } != 0 {
+            return Err(ShellError::builtin(format!(
+                "bg: %{}: {}",
+                id,
+                io::Error::last_os_error()
+            )));
+        }
+        println!("[{}] {} &", job.id(), job.command());
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::{FlagSpec, Flags},
+};
+
+/// How much of a file is read to tell what it is; tar headers end at 512 bytes
+const SNIFF_LEN: usize = 512;
+
+/// Leading bytes and the type they mark
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "PNG image data"),
+    (b"\xff\xd8\xff", "JPEG image data"),
+    (b"GIF87a", "GIF image data"),
+    (b"GIF89a", "GIF image data"),
+    (b"%PDF-", "PDF document"),
+    (b"PK\x03\x04", "Zip archive data"),
+    (b"\x1f\x8b", "gzip compressed data"),
+    (b"BZh", "bzip2 compressed data"),
+    (b"\xfd7zXZ\x00", "XZ compressed data"),
+    (b"\x28\xb5\x2f\xfd", "Zstandard compressed data"),
+    (b"7z\xbc\xaf\x27\x1c", "7-zip archive data"),
+    (b"\x00asm", "WebAssembly binary module"),
+    (b"SQLite format 3\x00", "SQLite 3.x database"),
+    (b"\xcf\xfa\xed\xfe", "Mach-O 64-bit executable"),
+    (b"ID3", "MP3 audio"),
+    (b"OggS", "Ogg data"),
+    (b"%!PS", "PostScript document"),
+];
+
+#[derive(Clone)]
+pub struct FileCommand;
+
+impl FileCommand {
+    fn describe_path(path: &Path) -> io::Result<String> {
+        let meta = fs::symlink_metadata(path)?;
+        let kind = meta.file_type();
+        if kind.is_symlink() {
+            return Ok(format!(
+                "symbolic link to {}",
+                fs::read_link(path)?.display()
+            ));
+        }
+        if kind.is_dir() {
+            return Ok("directory".to_string());
+        }
+        if !kind.is_file() {
+            return Ok("special file".to_string());
+        }
+
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        fs::File::open(path)?
+            .take(SNIFF_LEN as u64)
+            .read_to_end(&mut head)?;
+        Ok(Self::describe(&head))
+    }
+
+    /// Names the type of a file from its first bytes
+    fn describe(head: &[u8]) -> String {
+        if head.is_empty() {
+            return "empty".to_string();
+        }
+        if let Some((_, name)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+            return name.to_string();
+        }
+        if let Some(elf) = Self::describe_elf(head) {
+            return elf;
+        }
+        if head.len() >= 12 && head.starts_with(b"RIFF") {
+            match &head[8..12] {
+                b"WEBP" => return "WebP image data".to_string(),
+                b"WAVE" => return "WAVE audio".to_string(),
+                b"AVI " => return "AVI video".to_string(),
+                _ => {}
+            }
+        }
+        if head.get(257..262) == Some(b"ustar") {
+            return "POSIX tar archive".to_string();
+        }
+
+        // A multi-byte character may be cut off at the end of what was read
+        let text = match std::str::from_utf8(head) {
+            Ok(text) => text,
+            Err(e) if e.error_len().is_none() => {
+                std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default()
+            }
+            Err(_) => return "data".to_string(),
+        };
+        if text.contains('\0') {
+            return "data".to_string();
+        }
+        let encoding = if text.is_ascii() { "ASCII" } else { "UTF-8" };
+        match text.strip_prefix("#!") {
+            Some(shebang) => {
+                let line = shebang.lines().next().unwrap_or_default().trim();
+                let mut words = line.split_whitespace();
+                let program = words.next().unwrap_or_default();
+                // `#!/usr/bin/env python3` names the interpreter second
+                let interpreter = match program.rsplit('/').next() {
+                    Some("env") => words.next().unwrap_or("env"),
+                    Some(name) => name,
+                    None => program,
+                };
+                format!("{} script, {} text executable", interpreter, encoding)
+            }
+            None => format!("{} text", encoding),
+        }
+    }
+
+    fn describe_elf(head: &[u8]) -> Option<String> {
+        if !head.starts_with(b"\x7fELF") || head.len() < 20 {
+            return None;
+        }
+        let bits = match head[4] {
+            1 => "32-bit",
+            2 => "64-bit",
+            _ => "unknown-class",
+        };
+        let little_endian = head[5] == 1;
+        let half = |offset: usize| {
+            let bytes = [head[offset], head[offset + 1]];
+            if little_endian {
+                u16::from_le_bytes(bytes)
+            } else {
+                u16::from_be_bytes(bytes)
+            }
+        };
+        let kind = match half(16) {
+            1 => "relocatable",
+            2 => "executable",
+            3 => "shared object",
+            4 => "core file",
+            _ => "file",
+        };
+        let machine = match half(18) {
+            0x03 => "Intel 80386",
+            0x28 => "ARM",
+            0x3e => "x86-64",
+            0xb7 => "ARM aarch64",
+            0xf3 => "RISC-V",
+            _ => "unknown machine",
+        };
+        let endian = if little_endian { "LSB" } else { "MSB" };
+        Some(format!("ELF {} {} {}, {}", bits, endian, kind, machine))
+    }
+}
+
+impl Command for FileCommand {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn description(&self) -> &'static str {
+        "Determine file types"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Print the type of each FILE, told from its first bytes rather than its name.\n\n\
+         Recognizes common images, archives, compressed files, documents, executables\n\
+         and scripts; anything else is reported as text or data."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[FlagSpec::new(
+            'b',
+            "brief",
+            "Print only the type, without the file name",
+        )];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-b] FILE..."
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let files = flags.positionals();
+        if files.is_empty() {
+            return Err(ShellError::Builtin {
+                message: "file: missing operand".to_string(),
+                code: 2,
+            });
+        }
+
+        let brief = flags.has_option('b', "brief");
+        let mut status = ExitCode::SUCCESS;
+        for file in files {
+            let description = match Self::describe_path(&context.resolve_path(file)) {
+                Ok(description) => description,
+                Err(e) => {
+                    status = ExitCode::FAILURE;
+                    format!("cannot open: {}", e)
+                }
+            };
+            if brief {
+                println!("{}", description);
+            } else {
+                println!("{}: {}", file, description);
+            }
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(FileCommand::describe(b""), "empty");
+        assert_eq!(
+            FileCommand::describe(b"\x89PNG\r\n\x1a\n\0\0"),
+            "PNG image data"
+        );
+        assert_eq!(
+            FileCommand::describe(b"#!/usr/bin/env python3\nprint()\n"),
+            "python3 script, ASCII text executable"
+        );
+        assert_eq!(FileCommand::describe("grüße\n".as_bytes()), "UTF-8 text");
+        assert_eq!(FileCommand::describe(b"\x00\x01\x02"), "data");
+
+        let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+        elf.resize(16, 0);
+        elf.extend([3, 0, 0x3e, 0]);
+        assert_eq!(
+            FileCommand::describe(&elf),
+            "ELF 64-bit LSB shared object, x86-64"
+        );
+    }
+}
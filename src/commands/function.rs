@@ -0,0 +1,114 @@
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::Flags,
+};
+
+#[derive(Clone)]
+pub struct Functions;
+
+impl Command for Functions {
+    fn name(&self) -> &'static str {
+        "functions"
+    }
+
+    fn description(&self) -> &'static str {
+        "Display function definitions"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Display function definitions.\n\n\
+         Without arguments, print every function as it was defined. With names,\n\
+         print those functions. Remove a function with `unset -f NAME`."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[NAME...]"
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        if args.is_empty() {
+            let mut functions: Vec<_> = context.functions.values().collect();
+            functions.sort_by(|a, b| a.name.cmp(&b.name));
+            for function in functions {
+                println!("{}", function.text);
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut status = ExitCode::SUCCESS;
+        for name in args {
+            match context.functions.get(*name) {
+                Some(function) => println!("{}", function.text),
+                None => {
+                    eprintln!("functions: {}: not found", name);
+                    status = ExitCode::FAILURE;
+                }
+            }
+        }
+        Ok(status)
+    }
+}
+
+#[derive(Clone)]
+pub struct Local;
+
+impl Command for Local {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn description(&self) -> &'static str {
+        "Define variables local to a function"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Define variables local to a function.\n\n\
+         Each NAME gets back the value it had before once the function returns.\n\
+         A NAME without a VALUE is unset until the function sets it."
+    }
+
+    fn usage(&self) -> &'static str {
+        "NAME[=VALUE]..."
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        if context.scopes.is_empty() {
+            return Err(ShellError::builtin("local: can only be used in a function"));
+        }
+
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (*arg, None),
+            };
+            if name.is_empty() {
+                return Err(ShellError::builtin(format!(
+                    "local: `{}`: not a valid name",
+                    arg
+                )));
+            }
+            let previous = context.var(name).map(String::from);
+            if let Some(scope) = context.scopes.last_mut() {
+                scope.entry(name.to_string()).or_insert(previous);
+            }
+            match value {
+                Some(value) => context.set_var(name, value),
+                None => {
+                    context.unset_var(name);
+                }
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
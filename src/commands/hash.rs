@@ -0,0 +1,87 @@
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::{FlagSpec, Flags},
+};
+
+#[derive(Clone)]
+pub struct Hash;
+
+impl Command for Hash {
+    fn name(&self) -> &'static str {
+        "hash"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show or reset the remembered locations of commands"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Show or reset where the shell finds commands on PATH.\n\n\
+         Without arguments, list the commands run so far with how often each was\n\
+         run and where it was found. With names, look each one up on PATH again\n\
+         and remember the result."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[FlagSpec::new(
+            'r',
+            "reset",
+            "Forget every remembered location and rescan PATH",
+        )];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-r] [NAME...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        if flags.has_flag('r') {
+            context.path_cache.rehash();
+        }
+
+        let names = flags.positionals();
+        if names.is_empty() {
+            if flags.has_flag('r') {
+                return Ok(ExitCode::SUCCESS);
+            }
+            let hits = context.path_cache.hits();
+            if hits.is_empty() {
+                println!("hash: hash table empty");
+                return Ok(ExitCode::SUCCESS);
+            }
+            println!("hits\tcommand");
+            for (_, path, count) in hits {
+                println!("{:>4}\t{}", count, path.display());
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut missing = Vec::new();
+        for name in names {
+            // Builtins never go through PATH, so there is nothing to remember
+            if context.is_builtin(name) {
+                continue;
+            }
+            if name.contains('/') || context.path_cache.search(name).is_none() {
+                missing.push(name.as_str());
+            }
+        }
+        match missing.as_slice() {
+            [] => Ok(ExitCode::SUCCESS),
+            names => Err(ShellError::builtin(
+                names
+                    .iter()
+                    .map(|name| format!("hash: {}: not found", name))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )),
+        }
+    }
+}
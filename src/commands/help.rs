@@ -1 +1,84 @@
+use std::{collections::BTreeMap, fmt::Write};
 
+use crate::{
+    core::{
+        command::{help_text, Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::Flags,
+    },
+    pager,
+};
+
+/// Lists builtins, or prints the usage of one.
+///
+/// The help pages are generated once from the registered commands, since the
+/// registry is not reachable from inside `execute`.
+#[derive(Clone)]
+pub struct Help {
+    pages: BTreeMap<&'static str, (&'static str, String)>,
+}
+
+impl Help {
+    pub fn new(commands: &[Box<dyn Command>]) -> Self {
+        let mut help = Self {
+            pages: commands
+                .iter()
+                .map(|cmd| (cmd.name(), (cmd.description(), help_text(cmd.as_ref()))))
+                .collect(),
+        };
+        let own_page = help_text(&help);
+        help.pages
+            .insert(help.name(), (help.description(), own_page));
+        help
+    }
+}
+
+impl Command for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show help for builtin commands"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Without arguments, list every builtin. With a name, show its usage and options."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[COMMAND]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let Some(name) = flags.positionals().first() else {
+            let width = self.pages.keys().map(|name| name.len()).max().unwrap_or(0);
+            let mut output = String::new();
+            for (name, (description, _)) in &self.pages {
+                writeln!(output, "{:<width$}  {}", name, description).ok();
+            }
+            pager::page(&output, &context.variables, context.options.accessible)?;
+            return Ok(ExitCode::SUCCESS);
+        };
+
+        match self.pages.get(name.as_str()) {
+            Some((_, page)) => {
+                pager::page(
+                    &format!("{}\n", page),
+                    &context.variables,
+                    context.options.accessible,
+                )?;
+                Ok(ExitCode::SUCCESS)
+            }
+            None => Err(ShellError::builtin(format!(
+                "help: no help topics match '{}'",
+                name
+            ))),
+        }
+    }
+}
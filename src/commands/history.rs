@@ -1,8 +1,14 @@
-use crate::core::{
-    command::{Command, CommandContext},
-    flags::Flags,
+use std::fmt::Write;
+
+use crate::{
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::ShellResult,
+        flags::{FlagSpec, Flags},
+        records::{Table, Value, CSV_FLAG, JSON_FLAG},
+    },
+    pager,
 };
-use std::error::Error;
 
 #[derive(Clone)]
 pub struct History;
@@ -20,15 +26,33 @@ impl Command for History {
         "Display the command history with line numbers"
     }
 
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[JSON_FLAG, CSV_FLAG];
+        FLAGS
+    }
+
     fn execute(
         &self,
         _args: &[&str],
-        _flags: &Flags,
-        context: &CommandContext,
-    ) -> Result<(), Box<dyn Error>> {
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        if Table::wanted(flags) {
+            let mut table = Table::new(&["index", "command"]);
+            for (i, cmd) in context.history.iter().enumerate() {
+                table
+                    .rows
+                    .push(vec![Value::Int(i as i64 + 1), Value::Text(cmd.clone())]);
+            }
+            table.print_data(flags);
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut output = String::new();
         for (i, cmd) in context.history.iter().enumerate() {
-            println!("{:5} {}", i + 1, cmd);
+            writeln!(output, "{:5} {}", i + 1, cmd).ok();
         }
-        Ok(())
+        pager::page(&output, &context.variables, context.options.accessible)?;
+        Ok(ExitCode::SUCCESS)
     }
 }
@@ -0,0 +1,137 @@
+use std::io::{self, Write};
+
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::{FlagSpec, Flags},
+    records::{Table, Value, CSV_FLAG, JSON_FLAG},
+};
+
+#[derive(Clone)]
+pub struct Jobs;
+
+impl Command for Jobs {
+    fn name(&self) -> &'static str {
+        "jobs"
+    }
+
+    fn description(&self) -> &'static str {
+        "List background jobs and show their output"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "List the jobs started with '&', including those that finished since the\n\
+         last prompt; a finished job is shown as Done or Exit N and then forgotten.\n\n\
+         A background job's output is collected instead of being written over the\n\
+         prompt. Use -o to show what a job has printed so far; the rest is shown\n\
+         when the job finishes. Set pass_through = true under [jobs] in the config\n\
+         file to let jobs write to the terminal directly.\n\n\
+         The current job is marked with + and the one before it with -. JOB is a\n\
+         job spec such as %1, %+, %- or %str (see fg)."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[
+            FlagSpec::new(
+                'o',
+                "output",
+                "Show the output the jobs have printed so far",
+            ),
+            JSON_FLAG,
+            CSV_FLAG,
+        ];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-o] [JOB...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let mut ids = Vec::new();
+        for spec in flags.positionals() {
+            let spec = if spec.starts_with('%') {
+                spec.to_string()
+            } else {
+                format!("%{}", spec)
+            };
+            let job = context
+                .jobs
+                .resolve(&spec)
+                .map_err(|e| ShellError::builtin(format!("jobs: {}", e)))?;
+            ids.push(job.id());
+        }
+        let current = context.jobs.current().map(|job| job.id());
+        let previous = context.jobs.previous().map(|job| job.id());
+        if flags.positionals().is_empty() {
+            ids.extend(context.jobs.jobs().iter().map(|job| job.id()));
+        }
+
+        let mut table = Table::new(&["id", "pid", "state", "command", "output"]);
+        let mut stdout = io::stdout();
+        for id in ids {
+            let marker = if Some(id) == current {
+                '+'
+            } else if Some(id) == previous {
+                '-'
+            } else {
+                ' '
+            };
+
+            if let Some(finished) = context.jobs.reap_job(id)? {
+                if Table::wanted(flags) {
+                    table.rows.push(vec![
+                        Value::Int(id as i64),
+                        Value::Int(finished.pid.into()),
+                        Value::Text(finished.state()),
+                        Value::Text(finished.command),
+                        Value::Size(finished.output.len() as u64),
+                    ]);
+                } else if flags.has_flag('o') {
+                    stdout.write_all(&finished.output)?;
+                } else {
+                    println!(
+                        "[{}]{} {} {}",
+                        id,
+                        marker,
+                        finished.state(),
+                        finished.command
+                    );
+                }
+                continue;
+            }
+
+            let Some(job) = context.jobs.jobs().iter().find(|job| job.id() == id) else {
+                continue;
+            };
+            if Table::wanted(flags) {
+                table.rows.push(vec![
+                    Value::Int(id as i64),
+                    Value::Int(job.pid().into()),
+                    Value::Text("Running".to_string()),
+                    Value::Text(job.command().to_string()),
+                    Value::Size(job.pending_output() as u64),
+                ]);
+            } else if flags.has_flag('o') {
+                stdout.write_all(&job.take_output())?;
+            } else {
+                let pending = match job.pending_output() {
+                    0 => String::new(),
+                    bytes => format!("  ({} bytes of output, see jobs -o)", bytes),
+                };
+                println!("[{}]{} Running {}{}", id, marker, job.command(), pending);
+            }
+        }
+
+        if Table::wanted(flags) {
+            table.print_data(flags);
+        }
+        stdout.flush()?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
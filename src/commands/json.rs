@@ -0,0 +1,276 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+};
+
+use serde_json::Value;
+
+use crate::{
+    color::Color,
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::{FlagSpec, Flags},
+    },
+};
+
+const FLAGS: &[FlagSpec] = &[
+    FlagSpec::new('c', "compact", "Print each value on one line"),
+    FlagSpec::new('r', "raw", "Print strings without quotes"),
+];
+
+/// One step of a path like `.items[0].name`
+#[derive(Debug, PartialEq)]
+enum Step {
+    Key(String),
+    Index(i64),
+}
+
+/// Splits a path into steps; `.` on its own is the whole value
+fn parse_path(path: &str) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let (index, after) = after
+                .split_once(']')
+                .ok_or_else(|| format!("{}: unclosed '['", path))?;
+            let index = index
+                .trim()
+                .parse()
+                .map_err(|_| format!("{}: '{}' is not an index", path, index))?;
+            steps.push(Step::Index(index));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix(".\"") {
+            // Quoted keys can hold the dots and brackets a plain one can't
+            let (key, after) = after
+                .split_once('"')
+                .ok_or_else(|| format!("{}: unclosed '\"'", path))?;
+            steps.push(Step::Key(key.to_string()));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end > 0 {
+                steps.push(Step::Key(after[..end].to_string()));
+            } else if !after.is_empty() && !after.starts_with('[') {
+                return Err(format!("{}: empty key", path));
+            }
+            rest = &after[end..];
+        } else {
+            return Err(format!("{}: paths start with '.' or '['", path));
+        }
+    }
+    Ok(steps)
+}
+
+/// Follows `steps` into `value`; a missing key or index gives `null`, as in jq
+fn query<'a>(value: &'a Value, steps: &[Step]) -> Result<&'a Value, String> {
+    let mut current = value;
+    for step in steps {
+        current = match (step, current) {
+            (Step::Key(key), Value::Object(map)) => map.get(key).unwrap_or(&Value::Null),
+            (Step::Index(index), Value::Array(items)) => {
+                let index = if *index < 0 {
+                    items.len() as i64 + index
+                } else {
+                    *index
+                };
+                usize::try_from(index)
+                    .ok()
+                    .and_then(|index| items.get(index))
+                    .unwrap_or(&Value::Null)
+            }
+            (_, Value::Null) => &Value::Null,
+            (Step::Key(key), other) => {
+                return Err(format!("cannot look up '{}' in {}", key, kind(other)))
+            }
+            (Step::Index(index), other) => {
+                return Err(format!("cannot index {} with {}", kind(other), index))
+            }
+        };
+    }
+    Ok(current)
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Formats `value` as JSON, colored like jq and indented unless `compact`
+pub(super) fn render(value: &Value, compact: bool) -> String {
+    let mut out = String::new();
+    render_into(&mut out, value, compact, 0);
+    out
+}
+
+fn render_into(out: &mut String, value: &Value, compact: bool, depth: usize) {
+    let newline = |out: &mut String, depth: usize| {
+        if !compact {
+            out.push('\n');
+            out.push_str(&"  ".repeat(depth));
+        }
+    };
+
+    match value {
+        Value::Null => out.push_str(&Color::BrightBlack.paint("null")),
+        Value::Bool(b) => out.push_str(&Color::Yellow.paint(&b.to_string())),
+        Value::Number(n) => out.push_str(&Color::Cyan.paint(&n.to_string())),
+        Value::String(s) => out.push_str(&Color::Green.paint(&Value::from(s.as_str()).to_string())),
+        Value::Array(items) if items.is_empty() => out.push_str("[]"),
+        Value::Object(map) if map.is_empty() => out.push_str("{}"),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, depth + 1);
+                render_into(out, item, compact, depth + 1);
+            }
+            newline(out, depth);
+            out.push(']');
+        }
+        Value::Object(map) => {
+            out.push('{');
+            for (i, (key, item)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, depth + 1);
+                out.push_str(&Color::BrightBlue.paint(&Value::from(key.as_str()).to_string()));
+                out.push_str(if compact { ":" } else { ": " });
+                render_into(out, item, compact, depth + 1);
+            }
+            newline(out, depth);
+            out.push('}');
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Json;
+
+impl Json {
+    /// Prints the selected part of every JSON value in `input`
+    fn print_values(input: impl Read, steps: &[Step], flags: &Flags) -> Result<(), String> {
+        let values = serde_json::Deserializer::from_reader(BufReader::new(input)).into_iter();
+        for value in values {
+            let value = value.map_err(|e| e.to_string())?;
+            match query(&value, steps)? {
+                Value::String(s) if flags.has_option('r', "raw") => println!("{}", s),
+                selected => println!("{}", render(selected, flags.has_option('c', "compact"))),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Command for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn description(&self) -> &'static str {
+        "Pretty-print and query JSON"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Read JSON from each FILE, or standard input when there is none, and print it\n\
+         indented and colored. Input may hold several values one after another.\n\n\
+         PATH picks out part of each value: `.items[0].name` is the name of the first\n\
+         item, `[-1]` the last element and `.\"a.b\"` a key containing a dot. Keys\n\
+         and indexes that don't exist give null."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-cr] [PATH] [FILE...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let mut operands = flags.positionals();
+        let mut steps = Vec::new();
+        if let Some(path) = operands
+            .first()
+            .filter(|path| path.starts_with('.') || path.starts_with('['))
+        {
+            steps = parse_path(path).map_err(|e| ShellError::Builtin {
+                message: format!("json: {}", e),
+                code: 2,
+            })?;
+            operands = &operands[1..];
+        }
+
+        if operands.is_empty() {
+            return Self::print_values(io::stdin().lock(), &steps, flags)
+                .map(|_| ExitCode::SUCCESS)
+                .map_err(|e| ShellError::builtin(format!("json: {}", e)));
+        }
+
+        let mut status = ExitCode::SUCCESS;
+        for file in operands {
+            let result = File::open(context.resolve_path(file))
+                .map_err(|e| e.to_string())
+                .and_then(|input| Self::print_values(input, &steps, flags));
+            if let Err(e) = result {
+                eprintln!("json: {}: {}", file, e);
+                status = ExitCode::FAILURE;
+            }
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(parse_path(".").unwrap(), []);
+        assert_eq!(
+            parse_path(".items[0].name").unwrap(),
+            [
+                Step::Key("items".into()),
+                Step::Index(0),
+                Step::Key("name".into())
+            ]
+        );
+        assert_eq!(
+            parse_path("[-1].\"a.b\"").unwrap(),
+            [Step::Index(-1), Step::Key("a.b".into())]
+        );
+        assert!(parse_path("items").is_err());
+        assert!(parse_path(".a[x]").is_err());
+        assert!(parse_path(".a[0").is_err());
+    }
+
+    #[test]
+    fn test_query() {
+        let value = json!({"items": [{"name": "a"}, {"name": "b"}], "n": 1});
+        let get = |path| query(&value, &parse_path(path).unwrap()).cloned();
+
+        assert_eq!(get(".items[1].name"), Ok(json!("b")));
+        assert_eq!(get(".items[-2].name"), Ok(json!("a")));
+        assert_eq!(get(".items[5]"), Ok(Value::Null));
+        assert_eq!(get(".missing.deeper"), Ok(Value::Null));
+        assert!(get(".n.x").is_err());
+        assert!(get(".items.name").is_err());
+    }
+}
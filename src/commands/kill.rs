@@ -0,0 +1,146 @@
+use std::io;
+
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::Flags,
+};
+
+/// The signals `kill` knows by name
+const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("WINCH", libc::SIGWINCH),
+];
+
+#[derive(Clone)]
+pub struct Kill;
+
+impl Kill {
+    /// Reads a signal given as a number, a name, or a name with the SIG prefix
+    fn parse_signal(text: &str) -> Option<i32> {
+        if let Ok(number) = text.parse() {
+            return Some(number);
+        }
+        let name = text.to_ascii_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        SIGNALS
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, number)| *number)
+    }
+
+    fn usage_error(message: impl Into<String>) -> ShellError {
+        ShellError::Builtin {
+            message: message.into(),
+            code: 2,
+        }
+    }
+}
+
+impl Command for Kill {
+    fn name(&self) -> &'static str {
+        "kill"
+    }
+
+    fn description(&self) -> &'static str {
+        "Send a signal to jobs or processes"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Send SIGNAL, or TERM by default, to each TARGET. A TARGET is a job spec\n\
+         such as %1, %+, %- or %str (see fg) or a process ID.\n\n\
+         SIGNAL can be given as -9, -KILL, -SIGKILL or -s KILL. Use -l to list\n\
+         the signal names."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-s SIGNAL | -SIGNAL] TARGET... | -l"
+    }
+
+    /// `-9` and `-KILL` are signals, not flags
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let mut signal = libc::SIGTERM;
+        let mut targets = args;
+        match args.first().copied() {
+            Some("-l") | Some("--list") => {
+                for (name, number) in SIGNALS {
+                    println!("{:>2}) SIG{}", number, name);
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+            Some("-s") => {
+                let name = args
+                    .get(1)
+                    .ok_or_else(|| Self::usage_error("kill: -s: expected a signal name"))?;
+                signal = Self::parse_signal(name)
+                    .ok_or_else(|| Self::usage_error(format!("kill: {}: unknown signal", name)))?;
+                targets = &args[2..];
+            }
+            Some("--") => targets = &args[1..],
+            Some(arg) if arg.len() > 1 && arg.starts_with('-') && !arg.starts_with("-%") => {
+                signal = Self::parse_signal(&arg[1..]).ok_or_else(|| {
+                    Self::usage_error(format!("kill: {}: unknown signal", &arg[1..]))
+                })?;
+                targets = &args[1..];
+            }
+            _ => {}
+        }
+        if targets.is_empty() {
+            return Err(Self::usage_error(format!(
+                "kill: usage: kill {}",
+                self.usage()
+            )));
+        }
+
+        let mut errors = Vec::new();
+        for target in targets {
+            let pid = if target.starts_with('%') {
+                context
+                    .jobs
+                    .resolve(target)
+                    .map(|job| job.pid() as libc::pid_t)
+            } else {
+                target
+                    .parse()
+                    .map_err(|_| format!("{}: arguments must be process or job IDs", target))
+            };
+            let result = pid.and_then(|pid| {
+                if unsafe { libc::kill(pid, signal) } == 0 {
+                    Ok(())
+                } else {
+                    Err(format!("({}) - {}", pid, io::Error::last_os_error()))
+                }
+            });
+            if let Err(e) = result {
+                errors.push(format!("kill: {}", e));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(ExitCode::SUCCESS)
+        } else {
+            Err(ShellError::builtin(errors.join("\n")))
+        }
+    }
+}
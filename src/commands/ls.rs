@@ -1,8 +1,4 @@
-use colored::Colorize;
-use core::num;
 use std::{
-    env,
-    error::Error,
     fs::{self, DirEntry, FileType, Metadata},
     os::unix::fs::MetadataExt,
     path::{Path, PathBuf},
@@ -10,11 +6,17 @@ use std::{
 };
 
 use crate::{
-    core::{command::Command, command::CommandContext, flags::Flags},
+    color::{ColorChoice, Palette},
+    core::{
+        command::{Command, CommandContext, ExitCode, Records},
+        error::{ShellError, ShellResult},
+        flags::{FlagSpec, Flags},
+        records::{Table, Value, CSV_FLAG, JSON_FLAG},
+    },
     utils,
 };
 
-type DirResult<T> = Result<T, Box<dyn Error>>;
+type DirResult<T> = ShellResult<T>;
 
 #[derive(Debug, Clone)]
 pub struct ListDirectory;
@@ -36,32 +38,7 @@ impl FileEntry {
     }
 
     fn format_permissions(&self) -> String {
-        let mode = self.metadata.mode();
-        let mut perms = String::with_capacity(10);
-
-        // File type
-        perms.push(match self.file_type {
-            t if t.is_dir() => 'd',
-            t if t.is_symlink() => 'l',
-            _ => '-',
-        });
-
-        // Owner permissions
-        perms.push(if mode & 0o400 != 0 { 'r' } else { '-' });
-        perms.push(if mode & 0o200 != 0 { 'w' } else { '-' });
-        perms.push(if mode & 0o100 != 0 { 'x' } else { '-' });
-
-        // Group permissions
-        perms.push(if mode & 0o040 != 0 { 'r' } else { '-' });
-        perms.push(if mode & 0o020 != 0 { 'w' } else { '-' });
-        perms.push(if mode & 0o010 != 0 { 'x' } else { '-' });
-
-        // Others permissions
-        perms.push(if mode & 0o004 != 0 { 'r' } else { '-' });
-        perms.push(if mode & 0o002 != 0 { 'w' } else { '-' });
-        perms.push(if mode & 0o001 != 0 { 'x' } else { '-' });
-
-        perms
+        format_mode(self.metadata.mode())
     }
 
     fn format_long(&self) -> DirResult<String> {
@@ -70,8 +47,9 @@ impl FileEntry {
         let mtime = self
             .metadata
             .modified()?
-            .duration_since(UNIX_EPOCH)?
-            .as_secs();
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
 
         let time_str = utils::format_time(mtime);
 
@@ -84,30 +62,78 @@ impl FileEntry {
         ))
     }
 
-    fn colorize(&self) -> String {
+    fn colorize(&self, palette: &Palette) -> String {
         if self.file_type.is_dir() {
-            self.name.bright_blue().to_string()
+            palette.directory.paint(&self.name)
         } else if self.metadata.mode() & 0o111 != 0 {
-            self.name.green().to_string()
+            palette.executable.paint(&self.name)
         } else {
             self.name.clone()
         }
     }
 }
 
+/// Renders `st_mode` the way `ls -l` does, e.g. `drwxr-sr-x` or `-rwsr-xr-x`.
+///
+/// Works from the raw mode bits rather than `FileType`, since the type and
+/// permission bits are the same on Linux (glibc and musl), macOS and the BSDs.
+fn format_mode(mode: u32) -> String {
+    const S_IFMT: u32 = 0o170000;
+
+    let file_type = match mode & S_IFMT {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        0o010000 => 'p',
+        0o140000 => 's',
+        // Whiteouts only exist on union mounts of BSD-derived systems
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "openbsd",
+            target_os = "netbsd",
+            target_os = "dragonfly"
+        ))]
+        0o160000 => 'w',
+        _ => '-',
+    };
+
+    // Each class's execute slot doubles as the display for its special bit
+    let class = |shift: u32, special: u32, set_char: char| {
+        let bits = (mode >> shift) & 0o7;
+        let execute = match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => set_char,
+            (false, true) => set_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        };
+        [
+            if bits & 0o4 != 0 { 'r' } else { '-' },
+            if bits & 0o2 != 0 { 'w' } else { '-' },
+            execute,
+        ]
+    };
+
+    let mut perms = String::with_capacity(10);
+    perms.push(file_type);
+    perms.extend(class(6, 0o4000, 's'));
+    perms.extend(class(3, 0o2000, 's'));
+    perms.extend(class(0, 0o1000, 't'));
+    perms
+}
+
 #[derive(Debug, Default)]
 struct ListOptions {
     show_hidden: bool,
     long_format: bool,
-    help: bool,
 }
 
 impl ListOptions {
     fn from_flags(flags: &Flags) -> Self {
         Self {
-            show_hidden: flags.has_flag('a'),
+            show_hidden: flags.has_option('a', "all"),
             long_format: flags.has_flag('l'),
-            help: flags.has_flag('?'),
         }
     }
 }
@@ -117,23 +143,31 @@ impl Command for ListDirectory {
         "ls"
     }
 
-    fn execute(&self, args: &[&str], flags: &Flags, _context: &CommandContext) -> DirResult<()> {
-        let path = self.get_target_path(args)?;
+    fn execute(
+        &self,
+        args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> DirResult<ExitCode> {
+        if Table::wanted(flags) {
+            self.execute_records(None, args, flags, context)?
+                .print_data(flags);
+            return Ok(ExitCode::SUCCESS);
+        }
+        let path = self.get_target_path(flags, context);
         let options = ListOptions::from_flags(flags);
-
-        if options.help {
-            println!("{}", self.extended_description());
-            return Ok(());
+        if let Some(when) = flags.get_long_value("color") {
+            when.parse::<ColorChoice>()?.apply(&context.variables);
         }
 
         let entries = self.read_directory_entries(&path, &options)?;
-        self.display_entries(&entries, &options)?;
+        self.display_entries(&entries, &options, &context.palette)?;
 
         if !options.long_format && !entries.is_empty() {
             println!();
         }
 
-        Ok(())
+        Ok(ExitCode::SUCCESS)
     }
 
     fn description(&self) -> &'static str {
@@ -142,20 +176,66 @@ impl Command for ListDirectory {
 
     fn extended_description(&self) -> &'static str {
         "List directory contents with optional formatting.\n\n\
-         Flags:\n\
-         -a: Show hidden files\n\
-         -l: Use long listing format\n\n\
          If no path is provided, the current directory is used."
     }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[
+            FlagSpec::new('a', "all", "Show hidden files"),
+            FlagSpec::short('l', "Use long listing format"),
+            FlagSpec::long("color", "Colorize names: auto, always or never").takes_value("WHEN"),
+            JSON_FLAG,
+            CSV_FLAG,
+        ];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[PATH]"
+    }
+
+    fn records(&self) -> Records {
+        Records::Produces
+    }
+
+    fn execute_records(
+        &self,
+        _input: Option<Table>,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<Table> {
+        let path = self.get_target_path(flags, context);
+        let entries = self.read_directory_entries(&path, &ListOptions::from_flags(flags))?;
+
+        let mut table = Table::new(&["name", "type", "size", "modified"]);
+        for entry in entries {
+            let kind = if entry.file_type.is_dir() {
+                "dir"
+            } else if entry.file_type.is_symlink() {
+                "symlink"
+            } else if entry.file_type.is_file() {
+                "file"
+            } else {
+                "other"
+            };
+            table.rows.push(vec![
+                Value::Text(entry.name),
+                Value::Text(kind.to_string()),
+                Value::Size(entry.metadata.len()),
+                Value::Time(entry.metadata.mtime()),
+            ]);
+        }
+        Ok(table)
+    }
 }
 
 impl ListDirectory {
-    fn get_target_path(&self, args: &[&str]) -> DirResult<PathBuf> {
-        Ok(args
-            .iter()
-            .find(|arg| !arg.starts_with('-'))
-            .map(|&arg| Ok(PathBuf::from(arg)))
-            .unwrap_or_else(env::current_dir)?)
+    fn get_target_path(&self, flags: &Flags, context: &CommandContext) -> PathBuf {
+        match flags.positionals().first() {
+            Some(arg) => context.resolve_path(arg),
+            None => context.cwd.clone(),
+        }
     }
 
     fn read_directory_entries(
@@ -163,7 +243,8 @@ impl ListDirectory {
         path: &Path,
         options: &ListOptions,
     ) -> DirResult<Vec<FileEntry>> {
-        let mut entries = fs::read_dir(path)?
+        let mut entries = fs::read_dir(path)
+            .map_err(|e| ShellError::builtin(format!("ls: {}: {}", path.display(), e)))?
             .filter_map(|entry| {
                 let entry = entry.ok()?;
                 let file_name = entry.file_name().to_string_lossy().into_owned();
@@ -180,29 +261,33 @@ impl ListDirectory {
         Ok(entries)
     }
 
-    fn display_entries(&self, entries: &[FileEntry], options: &ListOptions) -> DirResult<()> {
+    fn display_entries(
+        &self,
+        entries: &[FileEntry],
+        options: &ListOptions,
+        palette: &Palette,
+    ) -> DirResult<()> {
         if options.long_format {
-            self.display_long_format(entries)
+            self.display_long_format(entries, palette)
         } else {
-            self.display_grid_format(entries)
+            self.display_grid_format(entries, palette)
         }
     }
 
-    fn display_long_format(&self, entries: &[FileEntry]) -> DirResult<()> {
+    fn display_long_format(&self, entries: &[FileEntry], palette: &Palette) -> DirResult<()> {
         for entry in entries {
             let formatted = entry.format_long()?;
-            println!("{} {}", formatted, entry.colorize());
+            println!("{} {}", formatted, entry.colorize(palette));
         }
         Ok(())
     }
 
-    fn display_grid_format(&self, entries: &[FileEntry]) -> DirResult<()> {
+    fn display_grid_format(&self, entries: &[FileEntry], palette: &Palette) -> DirResult<()> {
         if entries.is_empty() {
             return Ok(());
         }
 
-        // Get terminal width (fallback to 80 if can't determine)
-        let term_width = term_size::dimensions().map(|(w, _)| w).unwrap_or(80);
+        let term_width = utils::term_width();
 
         // Calculate max length of visible characters by using the original name
         let max_len = entries
@@ -213,12 +298,12 @@ impl ListDirectory {
 
         let col_width = max_len + 2;
         let num_cols = std::cmp::max(1, term_width / col_width);
-        let num_rows = (entries.len() + num_cols - 1) / num_cols;
+        let num_rows = entries.len().div_ceil(num_cols);
 
         // dont worry about aligninf for other rows
         if num_rows == 1 {
             for entry in entries {
-                let colored_name = entry.colorize();
+                let colored_name = entry.colorize(palette);
                 println!("{:width$}", colored_name, width = col_width);
             }
             return Ok(());
@@ -234,7 +319,7 @@ impl ListDirectory {
                 }
 
                 let entry = &entries[idx];
-                let colored_name = entry.colorize();
+                let colored_name = entry.colorize(palette);
                 line.push_str(&colored_name);
 
                 // Only add padding if this isn't the last column
@@ -251,3 +336,29 @@ impl ListDirectory {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_mode_types() {
+        assert_eq!(format_mode(0o100644), "-rw-r--r--");
+        assert_eq!(format_mode(0o040755), "drwxr-xr-x");
+        assert_eq!(format_mode(0o120777), "lrwxrwxrwx");
+        assert_eq!(format_mode(0o020620), "crw--w----");
+        assert_eq!(format_mode(0o060660), "brw-rw----");
+        assert_eq!(format_mode(0o010644), "prw-r--r--");
+        assert_eq!(format_mode(0o140755), "srwxr-xr-x");
+    }
+
+    #[test]
+    fn test_format_mode_special_bits() {
+        assert_eq!(format_mode(0o104755), "-rwsr-xr-x");
+        assert_eq!(format_mode(0o104644), "-rwSr--r--");
+        assert_eq!(format_mode(0o042755), "drwxr-sr-x");
+        assert_eq!(format_mode(0o102644), "-rw-r-Sr--");
+        assert_eq!(format_mode(0o041777), "drwxrwxrwt");
+        assert_eq!(format_mode(0o041770), "drwxrwx--T");
+    }
+}
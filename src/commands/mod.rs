@@ -1,13 +1,85 @@
+mod alias;
+mod builtin;
 mod cd;
+mod checksum;
+mod command;
+mod config;
+mod diff;
+mod doctor;
 mod echo;
+mod encode;
+mod env;
+mod export;
+mod fetch;
+mod fg;
+mod file;
+mod function;
+mod hash;
+mod help;
 mod history;
+mod jobs;
+mod json;
+mod kill;
 mod ls;
+mod nice;
+mod onchange;
+mod pager;
+mod parallel;
 mod pwd;
+mod read;
+mod realpath;
+mod record;
+mod records;
+mod rehash;
+mod serve;
+mod set;
+mod system;
+mod theme;
+mod timeout;
 mod type_cmd;
+mod unset;
+mod wait;
+mod which;
 
+pub use alias::{Alias, Unalias};
+pub use builtin::Builtin;
 pub use cd::ChangeDirectory;
+pub use checksum::{Md5Sum, Sha256Sum};
+pub use command::CommandBuiltin;
+pub use config::ConfigCommand;
+pub use diff::Diff;
+pub use doctor::Doctor;
 pub use echo::Echo;
+pub use encode::{Base64, UrlDecode, UrlEncode};
+pub use env::Env;
+pub use export::Export;
+pub use fetch::Fetch;
+pub use fg::{Background, Foreground};
+pub use file::FileCommand;
+pub use function::{Functions, Local};
+pub use hash::Hash;
+pub use help::Help;
 pub use history::History;
+pub use jobs::Jobs;
+pub use json::Json;
+pub use kill::Kill;
 pub use ls::ListDirectory;
+pub use nice::Nice;
+pub use onchange::OnChange;
+pub use pager::PagerCommand;
+pub use parallel::Parallel;
 pub use pwd::PrintWorkingDirectory;
+pub use read::Read;
+pub use realpath::RealPath;
+pub use record::Record;
+pub use records::{Select, SortBy, Where};
+pub use rehash::Rehash;
+pub use serve::Serve;
+pub use set::Set;
+pub use system::{Free, Ps, Uptime};
+pub use theme::Theme;
+pub use timeout::Timeout;
 pub use type_cmd::TypeCommand;
+pub use unset::Unset;
+pub use wait::Wait;
+pub use which::Which;
@@ -0,0 +1,103 @@
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    external::Priority,
+    flags::Flags,
+};
+
+/// The niceness increment when none is given, as with the `nice` program
+const DEFAULT_NICE: i32 = 10;
+
+#[derive(Clone)]
+pub struct Nice;
+
+impl Nice {
+    fn usage_error(message: String) -> ShellError {
+        ShellError::Builtin { message, code: 2 }
+    }
+
+    /// Reads the options in front of the command, returning the priority and where the command starts
+    fn parse(args: &[&str]) -> ShellResult<(Priority, usize)> {
+        let mut priority = Priority {
+            nice: DEFAULT_NICE,
+            idle_io: false,
+        };
+        let mut i = 0;
+        while let Some(arg) = args.get(i) {
+            let value = match *arg {
+                "-n" | "--nice" => {
+                    i += 1;
+                    args.get(i).copied()
+                }
+                "--idle-io" => {
+                    priority.idle_io = true;
+                    i += 1;
+                    continue;
+                }
+                "--" => return Ok((priority, i + 1)),
+                arg if arg.starts_with("--nice=") => Some(&arg["--nice=".len()..]),
+                arg if arg.starts_with('-') => {
+                    return Err(Self::usage_error(format!("nice: unknown option '{}'", arg)))
+                }
+                _ => break,
+            };
+            let value = value.ok_or_else(|| Self::usage_error("nice: -n needs a number".into()))?;
+            priority.nice = value
+                .parse()
+                .map_err(|_| Self::usage_error(format!("nice: invalid adjustment '{}'", value)))?;
+            i += 1;
+        }
+        Ok((priority, i))
+    }
+}
+
+impl Command for Nice {
+    fn name(&self) -> &'static str {
+        "nice"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run a program at a lower scheduling priority"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Run COMMAND with its niceness raised by N, 10 if not given.\n\n\
+         -n, --nice N   Change the niceness by N; negative values need privileges\n\
+         --idle-io      Only let the command use the disk when nothing else wants it\n\n\
+         Priorities for commands matching a pattern can be set in the config file:\n\n\
+         [[priorities]]\n\
+         pattern = \"cargo build\"\n\
+         nice = 10\n\
+         idle_io = true"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-n N] [--idle-io] COMMAND [ARG...]"
+    }
+
+    /// The command's own options must reach it untouched
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let (priority, start) = Self::parse(args)?;
+        let Some((command, args)) = args[start..].split_first() else {
+            return Err(Self::usage_error(format!(
+                "nice: usage: nice {}",
+                self.usage()
+            )));
+        };
+
+        context
+            .external_command()
+            .with_priority(priority)
+            .execute(command, args)
+            .map_err(|e| ShellError::from_spawn(command, e))
+    }
+}
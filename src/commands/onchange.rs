@@ -0,0 +1,205 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    color::Color,
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::Flags,
+    },
+    glob,
+    signals::InterruptGuard,
+};
+
+/// How often the watched files are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long the files must stay unchanged before the command runs, by default
+const DEFAULT_DELAY: Duration = Duration::from_millis(300);
+
+/// The modification time and size of every watched file
+type Snapshot = BTreeMap<PathBuf, (SystemTime, u64)>;
+
+/// The parsed command line
+struct Watch<'a> {
+    clear: bool,
+    delay: Duration,
+    patterns: &'a [&'a str],
+    command: &'a str,
+    args: &'a [&'a str],
+}
+
+#[derive(Clone)]
+pub struct OnChange;
+
+impl OnChange {
+    fn usage_error(message: impl Into<String>) -> ShellError {
+        ShellError::Builtin {
+            message: message.into(),
+            code: 2,
+        }
+    }
+
+    fn parse<'a>(&self, args: &'a [&'a str]) -> ShellResult<Watch<'a>> {
+        let mut clear = false;
+        let mut delay = DEFAULT_DELAY;
+        let mut rest = args;
+        while let Some((&arg, after)) = rest.split_first() {
+            let value = match arg {
+                "-c" | "--clear" => {
+                    clear = true;
+                    rest = after;
+                    continue;
+                }
+                "-d" | "--delay" => {
+                    let (&value, after) = after
+                        .split_first()
+                        .ok_or_else(|| Self::usage_error("onchange: -d: expected milliseconds"))?;
+                    rest = after;
+                    value
+                }
+                _ => match arg.strip_prefix("--delay=") {
+                    Some(value) => {
+                        rest = after;
+                        value
+                    }
+                    None => break,
+                },
+            };
+            delay = value
+                .parse()
+                .map(Duration::from_millis)
+                .map_err(|_| Self::usage_error(format!("onchange: invalid delay '{}'", value)))?;
+        }
+
+        let usage = || Self::usage_error(format!("onchange: usage: onchange {}", self.usage()));
+        let split = rest.iter().position(|&arg| arg == "--").ok_or_else(usage)?;
+        let (patterns, command) = (&rest[..split], &rest[split + 1..]);
+        let Some((command, args)) = command.split_first() else {
+            return Err(usage());
+        };
+        if patterns.is_empty() {
+            return Err(usage());
+        }
+        Ok(Watch {
+            clear,
+            delay,
+            patterns,
+            command,
+            args,
+        })
+    }
+
+    /// The files each pattern names: matches of a glob, everything under a
+    /// directory, or the path itself, which may not exist yet
+    fn snapshot(patterns: &[&str], cwd: &Path) -> Snapshot {
+        let mut files = Vec::new();
+        for pattern in patterns {
+            let path = cwd.join(pattern);
+            if glob::has_wildcards(pattern) {
+                files.extend(glob::expand(pattern, cwd).into_iter().map(|p| cwd.join(p)));
+            } else if path.is_dir() {
+                files.extend(glob::files_under(&path));
+            } else {
+                files.push(path);
+            }
+        }
+
+        files
+            .into_iter()
+            .filter_map(|path| {
+                let metadata = fs::metadata(&path).ok().filter(|m| m.is_file())?;
+                let modified = metadata.modified().ok()?;
+                Some((path, (modified, metadata.len())))
+            })
+            .collect()
+    }
+
+    fn run(watch: &Watch, context: &CommandContext) -> ShellResult<ExitCode> {
+        if watch.clear {
+            print!("\x1b[2J\x1b[H");
+            io::stdout().flush().ok();
+        }
+        let status = context
+            .external_command()
+            .execute(watch.command, watch.args)
+            .map_err(|e| ShellError::from_spawn(watch.command, e))?;
+        println!(
+            "{}",
+            Color::BrightBlack.paint(&format!(
+                "onchange: exited with {}, waiting for changes (Ctrl-C to stop)",
+                status.code()
+            ))
+        );
+        Ok(status)
+    }
+}
+
+impl Command for OnChange {
+    fn name(&self) -> &'static str {
+        "onchange"
+    }
+
+    fn description(&self) -> &'static str {
+        "Re-run a command whenever files change"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Run COMMAND, then run it again each time a file matching one of the PATTERNs\n\
+         changes, until Ctrl-C. A PATTERN is a file, a directory (every file below it)\n\
+         or a glob, where ** matches any number of directories: quote it so it reaches\n\
+         onchange as written, as in `onchange 'src/**/*.rs' -- cargo test`.\n\n\
+         Changes are batched: COMMAND runs once the files have been still for MS\n\
+         milliseconds, 300 by default. With -c, the screen is cleared before each run.\n\n\
+         The files are checked a few times a second rather than through file system\n\
+         events, so very large trees take a moment to notice."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-c] [-d MS] PATTERN... -- COMMAND [ARG...]"
+    }
+
+    // Everything after -- belongs to the command being run
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let watch = self.parse(args)?;
+        let cwd = context.resolve_path(".");
+        let guard = InterruptGuard::new();
+
+        let mut seen = Self::snapshot(watch.patterns, &cwd);
+        let mut status = Self::run(&watch, context)?;
+        let mut changed_at: Option<Instant> = None;
+
+        while !guard.interrupted() {
+            thread::sleep(POLL_INTERVAL);
+            let current = Self::snapshot(watch.patterns, &cwd);
+            if current != seen {
+                seen = current;
+                changed_at = Some(Instant::now());
+                continue;
+            }
+            if changed_at.is_some_and(|at| at.elapsed() >= watch.delay) && !guard.interrupted() {
+                changed_at = None;
+                status = Self::run(&watch, context)?;
+                // Whatever the command itself wrote is not a change to react to
+                seen = Self::snapshot(watch.patterns, &cwd);
+            }
+        }
+        Ok(status)
+    }
+}
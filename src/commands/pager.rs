@@ -0,0 +1,62 @@
+use std::{
+    fs,
+    io::{self, IsTerminal, Read},
+};
+
+use crate::{
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::Flags,
+    },
+    pager,
+};
+
+#[derive(Clone)]
+pub struct PagerCommand;
+
+impl Command for PagerCommand {
+    fn name(&self) -> &'static str {
+        "pager"
+    }
+
+    fn description(&self) -> &'static str {
+        "View text one screen at a time"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "View a file, or piped input, one screen at a time.\n\n\
+         Keys: j/k or arrows scroll a line, space/b a page, d/u half a page,\n\
+         g/G jump to the start/end, /pattern searches, n/N repeat the search,\n\
+         q quits."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[FILE]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let text = match flags.positionals().first() {
+            Some(file) => fs::read_to_string(context.resolve_path(file))
+                .map_err(|e| ShellError::builtin(format!("pager: {}: {}", file, e)))?,
+            None if !io::stdin().is_terminal() => {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text)?;
+                text
+            }
+            None => return Err(ShellError::builtin("pager: no file given")),
+        };
+
+        if io::stdout().is_terminal() {
+            pager::run(&text)?;
+        } else {
+            print!("{}", text);
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    color::Color,
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::Flags,
+        jobs::JobTable,
+    },
+};
+
+/// GNU parallel's cap, so the count can't wrap into something that looks like success
+const MAX_FAILURES: i32 = 101;
+
+/// A command to run, and the label its output lines are prefixed with
+struct Task {
+    label: String,
+    words: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct Parallel;
+
+impl Parallel {
+    fn usage_error(message: impl Into<String>) -> ShellError {
+        ShellError::Builtin {
+            message: message.into(),
+            code: 2,
+        }
+    }
+
+    /// Splits off `-j N`, returning the job limit and the rest of the arguments
+    fn parse_jobs<'a>(args: &'a [&'a str]) -> ShellResult<(usize, &'a [&'a str])> {
+        let mut jobs = None;
+        let mut rest = args;
+        while let Some((&arg, after)) = rest.split_first() {
+            let value = match arg {
+                "--" => {
+                    rest = after;
+                    break;
+                }
+                "-j" | "--jobs" => {
+                    let (&value, after) = after
+                        .split_first()
+                        .ok_or_else(|| Self::usage_error("parallel: -j: expected a number"))?;
+                    rest = after;
+                    value
+                }
+                _ => match arg
+                    .strip_prefix("--jobs=")
+                    .or_else(|| arg.strip_prefix("-j"))
+                {
+                    Some(value) => {
+                        rest = after;
+                        value
+                    }
+                    None => break,
+                },
+            };
+            jobs = Some(value.parse().ok().filter(|&n| n > 0).ok_or_else(|| {
+                Self::usage_error(format!("parallel: invalid job count '{}'", value))
+            })?);
+        }
+
+        let jobs = jobs.unwrap_or_else(|| thread::available_parallelism().map_or(1, usize::from));
+        Ok((jobs, rest))
+    }
+
+    /// One task per argument after `:::`, substituted for `{}` or appended
+    fn tasks_from_args(template: &[&str], inputs: &[&str]) -> Vec<Task> {
+        let has_placeholder = template.iter().any(|word| word.contains("{}"));
+        inputs
+            .iter()
+            .map(|input| {
+                let mut words: Vec<String> = template
+                    .iter()
+                    .map(|word| word.replace("{}", input))
+                    .collect();
+                if !has_placeholder {
+                    words.push(input.to_string());
+                }
+                Task {
+                    label: input.to_string(),
+                    words,
+                }
+            })
+            .collect()
+    }
+
+    /// One task per line of stdin, each a command line for `sh -c`
+    fn tasks_from_stdin() -> io::Result<Vec<Task>> {
+        let mut tasks = Vec::new();
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            tasks.push(Task {
+                label: line.clone(),
+                words: vec!["sh".to_string(), "-c".to_string(), line],
+            });
+        }
+        Ok(tasks)
+    }
+
+    /// Prints every complete line in `output`, keeping a trailing partial line in `pending`
+    fn print_lines(label: &str, pending: &mut Vec<u8>, output: &[u8], stdout: &mut impl Write) {
+        pending.extend_from_slice(output);
+        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            write!(stdout, "{} ", Color::Cyan.paint(&format!("[{}]", label))).ok();
+            stdout.write_all(&line).ok();
+        }
+    }
+
+    /// Runs up to `limit` tasks at a time, returning how many failed
+    fn run(tasks: Vec<Task>, limit: usize, context: &CommandContext) -> ShellResult<i32> {
+        let mut table = JobTable::default();
+        let mut queue = tasks.into_iter();
+        let mut pending: HashMap<usize, Vec<u8>> = HashMap::new();
+        let mut failures = 0;
+        let mut stdout = io::stdout();
+
+        loop {
+            while table.jobs().len() < limit {
+                let Some(task) = queue.next() else { break };
+                let (command, args) = task.words.split_first().expect("tasks have a command");
+                let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                match context
+                    .external_command()
+                    .spawn_background_piped(command, &args)
+                {
+                    Ok(child) => {
+                        pending.insert(table.add(child, &task.label), Vec::new());
+                    }
+                    Err(e) => {
+                        eprintln!("parallel: {}", ShellError::from_spawn(command, e));
+                        failures += 1;
+                    }
+                }
+            }
+            if table.jobs().is_empty() {
+                break;
+            }
+
+            for job in table.jobs() {
+                let buffer = pending.entry(job.id()).or_default();
+                Self::print_lines(job.command(), buffer, &job.take_output(), &mut stdout);
+            }
+            for job in table.reap()? {
+                let mut buffer = pending.remove(&job.id).unwrap_or_default();
+                Self::print_lines(&job.command, &mut buffer, &job.output, &mut stdout);
+                if !buffer.is_empty() {
+                    Self::print_lines(&job.command, &mut buffer, b"\n", &mut stdout);
+                }
+                if !job.status.success() {
+                    failures += 1;
+                }
+            }
+            stdout.flush().ok();
+            thread::sleep(Duration::from_millis(20));
+        }
+        Ok(failures)
+    }
+}
+
+impl Command for Parallel {
+    fn name(&self) -> &'static str {
+        "parallel"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run commands concurrently"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Run COMMAND once for each ARG after :::, with the ARG in place of every {}\n\
+         or added at the end when there is no {}. Without :::, each line of standard\n\
+         input is a command line of its own, run by sh.\n\n\
+         At most N commands run at once, one per CPU by default. Each line of output\n\
+         is prefixed with the ARG or command line that printed it.\n\n\
+         The exit status is the number of commands that failed, up to 101."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-j N] [--] COMMAND [ARG...] ::: ARG... | [-j N] < COMMANDS"
+    }
+
+    // Everything after the job count belongs to the commands being run
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let (limit, rest) = Self::parse_jobs(args)?;
+        let tasks = match rest.iter().position(|&arg| arg == ":::") {
+            Some(0) => return Err(Self::usage_error("parallel: ::: needs a command before it")),
+            Some(i) => Self::tasks_from_args(&rest[..i], &rest[i + 1..]),
+            None if rest.is_empty() => Self::tasks_from_stdin()
+                .map_err(|e| ShellError::builtin(format!("parallel: {}", e)))?,
+            None => {
+                return Err(Self::usage_error(format!(
+                    "parallel: usage: parallel {}",
+                    self.usage()
+                )))
+            }
+        };
+
+        let failures = Self::run(tasks, limit, context)?;
+        Ok(ExitCode::new(failures.min(MAX_FAILURES)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_jobs() {
+        fn jobs<'a>(args: &'a [&'a str]) -> Result<(usize, Vec<&'a str>), i32> {
+            Parallel::parse_jobs(args)
+                .map(|(jobs, rest)| (jobs, rest.to_vec()))
+                .map_err(|e| e.exit_code())
+        }
+        assert_eq!(jobs(&["-j", "3", "echo"]), Ok((3, vec!["echo"])));
+        assert_eq!(jobs(&["-j4", "--", "-j", "x"]), Ok((4, vec!["-j", "x"])));
+        assert_eq!(jobs(&["--jobs=2", "--jobs", "5"]), Ok((5, vec![])));
+        assert_eq!(jobs(&["echo", "-j", "2"]).unwrap().1, ["echo", "-j", "2"]);
+        assert!(jobs(&[]).unwrap().0 >= 1);
+
+        assert_eq!(jobs(&["-j"]), Err(2));
+        assert_eq!(jobs(&["-j", "0"]), Err(2));
+        assert_eq!(jobs(&["-jmany"]), Err(2));
+        assert_eq!(jobs(&["--jobs=-1"]), Err(2));
+    }
+
+    #[test]
+    fn test_tasks_from_args() {
+        let tasks = Parallel::tasks_from_args(&["gzip", "-k", "{}.log"], &["a", "b"]);
+        assert_eq!(tasks[0].words, ["gzip", "-k", "a.log"]);
+        assert_eq!(tasks[1].words, ["gzip", "-k", "b.log"]);
+        assert_eq!(tasks[1].label, "b");
+
+        let tasks = Parallel::tasks_from_args(&["echo", "x"], &["1"]);
+        assert_eq!(tasks[0].words, ["echo", "x", "1"]);
+        assert!(Parallel::tasks_from_args(&["echo"], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        let mut context = CommandContext::default();
+        let mut run = |args: &[&str]| {
+            Parallel
+                .execute(args, &Flags::default(), &mut context)
+                .map_err(|e| e.exit_code())
+        };
+        assert_eq!(run(&[":::", "a"]), Err(2));
+        assert_eq!(run(&["-j", "2", ":::"]), Err(2));
+        assert_eq!(run(&["echo", "a"]), Err(2));
+    }
+
+    #[test]
+    fn test_failures_are_capped() {
+        let inputs: Vec<String> = (0..MAX_FAILURES + 5).map(|i| i.to_string()).collect();
+        let mut args = vec!["hermit-no-such-command", ":::"];
+        args.extend(inputs.iter().map(String::as_str));
+        let mut context = CommandContext::default();
+        let status = Parallel
+            .execute(&args, &Flags::default(), &mut context)
+            .unwrap();
+        assert_eq!(status, ExitCode::new(MAX_FAILURES));
+    }
+}
@@ -1,7 +1,6 @@
-use std::{env, error::Error};
-
 use crate::core::{
-    command::{Command, CommandContext},
+    command::{Command, CommandContext, ExitCode},
+    error::ShellResult,
     flags::Flags,
 };
 
@@ -13,10 +12,10 @@ impl Command for PrintWorkingDirectory {
         &self,
         _args: &[&str],
         _flags: &Flags,
-        _context: &CommandContext,
-    ) -> Result<(), Box<dyn Error>> {
-        println!("{}", env::current_dir()?.display());
-        Ok(())
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        println!("{}", context.cwd.display());
+        Ok(ExitCode::SUCCESS)
     }
 
     fn name(&self) -> &'static str {
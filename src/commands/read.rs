@@ -0,0 +1,136 @@
+use crate::{
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::{FlagSpec, Flags},
+    },
+    input,
+};
+
+#[derive(Clone)]
+pub struct Read;
+
+impl Read {
+    /// Drops each backslash, keeping the character after it, as `read` does without `-r`
+    fn unescape(line: &str) -> String {
+        let mut unescaped = String::with_capacity(line.len());
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => unescaped.extend(chars.next()),
+                _ => unescaped.push(c),
+            }
+        }
+        unescaped
+    }
+
+    /// Splits `line` into words for `names`, the last name taking the rest of
+    /// the line, or puts all of it in `$REPLY` when there are no names
+    fn assign(line: &str, names: &[String], context: &mut CommandContext) {
+        let Some((last, names)) = names.split_last() else {
+            context.set_var("REPLY", line);
+            return;
+        };
+        let mut rest = line.trim_start();
+        for name in names {
+            let (word, remaining) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            context.set_var(name, word);
+            rest = remaining.trim_start();
+        }
+        context.set_var(last, rest.trim_end());
+    }
+}
+
+impl Command for Read {
+    fn name(&self) -> &'static str {
+        "read"
+    }
+
+    fn description(&self) -> &'static str {
+        "Read a line into variables"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Read a line from standard input and split it into words, one per NAME.\n\n\
+         The last NAME gets the rest of the line. Without a NAME the whole line goes\n\
+         into $REPLY. Exits with status 1 at the end of input.\n\n\
+         With -s the typing is not shown, and the value never reaches the history or\n\
+         a session recording."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[
+            FlagSpec::short('p', "Show PROMPT first when reading from a terminal")
+                .takes_value("PROMPT"),
+            FlagSpec::short('r', "Keep backslashes instead of treating them as escapes"),
+            FlagSpec::short(
+                's',
+                "Do not echo the input, for passwords and other secrets",
+            ),
+        ];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-rs] [-p PROMPT] [NAME...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let prompt = flags.get_value('p');
+        let line = if flags.has_flag('s') {
+            input::secret(prompt.unwrap_or_default())
+        } else {
+            input::line(prompt)
+        }
+        .map_err(|e| ShellError::builtin(format!("read: {}", e)))?;
+        let Some(line) = line else {
+            return Ok(ExitCode::FAILURE);
+        };
+        let line = if flags.has_flag('r') {
+            line
+        } else {
+            Self::unescape(&line)
+        };
+        Self::assign(&line, flags.positionals(), context);
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(Read::unescape(r"a\ b\\c"), r"a b\c");
+        assert_eq!(Read::unescape(r"end\"), "end");
+        assert_eq!(Read::unescape("plain"), "plain");
+    }
+
+    #[test]
+    fn test_assign() {
+        let mut context = CommandContext::default();
+        let names = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        Read::assign("  one two  three four ", &names(&["A", "B"]), &mut context);
+        assert_eq!(context.var("A"), Some("one"));
+        assert_eq!(context.var("B"), Some("two  three four"));
+
+        Read::assign("x", &names(&["A", "B", "C"]), &mut context);
+        assert_eq!(context.var("A"), Some("x"));
+        assert_eq!(context.var("C"), Some(""));
+
+        Read::assign("  kept as is ", &[], &mut context);
+        assert_eq!(context.var("REPLY"), Some("  kept as is "));
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::{FlagSpec, Flags},
+};
+
+#[derive(Clone)]
+pub struct RealPath;
+
+impl RealPath {
+    /// The path from `base` to `path`, both absolute and free of `..`
+    fn relative(path: &Path, base: &Path) -> PathBuf {
+        let path: Vec<Component> = path.components().collect();
+        let base: Vec<Component> = base.components().collect();
+        let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+
+        let mut relative = PathBuf::new();
+        for _ in common..base.len() {
+            relative.push("..");
+        }
+        relative.extend(&path[common..]);
+        if relative.as_os_str().is_empty() {
+            relative.push(".");
+        }
+        relative
+    }
+}
+
+impl Command for RealPath {
+    fn name(&self) -> &'static str {
+        "realpath"
+    }
+
+    fn description(&self) -> &'static str {
+        "Print the resolved absolute path"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Print each PATH as an absolute path, with symbolic links, `.` and `..` resolved.\n\n\
+         Every part of the path must exist."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] =
+            &[FlagSpec::long("relative-to", "Print the paths relative to DIR").takes_value("DIR")];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[--relative-to=DIR] PATH..."
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let paths = flags.positionals();
+        if paths.is_empty() {
+            return Err(ShellError::Builtin {
+                message: "realpath: missing operand".to_string(),
+                code: 2,
+            });
+        }
+        let base = match flags.get_long_value("relative-to") {
+            Some(dir) => Some(
+                context
+                    .resolve_path(dir)
+                    .canonicalize()
+                    .map_err(|e| ShellError::builtin(format!("realpath: {}: {}", dir, e)))?,
+            ),
+            None => None,
+        };
+
+        let mut status = ExitCode::SUCCESS;
+        for path in paths {
+            match context.resolve_path(path).canonicalize() {
+                Ok(resolved) => match &base {
+                    Some(base) => println!("{}", Self::relative(&resolved, base).display()),
+                    None => println!("{}", resolved.display()),
+                },
+                Err(e) => {
+                    eprintln!("realpath: {}: {}", path, e);
+                    status = ExitCode::FAILURE;
+                }
+            }
+        }
+        Ok(status)
+    }
+}
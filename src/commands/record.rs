@@ -0,0 +1,122 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::{FlagSpec, Flags},
+    recorder::{self, Recorder},
+};
+
+#[derive(Clone)]
+pub struct Record;
+
+impl Record {
+    fn start(context: &mut CommandContext, file: Option<&String>) -> ShellResult<ExitCode> {
+        let file = file.ok_or_else(|| ShellError::builtin("record: start: no file given"))?;
+        if let Some(recorder) = &context.recorder {
+            let path = recorder.lock().map(|r| r.path().display().to_string());
+            return Err(ShellError::builtin(format!(
+                "record: already recording to {}",
+                path.unwrap_or_default()
+            )));
+        }
+
+        let path = context.resolve_path(file);
+        let recorder = Recorder::create(&path)
+            .map_err(|e| ShellError::builtin(format!("record: {}: {}", file, e)))?;
+        context.recorder = Some(Arc::new(Mutex::new(recorder)));
+        Ok(ExitCode::SUCCESS)
+    }
+
+    fn stop(context: &mut CommandContext) -> ShellResult<ExitCode> {
+        let recorder = context
+            .recorder
+            .take()
+            .ok_or_else(|| ShellError::builtin("record: not recording"))?;
+        let mut recorder = recorder.lock().unwrap_or_else(|e| e.into_inner());
+        recorder.flush()?;
+        println!("Recording saved to {}", recorder.path().display());
+        Ok(ExitCode::SUCCESS)
+    }
+
+    fn play(
+        context: &CommandContext,
+        file: Option<&String>,
+        flags: &Flags,
+    ) -> ShellResult<ExitCode> {
+        let file = file.ok_or_else(|| ShellError::builtin("record: play: no file given"))?;
+        let speed = match flags.get_value('s') {
+            Some(speed) => speed
+                .parse::<f64>()
+                .ok()
+                .filter(|speed| *speed > 0.0)
+                .ok_or_else(|| ShellError::builtin(format!("record: invalid speed '{}'", speed)))?,
+            None => 1.0,
+        };
+
+        recorder::replay(&context.resolve_path(file), &mut io::stdout(), speed)
+            .map_err(|e| ShellError::builtin(format!("record: {}: {}", file, e)))?;
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+impl Command for Record {
+    fn name(&self) -> &'static str {
+        "record"
+    }
+
+    fn description(&self) -> &'static str {
+        "Record the session to a file and play it back"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Record the terminal session in asciicast v2 format.\n\n\
+         record start FILE  Start recording prompts, typed commands and their output\n\
+         record stop        Stop recording and save the file\n\
+         record play FILE   Replay a recording with its original timing\n\
+         record             Show whether a recording is running\n\n\
+         Command output is captured as it passes through the shell, so commands see\n\
+         a pipe instead of a terminal and full-screen programs may behave differently."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] =
+            &[FlagSpec::new('s', "speed", "Playback speed multiplier").takes_value("FACTOR")];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[start FILE | stop | play FILE]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let positionals = flags.positionals();
+        match positionals.first().map(String::as_str) {
+            Some("start") => Self::start(context, positionals.get(1)),
+            Some("stop") => Self::stop(context),
+            Some("play") => Self::play(context, positionals.get(1), flags),
+            Some(other) => Err(ShellError::builtin(format!(
+                "record: {}: unknown action",
+                other
+            ))),
+            None => {
+                match &context.recorder {
+                    Some(recorder) => {
+                        let recorder = recorder.lock().unwrap_or_else(|e| e.into_inner());
+                        println!("Recording to {}", recorder.path().display());
+                    }
+                    None => println!("Not recording"),
+                }
+                Ok(ExitCode::SUCCESS)
+            }
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::cmp::Ordering;
+
+use crate::core::{
+    command::{Command, CommandContext, ExitCode, Records},
+    error::{ShellError, ShellResult},
+    flags::{FlagSpec, Flags},
+    records::{Table, Value},
+};
+
+/// What running a table-only command on its own reports
+fn needs_input(name: &str) -> ShellError {
+    ShellError::builtin(format!(
+        "{}: reads a table from the command before it, as in `ls | {} ...`",
+        name, name
+    ))
+}
+
+fn usage_error(name: &str, usage: &str) -> ShellError {
+    ShellError::Builtin {
+        message: format!("{}: usage: {} {}", name, name, usage),
+        code: 2,
+    }
+}
+
+fn column_error(name: &str, message: String) -> ShellError {
+    ShellError::builtin(format!("{}: {}", name, message))
+}
+
+#[derive(Clone)]
+pub struct Where;
+
+impl Where {
+    /// Whether `cell` passes `OPERATOR VALUE`, with `value` read as the cell's kind
+    fn matches(cell: &Value, operator: &str, value: &str) -> Result<bool, String> {
+        match operator {
+            "=~" => return Ok(cell.to_string().contains(value)),
+            "!~" => return Ok(!cell.to_string().contains(value)),
+            _ => {}
+        }
+
+        let value = cell
+            .parse_like(value)
+            .ok_or_else(|| format!("'{}' can't be compared with {}", value, cell))?;
+        let ordering = cell.compare(&value).unwrap_or(Ordering::Equal);
+        match operator {
+            "==" | "=" => Ok(ordering == Ordering::Equal),
+            "!=" => Ok(ordering != Ordering::Equal),
+            "<" => Ok(ordering == Ordering::Less),
+            "<=" => Ok(ordering != Ordering::Greater),
+            ">" => Ok(ordering == Ordering::Greater),
+            ">=" => Ok(ordering != Ordering::Less),
+            _ => Err(format!("unknown operator '{}'", operator)),
+        }
+    }
+}
+
+impl Command for Where {
+    fn name(&self) -> &'static str {
+        "where"
+    }
+
+    fn description(&self) -> &'static str {
+        "Keep the rows of a table that match a condition"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Keep the rows whose COLUMN compares with VALUE as OPERATOR says. Needs\n\
+         structured pipelines (`set -o structured`), as in `ls | where size > 1MB`.\n\n\
+         OPERATOR is one of == != < <= > >=, or =~ and !~ for whether the column\n\
+         contains VALUE. VALUE is read as the column's kind: sizes like 4K or 1.5MB,\n\
+         times like 2024-01-31, 2024-01-31 12:00 or 2d (ago), and durations like 90s."
+    }
+
+    fn usage(&self) -> &'static str {
+        "COLUMN OPERATOR VALUE"
+    }
+
+    // Values such as -1 aren't options
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn records(&self) -> Records {
+        Records::Transforms
+    }
+
+    fn execute_records(
+        &self,
+        input: Option<Table>,
+        args: &[&str],
+        _flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<Table> {
+        let mut table = input.ok_or_else(|| needs_input(self.name()))?;
+        let [column, operator, value @ ..] = args else {
+            return Err(usage_error(self.name(), self.usage()));
+        };
+        if value.is_empty() {
+            return Err(usage_error(self.name(), self.usage()));
+        }
+        let value = value.join(" ");
+        let index = table
+            .column(column)
+            .map_err(|e| column_error(self.name(), e))?;
+
+        let mut rows = Vec::new();
+        for row in table.rows {
+            if Self::matches(&row[index], operator, &value)
+                .map_err(|e| column_error(self.name(), e))?
+            {
+                rows.push(row);
+            }
+        }
+        table.rows = rows;
+        Ok(table)
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        _flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        Err(needs_input(self.name()))
+    }
+}
+
+#[derive(Clone)]
+pub struct SortBy;
+
+impl Command for SortBy {
+    fn name(&self) -> &'static str {
+        "sort-by"
+    }
+
+    fn description(&self) -> &'static str {
+        "Sort the rows of a table by a column"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Sort the rows by COLUMN, smallest, oldest or first in the alphabet first.\n\
+         Needs structured pipelines (`set -o structured`), as in `ls | sort-by modified`."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[FlagSpec::new('r', "reverse", "Sort largest first")];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "COLUMN"
+    }
+
+    fn records(&self) -> Records {
+        Records::Transforms
+    }
+
+    fn execute_records(
+        &self,
+        input: Option<Table>,
+        _args: &[&str],
+        flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<Table> {
+        let mut table = input.ok_or_else(|| needs_input(self.name()))?;
+        let [column] = flags.positionals() else {
+            return Err(usage_error(self.name(), self.usage()));
+        };
+        let index = table
+            .column(column)
+            .map_err(|e| column_error(self.name(), e))?;
+
+        table
+            .rows
+            .sort_by(|a, b| a[index].compare(&b[index]).unwrap_or(Ordering::Equal));
+        if flags.has_option('r', "reverse") {
+            table.rows.reverse();
+        }
+        Ok(table)
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        _flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        Err(needs_input(self.name()))
+    }
+}
+
+#[derive(Clone)]
+pub struct Select;
+
+impl Command for Select {
+    fn name(&self) -> &'static str {
+        "select"
+    }
+
+    fn description(&self) -> &'static str {
+        "Keep only some columns of a table"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Keep the named COLUMNs, in the order given. Needs structured pipelines\n\
+         (`set -o structured`), as in `ps | select pid command`."
+    }
+
+    fn usage(&self) -> &'static str {
+        "COLUMN..."
+    }
+
+    fn records(&self) -> Records {
+        Records::Transforms
+    }
+
+    fn execute_records(
+        &self,
+        input: Option<Table>,
+        _args: &[&str],
+        flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<Table> {
+        let table = input.ok_or_else(|| needs_input(self.name()))?;
+        if flags.positionals().is_empty() {
+            return Err(usage_error(self.name(), self.usage()));
+        }
+        let indexes = flags
+            .positionals()
+            .iter()
+            .map(|column| table.column(column))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| column_error(self.name(), e))?;
+
+        Ok(Table {
+            columns: indexes.iter().map(|&i| table.columns[i].clone()).collect(),
+            rows: table
+                .rows
+                .iter()
+                .map(|row| indexes.iter().map(|&i| row[i].clone()).collect())
+                .collect(),
+        })
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        _flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        Err(needs_input(self.name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_where_matches() {
+        let size = Value::Size(2 * 1024 * 1024);
+        assert_eq!(Where::matches(&size, ">", "1MB"), Ok(true));
+        assert_eq!(Where::matches(&size, "<=", "1MB"), Ok(false));
+        assert_eq!(Where::matches(&size, "==", "2M"), Ok(true));
+        assert!(Where::matches(&size, ">", "big").is_err());
+        assert!(Where::matches(&size, "<>", "1").is_err());
+
+        let name = Value::Text("Cargo.toml".to_string());
+        assert_eq!(Where::matches(&name, "=~", "toml"), Ok(true));
+        assert_eq!(Where::matches(&name, "!~", "toml"), Ok(false));
+        assert_eq!(Where::matches(&name, "!=", "Cargo.lock"), Ok(true));
+    }
+}
@@ -0,0 +1,33 @@
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::ShellResult,
+    flags::Flags,
+};
+
+#[derive(Clone)]
+pub struct Rehash;
+
+impl Command for Rehash {
+    fn name(&self) -> &'static str {
+        "rehash"
+    }
+
+    fn description(&self) -> &'static str {
+        "Rescan PATH for executables"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Rebuild the cached table of executables on PATH.\n\n\
+         Use it after installing new programs while the shell is running."
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        context.path_cache.rehash();
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,130 @@
+use std::{io, net::TcpListener, sync::Arc, thread, time::Duration};
+
+use crate::{
+    color::Color,
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::{FlagSpec, Flags},
+    },
+    http::server,
+    signals::InterruptGuard,
+};
+
+const DEFAULT_PORT: u16 = 8000;
+
+/// How long to wait between checks for new connections and Ctrl-C
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone)]
+pub struct Serve;
+
+impl Command for Serve {
+    fn name(&self) -> &'static str {
+        "serve"
+    }
+
+    fn description(&self) -> &'static str {
+        "Serve a directory over HTTP"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Share DIR, the current directory by default, over HTTP on every network\n\
+         interface until Ctrl-C. Directories show their index.html or a list of their\n\
+         files. Each request is logged with its status.\n\n\
+         The port is 8000 unless -p says otherwise; -p 0 picks a free one."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] =
+            &[FlagSpec::new('p', "port", "Listen on PORT").takes_value("PORT")];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-p PORT] [DIR]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let dir = match flags.positionals() {
+            [] => ".",
+            [dir] => dir.as_str(),
+            _ => {
+                return Err(ShellError::Builtin {
+                    message: format!("serve: usage: serve {}", self.usage()),
+                    code: 2,
+                })
+            }
+        };
+        let port = match flags.option_value('p', "port") {
+            Some(port) => port.parse().map_err(|_| ShellError::Builtin {
+                message: format!("serve: invalid port '{}'", port),
+                code: 2,
+            })?,
+            None => DEFAULT_PORT,
+        };
+
+        let root = context
+            .resolve_path(dir)
+            .canonicalize()
+            .map_err(|e| ShellError::builtin(format!("serve: {}: {}", dir, e)))?;
+        if !root.is_dir() {
+            return Err(ShellError::builtin(format!(
+                "serve: {}: not a directory",
+                dir
+            )));
+        }
+
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .map_err(|e| ShellError::builtin(format!("serve: port {}: {}", port, e)))?;
+        let port = listener.local_addr()?.port();
+        println!(
+            "Serving {} at {} (Ctrl-C to stop)",
+            root.display(),
+            Color::Cyan.paint(&format!("http://localhost:{}/", port))
+        );
+
+        let guard = InterruptGuard::new();
+        let root = Arc::new(root);
+        while !guard.interrupted() {
+            let (stream, peer) = match listener.accept() {
+                Ok(connection) => connection,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(ACCEPT_INTERVAL);
+                    continue;
+                }
+                Err(e) => return Err(ShellError::builtin(format!("serve: {}", e))),
+            };
+            let root = Arc::clone(&root);
+            thread::spawn(move || {
+                if stream.set_nonblocking(false).is_err() {
+                    return;
+                }
+                // A client that hangs up early has nothing to be told
+                let Ok(served) = server::handle(stream, &root) else {
+                    return;
+                };
+                let status = served.status.to_string();
+                let status = match served.status {
+                    200..=299 => Color::Green.paint(&status),
+                    300..=399 => Color::Cyan.paint(&status),
+                    _ => Color::Yellow.paint(&status),
+                };
+                println!(
+                    "{} {} {} {}",
+                    peer.ip(),
+                    served.method,
+                    served.target,
+                    status
+                );
+            });
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,109 @@
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::Flags,
+    options::ShellOptions,
+};
+
+#[derive(Clone)]
+pub struct Set;
+
+impl Set {
+    fn invalid(option: &str) -> ShellError {
+        ShellError::Builtin {
+            message: format!("set: {}: invalid option", option),
+            code: 2,
+        }
+    }
+
+    fn print_options(context: &CommandContext) {
+        for (name, _) in ShellOptions::NAMES {
+            let enabled = context.options.get(name).unwrap_or_default();
+            println!("{:<15} {}", name, if enabled { "on" } else { "off" });
+        }
+    }
+}
+
+impl Command for Set {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn description(&self) -> &'static str {
+        "Change shell options"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Turn shell options on with -x or -o NAME, and off with +x or +o NAME.\n\n\
+         Without arguments, or with a bare -o, list every option and its state.\n\n\
+         Arguments after the options, or after --, become the positional parameters\n\
+         $1, $2 and on; a bare -- clears them.\n\n\
+         Options:\n\
+         -o accessible: Screen-reader mode: no color, a plain prompt, completions listed\n\
+         -e, -o errexit: Stop at the first command that fails outside `if`, `&&`, `||` or `!`\n\
+         -o funcfirst: Let functions run in place of builtins with the same name\n\
+         -C, -o noclobber: Refuse to overwrite existing files with >; >| overwrites anyway\n\
+         -o noconfirm: Run commands matching confirmation patterns without asking\n\
+         -n, -o noexec: Print commands instead of running them; `set` itself still runs\n\
+         -o pipefail: A pipeline fails with the last of its commands that failed\n\
+         -o posix: Behave like POSIX sh, without hermit's own builtins and project files\n\
+         -o structured: Pipe tables between builtins, as in `ls | where size > 1MB`\n\
+         -x, -o xtrace: Print each command, prefixed with $PS4, before running it"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-+Cenx] [-+o NAME] [--] [ARG...]"
+    }
+
+    // `+x` is not a flag to the parser, so `set` reads its arguments itself
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        if args.is_empty() {
+            Self::print_options(context);
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            let (enabled, letters) = match arg.split_at_checked(1) {
+                _ if arg == "--" => {
+                    context.frame.args = args.map(|arg| arg.to_string()).collect();
+                    break;
+                }
+                Some(("-", letters)) if !letters.is_empty() => (true, letters),
+                Some(("+", letters)) if !letters.is_empty() => (false, letters),
+                Some(("-" | "+", _)) => return Err(Self::invalid(arg)),
+                _ => {
+                    let params = std::iter::once(arg).chain(args.copied());
+                    context.frame.args = params.map(str::to_string).collect();
+                    break;
+                }
+            };
+
+            if letters == "o" {
+                match args.next() {
+                    Some(name) if context.options.set(name, enabled) => {}
+                    Some(name) => return Err(Self::invalid(name)),
+                    None => Self::print_options(context),
+                }
+                continue;
+            }
+
+            for letter in letters.chars() {
+                if !context.options.set_short(letter, enabled) {
+                    return Err(Self::invalid(&format!("{}{}", &arg[..1], letter)));
+                }
+            }
+        }
+
+        Ok(ExitCode::SUCCESS)
+    }
+}
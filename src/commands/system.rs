@@ -0,0 +1,242 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    core::{
+        command::{Command, CommandContext, ExitCode, Records},
+        error::{ShellError, ShellResult},
+        flags::{FlagSpec, Flags},
+        records::{Table, Value, CSV_FLAG, JSON_FLAG},
+    },
+    system, utils,
+};
+
+fn system_error(name: &str, error: std::io::Error) -> ShellError {
+    ShellError::builtin(format!("{}: {}", name, error))
+}
+
+#[derive(Clone)]
+pub struct Ps;
+
+impl Ps {
+    /// The current user's processes, or everyone's with `-e`
+    fn processes(flags: &Flags) -> ShellResult<Vec<system::Process>> {
+        let every = flags.has_option('e', "every");
+        // SAFETY: getuid cannot fail
+        let uid = unsafe { libc::getuid() };
+        let mut processes = system::processes().map_err(|e| system_error("ps", e))?;
+        processes.retain(|process| every || process.uid == uid);
+        Ok(processes)
+    }
+}
+
+impl Command for Ps {
+    fn name(&self) -> &'static str {
+        "ps"
+    }
+
+    fn description(&self) -> &'static str {
+        "List running processes"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "List your processes with their state, resident memory, CPU time and command line.\n\n\
+         States: R running, S sleeping, D waiting on I/O, T stopped, Z zombie."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[
+            FlagSpec::new('e', "every", "List every user's processes"),
+            JSON_FLAG,
+            CSV_FLAG,
+        ];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-e] [--json | --csv]"
+    }
+
+    fn records(&self) -> Records {
+        Records::Produces
+    }
+
+    fn execute_records(
+        &self,
+        _input: Option<Table>,
+        _args: &[&str],
+        flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<Table> {
+        let mut table = Table::new(&["pid", "state", "rss", "time", "command"]);
+        for process in Self::processes(flags)? {
+            table.rows.push(vec![
+                Value::Int(process.pid.into()),
+                Value::Text(process.state.to_string()),
+                Value::Size(process.rss),
+                Value::Duration(process.cpu_time),
+                Value::Text(process.command),
+            ]);
+        }
+        Ok(table)
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        if Table::wanted(flags) {
+            self.execute_records(None, args, flags, context)?
+                .print_data(flags);
+            return Ok(ExitCode::SUCCESS);
+        }
+        let processes = Self::processes(flags)?;
+
+        println!(
+            "{:>7} {:<4} {:>8} {:>8} COMMAND",
+            "PID", "STAT", "RSS", "TIME"
+        );
+        // The header leaves this much of the line for the command
+        let width = utils::term_width().saturating_sub(31).max(20);
+        for process in processes {
+            println!(
+                "{:>7} {:<4} {:>8} {:>8} {}",
+                process.pid,
+                process.state,
+                utils::format_size(process.rss),
+                system::format_cpu_time(process.cpu_time),
+                process.command.chars().take(width).collect::<String>()
+            );
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+#[derive(Clone)]
+pub struct Uptime;
+
+impl Uptime {
+    /// The local time of day as `HH:MM:SS`
+    fn clock() -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs()) as libc::time_t;
+        // SAFETY: localtime_r only writes to the struct it is given
+        let tm = unsafe {
+            let mut tm: libc::tm = std::mem::zeroed();
+            libc::localtime_r(&now, &mut tm);
+            tm
+        };
+        format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+    }
+}
+
+impl Command for Uptime {
+    fn name(&self) -> &'static str {
+        "uptime"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show how long the system has been running"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Show the time, how long the system has been up, and the load averages over\n\
+         the last 1, 5 and 15 minutes."
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        _flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let uptime = system::uptime().map_err(|e| system_error("uptime", e))?;
+        let [one, five, fifteen] = system::load_average().map_err(|e| system_error("uptime", e))?;
+        println!(
+            " {} up {},  load average: {:.2}, {:.2}, {:.2}",
+            Self::clock(),
+            system::format_uptime(uptime),
+            one,
+            five,
+            fifteen
+        );
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+#[derive(Clone)]
+pub struct Free;
+
+impl Command for Free {
+    fn name(&self) -> &'static str {
+        "free"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show memory and swap usage"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Show total, used and free memory and swap, in kibibytes unless -h is given.\n\n\
+         `available` estimates how much can be used by new programs without swapping,\n\
+         counting caches the kernel would give up."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[FlagSpec::new(
+            'h',
+            "human",
+            "Show sizes like 1.5GB instead of kibibytes",
+        )];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-h]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let memory = system::memory().map_err(|e| system_error("free", e))?;
+        let size = |bytes: u64| {
+            if flags.has_option('h', "human") {
+                utils::format_size(bytes)
+            } else {
+                (bytes / 1024).to_string()
+            }
+        };
+
+        println!(
+            "{:<6}{:>12}{:>12}{:>12}{:>12}{:>12}{:>12}",
+            "", "total", "used", "free", "shared", "buff/cache", "available"
+        );
+        println!(
+            "{:<6}{:>12}{:>12}{:>12}{:>12}{:>12}{:>12}",
+            "Mem:",
+            size(memory.total),
+            size(memory.used()),
+            size(memory.free),
+            size(memory.shared),
+            size(memory.buffers_cache),
+            size(memory.available)
+        );
+        println!(
+            "{:<6}{:>12}{:>12}{:>12}",
+            "Swap:",
+            size(memory.swap_total),
+            size(memory.swap_used()),
+            size(memory.swap_free)
+        );
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    color::Palette,
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::{FlagSpec, Flags},
+    },
+};
+
+#[derive(Clone)]
+pub struct Theme;
+
+impl Theme {
+    /// A sample of everything a palette colors, laid out like the real output
+    fn preview(palette: &Palette) -> String {
+        format!(
+            "{}@{} {} {}{} > {} {} {}",
+            palette.user.paint("user"),
+            palette.host.paint("host"),
+            palette.cwd.paint("~/src"),
+            palette.git_branch.paint("main"),
+            palette.git_status.paint(" !1"),
+            palette.directory.paint("docs"),
+            palette.executable.paint("build.sh"),
+            palette.error.paint("Error:"),
+        )
+    }
+}
+
+impl Command for Theme {
+    fn name(&self) -> &'static str {
+        "theme"
+    }
+
+    fn description(&self) -> &'static str {
+        "List, preview or switch color themes"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "List, preview or switch color themes.\n\n\
+         Without arguments, list every theme with a preview; the active one is marked\n\
+         with '*'. With a name, switch to that theme for the rest of the session.\n\
+         Themes are defined under [themes.<name>] in the config file."
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[FlagSpec::new(
+            'p',
+            "preview",
+            "Show the theme without switching to it",
+        )];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[NAME]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let Some(name) = flags.positionals().first() else {
+            let width = context.themes.names().map(str::len).max().unwrap_or(0);
+            for name in context.themes.names() {
+                let marker = if name == context.themes.current() {
+                    '*'
+                } else {
+                    ' '
+                };
+                let palette = context.themes.get(name).copied().unwrap_or_default();
+                println!("{} {:<width$}  {}", marker, name, Self::preview(&palette));
+            }
+            return Ok(ExitCode::SUCCESS);
+        };
+
+        let unknown = || ShellError::builtin(format!("theme: {}: no such theme", name));
+        if flags.has_flag('p') {
+            let palette = context.themes.get(name).ok_or_else(unknown)?;
+            println!("{}", Self::preview(palette));
+        } else {
+            context.palette = context.themes.select(name).ok_or_else(unknown)?;
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::Flags,
+    },
+    utils,
+};
+
+#[derive(Clone)]
+pub struct Timeout;
+
+impl Command for Timeout {
+    fn name(&self) -> &'static str {
+        "timeout"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run a program, stopping it after a time limit"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Run COMMAND, and stop it if it is still running after DURATION.\n\n\
+         DURATION is a number with an optional unit: ms, s (the default), m, h or d,\n\
+         as in 500ms, 30s or 1.5m. The command is sent SIGTERM when the time is up,\n\
+         and killed if it has not exited shortly after. The exit status is then 124.\n\n\
+         Limits for commands matching a pattern can be set in the config file:\n\n\
+         [[timeouts]]\n\
+         pattern = \"cargo test\"\n\
+         after = \"10m\""
+    }
+
+    fn usage(&self) -> &'static str {
+        "DURATION COMMAND [ARG...]"
+    }
+
+    /// Everything after the duration belongs to the command
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let [duration, command, args @ ..] = args else {
+            return Err(ShellError::Builtin {
+                message: format!("timeout: usage: timeout {}", self.usage()),
+                code: 2,
+            });
+        };
+        let limit = utils::parse_duration(duration).ok_or_else(|| ShellError::Builtin {
+            message: format!("timeout: invalid duration '{}'", duration),
+            code: 2,
+        })?;
+
+        context
+            .external_command()
+            .with_timeout(Some(limit))
+            .execute(command, args)
+            .map_err(|e| ShellError::from_spawn(command, e))
+    }
+}
@@ -1,4 +1,8 @@
-use crate::core::{command::Command, command::CommandContext, flags::Flags};
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::Flags,
+};
 
 #[derive(Clone)]
 pub struct TypeCommand;
@@ -16,39 +20,35 @@ impl Command for TypeCommand {
         "Display information about command type"
     }
 
+    fn usage(&self) -> &'static str {
+        "NAME"
+    }
+
     fn execute(
         &self,
         args: &[&str],
         _flags: &Flags,
-        context: &CommandContext,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
         if args.is_empty() {
-            return Err("No command provided".into());
+            return Err(ShellError::builtin("type: no command provided"));
         }
 
         let cmd = args[0];
 
-        if context.builtins.contains(&cmd) {
+        if let Some(value) = context.aliases.get(cmd) {
+            println!("{} is aliased to `{}'", cmd, value);
+        } else if context.function(cmd).is_some() {
+            println!("{} is a function", cmd);
+        } else if context.is_builtin(cmd) {
             println!("{} is a shell builtin", cmd);
+        } else if let Some(path) = context.path_cache.lookup(cmd) {
+            println!("{} is {}", cmd, path.display());
         } else {
-            let path = std::env::var("PATH")?;
-            let paths = path.split(':');
-            let mut found = false;
-
-            for p in paths {
-                let full_path = format!("{}/{}", p, cmd);
-                if std::path::Path::new(&full_path).exists() {
-                    println!("{} is {}", cmd, full_path);
-                    found = true;
-                    break;
-                }
-            }
-
-            if !found {
-                println!("{} not found", cmd);
-            }
+            println!("{} not found", cmd);
+            return Ok(ExitCode::FAILURE);
         }
 
-        Ok(())
+        Ok(ExitCode::SUCCESS)
     }
 }
@@ -1 +1,48 @@
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::ShellResult,
+    flags::{FlagSpec, Flags},
+};
 
+#[derive(Clone)]
+pub struct Unset;
+
+impl Command for Unset {
+    fn name(&self) -> &'static str {
+        "unset"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove environment variables or functions"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Remove environment variables, or functions with -f"
+    }
+
+    fn flags(&self) -> &'static [FlagSpec] {
+        const FLAGS: &[FlagSpec] = &[FlagSpec::short('f', "Remove functions")];
+        FLAGS
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-f] NAME..."
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        for name in flags.positionals() {
+            if flags.has_flag('f') {
+                context.functions.remove(name);
+            } else {
+                context.unset_var(name);
+                context.exported.remove(name);
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
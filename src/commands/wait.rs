@@ -0,0 +1,78 @@
+use std::io::{self, Write};
+
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::Flags,
+};
+
+#[derive(Clone)]
+pub struct Wait;
+
+impl Command for Wait {
+    fn name(&self) -> &'static str {
+        "wait"
+    }
+
+    fn description(&self) -> &'static str {
+        "Wait for background jobs to finish"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Wait for each JOB to exit, showing its output as it arrives. JOB is a job\n\
+         spec such as %1, %+, %- or %str (see fg), or the process ID of a job.\n\n\
+         Without JOB, wait for every background job and exit with 0. Otherwise the\n\
+         exit status is that of the last JOB."
+    }
+
+    fn usage(&self) -> &'static str {
+        "[JOB...]"
+    }
+
+    fn execute(
+        &self,
+        _args: &[&str],
+        flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let mut ids = Vec::new();
+        for spec in flags.positionals() {
+            let job = match spec.parse::<u32>() {
+                Ok(pid) => context
+                    .jobs
+                    .jobs()
+                    .iter()
+                    .find(|job| job.pid() == pid)
+                    .ok_or_else(|| format!("pid {} is not a child of this shell", pid)),
+                Err(_) => context.jobs.resolve(spec),
+            };
+            let job = job.map_err(|e| ShellError::Builtin {
+                message: format!("wait: {}", e),
+                code: 127,
+            })?;
+            ids.push(job.id());
+        }
+        let all = flags.positionals().is_empty();
+        if all {
+            ids.extend(context.jobs.jobs().iter().map(|job| job.id()));
+        }
+
+        let mut stdout = io::stdout();
+        let mut status = ExitCode::SUCCESS;
+        for id in ids {
+            let finished = context.jobs.wait(id, |output| {
+                stdout.write_all(output).ok();
+                stdout.flush().ok();
+            })?;
+            if let Some(finished) = finished {
+                stdout.write_all(&finished.output)?;
+                status = finished.status.into();
+            }
+        }
+        stdout.flush()?;
+        if all {
+            return Ok(ExitCode::SUCCESS);
+        }
+        Ok(status)
+    }
+}
@@ -0,0 +1,42 @@
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::ShellResult,
+    flags::Flags,
+};
+
+#[derive(Clone)]
+pub struct Which;
+
+impl Command for Which {
+    fn name(&self) -> &'static str {
+        "which"
+    }
+
+    fn description(&self) -> &'static str {
+        "Locate executables on PATH"
+    }
+
+    fn extended_description(&self) -> &'static str {
+        "Print the full path of each executable that would be run for the given names"
+    }
+
+    fn usage(&self) -> &'static str {
+        "NAME..."
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let mut status = ExitCode::SUCCESS;
+        for name in args {
+            match context.path_cache.lookup(name) {
+                Some(path) => println!("{}", path.display()),
+                None => status = ExitCode::FAILURE,
+            }
+        }
+        Ok(status)
+    }
+}
@@ -0,0 +1,349 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::PathBuf, time::SystemTime};
+
+use crate::color::{Color, ColorChoice, Palette};
+
+/// User settings read from `~/.config/hermit/config.toml`.
+///
+/// Every field has a default, so a missing file or a partial one is fine.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Whether the prompt and builtins use color: `auto`, `always` or `never`
+    pub color: ColorChoice,
+    /// Start in screen-reader mode, as with `set -o accessible`
+    pub accessible: bool,
+    pub prompt: PromptConfig,
+    pub history: HistoryConfig,
+    pub jobs: JobsConfig,
+    /// Name of the theme to start with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    /// Extra themes, as `[themes.<name>]` tables of palette colors
+    pub themes: HashMap<String, Palette>,
+    pub confirm: ConfirmConfig,
+    /// Limits on how long matching commands may run, as `[[timeouts]]` tables
+    pub timeouts: Vec<TimeoutRule>,
+    /// Lower scheduling priority for matching commands, as `[[priorities]]` tables
+    pub priorities: Vec<PriorityRule>,
+    pub plugins: PluginConfig,
+    pub hooks: HooksConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TimeoutRule {
+    /// Command words to match, written like the `[confirm]` patterns
+    pub pattern: String,
+    /// How long the command may run, e.g. `30s`, `5m` or `1h`
+    pub after: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PluginConfig {
+    /// Directories searched for plugin libraries at startup; `~` means the home directory
+    pub directories: Vec<PathBuf>,
+    /// Executables that act as builtins, as `[[plugins.builtins]]` tables
+    pub builtins: Vec<ExternalBuiltin>,
+    /// Executables that print prompt segments, as `[[plugins.segments]]` tables
+    pub segments: Vec<ExternalSegment>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExternalBuiltin {
+    pub name: String,
+    /// Program and arguments to start, e.g. `python3 ~/plugins/todo.py`
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExternalSegment {
+    pub command: String,
+}
+
+/// Command lines the shell runs on its own when something happens
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Run after the current directory changes, with the previous one in `$OLDPWD`
+    pub chpwd: Vec<String>,
+    /// Run after a command fails, with its command line in `$HERMIT_COMMAND`
+    /// and its exit status in `$HERMIT_STATUS`
+    pub error: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConfirmConfig {
+    /// Commands to ask about before running, e.g. `rm -rf *` or `git push --force`
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PromptConfig {
+    /// Show the machine's hostname after the user name instead of the OS name
+    pub show_hostname: bool,
+    /// Shorten each directory in the path to this many characters, like fish; 0 keeps them whole
+    pub dir_length: usize,
+    /// How many directories at the end of the path are never shortened
+    pub full_dirs: usize,
+    /// `one-line`, or `two-line` to type commands on a line below the prompt
+    pub layout: PromptLayout,
+    /// The prompt for continuation lines, where `{open}` names the unfinished
+    /// construct (`quote`, `heredoc`, `pipe`, `and`, `or`, `subshell` or
+    /// `backslash`); `$PS2` overrides it
+    pub continuation: String,
+    pub continuation_color: Option<Color>,
+    pub cloud: CloudConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CloudConfig {
+    /// Show the active AWS profile and GCP project in the prompt
+    pub show: bool,
+    /// Profiles and projects whose names contain any of these get the production color
+    pub production: Vec<String>,
+}
+
+impl Default for CloudConfig {
+    fn default() -> Self {
+        Self {
+            show: true,
+            production: vec!["prod".to_string(), "live".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PromptLayout {
+    #[default]
+    OneLine,
+    TwoLine,
+}
+
+impl Default for PromptConfig {
+    fn default() -> Self {
+        Self {
+            show_hostname: false,
+            dir_length: 0,
+            full_dirs: 1,
+            layout: PromptLayout::OneLine,
+            continuation: "… ".to_string(),
+            continuation_color: None,
+            cloud: CloudConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct JobsConfig {
+    /// Let background jobs write straight to the terminal instead of collecting their output
+    pub pass_through: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// How many entries the line editor keeps and saves
+    pub max_size: usize,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PriorityRule {
+    /// Command words to match, written like the `[confirm]` patterns
+    pub pattern: String,
+    /// How much to raise the niceness by, as with `nice -n`
+    pub nice: i32,
+    /// Only let the command use the disk when nothing else wants it (Linux)
+    pub idle_io: bool,
+}
+
+impl Default for PriorityRule {
+    fn default() -> Self {
+        Self {
+            pattern: String::new(),
+            nice: 10,
+            idle_io: false,
+        }
+    }
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self { max_size: 100 }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        Self::file("config.toml")
+    }
+
+    /// A file in hermit's config directory, such as the login `profile`
+    pub fn file(name: &str) -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("hermit").join(name))
+    }
+
+    /// When the config file was last written, if it exists
+    pub fn modified() -> Option<SystemTime> {
+        fs::metadata(Self::path()?).and_then(|m| m.modified()).ok()
+    }
+
+    /// Loads the config file, falling back to defaults when it is missing or invalid
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        Self::parse(&contents).unwrap_or_else(|e| {
+            eprintln!("hermit: ignoring {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// The value of a dotted key such as `prompt.show_hostname`, defaults included
+    pub fn get(&self, key: &str) -> Option<toml::Value> {
+        let mut value = toml::Value::try_from(self).ok()?;
+        for part in key.split('.') {
+            value = value.as_table_mut()?.remove(part)?;
+        }
+        Some(value)
+    }
+}
+
+/// Sets a dotted key in the text of a config file, keeping its comments and layout.
+///
+/// `value` is read as TOML when it parses as such (`5000`, `true`,
+/// `["a", "b"]`), and as a plain string otherwise. The result must still be a
+/// valid config that has the key, so typos are caught before anything is saved.
+pub fn set_value(contents: &str, key: &str, value: &str) -> Result<String, String> {
+    let mut document: toml_edit::DocumentMut = contents.parse().map_err(|e| format!("{}", e))?;
+    let value = value
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| toml_edit::Value::from(value));
+
+    let parts: Vec<&str> = key.split('.').collect();
+    let (last, tables) = parts.split_last().ok_or("empty key")?;
+    let mut table = document.as_table_mut() as &mut dyn toml_edit::TableLike;
+    for part in tables {
+        table = table
+            .entry(part)
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .ok_or_else(|| format!("{} is not a table", part))?;
+    }
+    table.insert(last, toml_edit::Item::Value(value));
+
+    let updated = document.to_string();
+    let config = Config::parse(&updated).map_err(|e| e.message().to_string())?;
+    if config.get(key).is_none() {
+        return Err(format!("unknown setting '{}'", key));
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    #[test]
+    fn test_defaults_for_missing_keys() {
+        let config = Config::parse("").unwrap();
+        assert!(!config.prompt.show_hostname);
+        assert_eq!(config.color, ColorChoice::Auto);
+    }
+
+    #[test]
+    fn test_parse_themes() {
+        let config = Config::parse(
+            "theme = \"night\"\n\
+             [themes.night]\n\
+             cwd = \"#268bd2\"\n\
+             error = \"bright-red\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.theme.as_deref(), Some("night"));
+
+        let night = config.themes["night"];
+        assert_eq!(night.cwd, Color::Rgb(38, 139, 210));
+        assert_eq!(night.error, Color::BrightRed);
+        assert_eq!(night.user, Palette::default().user);
+
+        assert!(Config::parse("[themes.bad]\ncwd = \"nope\"\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_confirm_patterns() {
+        let config =
+            Config::parse("[confirm]\npatterns = [\"rm -rf *\", \"git push --force\"]\n").unwrap();
+        assert_eq!(config.confirm.patterns, ["rm -rf *", "git push --force"]);
+    }
+
+    #[test]
+    fn test_parse_hooks() {
+        let config = Config::parse("[hooks]\nchpwd = [\"ls\"]\n").unwrap();
+        assert_eq!(config.hooks.chpwd, ["ls"]);
+        assert!(config.hooks.error.is_empty());
+        assert!(Config::default().hooks.chpwd.is_empty());
+    }
+
+    #[test]
+    fn test_get_includes_defaults() {
+        let config = Config::parse("[themes.night]\ncwd = \"#268bd2\"\n").unwrap();
+        assert_eq!(config.get("color"), Some(toml::Value::from("auto")));
+        assert_eq!(
+            config.get("prompt.show_hostname"),
+            Some(toml::Value::from(false))
+        );
+        assert_eq!(
+            config.get("themes.night.cwd"),
+            Some(toml::Value::from("#268bd2"))
+        );
+        assert_eq!(config.get("prompt.format"), None);
+    }
+
+    #[test]
+    fn test_set_value_keeps_comments() {
+        let contents = "# my settings\ncolor = \"never\"\n";
+        let updated = set_value(contents, "prompt.show_hostname", "true").unwrap();
+        assert!(updated.starts_with("# my settings\ncolor = \"never\"\n"));
+        assert!(Config::parse(&updated).unwrap().prompt.show_hostname);
+
+        let updated = set_value(&updated, "theme", "solarized").unwrap();
+        assert_eq!(
+            Config::parse(&updated).unwrap().theme.as_deref(),
+            Some("solarized")
+        );
+
+        let updated = set_value(contents, "history.max_size", "5000").unwrap();
+        assert_eq!(Config::parse(&updated).unwrap().history.max_size, 5000);
+
+        assert!(set_value(contents, "history.size", "5000").is_err());
+        assert!(set_value(contents, "color", "sometimes").is_err());
+    }
+
+    #[test]
+    fn test_parse_prompt_section() {
+        let config = Config::parse("[prompt]\nshow_hostname = true\n").unwrap();
+        assert!(config.prompt.show_hostname);
+        assert!(Config::parse("[prompt]\nshow_hostname = 1\n").is_err());
+
+        let config = Config::parse("color = \"never\"\n").unwrap();
+        assert_eq!(config.color, ColorChoice::Never);
+    }
+}
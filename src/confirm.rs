@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+
+use crate::glob;
+
+/// Commands that need a yes from the user before they run.
+///
+/// A pattern is a list of words where `*` and `?` match as in filenames. The
+/// first word must match the command name; each following word must match
+/// some later argument, in order, so `git push --force` also catches
+/// `git push origin main --force`.
+#[derive(Debug, Clone, Default)]
+pub struct Guard {
+    patterns: Vec<String>,
+    /// Command lines the user chose to always allow this session
+    trusted: HashSet<String>,
+}
+
+impl Guard {
+    pub fn new(patterns: Vec<String>) -> Self {
+        Self {
+            patterns,
+            trusted: HashSet::new(),
+        }
+    }
+
+    /// Replaces the patterns, keeping the commands already trusted
+    pub fn set_patterns(&mut self, patterns: Vec<String>) {
+        self.patterns = patterns;
+    }
+
+    /// Returns the first pattern `words` matches, unless that exact command is trusted
+    pub fn check(&self, words: &[&str]) -> Option<&str> {
+        if self.trusted.contains(&words.join(" ")) {
+            return None;
+        }
+        self.patterns
+            .iter()
+            .find(|pattern| matches(pattern, words))
+            .map(String::as_str)
+    }
+
+    pub fn trust(&mut self, words: &[&str]) {
+        self.trusted.insert(words.join(" "));
+    }
+}
+
+/// Whether a command line matches a pattern as described on [`Guard`]
+pub fn matches(pattern: &str, words: &[&str]) -> bool {
+    let mut pattern = pattern.split_whitespace();
+    let (Some(name), Some((cmd, args))) = (pattern.next(), words.split_first()) else {
+        return false;
+    };
+    if !glob::matches(name, cmd) {
+        return false;
+    }
+
+    let mut args = args.iter();
+    pattern.all(|word| args.any(|arg| glob::matches(word, arg)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard() -> Guard {
+        Guard::new(vec![
+            "rm -rf *".to_string(),
+            "git push --force".to_string(),
+            "dd of=/dev/*".to_string(),
+        ])
+    }
+
+    #[test]
+    fn test_patterns_match_in_order() {
+        let guard = guard();
+        assert_eq!(guard.check(&["rm", "-rf", "build"]), Some("rm -rf *"));
+        assert_eq!(guard.check(&["rm", "-rf"]), None);
+        assert_eq!(guard.check(&["rm", "build"]), None);
+        assert_eq!(
+            guard.check(&["git", "push", "origin", "--force"]),
+            Some("git push --force")
+        );
+        assert_eq!(guard.check(&["git", "--force", "push"]), None);
+        assert_eq!(
+            guard.check(&["dd", "if=disk.img", "of=/dev/sda"]),
+            Some("dd of=/dev/*")
+        );
+        assert_eq!(guard.check(&["dd", "of=backup.img"]), None);
+    }
+
+    #[test]
+    fn test_trusted_commands_pass() {
+        let mut guard = guard();
+        guard.trust(&["rm", "-rf", "build"]);
+        assert_eq!(guard.check(&["rm", "-rf", "build"]), None);
+        assert!(guard.check(&["rm", "-rf", "src"]).is_some());
+    }
+}
@@ -0,0 +1,311 @@
+//! Integer arithmetic for `$(( ... ))`
+
+/// How deep variables may refer to other variables before giving up
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+/// Operators that take two characters, checked before the single ones
+const LONG_OPS: &[&str] = &["**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||"];
+const SHORT_OPS: &[&str] = &[
+    "+", "-", "*", "/", "%", "<", ">", "&", "|", "^", "!", "~", "?", ":", "(", ")",
+];
+
+#[derive(Debug)]
+enum Expr {
+    Number(i64),
+    Var(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+/// Evaluates an expression with the usual C operators on 64-bit integers.
+///
+/// Names are looked up with `var`; an unset or empty variable is 0, and one
+/// holding an expression is evaluated in turn. `&&`, `||` and `?:` only
+/// evaluate the side they need, so `0 && 1 / 0` is 0.
+pub fn eval(expr: &str, var: &dyn Fn(&str) -> Option<String>) -> Result<i64, String> {
+    eval_at(expr, var, 0)
+}
+
+fn eval_at(expr: &str, var: &dyn Fn(&str) -> Option<String>, depth: usize) -> Result<i64, String> {
+    let tokens = tokenize(expr)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let tree = parser.ternary()?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        return Err(format!("syntax error near `{}`", describe(token)));
+    }
+    Evaluator { var, depth }.eval(&tree)
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(number(&rest[..len])?));
+            rest = &rest[len..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..len].to_string()));
+            rest = &rest[len..];
+        } else if let Some(op) = LONG_OPS
+            .iter()
+            .chain(SHORT_OPS)
+            .find(|op| rest.starts_with(**op))
+        {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(format!("syntax error: invalid arithmetic operator `{}`", c));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Reads a decimal, `0x` hexadecimal or `0` octal number
+fn number(text: &str) -> Result<i64, String> {
+    let (digits, radix) =
+        if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            (hex, 16)
+        } else if text.len() > 1 && text.starts_with('0') {
+            (&text[1..], 8)
+        } else {
+            (text, 10)
+        };
+    // Wraps like the C arithmetic it stands for
+    u64::from_str_radix(digits, radix)
+        .map(|n| n as i64)
+        .map_err(|_| format!("{}: value too great for base", text))
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(n) => n.to_string(),
+        Token::Name(name) => name.clone(),
+        Token::Op(op) => op.to_string(),
+    }
+}
+
+/// How tightly a binary operator binds, higher being tighter
+fn precedence(op: &str) -> Option<u8> {
+    Some(match op {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | "<=" | ">" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        "**" => 11,
+        _ => return None,
+    })
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("syntax error: `{}` expected", op))
+        }
+    }
+
+    fn ternary(&mut self) -> Result<Expr, String> {
+        let condition = self.binary(1)?;
+        if self.peek_op() != Some("?") {
+            return Ok(condition);
+        }
+        self.pos += 1;
+        let then = self.ternary()?;
+        self.expect(":")?;
+        let otherwise = self.ternary()?;
+        Ok(Expr::Ternary(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn binary(&mut self, min: u8) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while let Some(op) = self.peek_op() {
+            let Some(prec) = precedence(op).filter(|&prec| prec >= min) else {
+                break;
+            };
+            self.pos += 1;
+            // `**` groups to the right, everything else to the left
+            let next = if op == "**" { prec } else { prec + 1 };
+            let right = self.binary(next)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Name(name)) => Ok(Expr::Var(name)),
+            Some(Token::Op(op @ ("-" | "+" | "!" | "~"))) => {
+                Ok(Expr::Unary(op, Box::new(self.unary()?)))
+            }
+            Some(Token::Op("(")) => {
+                let inner = self.ternary()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Some(Token::Op(op)) => Err(format!("syntax error: operand expected near `{}`", op)),
+            None => Err("syntax error: operand expected".to_string()),
+        }
+    }
+}
+
+struct Evaluator<'a> {
+    var: &'a dyn Fn(&str) -> Option<String>,
+    depth: usize,
+}
+
+impl Evaluator<'_> {
+    fn eval(&self, expr: &Expr) -> Result<i64, String> {
+        Ok(match expr {
+            Expr::Number(n) => *n,
+            Expr::Var(name) => match (self.var)(name) {
+                None => 0,
+                Some(value) if value.trim().is_empty() => 0,
+                Some(_) if self.depth >= MAX_DEPTH => {
+                    return Err(format!("{}: expression recursion level exceeded", name))
+                }
+                Some(value) => eval_at(&value, self.var, self.depth + 1)?,
+            },
+            Expr::Unary(op, operand) => {
+                let n = self.eval(operand)?;
+                match *op {
+                    "-" => n.wrapping_neg(),
+                    "+" => n,
+                    "!" => (n == 0) as i64,
+                    _ => !n,
+                }
+            }
+            Expr::Ternary(condition, then, otherwise) => {
+                if self.eval(condition)? != 0 {
+                    self.eval(then)?
+                } else {
+                    self.eval(otherwise)?
+                }
+            }
+            Expr::Binary("&&", left, right) => {
+                (self.eval(left)? != 0 && self.eval(right)? != 0) as i64
+            }
+            Expr::Binary("||", left, right) => {
+                (self.eval(left)? != 0 || self.eval(right)? != 0) as i64
+            }
+            Expr::Binary(op, left, right) => {
+                let (a, b) = (self.eval(left)?, self.eval(right)?);
+                match *op {
+                    "+" => a.wrapping_add(b),
+                    "-" => a.wrapping_sub(b),
+                    "*" => a.wrapping_mul(b),
+                    "/" | "%" if b == 0 => return Err("division by 0".to_string()),
+                    "/" => a.wrapping_div(b),
+                    "%" => a.wrapping_rem(b),
+                    "**" if b < 0 => return Err("exponent less than 0".to_string()),
+                    "**" => a.wrapping_pow(b.min(u32::MAX as i64) as u32),
+                    "<<" => a.wrapping_shl(b as u32),
+                    ">>" => a.wrapping_shr(b as u32),
+                    "<" => (a < b) as i64,
+                    "<=" => (a <= b) as i64,
+                    ">" => (a > b) as i64,
+                    ">=" => (a >= b) as i64,
+                    "==" => (a == b) as i64,
+                    "!=" => (a != b) as i64,
+                    "&" => a & b,
+                    "|" => a | b,
+                    _ => a ^ b,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calc(expr: &str) -> Result<i64, String> {
+        eval(expr, &|name| match name {
+            "X" => Some("7".to_string()),
+            "Y" => Some("X * 2".to_string()),
+            "EMPTY" => Some(String::new()),
+            "LOOP" => Some("LOOP".to_string()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(calc("2 * (3 + 4) - 1"), Ok(13));
+        assert_eq!(calc("1 + 2 * 3 ** 2"), Ok(19));
+        assert_eq!(calc("2 ** 3 ** 2"), Ok(512));
+        assert_eq!(calc("-2 ** 2"), Ok(4));
+        assert_eq!(calc("10 - 4 - 3"), Ok(3));
+        assert_eq!(calc("7 % 3 == 1 && 1 << 4 == 16"), Ok(1));
+        assert_eq!(calc("1 ? 2 : 0 ? 3 : 4"), Ok(2));
+        assert_eq!(calc("0 ? 2 : 0 ? 3 : 4"), Ok(4));
+        assert_eq!(calc("!0 + ~0 + (6 & 3) + (6 | 3) + (6 ^ 3)"), Ok(14));
+        assert_eq!(calc("0x1f + 010 + 0"), Ok(39));
+        assert_eq!(calc(""), Ok(0));
+    }
+
+    #[test]
+    fn test_variables() {
+        assert_eq!(calc("X + 1"), Ok(8));
+        assert_eq!(calc("Y"), Ok(14));
+        assert_eq!(calc("UNSET + EMPTY"), Ok(0));
+        assert!(calc("LOOP").unwrap_err().contains("recursion"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(calc("1 / 0"), Err("division by 0".to_string()));
+        assert_eq!(calc("0 && 1 / 0"), Ok(0));
+        assert_eq!(calc("1 || 1 % 0"), Ok(1));
+        assert!(calc("1 +").is_err());
+        assert!(calc("(1").is_err());
+        assert!(calc("1 2").is_err());
+        assert!(calc("1 $ 2").is_err());
+        assert!(calc("09").is_err());
+    }
+}
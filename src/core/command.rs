@@ -1,18 +1,230 @@
-use std::error::Error;
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    env,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    rc::Rc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
-use super::flags::Flags;
+use crate::{
+    color::{ColorChoice, Palette, Themes},
+    locale::Message,
+};
 
+use super::{
+    error::{ShellError, ShellResult},
+    external::ExternalCommand,
+    flags::{FlagSpec, Flags, HELP_FLAG},
+    jobs::JobTable,
+    options::ShellOptions,
+    parser::Function,
+    path_cache::PathCache,
+    recorder::SharedRecorder,
+    records::Table,
+    registry::POSIX_BUILTINS,
+};
+
+/// Exit status of a command, where zero means success
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExitCode(i32);
+
+impl ExitCode {
+    pub const SUCCESS: Self = Self(0);
+    pub const FAILURE: Self = Self(1);
+
+    pub const fn new(code: i32) -> Self {
+        Self(code)
+    }
+
+    pub const fn code(self) -> i32 {
+        self.0
+    }
+
+    /// The status of `! command`: success for any failure, failure for success
+    pub fn negate(self) -> Self {
+        if self == Self::SUCCESS {
+            Self::FAILURE
+        } else {
+            Self::SUCCESS
+        }
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    /// Processes killed by a signal report `128 + signal`, like other shells
+    fn from(status: ExitStatus) -> Self {
+        match (status.code(), status.signal()) {
+            (Some(code), _) => Self(code),
+            (None, Some(signal)) => Self(128 + signal),
+            (None, None) => Self::FAILURE,
+        }
+    }
+}
+
+/// The positional parameters of the shell, script or function that is running
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    /// `$0`, the name it was started by
+    pub name: String,
+    /// `$1` and on
+    pub args: Vec<String>,
+}
+
+/// Shell state shared with builtins.
+///
+/// The shell owns the working directory, variables, and aliases; builtins change
+/// them through this context instead of mutating process-wide state.
+#[derive(Debug, Default)]
 pub struct CommandContext {
     pub history: Vec<String>,
+    /// The file history is saved to on exit
+    pub history_path: PathBuf,
     pub builtins: Vec<&'static str>,
+    pub cwd: PathBuf,
+    pub variables: HashMap<String, String>,
+    /// Names of the variables passed on to the commands the shell runs
+    pub exported: HashSet<String>,
+    pub aliases: HashMap<String, String>,
+    pub path_cache: PathCache,
+    /// Color policy from the config; commands with `--color` may override it
+    pub color: ColorChoice,
+    pub palette: Palette,
+    pub themes: Themes,
+    pub options: ShellOptions,
+    /// Commands started with `&` that have not been reported finished
+    pub jobs: JobTable,
+    /// The session recording started with `record start`, if any
+    pub recorder: Option<SharedRecorder>,
+    /// Set by commands that wrote the config file, so the shell reloads it
+    pub config_changed: bool,
+    /// The exported variables as of the last `env snapshot`, or as the shell started with them
+    pub env_snapshot: HashMap<String, String>,
+    /// What `$0`, `$1` and on, `$@`, `$*` and `$#` expand to
+    pub frame: Frame,
+    /// Functions defined with `name() { ...; }`, by name
+    pub functions: HashMap<String, Rc<Function>>,
+    /// For each function running, innermost last, the variables it made
+    /// `local` and the values to put back when it returns
+    pub scopes: Vec<HashMap<String, Option<String>>>,
+    /// The status of the last command, for `$?`, `&&` and `||`
+    pub last_status: ExitCode,
+    /// The process id of the last command started with `&`, for `$!`
+    pub last_background: Option<u32>,
+    /// When the shell started, for `$SECONDS`
+    pub started: Option<Instant>,
+    /// State of the generator behind `$RANDOM`
+    random: Cell<u64>,
 }
 
-impl Default for CommandContext {
-    fn default() -> Self {
+impl CommandContext {
+    /// Creates a context rooted at `cwd` with variables inherited from the
+    /// environment, which stay exported
+    pub fn new(cwd: PathBuf) -> Self {
+        let variables: HashMap<String, String> = env::vars().collect();
+        let path_cache = PathCache::new(variables.get("PATH").map_or("", String::as_str));
+
         Self {
-            history: Vec::new(),
-            builtins: Vec::new(),
+            cwd,
+            exported: variables.keys().cloned().collect(),
+            variables,
+            path_cache,
+            frame: Frame {
+                name: env::args().next().unwrap_or_else(|| "hermit".to_string()),
+                args: Vec::new(),
+            },
+            started: Some(Instant::now()),
+            ..Self::default()
+        }
+    }
+
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(String::as_str)
+    }
+
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        self.variables.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn unset_var(&mut self, name: &str) -> Option<String> {
+        self.variables.remove(name)
+    }
+
+    /// The next number from 0 to 32767 for `$RANDOM`.
+    ///
+    /// Xorshift seeded from the clock; good enough for scripts, not for secrets.
+    pub fn random(&self) -> u16 {
+        let mut random = self.random.get();
+        if random == 0 {
+            random = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |d| d.as_nanos() as u64)
+                | 1;
         }
+        random ^= random << 13;
+        random ^= random >> 7;
+        random ^= random << 17;
+        self.random.set(random);
+        (random >> 33) as u16 & 0x7fff
+    }
+
+    /// The exported variables, which commands the shell runs get as their environment
+    pub fn environment(&self) -> HashMap<String, String> {
+        self.variables
+            .iter()
+            .filter(|(name, _)| self.exported.contains(*name))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Moves to `dir`, keeping `$PWD` and `$OLDPWD` up to date
+    pub fn set_cwd(&mut self, dir: PathBuf) {
+        let old_dir = std::mem::replace(&mut self.cwd, dir);
+        self.set_var("OLDPWD", &old_dir.to_string_lossy());
+        let pwd = self.cwd.to_string_lossy().into_owned();
+        self.set_var("PWD", &pwd);
+    }
+
+    /// Rebuilds the executable cache if `$PATH` has changed since it was scanned
+    pub fn refresh_path_cache(&mut self) -> bool {
+        let path = self.var("PATH").unwrap_or_default().to_string();
+        self.path_cache.refresh(&path)
+    }
+
+    /// The color policy in effect, which screen-reader mode overrides with `never`
+    pub fn color_choice(&self) -> ColorChoice {
+        if self.options.accessible {
+            ColorChoice::Never
+        } else {
+            self.color
+        }
+    }
+
+    /// The function `name` calls, unless a builtin of that name comes first
+    pub fn function(&self, name: &str) -> Option<Rc<Function>> {
+        let function = self.functions.get(name)?;
+        (self.options.funcfirst || !self.is_builtin(name)).then(|| Rc::clone(function))
+    }
+
+    /// Whether `name` runs as a builtin, which in posix mode only POSIX's own do
+    pub fn is_builtin(&self, name: &str) -> bool {
+        self.builtins.contains(&name) && (!self.options.posix || POSIX_BUILTINS.contains(&name))
+    }
+
+    /// Creates an executor that runs children in the shell's directory with
+    /// its exported variables
+    pub fn external_command(&self) -> ExternalCommand {
+        ExternalCommand::new(self.cwd.clone())
+            .with_env(self.environment())
+            .with_executables(self.path_cache.clone())
+            .with_pipefail(self.options.pipefail)
+    }
+
+    /// Resolves a path against the shell's working directory
+    pub fn resolve_path(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.cwd.join(path)
     }
 }
 
@@ -21,12 +233,83 @@ pub trait Command {
         &self,
         args: &[&str],
         flags: &Flags,
-        context: &CommandContext,
-    ) -> Result<(), Box<dyn Error>>;
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode>;
     fn name(&self) -> &'static str;
     fn description(&self) -> &'static str;
-    // TODO
     fn extended_description(&self) -> &'static str {
         self.description()
     }
+
+    /// Options the command accepts; anything else is rejected before `execute` runs
+    fn flags(&self) -> &'static [FlagSpec] {
+        &[]
+    }
+
+    /// Arguments shown after the name and options in the usage line, e.g. `[PATH]`
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    /// Whether arguments are parsed as options at all.
+    ///
+    /// Commands such as `echo` that print their arguments verbatim opt out.
+    fn parses_flags(&self) -> bool {
+        true
+    }
+
+    /// How the command takes part in structured pipelines (`set -o structured`)
+    fn records(&self) -> Records {
+        Records::None
+    }
+
+    /// Runs as a stage of a structured pipeline, given the table from the stage
+    /// before, or `None` for the first, and returning a table for the next
+    fn execute_records(
+        &self,
+        _input: Option<Table>,
+        _args: &[&str],
+        _flags: &Flags,
+        _context: &mut CommandContext,
+    ) -> ShellResult<Table> {
+        Err(ShellError::builtin(format!(
+            "{}: cannot be part of a structured pipeline",
+            self.name()
+        )))
+    }
+}
+
+/// What a builtin does with tables in a structured pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Records {
+    /// Only prints text
+    None,
+    /// Can start a pipeline, like `ls`
+    Produces,
+    /// Only makes sense after another stage, like `where`
+    Transforms,
+}
+
+/// Builds the `--help` text for a command from its declared options
+pub fn help_text(command: &dyn Command) -> String {
+    let mut usage = format!("{} {}", Message::Usage, command.name());
+    if !command.flags().is_empty() {
+        usage.push_str(" [OPTIONS]");
+    }
+    if !command.usage().is_empty() {
+        usage.push(' ');
+        usage.push_str(command.usage());
+    }
+
+    let mut specs = command.flags().to_vec();
+    if command.parses_flags() {
+        specs.push(HELP_FLAG);
+    }
+
+    let mut text = format!("{}\n\n{}", usage, command.extended_description());
+    if !specs.is_empty() {
+        text.push_str(&format!("\n\n{}\n", Message::Options));
+        text.push_str(&FlagSpec::table(&specs));
+    }
+    text
 }
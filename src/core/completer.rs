@@ -3,43 +3,91 @@ use rustyline::{
     error::ReadlineError,
     highlight::Highlighter,
     hint::Hinter,
-    validate::{self, MatchingBracketValidator, Validator},
+    validate::Validator,
     Context, Helper,
 };
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use super::path_cache::PathCache;
+use crate::{prompt::SharedPrompt, scripts::Runner};
+
+/// Live shell state the completer draws its candidates from
+#[derive(Debug, Default)]
+pub struct CompletionState {
+    pub builtins: Vec<String>,
+    pub aliases: Vec<String>,
+    pub functions: Vec<String>,
+    /// The script function printing the candidates for each command's arguments
+    pub arguments: HashMap<String, String>,
+    /// Runs those functions, when an argument is completed
+    pub scripts: Runner,
+    pub executables: PathCache,
+    pub cwd: PathBuf,
+    /// Screen-reader mode, where the line is drawn without highlighting
+    pub accessible: bool,
+}
+
+impl CompletionState {
+    /// Every name that can be used in command position
+    fn command_names(&self) -> impl Iterator<Item = &String> {
+        self.builtins
+            .iter()
+            .chain(&self.aliases)
+            .chain(&self.functions)
+            .chain(self.executables.names())
+    }
+}
+
+/// Completion state owned by the shell and read by the completer on every completion
+pub type SharedCompletionState = Arc<RwLock<CompletionState>>;
 
 pub struct CommandCompleter {
-    commands: Vec<String>,
+    state: SharedCompletionState,
+    prompt: SharedPrompt,
+    ignore_case: bool,
 }
 
 impl CommandCompleter {
-    pub fn new(commands: Vec<&'static str>) -> Self {
+    pub fn new(state: SharedCompletionState, prompt: SharedPrompt) -> Self {
         Self {
-            commands: commands.into_iter().map(String::from).collect(),
+            state,
+            prompt,
+            ignore_case: true,
         }
     }
-}
 
-impl Completer for CommandCompleter {
-    type Candidate = Pair;
+    /// Whether candidates match regardless of case, as they do unless an inputrc turns it off
+    pub fn set_ignore_case(&mut self, ignore_case: bool) {
+        self.ignore_case = ignore_case;
+    }
 
-    fn complete(
-        &self,
-        line: &str,
-        pos: usize,
-        _ctx: &Context<'_>,
-    ) -> Result<(usize, Vec<Pair>), ReadlineError> {
+    fn fold_case(&self, text: &str) -> String {
+        if self.ignore_case {
+            text.to_lowercase()
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn complete_line(&self, line: &str, pos: usize) -> (usize, Vec<Pair>) {
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
         let start = line[..pos].rfind(' ').map(|i| i + 1).unwrap_or(0);
-        let word = &line[start..pos].to_lowercase();
+        let word = &self.fold_case(&line[start..pos]);
 
         let mut matches = Vec::new();
 
         // Only match commands if we're at the start of the line
         if start == 0 {
             matches.extend(
-                self.commands
-                    .iter()
+                state
+                    .command_names()
                     .filter(|cmd| {
-                        let cmd_lower = cmd.to_lowercase();
+                        let cmd_lower = self.fold_case(cmd);
                         cmd_lower.starts_with(word.as_str()) || cmd_lower.contains(word.as_str())
                     })
                     .map(|cmd| Pair {
@@ -49,13 +97,27 @@ impl Completer for CommandCompleter {
             );
         }
 
+        let command = line[..start].split_whitespace().next();
+        if let Some(function) = command.and_then(|command| state.arguments.get(command)) {
+            let candidates = state.scripts.output(function).unwrap_or_default();
+            matches.extend(
+                candidates
+                    .lines()
+                    .filter(|candidate| self.fold_case(candidate).contains(word.as_str()))
+                    .map(|candidate| Pair {
+                        display: candidate.to_string(),
+                        replacement: candidate.to_string(),
+                    }),
+            );
+        }
+
         if word.starts_with("./") || word.starts_with('/') || !word.contains('/') {
-            if let Ok(entries) = std::fs::read_dir(".") {
+            if let Ok(entries) = std::fs::read_dir(&state.cwd) {
                 matches.extend(
                     entries
                         .filter_map(Result::ok)
                         .filter(|entry| {
-                            let name = entry.file_name().to_string_lossy().to_lowercase();
+                            let name = self.fold_case(&entry.file_name().to_string_lossy());
                             name.contains(word)
                         })
                         .map(|entry| {
@@ -77,8 +139,8 @@ impl Completer for CommandCompleter {
 
         // Sort matches: exact prefix matches first, then contained matches
         matches.sort_by(|a, b| {
-            let a_lower = a.display.to_lowercase();
-            let b_lower = b.display.to_lowercase();
+            let a_lower = self.fold_case(&a.display);
+            let b_lower = self.fold_case(&b.display);
             let a_starts = a_lower.starts_with(word);
             let b_starts = b_lower.starts_with(word);
 
@@ -88,29 +150,143 @@ impl Completer for CommandCompleter {
                 _ => a.display.cmp(&b.display),
             }
         });
+        matches.dedup_by(|a, b| a.display == b.display);
 
-        Ok((start, matches))
+        (start, matches)
     }
 }
 
-// Add missing trait implementations
-impl Validator for CommandCompleter {
-    fn validate(
+impl Completer for CommandCompleter {
+    type Candidate = Pair;
+
+    fn complete(
         &self,
-        ctx: &mut validate::ValidationContext,
-    ) -> rustyline::Result<validate::ValidationResult> {
-        MatchingBracketValidator::new().validate(ctx)
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Pair>), ReadlineError> {
+        Ok(self.complete_line(line, pos))
     }
 }
 
+// Unfinished lines are accepted and continued by the shell, under its PS2 prompt
+impl Validator for CommandCompleter {}
+
 impl Hinter for CommandCompleter {
     type Hint = String;
 }
 
 impl Highlighter for CommandCompleter {
-    fn highlight<'l>(&self, line: &'l str, pos: usize) -> std::borrow::Cow<'l, str> {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        if self
+            .state
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .accessible
+        {
+            return Cow::Borrowed(line);
+        }
         rustyline::highlight::MatchingBracketHighlighter::new().highlight(line, pos)
     }
+
+    /// Draws the shell's styled prompt; rustyline measures the plain text it was given
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        _default: bool,
+    ) -> Cow<'b, str> {
+        let rendered = self.prompt.read().unwrap_or_else(|e| e.into_inner());
+        if rendered.plain == prompt {
+            Cow::Owned(rendered.styled.clone())
+        } else {
+            Cow::Borrowed(prompt)
+        }
+    }
 }
 
 impl Helper for CommandCompleter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser;
+    use std::{fs, rc::Rc};
+    use tempfile::TempDir;
+
+    fn displays(completer: &CommandCompleter, line: &str) -> Vec<String> {
+        let (_, matches) = completer.complete_line(line, line.len());
+        matches.into_iter().map(|pair| pair.display).collect()
+    }
+
+    #[test]
+    fn test_completes_live_aliases() {
+        let state = SharedCompletionState::default();
+        state.write().unwrap().builtins = vec!["echo".to_string()];
+        let completer = CommandCompleter::new(state.clone(), SharedPrompt::default());
+        assert!(!displays(&completer, "gs").contains(&"gst".to_string()));
+
+        state.write().unwrap().aliases.push("gst".to_string());
+        assert!(displays(&completer, "gs").contains(&"gst".to_string()));
+
+        state.write().unwrap().functions.push("gsync".to_string());
+        assert!(displays(&completer, "gs").contains(&"gsync".to_string()));
+    }
+
+    #[test]
+    fn test_completes_files_in_shell_directory() {
+        let tmp_dir = TempDir::new().unwrap();
+        fs::write(tmp_dir.path().join("notes.txt"), "").unwrap();
+        fs::create_dir(tmp_dir.path().join("nested")).unwrap();
+
+        let state = SharedCompletionState::default();
+        state.write().unwrap().cwd = tmp_dir.path().to_path_buf();
+        let completer = CommandCompleter::new(state, SharedPrompt::default());
+
+        assert_eq!(displays(&completer, "cat no"), vec!["notes.txt"]);
+        assert_eq!(displays(&completer, "cd nes"), vec!["nested/"]);
+    }
+
+    #[test]
+    fn test_completes_script_arguments() {
+        let tmp_dir = TempDir::new().unwrap();
+        fs::write(tmp_dir.path().join("build.log"), "").unwrap();
+
+        let function =
+            parser::parse_function("hermit_complete_make() { printf 'build\\ntest\\n'; }");
+        let functions = HashMap::from([(function.name.clone(), Rc::new(function.clone()))]);
+
+        let state = SharedCompletionState::default();
+        {
+            let mut state = state.write().unwrap();
+            state.cwd = tmp_dir.path().to_path_buf();
+            state
+                .arguments
+                .insert("make".to_string(), function.name.clone());
+            // The function is also valid sh, which stands in for hermit here
+            state.scripts = Runner::new(
+                PathBuf::from("sh"),
+                &functions,
+                HashMap::new(),
+                tmp_dir.path().to_path_buf(),
+            );
+        }
+        let completer = CommandCompleter::new(state, SharedPrompt::default());
+
+        assert_eq!(displays(&completer, "make bu"), vec!["build", "build.log"]);
+        assert_eq!(displays(&completer, "make -j2 te"), vec!["test"]);
+        assert_eq!(displays(&completer, "cat bu"), vec!["build.log"]);
+        assert!(displays(&completer, "mak").is_empty());
+    }
+
+    #[test]
+    fn test_ignore_case_can_be_turned_off() {
+        let state = SharedCompletionState::default();
+        state.write().unwrap().builtins = vec!["Makefile-gen".to_string()];
+        let mut completer = CommandCompleter::new(state, SharedPrompt::default());
+        assert_eq!(displays(&completer, "make"), vec!["Makefile-gen"]);
+
+        completer.set_ignore_case(false);
+        assert!(displays(&completer, "make").is_empty());
+        assert_eq!(displays(&completer, "Make"), vec!["Makefile-gen"]);
+    }
+}
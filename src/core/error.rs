@@ -0,0 +1,96 @@
+use std::io;
+
+use rustyline::error::ReadlineError;
+
+use super::{flags::FlagError, parser::SyntaxError};
+use crate::locale::Message;
+
+pub type ShellResult<T> = Result<T, ShellError>;
+
+/// Represents errors that can occur while reading, parsing, or running commands
+#[derive(Debug, thiserror::Error)]
+pub enum ShellError {
+    #[error("{}", Message::CommandNotFound.format(&[.0]))]
+    CommandNotFound(String),
+    #[error("{}", Message::PermissionDenied.format(&[.0]))]
+    PermissionDenied(String),
+    #[error("{}", Message::ParseError.format(&[.0]))]
+    Parse(String),
+    #[error("{message}")]
+    Builtin { message: String, code: i32 },
+    #[error(transparent)]
+    Flag(#[from] FlagError),
+    #[error(transparent)]
+    Readline(#[from] ReadlineError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl ShellError {
+    /// Creates a builtin error with the generic failure exit code
+    pub fn builtin(message: impl Into<String>) -> Self {
+        Self::Builtin {
+            message: message.into(),
+            code: 1,
+        }
+    }
+
+    /// Maps an IO error raised while launching `command` to the matching variant
+    pub fn from_spawn(command: &str, error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => Self::CommandNotFound(command.to_string()),
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied(command.to_string()),
+            _ => Self::Io(error),
+        }
+    }
+
+    /// Returns the exit code a shell conventionally reports for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::CommandNotFound(_) => 127,
+            Self::PermissionDenied(_) => 126,
+            Self::Parse(_) | Self::Flag(_) => 2,
+            Self::Builtin { code, .. } => *code,
+            Self::Readline(_) | Self::Io(_) => 1,
+        }
+    }
+}
+
+impl From<SyntaxError> for ShellError {
+    fn from(error: SyntaxError) -> Self {
+        Self::Parse(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_spawn() {
+        let not_found = io::Error::from(io::ErrorKind::NotFound);
+        let error = ShellError::from_spawn("nope", not_found);
+        assert!(matches!(error, ShellError::CommandNotFound(ref cmd) if cmd == "nope"));
+        assert_eq!(error.to_string(), "command not found: nope");
+
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        let error = ShellError::from_spawn("./script", denied);
+        assert!(matches!(error, ShellError::PermissionDenied(_)));
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(ShellError::CommandNotFound("x".into()).exit_code(), 127);
+        assert_eq!(ShellError::PermissionDenied("x".into()).exit_code(), 126);
+        assert_eq!(ShellError::Parse("x".into()).exit_code(), 2);
+        assert_eq!(ShellError::builtin("x").exit_code(), 1);
+        assert_eq!(
+            ShellError::Builtin {
+                message: "x".into(),
+                code: 3
+            }
+            .exit_code(),
+            3
+        );
+    }
+}
@@ -0,0 +1,801 @@
+//! Expansions applied to each word of a command before it runs
+
+use std::ffi::{CStr, CString};
+
+use crate::glob;
+
+use super::{
+    arith,
+    command::CommandContext,
+    error::{ShellError, ShellResult},
+    lexer::{Quoting, Scanner},
+};
+
+/// A character of a word with its position and how it is quoted
+type Scanned = (usize, char, Quoting);
+
+/// A word as it is being expanded
+#[derive(Default)]
+struct Expansion {
+    text: String,
+    /// The text as a pattern for filename expansion, in which only the
+    /// wildcards written unquoted are special
+    pattern: String,
+    /// Whether the pattern has any such wildcards
+    wild: bool,
+    /// Where the text and the pattern break into separate fields, at the
+    /// space between two of `$@`, and whether the field before had wildcards
+    breaks: Vec<(usize, usize, bool)>,
+    /// Whether a `$@` with no parameters was all there was to the word
+    vanished: bool,
+}
+
+impl Expansion {
+    fn push_literal(&mut self, text: &str) {
+        self.text.push_str(text);
+        self.pattern.push_str(&glob::escape(text));
+    }
+
+    fn push_wildcard(&mut self, c: char) {
+        self.text.push(c);
+        self.pattern.push(c);
+        self.wild = true;
+    }
+
+    fn push_break(&mut self) {
+        let wild = std::mem::take(&mut self.wild);
+        self.breaks
+            .push((self.text.len(), self.pattern.len(), wild));
+        self.push_literal(" ");
+    }
+
+    /// The text, pattern and wildness of each field
+    fn fields(&self) -> Vec<(&str, &str, bool)> {
+        let mut fields = Vec::new();
+        let (mut text, mut pattern) = (0, 0);
+        for &(text_end, pattern_end, wild) in &self.breaks {
+            fields.push((
+                &self.text[text..text_end],
+                &self.pattern[pattern..pattern_end],
+                wild,
+            ));
+            (text, pattern) = (text_end + 1, pattern_end + 1);
+        }
+        fields.push((&self.text[text..], &self.pattern[pattern..], self.wild));
+        fields
+    }
+}
+
+/// Expands a word as written into the text the command receives.
+///
+/// That means tilde expansion, of a leading `~` and in assignments such as
+/// `PATH=~/bin:~/.local/bin` of each `~` after the `=` or a `:`, then
+/// parameter and arithmetic expansion, then removing the quotes. A quoted
+/// `~` stays as it is, and so does a `$` in single quotes. As in zsh, the
+/// value of an unquoted `$VAR` stays one word, spaces and all, though
+/// [`fields`] drops the word when it comes out empty.
+pub fn word(word: &str, context: &mut CommandContext) -> ShellResult<String> {
+    let chars: Vec<Scanned> = Scanner::new(word).collect();
+    Ok(expand(&chars, assignment_value(word), context)?.text)
+}
+
+/// Expands a word as written into a pattern to match text against, in which
+/// only the `*`, `?` and `[...]` written unquoted are special
+pub fn pattern(word: &str, context: &mut CommandContext) -> ShellResult<String> {
+    let chars: Vec<Scanned> = Scanner::new(word).collect();
+    Ok(expand(&chars, None, context)?.pattern)
+}
+
+/// Turns a word into the arguments it stands for: its brace expansions, each
+/// expanded like [`word`], then replaced by the paths matching its unquoted
+/// `*`, `?` and `[...]`, sorted.
+///
+/// A pattern that matches nothing is left as it is. Assignments are expanded
+/// like [`word`] alone, and wildcards that come from a variable's value stay.
+/// `$@`, and `$*` outside double quotes, is a field for each positional
+/// parameter, and `"$@"` with none is no field at all. Neither is a word with
+/// nothing quoted in it that expands to nothing, like `$UNSET`, while `""`
+/// and `"$UNSET"` are an empty field.
+pub fn fields(word: &str, context: &mut CommandContext) -> ShellResult<Vec<String>> {
+    if assignment_value(word).is_some() {
+        return Ok(vec![self::word(word, context)?]);
+    }
+
+    let mut fields = Vec::new();
+    for word in braces(word) {
+        let chars: Vec<Scanned> = Scanner::new(&word).collect();
+        let expansion = expand(&chars, None, context)?;
+        let unquoted = chars.iter().all(|&(_, _, q)| q == Quoting::Unquoted);
+        if expansion.text.is_empty() && (expansion.vanished || unquoted) {
+            continue;
+        }
+        for (text, pattern, wild) in expansion.fields() {
+            let paths = match wild {
+                true => glob::expand(pattern, &context.cwd),
+                false => Vec::new(),
+            };
+            if paths.is_empty() {
+                fields.push(text.to_string());
+            } else {
+                fields.extend(paths.iter().map(|path| path.to_string_lossy().into_owned()));
+            }
+        }
+    }
+    Ok(fields)
+}
+
+/// Expands the first unquoted `{a,b}` or `{1..5}` in a word, and in turn the
+/// ones in each result, so `x{1..2}{a,b}` is `x1a x1b x2a x2b`.
+///
+/// A sequence of numbers or letters may take a step, as in `{0..10..5}`, and
+/// keeps leading zeros, as in `{01..10}`. Braces with neither a comma nor a
+/// sequence inside, like `{}` or the ones of `${NAME}`, are left alone.
+fn braces(word: &str) -> Vec<String> {
+    let chars: Vec<Scanned> = Scanner::new(word).collect();
+    let unquoted = |i: usize, wanted: char| {
+        chars
+            .get(i)
+            .is_some_and(|&(_, c, q)| c == wanted && q == Quoting::Unquoted)
+    };
+
+    for open in 0..chars.len() {
+        if !unquoted(open, '{') || (open > 0 && chars[open - 1].1 == '$') {
+            continue;
+        }
+        // The matching `}`, and the commas between that are not nested deeper
+        let mut depth = 0;
+        let mut commas = Vec::new();
+        let mut close = None;
+        for i in open + 1..chars.len() {
+            if unquoted(i, '{') {
+                depth += 1;
+            } else if unquoted(i, '}') && depth > 0 {
+                depth -= 1;
+            } else if unquoted(i, '}') {
+                close = Some(i);
+                break;
+            } else if unquoted(i, ',') && depth == 0 {
+                commas.push(i);
+            }
+        }
+        let Some(close) = close else {
+            break;
+        };
+
+        let (start, end) = (chars[open].0, chars[close].0);
+        let (prefix, suffix) = (&word[..start], &word[end + 1..]);
+        let body = &word[start + 1..end];
+        let items: Vec<String> = if !commas.is_empty() {
+            let mut from = start + 1;
+            let mut items = Vec::new();
+            for &comma in &commas {
+                items.push(word[from..chars[comma].0].to_string());
+                from = chars[comma].0 + 1;
+            }
+            items.push(word[from..end].to_string());
+            items
+        } else if chars[open + 1..close]
+            .iter()
+            .all(|&(_, _, q)| q == Quoting::Unquoted)
+        {
+            match sequence(body) {
+                Some(items) => items,
+                None => continue,
+            }
+        } else {
+            continue;
+        };
+
+        return items
+            .iter()
+            .flat_map(|item| braces(&format!("{}{}{}", prefix, item, suffix)))
+            .collect();
+    }
+    vec![word.to_string()]
+}
+
+/// The items of a `{first..last}` or `{first..last..step}` sequence
+fn sequence(body: &str) -> Option<Vec<String>> {
+    let parts: Vec<&str> = body.split("..").collect();
+    let (first, last, step) = match parts[..] {
+        [first, last] => (first, last, 1),
+        [first, last, step] => (first, last, step.parse::<i64>().ok()?.unsigned_abs()),
+        _ => return None,
+    };
+    let step = step.max(1) as usize;
+
+    if let (Ok(a), Ok(b)) = (first.parse::<i64>(), last.parse::<i64>()) {
+        let padded = |n: &str| {
+            n.trim_start_matches('-').len() > 1 && n.trim_start_matches('-').starts_with('0')
+        };
+        let width = if padded(first) || padded(last) {
+            first.len().max(last.len())
+        } else {
+            0
+        };
+        let numbers: Vec<i64> = if a <= b {
+            (a..=b).step_by(step).collect()
+        } else {
+            (b..=a).rev().step_by(step).collect()
+        };
+        return Some(
+            numbers
+                .iter()
+                .map(|n| match n {
+                    n if *n < 0 => format!(
+                        "-{:0width$}",
+                        n.unsigned_abs(),
+                        width = width.saturating_sub(1)
+                    ),
+                    n => format!("{:0width$}", n, width = width),
+                })
+                .collect(),
+        );
+    }
+
+    let (mut a, mut b) = (first.chars(), last.chars());
+    match (a.next(), a.next(), b.next(), b.next()) {
+        (Some(a), None, Some(b), None) if a.is_ascii_alphabetic() && b.is_ascii_alphabetic() => {
+            let letters: Vec<char> = if a <= b {
+                (a..=b).step_by(step).collect()
+            } else {
+                (b..=a).rev().step_by(step).collect()
+            };
+            Some(letters.iter().map(char::to_string).collect())
+        }
+        _ => None,
+    }
+}
+
+fn expand(
+    chars: &[Scanned],
+    assignment: Option<usize>,
+    context: &mut CommandContext,
+) -> ShellResult<Expansion> {
+    let mut out = Expansion::default();
+    let mut at_tilde = true;
+    let mut i = 0;
+    while i < chars.len() {
+        let (pos, c, quoting) = chars[i];
+        if at_tilde && c == '~' && quoting == Quoting::Unquoted {
+            // The name after the tilde runs to the next unquoted `/`, or `:` in assignments
+            let end = chars[i..]
+                .iter()
+                .position(|&(_, c, quoting)| {
+                    quoting != Quoting::Unquoted || c == '/' || (assignment.is_some() && c == ':')
+                })
+                .map_or(chars.len(), |n| i + n);
+            let ends_name = chars
+                .get(end)
+                .is_none_or(|&(_, _, q)| q == Quoting::Unquoted);
+            let name: String = chars[i + 1..end].iter().map(|&(_, c, _)| c).collect();
+            if let Some(dir) = ends_name.then(|| tilde_dir(&name, context)).flatten() {
+                out.push_literal(&dir);
+                i = end;
+                at_tilde = false;
+                continue;
+            }
+        }
+        if c == '$' && special(quoting) {
+            if let Some(&(_, all @ ('@' | '*'), q)) = chars.get(i + 1) {
+                if special(q) {
+                    let split = all == '@' || quoting == Quoting::Unquoted;
+                    for (n, arg) in context.frame.args.iter().enumerate() {
+                        match n {
+                            0 => {}
+                            _ if split => out.push_break(),
+                            _ => out.push_literal(" "),
+                        }
+                        out.push_literal(arg);
+                    }
+                    out.vanished |= split && context.frame.args.is_empty();
+                    i += 2;
+                    at_tilde = false;
+                    continue;
+                }
+            }
+            if let Some((value, len)) = parameter(&chars[i..], context)? {
+                out.push_literal(&value);
+                i += len;
+                at_tilde = false;
+                continue;
+            }
+        }
+        at_tilde = quoting == Quoting::Unquoted
+            && assignment.is_some_and(|eq| pos == eq || (pos > eq && c == ':'));
+        match quoting {
+            Quoting::Syntax => {}
+            Quoting::Unquoted if matches!(c, '*' | '?' | '[') => out.push_wildcard(c),
+            _ => out.push_literal(c.encode_utf8(&mut [0; 4])),
+        }
+        i += 1;
+    }
+    Ok(out)
+}
+
+/// Whether a character quoted this way can start an expansion
+fn special(quoting: Quoting) -> bool {
+    matches!(quoting, Quoting::Unquoted | Quoting::Double)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Expands the `$NAME` or `${...}` at the start of `chars`, returning its value
+/// and how many characters it took, or None if the `$` starts nothing.
+///
+/// `$0` is the name of the shell or script, `$1` to `$9` and `${10}` and on
+/// its positional parameters and `$#` how many of them there are. `$$` is the
+/// shell's process id and `$!` that of the last command run with `&`, while
+/// `$RANDOM` is a new number up to 32767 each time and `$SECONDS` how long
+/// the shell has been running.
+///
+/// Inside braces, `${#NAME}` is the length of the value, and after the name
+/// `-word` stands for an unset variable, `=word` also assigns it, and `+word`
+/// replaces a set one. With a colon before the operator, as in `${NAME:-word}`,
+/// an empty variable counts as unset.
+fn parameter(
+    chars: &[Scanned],
+    context: &mut CommandContext,
+) -> ShellResult<Option<(String, usize)>> {
+    match chars.get(1) {
+        Some(&(_, '(', quoting)) if special(quoting) => {
+            return match chars.get(2) {
+                Some(&(_, '(', _)) => arithmetic(chars, context).map(Some),
+                _ => Ok(None),
+            };
+        }
+        Some(&(_, '{', quoting)) if special(quoting) => {}
+        Some(&(_, c, quoting)) if special(quoting) && (c.is_ascii_alphabetic() || c == '_') => {
+            let len = 1 + chars[1..]
+                .iter()
+                .take_while(|&&(_, c, q)| special(q) && is_name_char(c))
+                .count();
+            let name: String = chars[1..len].iter().map(|&(_, c, _)| c).collect();
+            let value = lookup(&name, context).unwrap_or_default();
+            return Ok(Some((value, len)));
+        }
+        Some(&(_, c, quoting))
+            if special(quoting) && (c.is_ascii_digit() || matches!(c, '#' | '$' | '!' | '?')) =>
+        {
+            let value = lookup(&c.to_string(), context).unwrap_or_default();
+            return Ok(Some((value, 2)));
+        }
+        _ => return Ok(None),
+    }
+
+    // Find the `}` that closes the brace, past any nested ones
+    let mut depth = 0;
+    let mut close = None;
+    for i in 2..chars.len() {
+        match chars[i] {
+            (_, '$', q) if special(q) && chars.get(i + 1).is_some_and(|&(_, c, _)| c == '{') => {
+                depth += 1
+            }
+            (_, '}', q) if special(q) && depth > 0 => depth -= 1,
+            (_, '}', q) if special(q) => {
+                close = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let Some(close) = close else {
+        return Err(bad_substitution(chars));
+    };
+    let bad_substitution = || bad_substitution(&chars[..=close]);
+    let inner = &chars[2..close];
+
+    let (length, inner) = match inner {
+        [(_, '#', _), rest @ ..] if !rest.is_empty() => (true, rest),
+        _ => (false, inner),
+    };
+    let name_len = match inner {
+        [(_, c, _), ..] if c.is_ascii_digit() => inner
+            .iter()
+            .take_while(|&&(_, c, _)| c.is_ascii_digit())
+            .count(),
+        [(_, '#' | '$' | '!' | '?', _)] => 1,
+        _ => inner
+            .iter()
+            .take_while(|&&(_, c, q)| special(q) && is_name_char(c))
+            .count(),
+    };
+    let name: String = inner[..name_len].iter().map(|&(_, c, _)| c).collect();
+    if name.is_empty() {
+        return Err(bad_substitution());
+    }
+    let value = lookup(&name, context);
+    let rest = &inner[name_len..];
+    if length {
+        if !rest.is_empty() {
+            return Err(bad_substitution());
+        }
+        let len = value.map_or(0, |v| v.chars().count());
+        return Ok(Some((len.to_string(), close + 1)));
+    }
+
+    let (colon, rest) = match rest {
+        [(_, ':', _), rest @ ..] => (true, rest),
+        _ => (false, rest),
+    };
+    let missing = match &value {
+        None => true,
+        Some(value) => colon && value.is_empty(),
+    };
+    let expanded = match rest {
+        [] if !colon => value.unwrap_or_default(),
+        [(_, '-', _), word @ ..] if missing => expand(word, None, context)?.text,
+        // Only variables can be assigned, not `$1` or `$#`
+        [(_, '=', _), ..]
+            if missing && !name.starts_with(|c: char| c == '_' || c.is_ascii_alphabetic()) =>
+        {
+            return Err(bad_substitution());
+        }
+        [(_, '=', _), word @ ..] if missing => {
+            let word = expand(word, None, context)?.text;
+            context.set_var(&name, &word);
+            word
+        }
+        [(_, '+', _), word @ ..] if !missing => expand(word, None, context)?.text,
+        [(_, '+', _), ..] => String::new(),
+        [(_, '-' | '=', _), ..] => value.unwrap_or_default(),
+        _ => return Err(bad_substitution()),
+    };
+    Ok(Some((expanded, close + 1)))
+}
+
+/// The value of a variable, a positional parameter or one of the parameters
+/// the shell keeps itself, such as `?`, `$` or `SECONDS`
+fn lookup(name: &str, context: &CommandContext) -> Option<String> {
+    let frame = &context.frame;
+    match name.parse::<usize>() {
+        Ok(0) => Some(frame.name.clone()),
+        Ok(n) => frame.args.get(n - 1).cloned(),
+        Err(_) => match name {
+            "#" => Some(frame.args.len().to_string()),
+            "?" => Some(context.last_status.code().to_string()),
+            "$" => Some(std::process::id().to_string()),
+            "!" => context.last_background.map(|pid| pid.to_string()),
+            "RANDOM" => Some(context.random().to_string()),
+            "SECONDS" => context
+                .started
+                .map(|started| started.elapsed().as_secs().to_string()),
+            _ => context.var(name).map(str::to_string),
+        },
+    }
+}
+
+/// Evaluates the `$(( ... ))` at the start of `chars`, returning the result
+/// and how many characters it took.
+///
+/// Parameters in the expression are expanded before it is evaluated, so both
+/// `$((X + 1))` and `$(($X + 1))` work.
+fn arithmetic(chars: &[Scanned], context: &mut CommandContext) -> ShellResult<(String, usize)> {
+    let mut depth = 0;
+    let mut close = None;
+    for i in 3..chars.len() {
+        match chars[i] {
+            (_, '(', q) if special(q) => depth += 1,
+            (_, ')', q) if special(q) && depth > 0 => depth -= 1,
+            (_, ')', q) if special(q) => {
+                if chars.get(i + 1).is_some_and(|&(_, c, _)| c == ')') {
+                    close = Some(i);
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+    let Some(close) = close else {
+        return Err(ShellError::Parse("missing `))` in arithmetic".to_string()));
+    };
+
+    let expr = expand(&chars[3..close], None, context)?.text;
+    let value = arith::eval(&expr, &|name| lookup(name, context))
+        .map_err(|e| ShellError::builtin(format!("{}: {}", expr.trim(), e)))?;
+    Ok((value.to_string(), close + 2))
+}
+
+fn bad_substitution(chars: &[Scanned]) -> ShellError {
+    let text: String = chars.iter().map(|&(_, c, _)| c).collect();
+    ShellError::builtin(format!("{}: bad substitution", text))
+}
+
+/// The directory `~name` stands for, if any.
+///
+/// `~` is `$HOME`, `~user` is that user's home directory, and `~+` and `~-`
+/// are `$PWD` and `$OLDPWD`.
+fn tilde_dir(name: &str, context: &CommandContext) -> Option<String> {
+    match name {
+        "" => context
+            .var("HOME")
+            .map(str::to_string)
+            .or_else(home_of_current_user),
+        "+" => context.var("PWD").map(str::to_string),
+        "-" => context.var("OLDPWD").map(str::to_string),
+        user => home_of(user),
+    }
+}
+
+/// Whether a word as written is a `NAME=value` assignment
+pub fn is_assignment(word: &str) -> bool {
+    assignment_value(word).is_some()
+}
+
+/// Where the value starts in a `NAME=value` word, if it is one
+fn assignment_value(word: &str) -> Option<usize> {
+    let eq = word.find('=')?;
+    let name = &word[..eq];
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then_some(eq)
+}
+
+/// Looks up a user's home directory in the password database
+fn home_of(user: &str) -> Option<String> {
+    let name = CString::new(user).ok()?;
+    // SAFETY: getpwnam returns a pointer into static storage, read right away
+    unsafe { passwd_home(libc::getpwnam(name.as_ptr())) }
+}
+
+fn home_of_current_user() -> Option<String> {
+    // SAFETY: as for `home_of`
+    unsafe { passwd_home(libc::getpwuid(libc::getuid())) }
+}
+
+unsafe fn passwd_home(entry: *const libc::passwd) -> Option<String> {
+    if entry.is_null() || (*entry).pw_dir.is_null() {
+        return None;
+    }
+    Some(
+        CStr::from_ptr((*entry).pw_dir)
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::command::ExitCode;
+
+    fn context() -> CommandContext {
+        let mut context = CommandContext::default();
+        context.set_var("HOME", "/home/me");
+        context.set_var("PWD", "/work");
+        context.set_var("OLDPWD", "/before");
+        context
+    }
+
+    #[test]
+    fn test_tilde() {
+        let mut context = context();
+        let mut word = |text| word(text, &mut context).unwrap();
+        assert_eq!(word("~"), "/home/me");
+        assert_eq!(word("~/notes"), "/home/me/notes");
+        assert_eq!(word("~+/src"), "/work/src");
+        assert_eq!(word("~-"), "/before");
+        assert_eq!(word("a~b"), "a~b");
+        assert_eq!(word("~no-such-user-here/x"), "~no-such-user-here/x");
+        assert_eq!(word("~root"), home_of("root").unwrap());
+    }
+
+    #[test]
+    fn test_word_expands_assignments() {
+        let mut context = context();
+        let mut word = |text| word(text, &mut context).unwrap();
+        assert_eq!(
+            word("PATH=~/bin:/usr/bin:~/.local/bin"),
+            "PATH=/home/me/bin:/usr/bin:/home/me/.local/bin"
+        );
+        assert_eq!(word("--dir=~/x"), "--dir=~/x");
+        assert_eq!(word("~/a:~/b"), "/home/me/a:~/b");
+        assert_eq!(word("X='~/a':~/b"), "X=~/a:/home/me/b");
+    }
+
+    #[test]
+    fn test_word_removes_quotes() {
+        let mut context = context();
+        let mut word = |text| word(text, &mut context).unwrap();
+        assert_eq!(word("'~'/x"), "~/x");
+        assert_eq!(word("\\~"), "~");
+        assert_eq!(word("~\"me\""), "~me");
+        assert_eq!(word("\"a b\"'c'\\ d"), "a bc d");
+        assert_eq!(word("$'a\\tb\\'$HOME'"), "a\tb'$HOME");
+        assert_eq!(word("\"\\$HOME \\t\""), "$HOME \\t");
+    }
+
+    #[test]
+    fn test_parameters() {
+        let mut context = context();
+        context.set_var("NAME", "world");
+        context.set_var("EMPTY", "");
+        let mut word = |text| word(text, &mut context);
+
+        assert_eq!(word("$NAME").unwrap(), "world");
+        assert_eq!(word("\"hi $NAME!\"").unwrap(), "hi world!");
+        assert_eq!(word("'$NAME'").unwrap(), "$NAME");
+        assert_eq!(word("\\$NAME").unwrap(), "$NAME");
+        assert_eq!(word("${NAME}s").unwrap(), "worlds");
+        assert_eq!(word("$UNSET.").unwrap(), ".");
+        assert_eq!(word("$ $% a$").unwrap(), "$ $% a$");
+
+        assert_eq!(word("${UNSET:-a b}").unwrap(), "a b");
+        assert_eq!(word("${EMPTY:-x}").unwrap(), "x");
+        assert_eq!(word("${EMPTY-x}").unwrap(), "");
+        assert_eq!(word("${NAME:-x}").unwrap(), "world");
+        assert_eq!(word("${UNSET:-${NAME}}").unwrap(), "world");
+        assert_eq!(word("${UNSET:-~/x}").unwrap(), "/home/me/x");
+        assert_eq!(word("${NAME:+set}").unwrap(), "set");
+        assert_eq!(word("${EMPTY:+set}").unwrap(), "");
+        assert_eq!(word("${EMPTY+set}").unwrap(), "set");
+        assert_eq!(word("${#NAME}").unwrap(), "5");
+        assert_eq!(word("${#UNSET}").unwrap(), "0");
+
+        assert_eq!(word("${NEW:=made}").unwrap(), "made");
+        assert_eq!(word("$NEW").unwrap(), "made");
+
+        assert!(word("${NAME").is_err());
+        assert!(word("${NAME?}").is_err());
+        assert!(word("${1x}").is_err());
+    }
+
+    #[test]
+    fn test_positional_parameters() {
+        let mut context = context();
+        context.frame.name = "script".to_string();
+        context.frame.args = ["a b", "c", "d", "e", "f", "g", "h", "i", "j", "ten"]
+            .map(String::from)
+            .to_vec();
+        let mut word = |text| word(text, &mut context);
+
+        assert_eq!(word("$0").unwrap(), "script");
+        assert_eq!(word("$1-$2").unwrap(), "a b-c");
+        assert_eq!(word("$10").unwrap(), "a b0");
+        assert_eq!(word("${10}").unwrap(), "ten");
+        assert_eq!(word("${11:-none}").unwrap(), "none");
+        assert_eq!(word("$#").unwrap(), "10");
+        assert_eq!(word("${#}").unwrap(), "10");
+        assert_eq!(word("${#1}").unwrap(), "3");
+        assert_eq!(word("'$1'").unwrap(), "$1");
+        assert!(word("${1x}").is_err());
+        assert!(word("${11:=x}").is_err());
+    }
+
+    #[test]
+    fn test_special_parameters() {
+        let mut context = context();
+        context.started = Some(std::time::Instant::now());
+        assert_eq!(word("$?", &mut context).unwrap(), "0");
+        context.last_status = ExitCode::new(127);
+        assert_eq!(
+            word("[$?] ${?} ${#?}", &mut context).unwrap(),
+            "[127] 127 3"
+        );
+        assert_eq!(word("$(($? + 1))", &mut context).unwrap(), "128");
+        assert_eq!(
+            word("$$", &mut context).unwrap(),
+            std::process::id().to_string()
+        );
+        assert_eq!(word("[$!]", &mut context).unwrap(), "[]");
+        context.last_background = Some(42);
+        assert_eq!(word("$! ${!}", &mut context).unwrap(), "42 42");
+        assert_eq!(word("$SECONDS", &mut context).unwrap(), "0");
+
+        let numbers: Vec<u16> = (0..20)
+            .map(|_| word("$RANDOM", &mut context).unwrap().parse().unwrap())
+            .collect();
+        assert!(numbers.iter().all(|&n| n <= 32767));
+        assert!(numbers.iter().any(|&n| n != numbers[0]));
+        let n: u16 = word("$((RANDOM % 6))", &mut context)
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(n < 6);
+    }
+
+    #[test]
+    fn test_all_parameters() {
+        let mut context = context();
+        context.frame.args = ["a b", "*"].map(String::from).to_vec();
+        let mut fields = |text| fields(text, &mut context).unwrap();
+
+        assert_eq!(fields("\"$@\""), ["a b", "*"]);
+        assert_eq!(fields("$@"), ["a b", "*"]);
+        assert_eq!(fields("x$@y"), ["xa b", "*y"]);
+        assert_eq!(fields("\"$*\""), ["a b *"]);
+        assert_eq!(fields("$*"), ["a b", "*"]);
+        assert_eq!(word("$@", &mut context).unwrap(), "a b *");
+
+        context.frame.args.clear();
+        let mut fields = |text| super::fields(text, &mut context).unwrap();
+        assert!(fields("\"$@\"").is_empty());
+        assert!(fields("$*").is_empty());
+        assert_eq!(fields("\"$*\""), [""]);
+        assert_eq!(fields("x$@"), ["x"]);
+    }
+
+    #[test]
+    fn test_fields() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in ["a.rs", "b.rs", "*.rs"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let mut context = context();
+        context.cwd = dir.path().to_path_buf();
+        context.set_var("P", "*.rs");
+        let mut fields = |text| fields(text, &mut context).unwrap();
+
+        assert_eq!(fields("*.rs"), ["*.rs", "a.rs", "b.rs"]);
+        assert_eq!(fields("\"*\".rs"), ["*.rs"]);
+        assert_eq!(fields("\\*.rs"), ["*.rs"]);
+        assert_eq!(fields("[a]*"), ["a.rs"]);
+        assert_eq!(fields("$P"), ["*.rs"]);
+        assert_eq!(fields("X=*.rs"), ["X=*.rs"]);
+        assert_eq!(fields("*.none"), ["*.none"]);
+    }
+
+    #[test]
+    fn test_empty_fields() {
+        let mut context = context();
+        context.set_var("EMPTY", "");
+        let mut fields = |text| fields(text, &mut context).unwrap();
+
+        assert!(fields("$UNSET").is_empty());
+        assert!(fields("$EMPTY${UNSET:-}").is_empty());
+        assert!(fields("{,}").is_empty());
+        assert_eq!(fields("\"$UNSET\""), [""]);
+        assert_eq!(fields("''"), [""]);
+        assert_eq!(fields("$EMPTY''"), [""]);
+        assert_eq!(fields("x$UNSET"), ["x"]);
+    }
+
+    #[test]
+    fn test_braces() {
+        assert_eq!(
+            braces("src/{commands,core,git}"),
+            ["src/commands", "src/core", "src/git"]
+        );
+        assert_eq!(
+            braces("file{1..3}.txt"),
+            ["file1.txt", "file2.txt", "file3.txt"]
+        );
+        assert_eq!(braces("{3..1}"), ["3", "2", "1"]);
+        assert_eq!(braces("{08..10}"), ["08", "09", "10"]);
+        assert_eq!(braces("{0..10..5}"), ["0", "5", "10"]);
+        assert_eq!(braces("{-1..1}"), ["-1", "0", "1"]);
+        assert_eq!(braces("{a..e..2}"), ["a", "c", "e"]);
+        assert_eq!(braces("x{1..2}{a,b}"), ["x1a", "x1b", "x2a", "x2b"]);
+        assert_eq!(braces("{a,{b,c}d}"), ["a", "bd", "cd"]);
+        assert_eq!(braces("{,un}do"), ["do", "undo"]);
+        assert_eq!(braces("'{a,b}'"), ["'{a,b}'"]);
+        assert_eq!(braces("{'a,b'}"), ["{'a,b'}"]);
+        assert_eq!(braces("{\"x\",y}"), ["\"x\"", "y"]);
+        for same in ["{}", "{a}", "${X}", "{1..a}", "{a,b"] {
+            assert_eq!(braces(same), [same]);
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut context = context();
+        context.set_var("N", "4");
+        let mut word = |text| word(text, &mut context);
+
+        assert_eq!(word("$((2 * (3 + N % 3)))").unwrap(), "8");
+        assert_eq!(word("x$(($N-5))y").unwrap(), "x-1y");
+        assert_eq!(word("\"$(( N ** 2 ))\"").unwrap(), "16");
+        assert_eq!(word("'$((1))'").unwrap(), "$((1))");
+        assert_eq!(word("${UNSET:-$((1 + 1))}").unwrap(), "2");
+        assert_eq!(word("$(ls)").unwrap(), "$(ls)");
+
+        let e = word("$((N / 0))").unwrap_err();
+        assert_eq!(e.to_string(), "N / 0: division by 0");
+        assert!(word("$((1 + 2)").is_err());
+    }
+}
@@ -1,76 +1,387 @@
 use os_pipe::pipe;
 use std::{
-    fs::OpenOptions,
-    io::{self, Error, ErrorKind},
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, Error, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::process::CommandExt,
+    },
     path::PathBuf,
-    process::{Child, Command, ExitStatus},
+    process::{Child, Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
 };
 
+use super::{command::ExitCode, lexer::Token, path_cache::PathCache};
+
 type CommandResult<T> = io::Result<T>;
 
+/// The status of a command stopped for taking too long, as GNU `timeout` reports it
+pub const TIMED_OUT: ExitCode = ExitCode::new(124);
+
+/// How long a timed out command has to exit after SIGTERM before it is killed
+const KILL_GRACE: Duration = Duration::from_secs(2);
+
+/// Scheduling changes applied to a child just before it starts the program
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Priority {
+    /// Added to the shell's own niceness; positive values run the child at lower priority
+    pub nice: i32,
+    /// Puts the child in the idle I/O class where the kernel has one
+    pub idle_io: bool,
+}
+
+impl Priority {
+    /// Called in the child between fork and exec, so it may only make system calls
+    fn apply(self, niceness: i32) -> io::Result<()> {
+        // SAFETY: plain system calls on the calling process
+        unsafe {
+            if self.nice != 0 && libc::setpriority(libc::PRIO_PROCESS, 0, niceness) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            #[cfg(target_os = "linux")]
+            if self.idle_io {
+                const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+                const IOPRIO_CLASS_IDLE: libc::c_long = 3 << 13;
+                // Best effort: older kernels and some sandboxes refuse it
+                libc::syscall(
+                    libc::SYS_ioprio_set,
+                    IOPRIO_WHO_PROCESS,
+                    0,
+                    IOPRIO_CLASS_IDLE,
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a redirection points one of a command's file descriptors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedirectTarget {
+    /// `<`: read from a file
+    Read(String),
+    /// `>`, `>>` or `>|`: write to a file, which `>|` overwrites even under noclobber
+    Write {
+        path: String,
+        append: bool,
+        force: bool,
+    },
+    /// `>&N` or `<&N`: share descriptor N as it is at that point
+    Dup(RawFd),
+    /// `<<` or `<<<`: read this text
+    Text(String),
+}
+
+/// One redirection, like `2> errors.log`; a command's redirections apply left to right
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub fd: RawFd,
+    pub target: RedirectTarget,
+}
+
+impl Redirect {
+    /// Splits the redirections out of a command's tokens, returning the words that remain.
+    ///
+    /// See [`Redirect::new`] for the operators; the ones that take a word take
+    /// the token after them.
+    pub fn extract(tokens: &[Token]) -> Result<(Vec<&str>, Vec<Redirect>), String> {
+        let mut remaining = Vec::new();
+        let mut redirects = Vec::new();
+        let mut tokens = tokens.iter();
+        while let Some(token) = tokens.next() {
+            let Token::Operator(operator) = token else {
+                remaining.push(token.text());
+                continue;
+            };
+            let target = match Self::takes_word(operator) {
+                true => match tokens.next() {
+                    Some(Token::Word(word)) => Some(word.as_str()),
+                    _ => return Err(format!("syntax error near `{}`", operator)),
+                },
+                false => None,
+            };
+            redirects.push(Self::new(operator, target)?);
+        }
+        Ok((remaining, redirects))
+    }
+
+    /// The redirection an operator stands for, given the word after it when
+    /// [`Redirect::takes_word`] says it needs one.
+    ///
+    /// The operators are `<`, `>`, `>>`, `>|`, `>&N` and `<&N`, optionally led by
+    /// a single-digit descriptor; all but the last two take a path.
+    /// `<<` takes the text of a here-document, which
+    /// [`inline_heredocs`](super::lexer::inline_heredocs) has put in its word,
+    /// and `<<<` takes a line of text.
+    pub fn new(word: &str, target: Option<&str>) -> Result<Redirect, String> {
+        let (fd, operator) = match word.as_bytes().first() {
+            Some(digit @ b'0'..=b'9') => (Some(RawFd::from(digit - b'0')), &word[1..]),
+            _ => (None, word),
+        };
+        let input = operator.starts_with('<');
+        let syntax_error = || format!("syntax error near `{}`", word);
+        let target = match (operator, target) {
+            ("<", Some(path)) => RedirectTarget::Read(path.to_string()),
+            ("<<" | "<<-", Some(text)) => RedirectTarget::Text(text.to_string()),
+            ("<<<", Some(text)) => RedirectTarget::Text(format!("{}\n", text)),
+            (">" | ">>" | ">|", Some(path)) => RedirectTarget::Write {
+                path: path.to_string(),
+                append: operator == ">>",
+                force: operator == ">|",
+            },
+            (_, None) => match operator
+                .strip_prefix(">&")
+                .or_else(|| operator.strip_prefix("<&"))
+            {
+                Some(source) if source.len() == 1 && source.as_bytes()[0].is_ascii_digit() => {
+                    RedirectTarget::Dup(RawFd::from(source.as_bytes()[0] - b'0'))
+                }
+                _ => return Err(syntax_error()),
+            },
+            _ => return Err(syntax_error()),
+        };
+        Ok(Redirect {
+            fd: fd.unwrap_or(if input { 0 } else { 1 }),
+            target,
+        })
+    }
+
+    /// Whether an operator is a redirection rather than something like `|` or `;`
+    pub fn is_redirection(operator: &str) -> bool {
+        operator
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .starts_with(['<', '>'])
+    }
+
+    /// Whether a redirection operator takes the word after it, as all but
+    /// `>&N` and `<&N` do
+    pub fn takes_word(operator: &str) -> bool {
+        !operator.contains('&')
+    }
+}
+
 /// Represents an external command executor that can run system commands
 #[derive(Debug, Clone)]
 pub struct ExternalCommand {
     current_dir: PathBuf,
+    env: Option<HashMap<String, String>>,
+    executables: Option<PathCache>,
+    timeout: Option<Duration>,
+    priority: Priority,
+    no_clobber: bool,
+    pipefail: bool,
+    /// Descriptors children keep under the same numbers, for `/dev/fd` paths
+    inherited: Vec<RawFd>,
 }
 
 impl ExternalCommand {
     /// Creates a new ExternalCommand instance with the specified working directory
     pub fn new(current_dir: PathBuf) -> Self {
-        Self { current_dir }
+        Self {
+            current_dir,
+            env: None,
+            executables: None,
+            timeout: None,
+            priority: Priority::default(),
+            no_clobber: false,
+            pipefail: false,
+            inherited: Vec::new(),
+        }
     }
 
-    /// Executes a single command with arguments
-    pub fn execute(&self, command: &str, args: &[&str]) -> CommandResult<()> {
-        let status = self.spawn_command(command, args)?.wait()?;
-        self.check_status(status, "Command")
+    /// Runs children with exactly these environment variables instead of inheriting the shell's
+    pub fn with_env(mut self, env: HashMap<String, String>) -> Self {
+        self.env = Some(env);
+        self
     }
 
-    /// Executes a pipeline of commands where each command's output feeds into the next command's input
-    pub fn execute_pipeline(&self, pipeline: &[(&str, Vec<&str>)]) -> CommandResult<()> {
-        if pipeline.is_empty() {
-            return Ok(());
-        }
+    /// Finds programs in this cache instead of searching `$PATH` on every run
+    pub fn with_executables(mut self, executables: PathCache) -> Self {
+        self.executables = Some(executables);
+        self
+    }
 
-        let mut processes = Vec::new();
-        let mut previous_pipe = None;
+    /// Stops foreground commands that run longer than `timeout`
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
 
-        // Set up and spawn all processes in the pipeline
-        for (i, (cmd, args)) in pipeline.iter().enumerate() {
-            let mut command = self.create_base_command(cmd, args);
+    /// Runs children with a different scheduling priority than the shell
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
 
-            // Connect pipes between processes
-            if let Some(prev_pipe) = previous_pipe.take() {
-                command.stdin(prev_pipe);
-            }
+    /// Refuses to redirect output into existing regular files instead of truncating them
+    pub fn with_no_clobber(mut self, no_clobber: bool) -> Self {
+        self.no_clobber = no_clobber;
+        self
+    }
+
+    /// Makes a pipeline fail with its rightmost failing command rather than
+    /// report the status of its last one
+    pub fn with_pipefail(mut self, pipefail: bool) -> Self {
+        self.pipefail = pipefail;
+        self
+    }
+
+    /// Leaves these descriptors of the shell open in children, so that a
+    /// `/dev/fd/N` argument names the same pipe for them as for the shell
+    pub fn with_inherited_fds(mut self, fds: Vec<RawFd>) -> Self {
+        self.inherited = fds;
+        self
+    }
+
+    /// Executes a single command with arguments and returns its exit code
+    pub fn execute(&self, command: &str, args: &[&str]) -> CommandResult<ExitCode> {
+        let child = self.spawn_command(command, args)?;
+        self.wait_for_processes(command, vec![child])
+    }
+
+    /// Starts a pipeline without waiting for it, its first command reading
+    /// from `stdin` and its last writing to `stdout` when they are given.
+    ///
+    /// Each command's redirections apply on top of the pipes.
+    pub fn spawn_pipeline(
+        &self,
+        pipeline: &[(&str, Vec<&str>, Vec<Redirect>)],
+        stdin: Option<OwnedFd>,
+        mut stdout: Option<OwnedFd>,
+    ) -> CommandResult<Vec<Child>> {
+        let mut processes = Vec::new();
+        let mut input = stdin;
 
+        // Set up and spawn all processes in the pipeline
+        for (i, (cmd, args, redirects)) in pipeline.iter().enumerate() {
             // Create pipe for next process if not last in pipeline
-            if i < pipeline.len() - 1 {
+            let (output, next) = if i < pipeline.len() - 1 {
                 let (reader, writer) = pipe()?;
-                command.stdout(writer);
-                previous_pipe = Some(reader);
-            }
-
-            processes.push(command.spawn()?);
+                (Some(writer.into()), Some(reader.into()))
+            } else {
+                (stdout.take(), None)
+            };
+            processes.push(self.spawn(cmd, args, redirects, input, output)?);
+            input = next;
         }
+        Ok(processes)
+    }
 
-        // Wait for all processes and check their status
-        self.wait_for_processes(processes)
+    /// Starts a command without waiting for it, reading from `stdin` and
+    /// writing to `stdout` when they are given, with its redirections on top
+    pub fn spawn(
+        &self,
+        command: &str,
+        args: &[&str],
+        redirects: &[Redirect],
+        stdin: Option<OwnedFd>,
+        stdout: Option<OwnedFd>,
+    ) -> CommandResult<Child> {
+        let mut cmd = self.create_base_command(command, args);
+        // The descriptors only need to stay open until the child has its copies
+        let _fds = self.apply_redirects(&mut cmd, redirects, stdin, stdout)?;
+        cmd.spawn()
     }
 
-    /// Executes a command and redirects its output to a file
+    /// Executes a command with its file descriptors redirected
     pub fn execute_redirect(
         &self,
         command: &str,
         args: &[&str],
-        redirect: &str,
-    ) -> CommandResult<()> {
-        let file = self.open_redirect_file(redirect)?;
+        redirects: &[Redirect],
+    ) -> CommandResult<ExitCode> {
+        let child = self.spawn(command, args, redirects, None, None)?;
+        self.wait_for_processes(command, vec![child])
+    }
 
-        let status = self
-            .spawn_command_with_output(command, args, file)?
-            .wait()?;
-        self.check_status(status, "Redirect command")
+    /// Points the shell's own descriptors where `redirects` say, on top of
+    /// `stdin` and `stdout` when they are given, so that a builtin run in the
+    /// meantime reads and writes through them. They are put back when the
+    /// returned value is dropped.
+    pub fn redirect_shell(
+        &self,
+        redirects: &[Redirect],
+        stdin: Option<OwnedFd>,
+        stdout: Option<OwnedFd>,
+    ) -> CommandResult<Swapped> {
+        let table = self.open_redirects(redirects, stdin, stdout)?;
+        // Output written so far belongs where stdout pointed before
+        io::stdout().flush()?;
+        let mut swapped = Swapped { saved: Vec::new() };
+        for (target, fd) in table {
+            // A descriptor that was not open is closed again afterwards
+            let saved = Self::dup_above(target, 10).ok();
+            // SAFETY: dup2 only changes the descriptor table; `saved` keeps
+            // what `target` was so it can be put back
+            if unsafe { libc::dup2(fd.as_raw_fd(), target) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            swapped.saved.push((target, saved));
+        }
+        Ok(swapped)
+    }
+
+    /// Copies everything read from `from` into a new pipe, returning its read
+    /// end. What the reader is not ready for yet is held in memory, so the
+    /// writer never waits on a command the shell has not got to.
+    ///
+    /// With `drain`, the writer's output is read to the end even once the
+    /// reader is gone, so a builtin writing it never sees a broken pipe.
+    /// Otherwise the writer is cut off as a program would be.
+    pub fn relay(from: OwnedFd, drain: bool) -> CommandResult<OwnedFd> {
+        let (reader, mut writer) = pipe()?;
+        let (sender, receiver) = mpsc::channel::<Vec<u8>>();
+        let mut from = File::from(from);
+        thread::spawn(move || {
+            let mut buffer = [0; 8192];
+            let mut sender = Some(sender);
+            loop {
+                let n = match from.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                };
+                let sent = sender
+                    .as_ref()
+                    .map(|sender| sender.send(buffer[..n].to_vec()));
+                if let Some(Err(_)) = sent {
+                    if !drain {
+                        break;
+                    }
+                    sender = None;
+                }
+            }
+        });
+        thread::spawn(move || {
+            for chunk in receiver {
+                if writer.write_all(&chunk).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(reader.into())
+    }
+
+    /// Spawns a command in the background without waiting for it to finish.
+    ///
+    /// The child gets no terminal input so it cannot compete with the prompt.
+    pub fn spawn_background(&self, command: &str, args: &[&str]) -> CommandResult<Child> {
+        self.create_base_command(command, args)
+            .stdin(Stdio::null())
+            .spawn()
+    }
+
+    /// Like `spawn_background`, but with stdout and stderr piped back to the shell
+    pub fn spawn_background_piped(&self, command: &str, args: &[&str]) -> CommandResult<Child> {
+        self.create_base_command(command, args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
     }
 
     // Helper methods
@@ -79,67 +390,335 @@ impl ExternalCommand {
         self.create_base_command(command, args).spawn()
     }
 
-    fn spawn_command_with_output(
+    /// Opens the redirections' files and hands them to `cmd`, returning the descriptors
+    /// the child copies from, which must stay open until it has been spawned.
+    fn apply_redirects(
         &self,
-        command: &str,
-        args: &[&str],
-        output: impl Into<std::process::Stdio>,
-    ) -> CommandResult<Child> {
-        self.create_base_command(command, args)
-            .stdout(output)
-            .spawn()
+        cmd: &mut Command,
+        redirects: &[Redirect],
+        stdin: Option<OwnedFd>,
+        stdout: Option<OwnedFd>,
+    ) -> CommandResult<Vec<OwnedFd>> {
+        let mut kept = Vec::new();
+        let mut moves = Vec::new();
+        for (target, fd) in self.open_redirects(redirects, stdin, stdout)? {
+            match target {
+                0 => {
+                    cmd.stdin(fd);
+                }
+                1 => {
+                    cmd.stdout(fd);
+                }
+                2 => {
+                    cmd.stderr(fd);
+                }
+                _ => {
+                    // Above every descriptor a redirection can name, so moving one
+                    // into place never overwrites another still to be moved
+                    let fd = Self::dup_above(fd.as_raw_fd(), 10)?;
+                    moves.push((fd.as_raw_fd(), target));
+                    kept.push(fd);
+                }
+            }
+        }
+        if !moves.is_empty() {
+            // SAFETY: dup2 is async-signal-safe and the sources are open until spawn returns
+            unsafe {
+                cmd.pre_exec(move || {
+                    for &(source, target) in &moves {
+                        if libc::dup2(source, target) == -1 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    }
+                    Ok(())
+                });
+            }
+        }
+        Ok(kept)
+    }
+
+    /// Opens the redirections' files, returning what each descriptor they
+    /// name ends up pointing at. `stdin` and `stdout`, when given, are where
+    /// descriptors 0 and 1 point before the redirections apply.
+    fn open_redirects(
+        &self,
+        redirects: &[Redirect],
+        stdin: Option<OwnedFd>,
+        stdout: Option<OwnedFd>,
+    ) -> CommandResult<Vec<(RawFd, OwnedFd)>> {
+        let mut table: Vec<(RawFd, OwnedFd)> = [(0, stdin), (1, stdout)]
+            .into_iter()
+            .filter_map(|(target, fd)| Some((target, fd?)))
+            .collect();
+        for redirect in redirects {
+            let fd = match &redirect.target {
+                RedirectTarget::Read(path) => File::open(self.current_dir.join(path))
+                    .map_err(|e| Error::other(format!("{}: {}", path, e)))?
+                    .into(),
+                RedirectTarget::Write {
+                    path,
+                    append,
+                    force,
+                } => self.open_redirect_file(path, *append, *force)?.into(),
+                RedirectTarget::Dup(source) => match table.iter().find(|(fd, _)| fd == source) {
+                    Some((_, fd)) => fd.try_clone()?,
+                    None => Self::dup_above(*source, 0)
+                        .map_err(|e| Error::other(format!("{}: {}", source, e)))?,
+                },
+                RedirectTarget::Text(text) => Self::text_pipe(text)?,
+            };
+            table.retain(|(target, _)| *target != redirect.fd);
+            table.push((redirect.fd, fd));
+        }
+        Ok(table)
+    }
+
+    /// The read end of a pipe that yields `text`, which a thread writes so the
+    /// pipe cannot fill up before the child starts reading
+    fn text_pipe(text: &str) -> CommandResult<OwnedFd> {
+        let (reader, mut writer) = pipe()?;
+        let text = text.to_string();
+        // A child that exits without reading it all ends the write with EPIPE
+        thread::spawn(move || writer.write_all(text.as_bytes()));
+        Ok(reader.into())
+    }
+
+    /// Duplicates `fd` onto the lowest free descriptor at or above `min`, closed on exec
+    fn dup_above(fd: RawFd, min: RawFd) -> CommandResult<OwnedFd> {
+        // SAFETY: fcntl only reads the descriptor table; a closed fd is reported as EBADF
+        let copy = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, min) };
+        if copy == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the copy is a fresh descriptor that nothing else owns
+        Ok(unsafe { OwnedFd::from_raw_fd(copy) })
     }
 
     fn create_base_command(&self, command: &str, args: &[&str]) -> Command {
-        let mut cmd = Command::new(command);
+        let cached = self
+            .executables
+            .as_ref()
+            .and_then(|executables| executables.resolve(command));
+        let mut cmd = match cached {
+            Some(path) => {
+                let mut cmd = Command::new(path);
+                // The program still sees the name it was run as
+                cmd.arg0(command);
+                cmd
+            }
+            None => Command::new(command),
+        };
         cmd.args(args).current_dir(&self.current_dir);
+        if let Some(env) = &self.env {
+            cmd.env_clear().envs(env);
+        }
+        if !self.inherited.is_empty() {
+            let inherited = self.inherited.clone();
+            // SAFETY: fcntl is async-signal-safe and the descriptors are the shell's own
+            unsafe {
+                cmd.pre_exec(move || {
+                    for &fd in &inherited {
+                        if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    }
+                    Ok(())
+                });
+            }
+        }
+        if self.priority != Priority::default() {
+            let priority = self.priority;
+            // SAFETY: reading our own priority has no memory effects
+            let current = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+            let niceness = (current + priority.nice).clamp(-20, 19);
+            // SAFETY: `apply` only makes async-signal-safe system calls
+            unsafe { cmd.pre_exec(move || priority.apply(niceness)) };
+        }
         cmd
     }
 
-    fn check_status(&self, status: ExitStatus, context: &str) -> CommandResult<()> {
-        if !status.success() {
-            return Err(Error::new(
-                ErrorKind::Other,
-                format!("{} exited with status: {}", context, status),
-            ));
+    fn wait_for_processes(&self, name: &str, processes: Vec<Child>) -> CommandResult<ExitCode> {
+        Ok(self.pipeline_status(&self.wait(name, processes)?))
+    }
+
+    /// Waits for the processes of a pipeline, returning each one's status.
+    ///
+    /// When they run past the timeout they are stopped, and all of them
+    /// count as timed out.
+    pub fn wait(&self, name: &str, mut processes: Vec<Child>) -> CommandResult<Vec<ExitCode>> {
+        if let Some(timeout) = self.timeout {
+            if !Self::wait_until(&mut processes, Instant::now() + timeout)? {
+                Self::terminate(&mut processes)?;
+                eprintln!("hermit: {}: timed out after {:?}", name, timeout);
+                return Ok(vec![TIMED_OUT; processes.len()]);
+            }
+        }
+
+        processes
+            .iter_mut()
+            .map(|process| Ok(process.wait()?.into()))
+            .collect()
+    }
+
+    /// The status of a pipeline given those of its commands in order: the
+    /// last one's, or with [`with_pipefail`](Self::with_pipefail) that of the
+    /// last one that failed
+    pub fn pipeline_status(&self, statuses: &[ExitCode]) -> ExitCode {
+        let mut status = ExitCode::SUCCESS;
+        for &code in statuses {
+            if !self.pipefail || code != ExitCode::SUCCESS {
+                status = code;
+            }
+        }
+        status
+    }
+
+    /// Waits for every process to exit, returning `false` if the deadline comes first
+    fn wait_until(processes: &mut [Child], deadline: Instant) -> CommandResult<bool> {
+        loop {
+            let mut running = false;
+            for process in processes.iter_mut() {
+                running |= process.try_wait()?.is_none();
+            }
+            if !running {
+                return Ok(true);
+            }
+            if Instant::now() >= deadline {
+                return Ok(false);
+            }
+            thread::sleep(Duration::from_millis(10));
         }
-        Ok(())
     }
 
-    fn wait_for_processes(&self, processes: Vec<Child>) -> CommandResult<()> {
-        for (i, mut process) in processes.into_iter().enumerate() {
-            let status = process.wait()?;
-            if !status.success() {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    format!("Pipeline command {} exited with status: {}", i + 1, status),
-                ));
+    /// Asks the processes to stop, killing any still running after a grace period
+    fn terminate(processes: &mut [Child]) -> CommandResult<()> {
+        for process in processes.iter_mut() {
+            // Only signal children not yet reaped, whose pids cannot have been reused
+            if process.try_wait()?.is_none() {
+                // SAFETY: kill has no memory effects; the pid is our own live child
+                unsafe { libc::kill(process.id() as libc::pid_t, libc::SIGTERM) };
+            }
+        }
+        if !Self::wait_until(processes, Instant::now() + KILL_GRACE)? {
+            for process in processes.iter_mut() {
+                process.kill().ok();
             }
         }
+        for process in processes {
+            process.wait()?;
+        }
         Ok(())
     }
 
-    fn open_redirect_file(&self, path: &str) -> CommandResult<std::fs::File> {
-        OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(path.trim())
-            .map_err(|e| {
-                Error::new(
-                    ErrorKind::Other,
-                    format!("Failed to open redirect file: {}", e),
-                )
-            })
+    fn open_redirect_file(&self, path: &str, append: bool, force: bool) -> CommandResult<File> {
+        let path = self.current_dir.join(path.trim());
+        let mut options = OpenOptions::new();
+        options.write(true);
+        let protected = self.no_clobber && !force;
+        if append {
+            options.create(true).append(true);
+        } else if protected && path.metadata().map_or(true, |meta| meta.is_file()) {
+            // Devices like /dev/null are never clobbered, so they stay writable
+            options.create_new(true);
+        } else {
+            options.create(true).truncate(true);
+        }
+        options.open(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => Error::other(format!(
+                "{}: cannot overwrite existing file",
+                path.display()
+            )),
+            _ => Error::other(format!("Failed to open redirect file: {}", e)),
+        })
+    }
+}
+
+/// The shell's own descriptors while [`ExternalCommand::redirect_shell`] has
+/// them pointed elsewhere, which are put back on drop
+pub struct Swapped {
+    /// Each descriptor replaced, in order, with a copy of what it was or
+    /// `None` if it was not open
+    saved: Vec<(RawFd, Option<OwnedFd>)>,
+}
+
+impl Drop for Swapped {
+    fn drop(&mut self) {
+        io::stdout().flush().ok();
+        if self.saved.iter().any(|(target, _)| *target == 0) {
+            discard_stdin_buffer();
+        }
+        for (target, saved) in self.saved.drain(..).rev() {
+            // SAFETY: dup2 and close only change the descriptor table, putting
+            // back what `target` was before
+            unsafe {
+                match saved {
+                    Some(fd) => libc::dup2(fd.as_raw_fd(), target),
+                    None => libc::close(target),
+                };
+            }
+        }
+    }
+}
+
+/// Throws away what stdin read ahead of a builtin from a redirection, which
+/// would otherwise be taken for the next input after it
+fn discard_stdin_buffer() {
+    let Ok(null) = File::open("/dev/null") else {
+        return;
+    };
+    // SAFETY: descriptor 0 is restored by the caller right after
+    if unsafe { libc::dup2(null.as_raw_fd(), 0) } == -1 {
+        return;
+    }
+    // With nothing left to read, a buffer once empty stays empty
+    let mut stdin = io::stdin().lock();
+    while let Ok(buffer) = stdin.fill_buf() {
+        let n = buffer.len();
+        if n == 0 {
+            break;
+        }
+        stdin.consume(n);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs;
+    use crate::core::lexer;
+    use std::{fs, os::unix::fs::PermissionsExt};
     use tempfile::TempDir;
 
+    fn stdout_to(path: &str) -> Vec<Redirect> {
+        vec![Redirect {
+            fd: 1,
+            target: RedirectTarget::Write {
+                path: path.to_string(),
+                append: false,
+                force: false,
+            },
+        }]
+    }
+
+    /// Runs a pipeline of programs to the end, as the shell does
+    fn execute_redirected_pipeline(
+        command: &ExternalCommand,
+        pipeline: &[(&str, Vec<&str>, Vec<Redirect>)],
+    ) -> CommandResult<ExitCode> {
+        let processes = command.spawn_pipeline(pipeline, None, None)?;
+        command.wait_for_processes("pipeline", processes)
+    }
+
+    fn execute_pipeline(
+        command: &ExternalCommand,
+        pipeline: &[(&str, Vec<&str>)],
+    ) -> CommandResult<ExitCode> {
+        let pipeline: Vec<_> = pipeline
+            .iter()
+            .map(|(cmd, args)| (*cmd, args.clone(), Vec::new()))
+            .collect();
+        execute_redirected_pipeline(command, &pipeline)
+    }
+
     fn setup() -> (ExternalCommand, TempDir) {
         let tmp_dir = TempDir::new().expect("Failed to create temp dir");
         let command = ExternalCommand::new(tmp_dir.path().to_path_buf());
@@ -164,15 +743,80 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_execute_exit_code() {
+        let (command, _tmp_dir) = setup();
+        assert_eq!(command.execute("true", &[]).unwrap(), ExitCode::SUCCESS);
+        assert_eq!(command.execute("false", &[]).unwrap(), ExitCode::FAILURE);
+        assert_eq!(
+            command.execute("sh", &["-c", "exit 3"]).unwrap(),
+            ExitCode::new(3)
+        );
+    }
+
+    #[test]
+    fn test_pipeline_exit_code() {
+        let (command, _tmp_dir) = setup();
+        let pipeline = vec![("false", vec![]), ("true", vec![])];
+        assert_eq!(
+            execute_pipeline(&command, &pipeline).unwrap(),
+            ExitCode::SUCCESS
+        );
+
+        let pipeline = vec![("true", vec![]), ("false", vec![])];
+        assert_eq!(
+            execute_pipeline(&command, &pipeline).unwrap(),
+            ExitCode::FAILURE
+        );
+    }
+
+    #[test]
+    fn test_pipefail() {
+        let (command, _tmp_dir) = setup();
+        let command = command.with_pipefail(true);
+        let pipeline = vec![
+            ("sh", vec!["-c", "exit 3"]),
+            ("sh", vec!["-c", "exit 4"]),
+            ("true", vec![]),
+        ];
+        assert_eq!(
+            execute_pipeline(&command, &pipeline).unwrap(),
+            ExitCode::new(4)
+        );
+
+        let pipeline = vec![("true", vec![]), ("true", vec![])];
+        assert_eq!(
+            execute_pipeline(&command, &pipeline).unwrap(),
+            ExitCode::SUCCESS
+        );
+    }
+
     #[test]
     fn test_execute_pipeline() {
-        let (command, tmp_dir) = setup();
-        let output_file = tmp_dir.path().join("output.txt");
+        let (command, _tmp_dir) = setup();
 
-        // Create a pipeline that writes to a file
         let pipeline = vec![("echo", vec!["hello world"]), ("grep", vec!["world"])];
 
-        command.execute_pipeline(&pipeline).unwrap();
+        execute_pipeline(&command, &pipeline).unwrap();
+    }
+
+    #[test]
+    fn test_redirected_pipeline() {
+        let (command, dir) = setup();
+        let pipeline = [
+            (
+                "cat",
+                vec![],
+                vec![Redirect {
+                    fd: 0,
+                    target: RedirectTarget::Text("b\na\n".to_string()),
+                }],
+            ),
+            ("sort", vec![], stdout_to("sorted.txt")),
+        ];
+        execute_redirected_pipeline(&command, &pipeline).unwrap();
+        let sorted = fs::read_to_string(dir.path().join("sorted.txt")).unwrap();
+        assert_eq!(sorted, "a\nb\n");
     }
 
     #[test]
@@ -182,24 +826,253 @@ mod tests {
         let output_path = output_file.to_str().unwrap();
 
         command
-            .execute_redirect("echo", &["hello"], output_path)
+            .execute_redirect("echo", &["hello"], &stdout_to(output_path))
             .unwrap();
 
         let content = fs::read_to_string(output_file).unwrap();
         assert_eq!(content.trim(), "hello");
     }
 
+    #[test]
+    fn test_no_clobber() {
+        let (command, tmp_dir) = setup();
+        let output_file = tmp_dir.path().join("kept.txt");
+        fs::write(&output_file, "important\n").unwrap();
+
+        let command = command.with_no_clobber(true);
+        assert!(command
+            .execute_redirect("echo", &["oops"], &stdout_to("kept.txt"))
+            .is_err());
+        assert_eq!(fs::read_to_string(&output_file).unwrap(), "important\n");
+
+        command
+            .execute_redirect("echo", &["new"], &stdout_to("new.txt"))
+            .unwrap();
+        command
+            .execute_redirect("echo", &["gone"], &stdout_to("/dev/null"))
+            .unwrap();
+    }
+
+    fn tokens(line: &str) -> Vec<Token> {
+        lexer::tokenize(line).unwrap()
+    }
+
+    #[test]
+    fn test_extract_redirects() {
+        let words = tokens("sort -r < in 2> err >> out 3>&1 x");
+        let (remaining, redirects) = Redirect::extract(&words).unwrap();
+        assert_eq!(remaining, ["sort", "-r", "x"]);
+        assert_eq!(
+            redirects,
+            vec![
+                Redirect {
+                    fd: 0,
+                    target: RedirectTarget::Read("in".to_string()),
+                },
+                Redirect {
+                    fd: 2,
+                    target: RedirectTarget::Write {
+                        path: "err".to_string(),
+                        append: false,
+                        force: false,
+                    },
+                },
+                Redirect {
+                    fd: 1,
+                    target: RedirectTarget::Write {
+                        path: "out".to_string(),
+                        append: true,
+                        force: false,
+                    },
+                },
+                Redirect {
+                    fd: 3,
+                    target: RedirectTarget::Dup(1),
+                },
+            ]
+        );
+
+        assert!(Redirect::extract(&tokens("echo 2>")).is_err());
+        assert!(Redirect::extract(&tokens("echo >&x")).is_err());
+        // Quoted operators are plain words
+        let words: Vec<Token> = ["echo", "a>b", ">&x"]
+            .iter()
+            .map(|word| Token::Word(word.to_string()))
+            .collect();
+        let (remaining, redirects) = Redirect::extract(&words).unwrap();
+        assert_eq!(remaining, ["echo", "a>b", ">&x"]);
+        assert!(redirects.is_empty());
+    }
+
+    #[test]
+    fn test_multiple_redirects() {
+        let (command, tmp_dir) = setup();
+        fs::write(tmp_dir.path().join("in.txt"), "b\na\n").unwrap();
+
+        let words = tokens("< in.txt > out.txt 2> err.txt 3> trace.txt");
+        let (_, redirects) = Redirect::extract(&words).unwrap();
+        let script = "sort; echo oops >&2; echo traced >&3";
+        command
+            .execute_redirect("sh", &["-c", script], &redirects)
+            .unwrap();
+
+        let read = |name: &str| fs::read_to_string(tmp_dir.path().join(name)).unwrap();
+        assert_eq!(read("out.txt"), "a\nb\n");
+        assert_eq!(read("err.txt"), "oops\n");
+        assert_eq!(read("trace.txt"), "traced\n");
+
+        // `2>&1` after `>` sends both streams to the file
+        let (_, redirects) = Redirect::extract(&tokens(">> out.txt 2>&1")).unwrap();
+        command
+            .execute_redirect("sh", &["-c", "echo out; echo err >&2"], &redirects)
+            .unwrap();
+        assert_eq!(read("out.txt"), "a\nb\nout\nerr\n");
+    }
+
+    #[test]
+    fn test_here_string() {
+        let (command, tmp_dir) = setup();
+        let words = [
+            Token::Word("tr".to_string()),
+            Token::Operator("<<<".to_string()),
+            Token::Word("some string".to_string()),
+            Token::Operator(">".to_string()),
+            Token::Word("out.txt".to_string()),
+        ];
+        let (remaining, redirects) = Redirect::extract(&words).unwrap();
+        assert_eq!(remaining, ["tr"]);
+        assert_eq!(
+            redirects[0].target,
+            RedirectTarget::Text("some string\n".to_string())
+        );
+
+        command
+            .execute_redirect("tr", &["a-z", "A-Z"], &redirects)
+            .unwrap();
+        let out = fs::read_to_string(tmp_dir.path().join("out.txt")).unwrap();
+        assert_eq!(out, "SOME STRING\n");
+        assert!(Redirect::extract(&tokens("cat <<<")).is_err());
+    }
+
+    #[test]
+    fn test_spawn_background() {
+        let (command, tmp_dir) = setup();
+        let test_file = tmp_dir.path().join("background.txt");
+
+        let mut child = command
+            .spawn_background("touch", &[test_file.to_str().unwrap()])
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+        assert!(test_file.exists());
+    }
+
+    #[test]
+    fn test_redirect_relative_to_current_dir() {
+        let (command, tmp_dir) = setup();
+
+        command
+            .execute_redirect("echo", &["relative"], &stdout_to("relative.txt"))
+            .unwrap();
+
+        let content = fs::read_to_string(tmp_dir.path().join("relative.txt")).unwrap();
+        assert_eq!(content.trim(), "relative");
+    }
+
+    #[test]
+    fn test_with_env() {
+        let (command, tmp_dir) = setup();
+        let output_file = tmp_dir.path().join("env.txt");
+        let env = HashMap::from([("HERMIT_TEST".to_string(), "value".to_string())]);
+
+        command
+            .with_env(env)
+            .execute_redirect(
+                "sh",
+                &["-c", "echo $HERMIT_TEST"],
+                &stdout_to(output_file.to_str().unwrap()),
+            )
+            .unwrap();
+
+        let content = fs::read_to_string(output_file).unwrap();
+        assert_eq!(content.trim(), "value");
+    }
+
+    #[test]
+    fn test_with_executables_counts_hits() {
+        let (command, tmp_dir) = setup();
+        let script = tmp_dir.path().join("hermit-tool");
+        fs::write(&script, "#!/bin/sh\necho ran\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let output_file = tmp_dir.path().join("tool.txt");
+
+        // Not on the real $PATH, so only the cache can find it
+        let executables = PathCache::new(tmp_dir.path().to_str().unwrap());
+        let status = command
+            .with_executables(executables.clone())
+            .execute_redirect(
+                "hermit-tool",
+                &[],
+                &stdout_to(output_file.to_str().unwrap()),
+            )
+            .unwrap();
+
+        assert_eq!(status, ExitCode::SUCCESS);
+        assert_eq!(
+            executables.hits(),
+            vec![("hermit-tool".to_string(), script, 1)]
+        );
+        let content = fs::read_to_string(output_file).unwrap();
+        assert_eq!(content.trim(), "ran");
+    }
+
+    #[test]
+    fn test_timeout_stops_command() {
+        let (command, _tmp_dir) = setup();
+        let command = command.with_timeout(Some(Duration::from_millis(100)));
+        let started = Instant::now();
+
+        assert_eq!(command.execute("sleep", &["5"]).unwrap(), TIMED_OUT);
+        let pipeline = vec![("sleep", vec!["5"]), ("cat", vec![])];
+        assert_eq!(execute_pipeline(&command, &pipeline).unwrap(), TIMED_OUT);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // Commands that ignore SIGTERM are killed after the grace period
+        let stubborn = ["-c", "trap '' TERM; sleep 5"];
+        assert_eq!(command.execute("sh", &stubborn).unwrap(), TIMED_OUT);
+        assert_eq!(command.execute("true", &[]).unwrap(), ExitCode::SUCCESS);
+    }
+
+    #[test]
+    fn test_priority_lowers_niceness() {
+        let (command, tmp_dir) = setup();
+        let output_file = tmp_dir.path().join("nice.txt");
+        let current = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+
+        let priority = Priority {
+            nice: 5,
+            idle_io: true,
+        };
+        command
+            .with_priority(priority)
+            .execute_redirect("nice", &[], &stdout_to(output_file.to_str().unwrap()))
+            .unwrap();
+
+        let content = fs::read_to_string(output_file).unwrap();
+        assert_eq!(content.trim(), (current + 5).min(19).to_string());
+    }
+
     #[test]
     fn test_empty_pipeline() {
         let (command, _tmp_dir) = setup();
-        let result = command.execute_pipeline(&[]);
-        assert!(result.is_ok());
+        let result = execute_pipeline(&command, &[]);
+        assert_eq!(result.unwrap(), ExitCode::SUCCESS);
     }
 
     #[test]
     fn test_invalid_redirect_path() {
         let (command, _tmp_dir) = setup();
-        let result = command.execute_redirect("echo", &["test"], "/nonexistent/path/file.txt");
+        let result =
+            command.execute_redirect("echo", &["test"], &stdout_to("/nonexistent/path/file.txt"));
         assert!(result.is_err());
     }
 }
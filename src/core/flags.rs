@@ -1,10 +1,17 @@
 use std::collections::{HashMap, HashSet};
 
-/// Represents command-line flags and their associated values
+/// Represents command-line flags and their associated values.
+///
+/// Understands compact short flags (`-abc`), GNU-style long options (`--all`,
+/// `--color=never`), and `--` to end option parsing. Everything that is not an
+/// option is collected as a positional argument.
 #[derive(Debug, Clone, Default)]
 pub struct Flags {
     flags: HashSet<char>,
     values: HashMap<char, String>,
+    long_flags: HashSet<String>,
+    long_values: HashMap<String, String>,
+    positionals: Vec<String>,
 }
 
 /// Represents errors that can occur during flag parsing
@@ -16,6 +23,95 @@ pub enum FlagError {
     MissingValue(char),
     #[error("Duplicate flag: {0}")]
     DuplicateFlag(char),
+    #[error("Duplicate option: --{0}")]
+    DuplicateOption(String),
+    #[error("Missing value for option: --{0}")]
+    MissingOptionValue(String),
+    #[error("Unknown option: {0}")]
+    UnknownOption(String),
+}
+
+/// Declares one option a command accepts.
+///
+/// Commands list their options as specs so arguments can be validated before
+/// `execute` runs and usage text can be generated instead of written by hand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlagSpec {
+    pub short: Option<char>,
+    pub long: Option<&'static str>,
+    /// Placeholder for the option's value in usage text, or `None` for a plain switch
+    pub value: Option<&'static str>,
+    pub description: &'static str,
+}
+
+/// `-?`/`--help`, accepted by every command
+pub const HELP_FLAG: FlagSpec = FlagSpec::new('?', "help", "Show this help");
+
+impl FlagSpec {
+    /// An option with both a short and a long form
+    pub const fn new(short: char, long: &'static str, description: &'static str) -> Self {
+        Self {
+            short: Some(short),
+            long: Some(long),
+            value: None,
+            description,
+        }
+    }
+
+    pub const fn short(short: char, description: &'static str) -> Self {
+        Self {
+            short: Some(short),
+            long: None,
+            value: None,
+            description,
+        }
+    }
+
+    pub const fn long(long: &'static str, description: &'static str) -> Self {
+        Self {
+            short: None,
+            long: Some(long),
+            value: None,
+            description,
+        }
+    }
+
+    /// Makes the option require a value, shown as `name` in usage text
+    pub const fn takes_value(mut self, name: &'static str) -> Self {
+        self.value = Some(name);
+        self
+    }
+
+    /// The option as written in usage text, e.g. `-a, --all` or `--color=WHEN`
+    pub fn synopsis(&self) -> String {
+        let value = self.value.unwrap_or_default();
+        match (self.short, self.long) {
+            (Some(short), Some(long)) if self.value.is_some() => {
+                format!("-{} {}, --{}={}", short, value, long, value)
+            }
+            (Some(short), Some(long)) => format!("-{}, --{}", short, long),
+            (Some(short), None) if self.value.is_some() => format!("-{} {}", short, value),
+            (Some(short), None) => format!("-{}", short),
+            (None, Some(long)) if self.value.is_some() => format!("--{}={}", long, value),
+            (None, Some(long)) => format!("--{}", long),
+            (None, None) => String::new(),
+        }
+    }
+
+    /// Formats specs as an aligned two-column option table
+    pub fn table(specs: &[FlagSpec]) -> String {
+        let width = specs
+            .iter()
+            .map(|spec| spec.synopsis().len())
+            .max()
+            .unwrap_or(0);
+
+        specs
+            .iter()
+            .map(|spec| format!("  {:<width$}  {}", spec.synopsis(), spec.description))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
 impl Flags {
@@ -23,10 +119,10 @@ impl Flags {
     ///
     /// # Arguments
     /// * `args` - Slice of argument strings
-    /// * `value_flags` - Set of flags that require values
     ///
     /// # Returns
     /// * `Result<Self, FlagError>` - New Flags instance or error
+    #[cfg(test)]
     pub fn new(args: &[&str]) -> Result<Self, FlagError> {
         Self::with_value_flags(args, &[])
     }
@@ -40,38 +136,117 @@ impl Flags {
     /// # Returns
     /// * `Result<Self, FlagError>` - New Flags instance or error
     pub fn with_value_flags(args: &[&str], value_flags: &[char]) -> Result<Self, FlagError> {
-        let mut flags = HashSet::new();
-        let mut values = HashMap::new();
+        let mut parsed = Self::default();
         let value_flags: HashSet<_> = value_flags.iter().copied().collect();
 
-        let mut i = 0;
-        while i < args.len() {
-            let arg = args[i];
-
-            if let Some(flag_chars) = arg.strip_prefix('-') {
-                if flag_chars.is_empty() {
-                    return Err(FlagError::InvalidFormat("Empty flag".to_string()));
-                }
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            if arg == "--" {
+                parsed.positionals.extend(args.map(|arg| arg.to_string()));
+                break;
+            }
 
+            if let Some(option) = arg.strip_prefix("--") {
+                parsed.parse_long(option)?;
+            } else if let Some(flag_chars) = arg.strip_prefix('-').filter(|f| !f.is_empty()) {
                 for c in flag_chars.chars() {
-                    if flags.contains(&c) {
+                    if parsed.flags.contains(&c) {
                         return Err(FlagError::DuplicateFlag(c));
                     }
 
                     if value_flags.contains(&c) {
-                        i += 1;
-                        if i >= args.len() {
-                            return Err(FlagError::MissingValue(c));
-                        }
-                        values.insert(c, args[i].to_string());
+                        let value = args.next().ok_or(FlagError::MissingValue(c))?;
+                        parsed.values.insert(c, value.to_string());
                     }
-                    flags.insert(c);
+                    parsed.flags.insert(c);
                 }
+            } else {
+                // A lone `-` conventionally means stdin, so it is positional too
+                parsed.positionals.push(arg.to_string());
             }
-            i += 1;
         }
 
-        Ok(Self { flags, values })
+        Ok(parsed)
+    }
+
+    /// Parses arguments against a command's declared options.
+    ///
+    /// Options missing from `specs` are rejected. A short option with a long
+    /// alias is recorded under both names, so either spelling can be queried.
+    /// Long options take their value as `--name=value`.
+    pub fn parse(args: &[&str], specs: &[FlagSpec]) -> Result<Self, FlagError> {
+        let value_flags: Vec<char> = specs
+            .iter()
+            .filter(|spec| spec.value.is_some())
+            .filter_map(|spec| spec.short)
+            .collect();
+        let mut parsed = Self::with_value_flags(args, &value_flags)?;
+
+        if let Some(flag) = parsed
+            .flags
+            .iter()
+            .filter(|&&flag| !specs.iter().any(|spec| spec.short == Some(flag)))
+            .min()
+        {
+            return Err(FlagError::UnknownOption(format!("-{}", flag)));
+        }
+        if let Some(name) = parsed
+            .long_flags
+            .iter()
+            .filter(|name| !specs.iter().any(|spec| spec.long == Some(name.as_str())))
+            .min()
+        {
+            return Err(FlagError::UnknownOption(format!("--{}", name)));
+        }
+
+        for spec in specs {
+            let Some(long) = spec.long else {
+                continue;
+            };
+            if spec.value.is_some()
+                && parsed.long_flags.contains(long)
+                && !parsed.long_values.contains_key(long)
+            {
+                return Err(FlagError::MissingOptionValue(long.to_string()));
+            }
+
+            let Some(short) = spec.short else {
+                continue;
+            };
+            if parsed.long_flags.contains(long) {
+                parsed.flags.insert(short);
+                if let Some(value) = parsed.long_values.get(long) {
+                    parsed.values.insert(short, value.clone());
+                }
+            } else if parsed.flags.contains(&short) {
+                parsed.long_flags.insert(long.to_string());
+                if let Some(value) = parsed.values.get(&short) {
+                    parsed.long_values.insert(long.to_string(), value.clone());
+                }
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Parses a long option without its leading `--`, e.g. `all` or `color=never`
+    fn parse_long(&mut self, option: &str) -> Result<(), FlagError> {
+        let (name, value) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (option, None),
+        };
+
+        if name.is_empty() {
+            return Err(FlagError::InvalidFormat(format!("--{}", option)));
+        }
+        if !self.long_flags.insert(name.to_string()) {
+            return Err(FlagError::DuplicateOption(name.to_string()));
+        }
+        if let Some(value) = value {
+            self.long_values.insert(name.to_string(), value.to_string());
+        }
+
+        Ok(())
     }
 
     /// Checks if a flag is present
@@ -90,66 +265,43 @@ impl Flags {
         self.values.get(&flag).map(String::as_str)
     }
 
-    /// Adds a flag
+    /// Checks if a long option is present
     ///
     /// # Arguments
-    /// * `flag` - The flag character to add
-    pub fn add_flag(&mut self, flag: char) {
-        self.flags.insert(flag);
+    /// * `name` - The option name without its leading `--`
+    pub fn has_long(&self, name: &str) -> bool {
+        self.long_flags.contains(name)
     }
 
-    /// Adds a flag with a value
+    /// Gets the value given to a long option with `--name=value`
     ///
     /// # Arguments
-    /// * `flag` - The flag character to add
-    /// * `value` - The value to associate with the flag
-    pub fn add_value(&mut self, flag: char, value: String) {
-        self.flags.insert(flag);
-        self.values.insert(flag, value);
-    }
-
-    /// Gets all flags
-    pub fn flags(&self) -> &HashSet<char> {
-        &self.flags
+    /// * `name` - The option name without its leading `--`
+    pub fn get_long_value(&self, name: &str) -> Option<&str> {
+        self.long_values.get(name).map(String::as_str)
     }
 
-    /// Gets all flag values
-    pub fn values(&self) -> &HashMap<char, String> {
-        &self.values
-    }
-
-    /// Creates flags from a string
+    /// Checks if an option is present in either its short or long form
     ///
     /// # Arguments
-    /// * `s` - The string to parse flags from
-    pub fn from_str(s: &str) -> Result<Self, FlagError> {
-        let args: Vec<&str> = s.split_whitespace().collect();
-        Self::new(&args)
+    /// * `short` - The short flag character
+    /// * `long` - The long option name without its leading `--`
+    pub fn has_option(&self, short: char, long: &str) -> bool {
+        self.has_flag(short) || self.has_long(long)
     }
 
-    /// Returns number of flags
-    pub fn len(&self) -> usize {
-        self.flags.len()
-    }
-
-    /// Checks if there are no flags
-    pub fn is_empty(&self) -> bool {
-        self.flags.is_empty()
-    }
-
-    /// Removes a flag
+    /// Gets the value of an option given in either its short or long form
     ///
     /// # Arguments
-    /// * `flag` - The flag character to remove
-    pub fn remove_flag(&mut self, flag: char) {
-        self.flags.remove(&flag);
-        self.values.remove(&flag);
+    /// * `short` - The short flag character
+    /// * `long` - The long option name without its leading `--`
+    pub fn option_value(&self, short: char, long: &str) -> Option<&str> {
+        self.get_value(short).or_else(|| self.get_long_value(long))
     }
 
-    /// Clears all flags and values
-    pub fn clear(&mut self) {
-        self.flags.clear();
-        self.values.clear();
+    /// Gets the arguments that are not options, in order
+    pub fn positionals(&self) -> &[String] {
+        &self.positionals
     }
 }
 
@@ -204,38 +356,102 @@ mod tests {
     }
 
     #[test]
-    fn test_from_str() {
-        let flags = Flags::from_str("-abc -d value").unwrap();
-        assert!(flags.has_flag('a'));
-        assert!(flags.has_flag('b'));
-        assert!(flags.has_flag('c'));
-        assert!(flags.has_flag('d'));
+    fn test_long_options() {
+        let args = vec!["--all", "--color=never", "file"];
+        let flags = Flags::new(&args).unwrap();
+        assert!(flags.has_long("all"));
+        assert!(flags.has_long("color"));
+        assert_eq!(flags.get_long_value("color"), Some("never"));
+        assert_eq!(flags.get_long_value("all"), None);
+        assert_eq!(flags.positionals(), ["file"]);
     }
 
     #[test]
-    fn test_add_and_remove() {
-        let mut flags = Flags::default();
-        flags.add_flag('a');
+    fn test_parse_against_specs() {
+        const SPECS: &[FlagSpec] = &[
+            FlagSpec::new('a', "all", "Show everything"),
+            FlagSpec::long("color", "Colorize output").takes_value("WHEN"),
+            FlagSpec::short('n', "Limit entries").takes_value("COUNT"),
+        ];
+
+        let flags = Flags::parse(&["--all", "-n", "5", "dir"], SPECS).unwrap();
         assert!(flags.has_flag('a'));
-        flags.remove_flag('a');
-        assert!(!flags.has_flag('a'));
+        assert!(flags.has_long("all"));
+        assert_eq!(flags.get_value('n'), Some("5"));
+        assert_eq!(flags.positionals(), ["dir"]);
+
+        let flags = Flags::parse(&["-a", "--color=never"], SPECS).unwrap();
+        assert!(flags.has_long("all"));
+        assert_eq!(flags.get_long_value("color"), Some("never"));
+
+        assert!(matches!(
+            Flags::parse(&["-ax"], SPECS),
+            Err(FlagError::UnknownOption(ref option)) if option == "-x"
+        ));
+        assert!(matches!(
+            Flags::parse(&["--nope"], SPECS),
+            Err(FlagError::UnknownOption(ref option)) if option == "--nope"
+        ));
+        assert!(matches!(
+            Flags::parse(&["--color"], SPECS),
+            Err(FlagError::MissingOptionValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_spec_table() {
+        let specs = [
+            FlagSpec::new('a', "all", "Show everything"),
+            FlagSpec::long("color", "Colorize output").takes_value("WHEN"),
+        ];
+        assert_eq!(
+            FlagSpec::table(&specs),
+            "  -a, --all     Show everything\n  --color=WHEN  Colorize output"
+        );
+    }
+
+    #[test]
+    fn test_short_and_long_aliases() {
+        let short = Flags::new(&["-a"]).unwrap();
+        let long = Flags::new(&["--all"]).unwrap();
+        assert!(short.has_option('a', "all"));
+        assert!(long.has_option('a', "all"));
+
+        let flags = Flags::with_value_flags(&["-n", "5"], &['n']).unwrap();
+        assert_eq!(flags.option_value('n', "lines"), Some("5"));
+        let flags = Flags::new(&["--lines=7"]).unwrap();
+        assert_eq!(flags.option_value('n', "lines"), Some("7"));
     }
 
     #[test]
-    fn test_add_value() {
-        let mut flags = Flags::default();
-        flags.add_value('a', "value".to_string());
+    fn test_double_dash_terminator() {
+        let args = vec!["-a", "--", "-weirdfile", "--all"];
+        let flags = Flags::new(&args).unwrap();
         assert!(flags.has_flag('a'));
-        assert_eq!(flags.get_value('a'), Some("value"));
+        assert!(!flags.has_flag('w'));
+        assert!(!flags.has_long("all"));
+        assert_eq!(flags.positionals(), ["-weirdfile", "--all"]);
+    }
+
+    #[test]
+    fn test_positionals() {
+        let args = vec!["src", "-l", "-", "docs"];
+        let flags = Flags::with_value_flags(&["-o", "out", "in"], &['o']).unwrap();
+        assert_eq!(flags.positionals(), ["in"]);
+
+        let flags = Flags::new(&args).unwrap();
+        assert_eq!(flags.positionals(), ["src", "-", "docs"]);
     }
 
     #[test]
-    fn test_clear() {
-        let mut flags = Flags::default();
-        flags.add_flag('a');
-        flags.add_value('b', "value".to_string());
-        flags.clear();
-        assert!(flags.is_empty());
-        assert_eq!(flags.len(), 0);
+    fn test_invalid_long_options() {
+        assert!(matches!(
+            Flags::new(&["--=value"]),
+            Err(FlagError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            Flags::new(&["--all", "--all"]),
+            Err(FlagError::DuplicateOption(ref name)) if name == "all"
+        ));
     }
 }
@@ -0,0 +1,208 @@
+//! History expansion, rewriting `!!`, `!n`, `!$`, `!string` and `^old^new` from earlier lines
+
+use super::lexer::{self, Quoting, Scanner};
+
+/// Replaces the history references in a line with the text they refer to.
+///
+/// `!!` is the previous line, `!n` line n of the history and `!-n` the nth
+/// line back, `!$` the last word of the previous line and `!string` the latest
+/// line starting with string. A `!` in single quotes, after a backslash or a
+/// `$`, or followed by a space, `=`, `(`, `"` or nothing stands for itself.
+/// A line starting with `^`, as in `^old^new`, is the previous line with the
+/// first `old` in it replaced by `new`.
+///
+/// Returns None when the line refers to nothing, and an error naming the first
+/// reference that has no line to go with it.
+pub fn expand(line: &str, history: &[String]) -> Result<Option<String>, String> {
+    if let Some(substitution) = line.strip_prefix('^') {
+        return quick_substitution(substitution, history).map(Some);
+    }
+
+    let chars: Vec<(usize, char, Quoting)> = Scanner::new(line).collect();
+    let mut expanded = String::new();
+    let mut copied = 0;
+    let mut changed = false;
+    let mut k = 0;
+    while k < chars.len() {
+        let (i, c, quoting) = chars[k];
+        let previous = k.checked_sub(1).map(|p| chars[p].1);
+        let after_dollar =
+            previous == Some('$') || (previous == Some('{') && k >= 2 && chars[k - 2].1 == '$');
+        if c != '!'
+            || !matches!(quoting, Quoting::Unquoted | Quoting::Double)
+            || previous == Some('\\')
+            || after_dollar
+        {
+            k += 1;
+            continue;
+        }
+
+        let rest: Vec<char> = chars[k + 1..].iter().map(|&(_, c, _)| c).collect();
+        let taken = match rest.first() {
+            None | Some('=' | '(' | '"') => 0,
+            Some(c) if c.is_whitespace() => 0,
+            Some('!' | '$') => 1,
+            Some('-') => 1 + rest[1..].iter().take_while(|c| c.is_ascii_digit()).count(),
+            Some(c) if c.is_ascii_digit() => rest.iter().take_while(|c| c.is_ascii_digit()).count(),
+            Some(_) => rest
+                .iter()
+                .take_while(|&&c| !c.is_whitespace() && !";|&()<>'\"".contains(c))
+                .count(),
+        };
+        if taken == 0 {
+            k += 1;
+            continue;
+        }
+
+        let reference: String = rest[..taken].iter().collect();
+        let text =
+            event(&reference, history).ok_or_else(|| format!("!{}: event not found", reference))?;
+        expanded.push_str(&line[copied..i]);
+        expanded.push_str(&text);
+        changed = true;
+        k += 1 + taken;
+        copied = chars.get(k).map_or(line.len(), |&(i, _, _)| i);
+    }
+
+    if !changed {
+        return Ok(None);
+    }
+    expanded.push_str(&line[copied..]);
+    Ok(Some(expanded))
+}
+
+/// The previous line with the first `old` of an `old^new^rest` replaced by
+/// `new`, and `rest` added to the end
+fn quick_substitution(substitution: &str, history: &[String]) -> Result<String, String> {
+    let (old, new) = substitution.split_once('^').unwrap_or((substitution, ""));
+    let (new, rest) = new.split_once('^').unwrap_or((new, ""));
+    let previous = history.last().ok_or("^: event not found")?;
+    if old.is_empty() || !previous.contains(old) {
+        return Err(format!("^{}: substitution failed", old));
+    }
+    Ok(format!("{}{}", previous.replacen(old, new, 1), rest))
+}
+
+/// The text a reference, the part after its `!`, stands for
+fn event(reference: &str, history: &[String]) -> Option<String> {
+    let previous = history.last();
+    match reference {
+        "!" => previous.cloned(),
+        "$" => previous.and_then(|line| last_word(line)),
+        _ if reference.starts_with('-') => {
+            let back: usize = reference[1..].parse().ok()?;
+            let index = history.len().checked_sub(back)?;
+            (back > 0).then(|| history[index].clone())
+        }
+        _ if reference.starts_with(|c: char| c.is_ascii_digit()) => {
+            let number: usize = reference.parse().ok()?;
+            history.get(number.checked_sub(1)?).cloned()
+        }
+        _ => history
+            .iter()
+            .rev()
+            .find(|line| line.starts_with(reference))
+            .cloned(),
+    }
+}
+
+/// The last word of a line as written, quotes and all
+fn last_word(line: &str) -> Option<String> {
+    match lexer::tokenize_spanned(line) {
+        Ok(tokens) => tokens.last().map(|(_, token)| token.text().to_string()),
+        Err(_) => line.split_whitespace().last().map(String::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> Vec<String> {
+        ["ls -l", "cd /tmp", "vim notes.txt", "echo 'a b'"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    #[test]
+    fn test_expand() {
+        let history = history();
+        let cases = [
+            ("sudo !!", "sudo echo 'a b'"),
+            ("!1", "ls -l"),
+            ("!-2", "vim notes.txt"),
+            ("cat !$", "cat 'a b'"),
+            ("!vim", "vim notes.txt"),
+            ("!c; !l", "cd /tmp; ls -l"),
+            ("echo \"!!\"", "echo \"echo 'a b'\""),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(
+                expand(line, &history),
+                Ok(Some(expected.to_string())),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_literal_bangs() {
+        let history = history();
+        for line in [
+            "echo hi",
+            "! false",
+            "echo '!!'",
+            "echo \\!!",
+            "echo $!",
+            "echo ${!name}",
+            "echo \"hi!\"",
+            "[ a != b ]",
+            "echo wow!",
+        ] {
+            assert_eq!(expand(line, &history), Ok(None), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_quick_substitution() {
+        let history = ["ehco one one".to_string()];
+        let cases = [
+            ("^hc^ch", "echo one one"),
+            ("^hc^ch^", "echo one one"),
+            ("^one^two^ three", "ehco two one three"),
+            ("^ one", "ehco one"),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(
+                expand(line, &history),
+                Ok(Some(expected.to_string())),
+                "{}",
+                line
+            );
+        }
+        assert_eq!(
+            expand("^xyz^a", &history),
+            Err("^xyz: substitution failed".to_string())
+        );
+        assert!(expand("^a^b", &[]).is_err());
+    }
+
+    #[test]
+    fn test_event_not_found() {
+        let history = history();
+        assert_eq!(
+            expand("!nope", &history),
+            Err("!nope: event not found".to_string())
+        );
+        assert_eq!(
+            expand("!0", &history),
+            Err("!0: event not found".to_string())
+        );
+        assert_eq!(
+            expand("!-9", &history),
+            Err("!-9: event not found".to_string())
+        );
+        assert!(expand("!!", &[]).is_err());
+    }
+}
@@ -0,0 +1,452 @@
+use std::{
+    fmt,
+    io::{self, Read},
+    os::unix::process::ExitStatusExt,
+    process::{Child, ExitStatus},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long to wait for a finished job's last output to be read
+const OUTPUT_DRAIN: Duration = Duration::from_millis(100);
+
+/// What a background job printed that has not been shown yet
+#[derive(Debug, Default)]
+struct Output {
+    bytes: Vec<u8>,
+    /// Streams still being read; none once the job and its children have closed them
+    open: usize,
+}
+
+/// A command running in the background, identified by its job number
+#[derive(Debug)]
+pub struct Job {
+    id: usize,
+    command: String,
+    child: Child,
+    /// Collected output, when the job's stdout and stderr were piped to the shell
+    output: Option<Arc<Mutex<Output>>>,
+}
+
+impl Job {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Takes the output collected so far, so it is only shown once
+    pub fn take_output(&self) -> Vec<u8> {
+        self.output
+            .as_ref()
+            .and_then(|output| output.lock().ok())
+            .map(|mut output| std::mem::take(&mut output.bytes))
+            .unwrap_or_default()
+    }
+
+    /// Bytes of output waiting to be shown
+    pub fn pending_output(&self) -> usize {
+        self.output
+            .as_ref()
+            .and_then(|output| output.lock().ok())
+            .map_or(0, |output| output.bytes.len())
+    }
+
+    /// Gives the reader threads a moment to catch up with a job that just exited
+    fn drain_output(&self) -> Vec<u8> {
+        let deadline = Instant::now() + OUTPUT_DRAIN;
+        while let Some(output) = &self.output {
+            let closed = output.lock().map_or(true, |output| output.open == 0);
+            if closed || Instant::now() >= deadline {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        self.take_output()
+    }
+}
+
+/// A background job that has finished since the last time the table was reaped
+#[derive(Debug)]
+pub struct FinishedJob {
+    pub id: usize,
+    pub pid: u32,
+    pub command: String,
+    pub status: ExitStatus,
+    /// Output that was collected but not shown while the job ran
+    pub output: Vec<u8>,
+}
+
+impl FinishedJob {
+    /// How the job ended: `Done`, `Exit N` or the name of the signal that killed it
+    pub fn state(&self) -> String {
+        match (self.status.code(), self.status.signal()) {
+            (Some(0), _) => "Done".to_string(),
+            (Some(code), _) => format!("Exit {}", code),
+            (None, Some(signal)) => signal_name(signal),
+            (None, None) => "Done".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for FinishedJob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {} {}", self.id, self.state(), self.command)
+    }
+}
+
+/// Keeps track of background jobs and reaps them without blocking
+#[derive(Debug, Default)]
+pub struct JobTable {
+    jobs: Vec<Job>,
+}
+
+impl JobTable {
+    /// Adds a spawned child to the table and returns its job number.
+    ///
+    /// If the child's stdout or stderr is piped, what it writes there is
+    /// collected for [`Job::take_output`] instead of reaching the terminal.
+    pub fn add(&mut self, mut child: Child, command: &str) -> usize {
+        let streams: Vec<Box<dyn Read + Send>> = [
+            child
+                .stdout
+                .take()
+                .map(|s| Box::new(s) as Box<dyn Read + Send>),
+            child
+                .stderr
+                .take()
+                .map(|s| Box::new(s) as Box<dyn Read + Send>),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        let output = (!streams.is_empty()).then(|| {
+            let output = Arc::new(Mutex::new(Output {
+                bytes: Vec::new(),
+                open: streams.len(),
+            }));
+            for stream in streams {
+                Self::collect(stream, Arc::clone(&output));
+            }
+            output
+        });
+
+        let id = self.next_id();
+        self.jobs.push(Job {
+            id,
+            command: command.to_string(),
+            child,
+            output,
+        });
+        id
+    }
+
+    /// Copies a job's stream into its output buffer until the stream closes
+    fn collect(mut stream: Box<dyn Read + Send>, output: Arc<Mutex<Output>>) {
+        thread::spawn(move || {
+            let mut chunk = [0; 4096];
+            loop {
+                let read = match stream.read(&mut chunk) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => read,
+                };
+                match output.lock() {
+                    Ok(mut output) => output.bytes.extend_from_slice(&chunk[..read]),
+                    Err(_) => break,
+                }
+            }
+            if let Ok(mut output) = output.lock() {
+                output.open -= 1;
+            }
+        });
+    }
+
+    /// Running jobs in the order they were started
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    /// The job `%+` names: the one started most recently
+    pub fn current(&self) -> Option<&Job> {
+        self.jobs.last()
+    }
+
+    /// The job `%-` names: the one started before the current job
+    pub fn previous(&self) -> Option<&Job> {
+        self.jobs.iter().rev().nth(1)
+    }
+
+    /// Finds the job a spec such as `%1`, `%+`, `%-`, `%vim` or `%?log` names.
+    ///
+    /// `%str` matches the start of a job's command and `%?str` any part of it;
+    /// either must match exactly one job.
+    pub fn resolve(&self, spec: &str) -> Result<&Job, String> {
+        let no_such = || format!("{}: no such job", spec);
+        let pattern = spec.strip_prefix('%').ok_or_else(no_such)?;
+        match pattern {
+            "" | "%" | "+" => self.current().ok_or_else(|| "no current job".to_string()),
+            "-" => self.previous().ok_or_else(|| "no previous job".to_string()),
+            _ => {
+                if let Ok(id) = pattern.parse() {
+                    return self.get(id).ok_or_else(no_such);
+                }
+                let mut matches = self
+                    .jobs
+                    .iter()
+                    .filter(|job| match pattern.strip_prefix('?') {
+                        Some(part) => job.command.contains(part),
+                        None => job.command.starts_with(pattern),
+                    });
+                match (matches.next(), matches.next()) {
+                    (Some(job), None) => Ok(job),
+                    (Some(_), Some(_)) => Err(format!("{}: ambiguous job spec", spec)),
+                    (None, _) => Err(no_such()),
+                }
+            }
+        }
+    }
+
+    /// Blocks until job `id` exits, handing its output to `sink` as it arrives
+    pub fn wait(
+        &mut self,
+        id: usize,
+        mut sink: impl FnMut(&[u8]),
+    ) -> io::Result<Option<FinishedJob>> {
+        let Some(i) = self.jobs.iter().position(|job| job.id == id) else {
+            return Ok(None);
+        };
+        let status = loop {
+            let job = &mut self.jobs[i];
+            if let Some(status) = job.child.try_wait()? {
+                break status;
+            }
+            sink(&job.take_output());
+            thread::sleep(Duration::from_millis(20));
+        };
+
+        Ok(Some(self.remove(i, status)))
+    }
+
+    /// Collects every job that has exited, removing it from the table.
+    ///
+    /// Uses non-blocking waits so finished children are reaped instead of
+    /// lingering as zombies, while running jobs are left untouched.
+    pub fn reap(&mut self) -> io::Result<Vec<FinishedJob>> {
+        let ids: Vec<usize> = self.jobs.iter().map(|job| job.id).collect();
+        let mut finished = Vec::new();
+        for id in ids {
+            finished.extend(self.reap_job(id)?);
+        }
+        Ok(finished)
+    }
+
+    /// Removes job `id` from the table if it has exited, without blocking
+    pub fn reap_job(&mut self, id: usize) -> io::Result<Option<FinishedJob>> {
+        let Some(i) = self.jobs.iter().position(|job| job.id == id) else {
+            return Ok(None);
+        };
+        let status = self.jobs[i].child.try_wait()?;
+        Ok(status.map(|status| self.remove(i, status)))
+    }
+
+    /// Takes the `i`th job out of the table once it has exited with `status`
+    fn remove(&mut self, i: usize, status: ExitStatus) -> FinishedJob {
+        let job = self.jobs.remove(i);
+        let output = job.drain_output();
+        FinishedJob {
+            id: job.id,
+            pid: job.child.id(),
+            command: job.command,
+            status,
+            output,
+        }
+    }
+
+    /// Job numbers are reused once the highest numbered job has finished
+    fn next_id(&self) -> usize {
+        self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1
+    }
+}
+
+fn signal_name(signal: i32) -> String {
+    match signal {
+        1 => "Hangup".to_string(),
+        2 => "Interrupt".to_string(),
+        9 => "Killed".to_string(),
+        15 => "Terminated".to_string(),
+        other => format!("Signal {}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    fn wait_for_reap(table: &mut JobTable) -> Vec<FinishedJob> {
+        for _ in 0..100 {
+            let finished = table.reap().unwrap();
+            if !finished.is_empty() {
+                return finished;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("background job never finished");
+    }
+
+    #[test]
+    fn test_job_numbers() {
+        let mut table = JobTable::default();
+        let first = table.add(Command::new("true").spawn().unwrap(), "true");
+        let second = table.add(Command::new("true").spawn().unwrap(), "true");
+        assert_eq!(first, 1);
+        assert_eq!(second, 2);
+    }
+
+    #[test]
+    fn test_reap_finished_job() {
+        let mut table = JobTable::default();
+        table.add(Command::new("true").spawn().unwrap(), "true");
+
+        let finished = wait_for_reap(&mut table);
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].to_string(), "[1] Done true");
+        assert!(table.jobs.is_empty());
+    }
+
+    #[test]
+    fn test_reap_failed_job() {
+        let mut table = JobTable::default();
+        table.add(Command::new("false").spawn().unwrap(), "false");
+
+        let finished = wait_for_reap(&mut table);
+        assert_eq!(finished[0].to_string(), "[1] Exit 1 false");
+    }
+
+    #[test]
+    fn test_output_is_collected() {
+        let mut table = JobTable::default();
+        let child = Command::new("sh")
+            .args(["-c", "echo out; echo err >&2; sleep 0.2; echo late"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let id = table.add(child, "talk");
+
+        thread::sleep(Duration::from_millis(100));
+        let early = table.get(id).unwrap().take_output();
+        let early = String::from_utf8(early).unwrap();
+        assert!(early.contains("out\n") && early.contains("err\n"));
+
+        let finished = wait_for_reap(&mut table);
+        assert_eq!(finished[0].output, b"late\n");
+    }
+
+    #[test]
+    fn test_resolve_job_specs() {
+        let mut table = JobTable::default();
+        table.add(Command::new("sleep").arg("5").spawn().unwrap(), "sleep 5");
+        table.add(
+            Command::new("sleep").arg("6").spawn().unwrap(),
+            "vim notes.txt",
+        );
+        table.add(
+            Command::new("sleep").arg("7").spawn().unwrap(),
+            "tail -f app.log",
+        );
+
+        let id = |spec| table.resolve(spec).map(Job::id);
+        assert_eq!(id("%2"), Ok(2));
+        assert_eq!(id("%+"), Ok(3));
+        assert_eq!(id("%%"), Ok(3));
+        assert_eq!(id("%-"), Ok(2));
+        assert_eq!(id("%vim"), Ok(2));
+        assert_eq!(id("%?app"), Ok(3));
+        assert!(id("%9").is_err());
+        assert!(id("%s").is_ok());
+        assert!(id("%?s").unwrap_err().contains("ambiguous"));
+        assert!(id("2").is_err());
+
+        for job in &mut table.jobs {
+            job.child.kill().unwrap();
+            job.child.wait().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_wait_streams_output() {
+        let mut table = JobTable::default();
+        let child = Command::new("sh")
+            .args(["-c", "echo one; sleep 0.1; echo two; exit 3"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let id = table.add(child, "count");
+
+        let mut seen = Vec::new();
+        let finished = table
+            .wait(id, |output| seen.extend_from_slice(output))
+            .unwrap()
+            .unwrap();
+        seen.extend_from_slice(&finished.output);
+        assert_eq!(seen, b"one\ntwo\n");
+        assert_eq!(finished.status.code(), Some(3));
+        assert!(table.jobs.is_empty());
+        assert!(table.wait(id, |_| {}).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_running_job_is_kept() {
+        let mut table = JobTable::default();
+        let child = Command::new("sleep").arg("5").spawn().unwrap();
+        table.add(child, "sleep 5");
+
+        assert!(table.reap().unwrap().is_empty());
+        assert_eq!(table.jobs.len(), 1);
+
+        let pid = table.jobs[0].child.id();
+        Command::new("kill").arg(pid.to_string()).status().unwrap();
+        let finished = wait_for_reap(&mut table);
+        assert_eq!(finished[0].to_string(), "[1] Terminated sleep 5");
+    }
+
+    #[test]
+    fn test_reap_one_job() {
+        let mut table = JobTable::default();
+        table.add(Command::new("sleep").arg("5").spawn().unwrap(), "sleep 5");
+        let child = Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+        let pid = child.id();
+        table.add(child, "sh");
+
+        assert!(table.reap_job(1).unwrap().is_none());
+        assert!(table.reap_job(7).unwrap().is_none());
+        let mut finished = None;
+        for _ in 0..100 {
+            finished = table.reap_job(2).unwrap();
+            if finished.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let finished = finished.expect("background job never finished");
+        assert_eq!(
+            (finished.pid, finished.state()),
+            (pid, "Exit 3".to_string())
+        );
+        assert_eq!(table.jobs.len(), 1);
+        table.jobs[0].child.kill().unwrap();
+    }
+}
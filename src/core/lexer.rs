@@ -0,0 +1,649 @@
+//! Splitting command lines into words and operators, following the quoting rules
+
+use std::{iter::Peekable, ops::Range, str::CharIndices};
+
+/// How a character of a command line is to be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quoting {
+    /// Outside quotes, where it may have a special meaning
+    Unquoted,
+    /// Inside single quotes or after a backslash, where it stands for itself
+    Quoted,
+    /// Inside double quotes, where only `$` and `` ` `` keep their meaning
+    Double,
+    /// A quote or backslash that only serves to quote something else, or a
+    /// character of an escape sequence other than the one standing for it
+    Syntax,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Unquoted,
+    Single,
+    Double,
+    /// Between the `$` and the quote of `$'...'`
+    AnsiOpen,
+    Ansi,
+}
+
+/// Walks a command line, telling how each character is to be read.
+///
+/// Inside `'...'` everything is literal. Inside `"..."` a backslash only
+/// escapes `$`, `` ` ``, `"`, `\` and a newline. Elsewhere a backslash makes
+/// the next character literal, and a backslash before a newline joins lines.
+///
+/// Inside `$'...'` a backslash starts an escape sequence as in C, such as
+/// `\n`, `\t`, `\'`, `\x41` or `\u00e9`. The last character of the sequence is
+/// read as the character it stands for, and the ones before it as syntax.
+pub struct Scanner<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    state: State,
+    escaped: bool,
+    /// How many characters of an escape sequence are left, and what it stands for
+    sequence: Option<(usize, char)>,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.char_indices().peekable(),
+            state: State::Unquoted,
+            escaped: false,
+            sequence: None,
+        }
+    }
+
+    /// Whether the input so far ends inside quotes
+    pub fn in_quotes(&self) -> bool {
+        self.state != State::Unquoted
+    }
+
+    /// Whether the input so far ends with a backslash that escapes nothing yet
+    pub fn in_escape(&self) -> bool {
+        self.escaped
+    }
+}
+
+impl Iterator for Scanner<'_> {
+    type Item = (usize, char, Quoting);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (i, c) = self.chars.next()?;
+        if let Some((left, value)) = self.sequence.take() {
+            if left > 1 {
+                self.sequence = Some((left - 1, value));
+                return Some((i, c, Quoting::Syntax));
+            }
+            return Some((i, value, Quoting::Quoted));
+        }
+        if std::mem::take(&mut self.escaped) {
+            let quoting = if c == '\n' {
+                Quoting::Syntax
+            } else {
+                Quoting::Quoted
+            };
+            return Some((i, c, quoting));
+        }
+
+        let quoting = match (self.state, c) {
+            (State::Single, '\'') | (State::Double, '"') => {
+                self.state = State::Unquoted;
+                Quoting::Syntax
+            }
+            (State::Single, _) => Quoting::Quoted,
+            (State::Double, '\\') => match self.chars.peek() {
+                Some((_, '$' | '`' | '"' | '\\' | '\n')) | None => {
+                    self.escaped = true;
+                    Quoting::Syntax
+                }
+                Some(_) => Quoting::Quoted,
+            },
+            (State::Double, _) => Quoting::Double,
+            (State::AnsiOpen, _) => {
+                self.state = State::Ansi;
+                Quoting::Syntax
+            }
+            (State::Ansi, '\'') => {
+                self.state = State::Unquoted;
+                Quoting::Syntax
+            }
+            (State::Ansi, '\\') => match escape_sequence(self.chars.clone()) {
+                Some(sequence) => {
+                    self.sequence = Some(sequence);
+                    Quoting::Syntax
+                }
+                None => Quoting::Quoted,
+            },
+            (State::Ansi, _) => Quoting::Quoted,
+            (State::Unquoted, '$') if self.chars.peek().is_some_and(|&(_, c)| c == '\'') => {
+                self.state = State::AnsiOpen;
+                Quoting::Syntax
+            }
+            (State::Unquoted, '\'') => {
+                self.state = State::Single;
+                Quoting::Syntax
+            }
+            (State::Unquoted, '"') => {
+                self.state = State::Double;
+                Quoting::Syntax
+            }
+            (State::Unquoted, '\\') => {
+                self.escaped = true;
+                Quoting::Syntax
+            }
+            (State::Unquoted, _) => Quoting::Unquoted,
+        };
+        Some((i, c, quoting))
+    }
+}
+
+/// Reads the escape sequence after a backslash in `$'...'`, returning how many
+/// characters it takes and the character it stands for
+fn escape_sequence(mut rest: Peekable<CharIndices>) -> Option<(usize, char)> {
+    let (_, c) = rest.next()?;
+    let mut digits = |radix: u32, max: usize| {
+        let mut digits = String::new();
+        while digits.len() < max {
+            match rest.next_if(|&(_, c)| c.is_digit(radix)) {
+                Some((_, c)) => digits.push(c),
+                None => break,
+            }
+        }
+        digits
+    };
+    let value = |digits: &str, radix: u32| {
+        let code = u32::from_str_radix(digits, radix).ok()?;
+        Some((1 + digits.len(), char::from_u32(code)?))
+    };
+    let simple = match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        'a' => '\x07',
+        'b' => '\x08',
+        'e' | 'E' => '\x1b',
+        'f' => '\x0c',
+        'v' => '\x0b',
+        '\\' | '\'' | '"' | '?' => c,
+        '0'..='7' => {
+            let octal = format!("{}{}", c, digits(8, 2));
+            return value(&octal, 8).map(|(len, c)| (len - 1, c));
+        }
+        'x' => return value(&digits(16, 2), 16),
+        'u' => return value(&digits(16, 4), 16),
+        'U' => return value(&digits(16, 8), 16),
+        'c' => {
+            let (_, control) = rest.next()?;
+            return control
+                .is_ascii()
+                .then_some((2, (control as u8 & 0x1f) as char));
+        }
+        _ => return None,
+    };
+    Some((1, simple))
+}
+
+/// A piece of a command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// A word, as written until it is expanded
+    Word(String),
+    /// An unquoted control operator such as `|`, `&&`, `;`, `;;` or `(`, or a
+    /// redirection such as `>`, `2>>`, `>&2` or `<<`
+    Operator(String),
+}
+
+impl Token {
+    pub fn text(&self) -> &str {
+        match self {
+            Token::Word(text) | Token::Operator(text) => text,
+        }
+    }
+
+    pub fn is_operator(&self, operator: &str) -> bool {
+        matches!(self, Token::Operator(text) if text == operator)
+    }
+}
+
+/// Follows how deep a command line is in `${...}`, `$(...)`, `<(...)` and
+/// `>(...)`, inside which spaces and operators belong to the word
+#[derive(Default)]
+struct Nesting {
+    depth: usize,
+    after_dollar: bool,
+    after_angle: bool,
+}
+
+impl Nesting {
+    fn update(&mut self, c: char, quoting: Quoting) {
+        if quoting != Quoting::Quoted {
+            match c {
+                '{' if self.after_dollar => self.depth += 1,
+                '(' if self.after_dollar || self.after_angle || self.depth > 0 => self.depth += 1,
+                '}' | ')' if self.depth > 0 => self.depth -= 1,
+                _ => {}
+            }
+        }
+        self.after_dollar = c == '$' && quoting != Quoting::Quoted;
+        self.after_angle = matches!(c, '<' | '>') && quoting == Quoting::Unquoted;
+    }
+
+    fn inside(&self) -> bool {
+        self.depth > 0
+    }
+}
+
+/// Splits a command into words and operators.
+///
+/// Words keep their quotes, which [`unquote`] removes once the word has been
+/// expanded. An operator needs no spaces around it, and one that is quoted or
+/// escaped is an ordinary part of a word, as in `echo '|'` or `echo a\>b`.
+#[cfg(test)]
+pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    Ok(tokenize_spanned(input)?
+        .into_iter()
+        .map(|(_, token)| token)
+        .collect())
+}
+
+/// Like [`tokenize`], with where in the input each token starts
+pub fn tokenize_spanned(input: &str) -> Result<Vec<(usize, Token)>, String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut start = 0;
+    let mut scanner = Scanner::new(input);
+    let mut nesting = Nesting::default();
+
+    while let Some((i, c, quoting)) = scanner.next() {
+        if quoting == Quoting::Syntax && c == '\n' {
+            // A backslash before a newline joins the lines
+            word.pop();
+            continue;
+        }
+        if word.is_empty() {
+            start = i;
+        }
+        // The bracket that closes a nesting still belongs to it
+        let closing = nesting.inside();
+        nesting.update(c, quoting);
+        if quoting != Quoting::Unquoted || closing || nesting.inside() {
+            // Words keep escape sequences as written, not what they stand for
+            word.extend(input[i..].chars().next());
+            continue;
+        }
+        match c {
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    tokens.push((start, Token::Word(std::mem::take(&mut word))));
+                }
+            }
+            // `<(...)` and `>(...)` are words, standing for a process substitution
+            '<' | '>'
+                if word.is_empty() && scanner.chars.peek().is_some_and(|&(_, c)| c == '(') =>
+            {
+                word.push(c);
+            }
+            '|' | '<' | '>' | '&' | ';' | '(' | ')' => {
+                // A lone digit right before a redirection names the descriptor
+                let (mut operator, at) = match word.as_bytes() {
+                    [digit] if digit.is_ascii_digit() && matches!(c, '<' | '>') => {
+                        (std::mem::take(&mut word), start)
+                    }
+                    _ => (String::new(), i),
+                };
+                if !word.is_empty() {
+                    tokens.push((start, Token::Word(std::mem::take(&mut word))));
+                }
+                operator.push(c);
+                let mut take = |wanted: fn(char) -> bool| {
+                    let next = scanner.chars.next_if(|&(_, next)| wanted(next))?;
+                    Some(next.1)
+                };
+                match c {
+                    '>' => {
+                        if let Some(next) = take(|c| matches!(c, '>' | '|' | '&')) {
+                            operator.push(next);
+                            if next == '&' {
+                                operator.extend(take(|c| c.is_ascii_digit()));
+                            }
+                        }
+                    }
+                    '<' => match take(|c| matches!(c, '&' | '<')) {
+                        Some('&') => {
+                            operator.push('&');
+                            operator.extend(take(|c| c.is_ascii_digit()));
+                        }
+                        Some(next) => {
+                            operator.push(next);
+                            operator.extend(take(|c| matches!(c, '<' | '-')));
+                        }
+                        None => {}
+                    },
+                    '|' => operator.extend(take(|c| c == '|')),
+                    '&' => operator.extend(take(|c| c == '&')),
+                    ';' => operator.extend(take(|c| c == ';')),
+                    _ => {}
+                }
+                tokens.push((at, Token::Operator(operator)));
+            }
+            c => word.push(c),
+        }
+    }
+
+    if scanner.in_quotes() {
+        return Err("unterminated quoted string".to_string());
+    }
+    if !word.is_empty() {
+        tokens.push((start, Token::Word(word)));
+    }
+    Ok(tokens)
+}
+
+/// A `<<` or `<<-` operator and the delimiter word after it
+struct HereDocOp {
+    /// The bytes of the line from the operator to the end of the word
+    range: Range<usize>,
+    delimiter: String,
+    /// Whether any of the word was quoted, which leaves the body unexpanded
+    quoted: bool,
+    /// `<<-`, which strips leading tabs from the body and delimiter lines
+    strip_tabs: bool,
+}
+
+/// Finds the here-document operators of a line, in order
+fn heredoc_ops(line: &str) -> Vec<HereDocOp> {
+    let chars: Vec<(usize, char, Quoting)> = Scanner::new(line).collect();
+    let unquoted = |i: usize, wanted: char| {
+        chars
+            .get(i)
+            .is_some_and(|&(_, c, q)| c == wanted && q == Quoting::Unquoted)
+    };
+    let ends_word = |i: usize| {
+        let (_, c, quoting) = chars[i];
+        quoting == Quoting::Unquoted && (c.is_whitespace() || "|&;<>()".contains(c))
+    };
+
+    let mut ops = Vec::new();
+    let mut nesting = Nesting::default();
+    let mut i = 0;
+    while i < chars.len() {
+        nesting.update(chars[i].1, chars[i].2);
+        if nesting.inside() || !(unquoted(i, '<') && unquoted(i + 1, '<')) {
+            i += 1;
+            continue;
+        }
+        if unquoted(i + 2, '<') {
+            // A here-string, whose word is on the line itself
+            i += 3;
+            continue;
+        }
+        let start = chars[i].0;
+        let strip_tabs = unquoted(i + 2, '-');
+        let mut j = i + if strip_tabs { 3 } else { 2 };
+        while j < chars.len() && chars[j].2 == Quoting::Unquoted && chars[j].1.is_whitespace() {
+            j += 1;
+        }
+        let word_start = j;
+        while j < chars.len() && !ends_word(j) {
+            j += 1;
+        }
+        if j > word_start {
+            let end = chars.get(j).map_or(line.len(), |&(pos, _, _)| pos);
+            let raw = &line[chars[word_start].0..end];
+            ops.push(HereDocOp {
+                range: start..end,
+                delimiter: unquote(raw),
+                quoted: chars[word_start..j]
+                    .iter()
+                    .any(|&(_, _, q)| q != Quoting::Unquoted),
+                strip_tabs,
+            });
+        }
+        i = j.max(i + 2);
+    }
+    ops
+}
+
+/// Moves the bodies of here-documents onto the lines that use them.
+///
+/// Each `<< EOF` is replaced by `<<` and its body as one quoted word: single
+/// quoted when the delimiter was quoted, so nothing in it is expanded, and
+/// double quoted otherwise. The lines after a command are its bodies, one per
+/// operator, each running up to a line holding only the delimiter. Returns the
+/// new input and the delimiter still awaited if the input ends first, in which
+/// case the body runs to the end.
+pub fn inline_heredocs(input: &str) -> (String, Option<String>) {
+    let mut lines = input.split('\n');
+    let mut out = Vec::new();
+    while let Some(line) = lines.next() {
+        let ops = heredoc_ops(line);
+        if ops.is_empty() {
+            out.push(line.to_string());
+            continue;
+        }
+
+        let mut rewritten = String::new();
+        let mut copied = 0;
+        for op in ops {
+            let mut body = String::new();
+            let mut terminated = false;
+            for body_line in lines.by_ref() {
+                let body_line = if op.strip_tabs {
+                    body_line.trim_start_matches('\t')
+                } else {
+                    body_line
+                };
+                if body_line == op.delimiter {
+                    terminated = true;
+                    break;
+                }
+                body.push_str(body_line);
+                body.push('\n');
+            }
+
+            rewritten.push_str(&line[copied..op.range.start]);
+            rewritten.push_str("<< ");
+            if op.quoted {
+                rewritten.push('\'');
+                rewritten.push_str(&body.replace('\'', r"'\''"));
+                rewritten.push('\'');
+            } else {
+                rewritten.push('"');
+                rewritten.push_str(&body.replace('"', r#"\""#));
+                rewritten.push('"');
+            }
+            copied = op.range.end;
+            if !terminated {
+                rewritten.push_str(&line[copied..]);
+                out.push(rewritten);
+                return (out.join("\n"), Some(op.delimiter));
+            }
+        }
+        rewritten.push_str(&line[copied..]);
+        out.push(rewritten);
+    }
+    (out.join("\n"), None)
+}
+
+/// Where the comment of a command line starts, if it has one.
+///
+/// A `#` inside quotes, after a backslash, in `$#` or inside `${...}` and
+/// `$(...)` is not a comment. With `word_start`, as POSIX has it, neither is
+/// one in the middle of a word, so `a#b` keeps its `#`.
+pub fn comment_start(line: &str, word_start: bool) -> Option<usize> {
+    let mut nesting = Nesting::default();
+    let mut previous = None;
+    for (i, c, quoting) in Scanner::new(line) {
+        let after_dollar = nesting.after_dollar;
+        nesting.update(c, quoting);
+        let starts_word = previous.is_none_or(|p: char| p.is_whitespace() || ";|&()".contains(p));
+        if c == '#'
+            && quoting == Quoting::Unquoted
+            && !after_dollar
+            && !nesting.inside()
+            && (starts_word || !word_start)
+        {
+            return Some(i);
+        }
+        previous = Some(c);
+    }
+    None
+}
+
+/// Removes the quotes and escaping backslashes from a word
+pub fn unquote(word: &str) -> String {
+    Scanner::new(word)
+        .filter(|&(_, _, quoting)| quoting != Quoting::Syntax)
+        .map(|(_, c, _)| c)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(tokens: &[Token]) -> Vec<String> {
+        tokens
+            .iter()
+            .map(|token| match token {
+                Token::Word(word) => unquote(word),
+                Token::Operator(operator) => format!("<{}>", operator),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_quoting() {
+        let tokens = tokenize(r#"echo "a \"b\" \c" it's"#);
+        assert_eq!(tokens, Err("unterminated quoted string".to_string()));
+
+        let tokens = tokenize(r#"echo 'hello  world' foo\ bar \"quoted\" "a \"b\" \c $""#).unwrap();
+        assert_eq!(
+            words(&tokens),
+            [
+                "echo",
+                "hello  world",
+                "foo bar",
+                "\"quoted\"",
+                r#"a "b" \c $"#
+            ]
+        );
+        assert_eq!(
+            words(&tokenize(r#"'it'\''s' "" x"#).unwrap()),
+            ["it's", "", "x"]
+        );
+        assert_eq!(words(&tokenize("a\\\nb \\\n c").unwrap()), ["ab", "c"]);
+        assert_eq!(
+            words(&tokenize("echo ${X:-a b|c} $(( (1 + 2) > 3 )) d").unwrap()),
+            ["echo", "${X:-a b|c}", "$(( (1 + 2) > 3 ))", "d"]
+        );
+    }
+
+    #[test]
+    fn test_ansi_c_quoting() {
+        let tokens = tokenize(r"printf $'a\tb\nc' $'it\'s | \\' x$'\x41\101é'").unwrap();
+        assert_eq!(tokens[1], Token::Word(r"$'a\tb\nc'".to_string()));
+        assert_eq!(words(&tokens), ["printf", "a\tb\nc", "it's | \\", "xAAé"]);
+        assert_eq!(
+            words(&tokenize(r#"$'\e[1m\cA\z\x' \$'a' "$'b'""#).unwrap()),
+            ["\x1b[1m\x01\\z\\x", "$a", "$'b'"]
+        );
+        assert_eq!(
+            tokenize("$'open"),
+            Err("unterminated quoted string".to_string())
+        );
+    }
+
+    #[test]
+    fn test_operators() {
+        assert_eq!(
+            words(&tokenize("ls|wc -l >out 2>&1 <in 2>>log >|f").unwrap()),
+            [
+                "ls", "<|>", "wc", "-l", "<>>", "out", "<2>&1>", "<<>", "in", "<2>>>", "log",
+                "<>|>", "f"
+            ]
+        );
+        assert_eq!(
+            words(&tokenize(r#"echo '|' a\>b ">" 12>x"#).unwrap()),
+            ["echo", "|", "a>b", ">", "12", "<>>", "x"]
+        );
+        assert_eq!(
+            words(&tokenize("cat <<EOF 0<<-E<<<'x y'").unwrap()),
+            ["cat", "<<<>", "EOF", "<0<<->", "E", "<<<<>", "x y"]
+        );
+        assert_eq!(
+            words(&tokenize("diff <(ls a | sort) >(wc -l) < <(echo x)").unwrap()),
+            ["diff", "<(ls a | sort)", ">(wc -l)", "<<>", "<(echo x)"]
+        );
+        assert_eq!(
+            words(&tokenize("a;;b; ;c").unwrap()),
+            ["a", "<;;>", "b", "<;>", "<;>", "c"]
+        );
+        let tokens = tokenize(r#"echo "|""#).unwrap();
+        assert!(!tokens[1].is_operator("|"));
+    }
+
+    #[test]
+    fn test_inline_heredocs() {
+        let input = "cat <<EOF >out; echo next\nsay \"hi\" $x\nEOF\necho after";
+        let (text, open) = inline_heredocs(input);
+        assert_eq!(
+            text,
+            "cat << \"say \\\"hi\\\" $x\n\" >out; echo next\necho after"
+        );
+        assert_eq!(open, None);
+        let tokens = tokenize(&text).unwrap();
+        assert!(tokens[1].is_operator("<<"));
+        assert_eq!(unquote(tokens[2].text()), "say \"hi\" $x\n");
+
+        let (text, _) = inline_heredocs("cat <<-'E'F\n\tit's $x\n\tEF");
+        assert_eq!(
+            words(&tokenize(&text).unwrap()),
+            ["cat", "<<<>", "it's $x\n"]
+        );
+
+        let (text, _) = inline_heredocs("paste <<A /dev/stdin <<B\n1\nA\n2\nB");
+        assert_eq!(
+            words(&tokenize(&text).unwrap()),
+            ["paste", "<<<>", "1\n", "/dev/stdin", "<<<>", "2\n"]
+        );
+
+        let (text, open) = inline_heredocs("cat <<EOF\nunfinished");
+        assert_eq!(open.as_deref(), Some("EOF"));
+        assert_eq!(text, "cat << \"unfinished\n\"");
+
+        assert_eq!(
+            inline_heredocs("echo '<<x'\nx"),
+            ("echo '<<x'\nx".to_string(), None)
+        );
+        assert_eq!(inline_heredocs("echo $((1<<x))\nx").1, None);
+    }
+
+    #[test]
+    fn test_scanner_state() {
+        let mut scanner = Scanner::new("echo 'open");
+        scanner.by_ref().for_each(drop);
+        assert!(scanner.in_quotes());
+
+        let mut scanner = Scanner::new("make \\");
+        scanner.by_ref().for_each(drop);
+        assert!(scanner.in_escape() && !scanner.in_quotes());
+    }
+
+    #[test]
+    fn test_comment_start() {
+        assert_eq!(comment_start("echo a # b", false), Some(7));
+        assert_eq!(comment_start("echo a#b", false), Some(6));
+        assert_eq!(comment_start("echo a#b", true), None);
+        assert_eq!(comment_start("echo a;#b", true), Some(7));
+        assert_eq!(comment_start("# all", true), Some(0));
+        for none in [
+            "echo \"issue #42\" 'x#y'",
+            "echo \\#1",
+            "echo $# ${#HOME} $(echo #)",
+            "echo \"a\" \"#\"",
+        ] {
+            assert_eq!(comment_start(none, false), None, "{}", none);
+        }
+    }
+}
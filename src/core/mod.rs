@@ -1,5 +1,16 @@
+pub mod arith;
 pub mod command;
 pub mod completer;
+pub mod error;
+pub mod expand;
 pub mod external;
 pub mod flags;
+pub mod history;
+pub mod jobs;
+pub mod lexer;
+pub mod options;
+pub mod parser;
+pub mod path_cache;
+pub mod recorder;
+pub mod records;
 pub mod registry;
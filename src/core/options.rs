@@ -0,0 +1,124 @@
+/// Shell behaviour toggled with `set`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShellOptions {
+    /// Print each command before running it (`set -x`)
+    pub xtrace: bool,
+    /// Parse and expand commands but only print them (`set -n`, `--dry-run`)
+    pub noexec: bool,
+    /// Run commands matching the config's confirmation patterns without asking
+    pub noconfirm: bool,
+    /// Refuse to overwrite existing files with `>`; `>|` still does (`set -C`)
+    pub noclobber: bool,
+    /// Behave like POSIX sh, without hermit's own additions (`--posix`)
+    pub posix: bool,
+    /// Pipelines of builtins pass tables along instead of text (`set -o structured`)
+    pub structured: bool,
+    /// Output for screen readers: no color, a plain prompt, completions listed as text
+    pub accessible: bool,
+    /// A pipeline fails if any of its commands does (`set -o pipefail`)
+    pub pipefail: bool,
+    /// Functions run in place of builtins of the same name (`set -o funcfirst`)
+    pub funcfirst: bool,
+    /// Stop at the first command that fails (`set -e`): a script exits, and
+    /// the rest of a line typed at the prompt is skipped
+    pub errexit: bool,
+}
+
+impl ShellOptions {
+    /// Long names as used with `set -o`, with the short letter for `set -x` style
+    pub const NAMES: &'static [(&'static str, Option<char>)] = &[
+        ("accessible", None),
+        ("errexit", Some('e')),
+        ("funcfirst", None),
+        ("noclobber", Some('C')),
+        ("noconfirm", None),
+        ("noexec", Some('n')),
+        ("pipefail", None),
+        ("posix", None),
+        ("structured", None),
+        ("xtrace", Some('x')),
+    ];
+
+    /// Turns the option called `name` on or off, returning `false` if there is none
+    pub fn set(&mut self, name: &str, enabled: bool) -> bool {
+        match self.get_mut(name) {
+            Some(option) => {
+                *option = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Like `set`, but for the option's short letter
+    pub fn set_short(&mut self, letter: char, enabled: bool) -> bool {
+        Self::NAMES
+            .iter()
+            .find(|(_, short)| *short == Some(letter))
+            .is_some_and(|(name, _)| self.set(name, enabled))
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "accessible" => Some(self.accessible),
+            "errexit" => Some(self.errexit),
+            "funcfirst" => Some(self.funcfirst),
+            "noclobber" => Some(self.noclobber),
+            "noconfirm" => Some(self.noconfirm),
+            "noexec" => Some(self.noexec),
+            "pipefail" => Some(self.pipefail),
+            "posix" => Some(self.posix),
+            "structured" => Some(self.structured),
+            "xtrace" => Some(self.xtrace),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "accessible" => Some(&mut self.accessible),
+            "errexit" => Some(&mut self.errexit),
+            "funcfirst" => Some(&mut self.funcfirst),
+            "noclobber" => Some(&mut self.noclobber),
+            "noconfirm" => Some(&mut self.noconfirm),
+            "noexec" => Some(&mut self.noexec),
+            "pipefail" => Some(&mut self.pipefail),
+            "posix" => Some(&mut self.posix),
+            "structured" => Some(&mut self.structured),
+            "xtrace" => Some(&mut self.xtrace),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_by_name_and_letter() {
+        let mut options = ShellOptions::default();
+        assert!(options.set_short('x', true));
+        assert!(options.xtrace);
+        assert!(options.set("xtrace", false));
+        assert_eq!(options.get("xtrace"), Some(false));
+
+        assert!(!options.set("nope", true));
+        assert!(!options.set_short('Q', true));
+        assert_eq!(options.get("nope"), None);
+    }
+
+    #[test]
+    fn test_every_name_is_settable() {
+        let mut options = ShellOptions::default();
+        for (name, letter) in ShellOptions::NAMES {
+            assert!(options.set(name, true));
+            assert_eq!(options.get(name), Some(true));
+            assert!(options.set(name, false));
+            if let Some(letter) = letter {
+                assert!(options.set_short(*letter, true));
+                assert_eq!(options.get(name), Some(true));
+            }
+        }
+    }
+}
@@ -0,0 +1,1054 @@
+//! Turning a command line into the lists, pipelines and commands it is made of
+
+use std::collections::HashMap;
+
+use super::{
+    expand,
+    external::Redirect,
+    lexer::{self, Token},
+};
+
+/// A command line: and-or lists run one after another, split by `;` or `&`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct List {
+    pub items: Vec<Item>,
+}
+
+/// An and-or list of a [`List`], and whether `&` sends it to the background
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    pub and_or: AndOrList,
+    pub background: bool,
+}
+
+/// Pipelines joined by `&&` and `||`, each but the first with the operator
+/// before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AndOrList {
+    pub pipelines: Vec<(Option<AndOr>, Pipeline)>,
+}
+
+/// The operator joining a pipeline to the one before it in an and-or list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AndOr {
+    And,
+    Or,
+}
+
+/// Commands joined by `|`, whose status `!` inverts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipeline {
+    pub negated: bool,
+    pub commands: Vec<Command>,
+    /// The pipeline as written, for hooks and the job table
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    /// `( ... )`, run in a copy of the shell's state
+    Subshell(List),
+    /// `if ... then ... fi`
+    If(If),
+    /// `case ... in ... esac`
+    Case(Case),
+    /// `{ ...; }`, run in the shell itself
+    Group(List),
+    /// `name() { ...; }`, which defines a function rather than running anything
+    Function(Function),
+    /// A compound command with redirections after it, as in `{ ...; } > log`
+    Redirected(Box<Command>, Vec<Redirection>),
+}
+
+/// An `if` command, with any `elif` and `else` parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct If {
+    /// Each condition with the list it guards, `if` first and then each `elif`
+    pub branches: Vec<(List, List)>,
+    /// The list after `else`, run when no condition holds
+    pub otherwise: Option<List>,
+}
+
+/// A `case` command: the word to match, as written, and its items in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub word: String,
+    pub items: Vec<CaseItem>,
+}
+
+/// The patterns of a `case` item, as written, and the list run when one matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseItem {
+    pub patterns: Vec<String>,
+    pub body: List,
+}
+
+/// A function definition: the name it is called by, the compound command
+/// it runs, and the definition as written, for `functions` to show
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub body: Box<Command>,
+    pub text: String,
+}
+
+/// A command's words, as written, with its redirections taken out of them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimpleCommand {
+    pub words: Vec<String>,
+    pub redirects: Vec<Redirection>,
+}
+
+impl SimpleCommand {
+    /// The command as tokens again, its words first and its redirections after
+    pub fn tokens(&self) -> Vec<Token> {
+        let words = self.words.iter().cloned().map(Token::Word);
+        let redirects = self.redirects.iter().flat_map(|redirect| {
+            let target = redirect.target.clone().map(Token::Word);
+            std::iter::once(Token::Operator(redirect.operator.clone())).chain(target)
+        });
+        words.chain(redirects).collect()
+    }
+}
+
+/// A redirection as written: its operator and, unless it is `>&N` or `<&N`,
+/// the word after it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirection {
+    pub operator: String,
+    pub target: Option<String>,
+}
+
+/// What is wrong with a command line, and where
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{message} at column {column}")]
+pub struct SyntaxError {
+    pub message: String,
+    /// Counting characters from 1
+    pub column: usize,
+}
+
+/// Reserved words that open a compound command, with the word closing it
+const COMPOUNDS: &[(&str, &str)] = &[("if", "fi"), ("case", "esac"), ("{", "}")];
+
+/// Reserved words within a compound command that end the list before them
+/// and start another
+const SEPARATORS: &[&str] = &["then", "elif", "else"];
+
+/// Whether a word ends the list before it, as `then` or `fi` do when a
+/// command could start there
+fn ends_list(word: &str) -> bool {
+    SEPARATORS.contains(&word) || COMPOUNDS.iter().any(|&(_, close)| close == word)
+}
+
+/// Whether a word can name a function: letters, digits and `_`, `-` or `.`,
+/// and not a reserved word
+fn is_function_name(word: &str) -> bool {
+    !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        && !ends_list(word)
+        && !COMPOUNDS.iter().any(|&(open, _)| open == word)
+}
+
+/// The compound commands a line leaves open, and the places it can be split
+/// into commands that parse on their own
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Outline {
+    /// Where each `;` outside parentheses and compound commands is, and each
+    /// of the two of a stray `;;`
+    pub separators: Vec<usize>,
+    /// The reserved words opening the compound commands left open, innermost last
+    pub open: Vec<&'static str>,
+    /// Whether the line ends where a command could start, as after `then`
+    pub continues: bool,
+}
+
+/// Outlines a line from its tokens alone, without parsing it.
+///
+/// Reserved words only count where a command could start, so `echo if`
+/// opens nothing.
+pub fn outline(input: &str) -> Result<Outline, String> {
+    let mut outline = Outline {
+        continues: true,
+        ..Outline::default()
+    };
+    let mut depth = 0usize;
+    // How deep in parentheses each compound command left open starts
+    let mut depths = Vec::new();
+    // How many words of `case WORD in` have been read, until the `in`
+    let mut case_words = None;
+    let mut after_open = false;
+    for (at, token) in lexer::tokenize_spanned(input)? {
+        let empty_parens = after_open && token.is_operator(")");
+        after_open = token.is_operator("(");
+        outline.continues = match &token {
+            Token::Operator(operator) => {
+                // The `)` after the patterns of a `case` item closes no group,
+                // and the body of `name()` may follow on the next line
+                let patterns_end = operator == ")"
+                    && outline.open.last() == Some(&"case")
+                    && depths.last() == Some(&depth);
+                match operator.as_str() {
+                    "(" => depth += 1,
+                    ")" if !patterns_end => depth = depth.saturating_sub(1),
+                    ";" | ";;" if depth == 0 && outline.open.is_empty() => {
+                        outline.separators.extend(at..at + operator.len())
+                    }
+                    _ => {}
+                }
+                (operator != ")" || patterns_end || empty_parens)
+                    && !Redirect::is_redirection(operator)
+            }
+            Token::Word(_) if case_words.is_some() => {
+                let read = case_words.map_or(0, |words| words + 1);
+                case_words = (read < 2).then_some(read);
+                // Patterns, which `esac` may stand in for, follow the `in`
+                read == 2
+            }
+            Token::Word(word) if outline.continues => {
+                if let Some(&(open, _)) = COMPOUNDS.iter().find(|&&(open, _)| open == word) {
+                    outline.open.push(open);
+                    depths.push(depth);
+                    if open == "case" {
+                        case_words = Some(0);
+                    }
+                    open != "case"
+                } else if let Some(&(open, _)) = COMPOUNDS.iter().find(|&&(_, close)| close == word)
+                {
+                    if outline.open.last() == Some(&open) {
+                        outline.open.pop();
+                        depths.pop();
+                    }
+                    false
+                } else {
+                    SEPARATORS.contains(&word.as_str()) || word == "!"
+                }
+            }
+            Token::Word(_) => false,
+        };
+    }
+    Ok(outline)
+}
+
+/// A token, where it starts in the line, and the aliases it was expanded from
+struct Spanned {
+    at: usize,
+    token: Token,
+    aliases: Vec<String>,
+    /// Whether the word is looked up as an alias even after a command name
+    lookup: bool,
+}
+
+/// Parses a command line, replacing a command's name with the alias of that
+/// name when it has one.
+///
+/// The first word of an alias's value is looked up in turn, except for the
+/// names of the aliases it came from, so `alias ls='ls -F'` or two aliases of
+/// each other stop. When a value ends in a blank, as in `alias sudo='sudo '`,
+/// the word after it is looked up too.
+pub fn parse(input: &str, aliases: &HashMap<String, String>) -> Result<List, SyntaxError> {
+    let tokens = lexer::tokenize_spanned(input).map_err(|message| SyntaxError {
+        message,
+        column: input.chars().count() + 1,
+    })?;
+    let mut parser = Parser {
+        input,
+        aliases,
+        tokens: tokens
+            .into_iter()
+            .map(|(at, token)| Spanned {
+                at,
+                token,
+                aliases: Vec::new(),
+                lookup: false,
+            })
+            .collect(),
+        pos: 0,
+    };
+    let list = parser.list()?;
+    match parser.peek() {
+        Some(token) => Err(parser.unexpected(token)),
+        None => Ok(list),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    aliases: &'a HashMap<String, String>,
+    tokens: Vec<Spanned>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|spanned| &spanned.token)
+    }
+
+    fn peek_operator(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Operator(operator)) => Some(operator),
+            _ => None,
+        }
+    }
+
+    fn peek_word(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Word(word)) => Some(word),
+            _ => None,
+        }
+    }
+
+    /// Whether the next token ends the list being read
+    fn at_list_end(&self) -> bool {
+        match self.peek() {
+            None => true,
+            Some(Token::Operator(operator)) => operator == ")" || operator == ";;",
+            Some(Token::Word(word)) => ends_list(word),
+        }
+    }
+
+    /// Reads the reserved word `word`, or fails on whatever is there instead,
+    /// blaming a missing end on the compound command opened at `open`
+    fn expect(&mut self, word: &str, open: usize, opener: &str) -> Result<(), SyntaxError> {
+        match self.peek() {
+            Some(Token::Word(next)) if next == word => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(token) => Err(self.unexpected(token)),
+            None => Err(self.not_closed(open, opener)),
+        }
+    }
+
+    /// An error for a compound command opened at `open` that the line ends inside
+    fn not_closed(&self, open: usize, opener: &str) -> SyntaxError {
+        self.error_at(open, format!("syntax error: `{}` is not closed", opener))
+    }
+
+    /// Where the next token starts, or the end of the line after the last
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map_or(self.input.len(), |spanned| spanned.at)
+    }
+
+    fn error_at(&self, at: usize, message: String) -> SyntaxError {
+        SyntaxError {
+            message,
+            column: self.input[..at.min(self.input.len())].chars().count() + 1,
+        }
+    }
+
+    fn unexpected(&self, token: &Token) -> SyntaxError {
+        self.error_at(
+            self.position(),
+            format!("syntax error near `{}`", token.text()),
+        )
+    }
+
+    /// An error for a command missing after the operator just read
+    fn missing_command(&self) -> SyntaxError {
+        match self.peek() {
+            Some(token) => self.unexpected(token),
+            None => {
+                let operator = &self.tokens[self.pos - 1];
+                self.error_at(
+                    operator.at,
+                    format!("syntax error near `{}`", operator.token.text()),
+                )
+            }
+        }
+    }
+
+    /// And-or lists up to the end of the line, a `)`, a `;;` or a reserved
+    /// word such as `then` or `fi`
+    fn list(&mut self) -> Result<List, SyntaxError> {
+        let mut list = List::default();
+        while !self.at_list_end() {
+            let and_or = self.and_or()?;
+            let background = match self.peek() {
+                Some(Token::Operator(separator)) if separator == ";" || separator == "&" => {
+                    let background = separator == "&";
+                    self.pos += 1;
+                    background
+                }
+                Some(token) if !self.at_list_end() => return Err(self.unexpected(token)),
+                _ => false,
+            };
+            list.items.push(Item { and_or, background });
+        }
+        Ok(list)
+    }
+
+    fn and_or(&mut self) -> Result<AndOrList, SyntaxError> {
+        let mut pipelines = vec![(None, self.pipeline()?)];
+        while let Some(operator @ ("&&" | "||")) = self.peek_operator() {
+            let and_or = if operator == "&&" {
+                AndOr::And
+            } else {
+                AndOr::Or
+            };
+            self.pos += 1;
+            pipelines.push((Some(and_or), self.pipeline()?));
+        }
+        Ok(AndOrList { pipelines })
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, SyntaxError> {
+        let start = self.position();
+        let negated = matches!(self.peek(), Some(Token::Word(word)) if word == "!")
+            && self
+                .tokens
+                .get(self.pos + 1)
+                .is_some_and(|next| match &next.token {
+                    Token::Word(_) => true,
+                    Token::Operator(operator) => {
+                        operator == "(" || Redirect::is_redirection(operator)
+                    }
+                });
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut commands = vec![self.command()?];
+        while self.peek_operator() == Some("|") {
+            self.pos += 1;
+            commands.push(self.command()?);
+        }
+        let text = self.input[start..self.position()].trim().to_string();
+        Ok(Pipeline {
+            negated,
+            commands,
+            text,
+        })
+    }
+
+    fn command(&mut self) -> Result<Command, SyntaxError> {
+        let command = match self.peek_word() {
+            Some("if") => Command::If(self.if_clause()?),
+            Some("case") => Command::Case(self.case_clause()?),
+            Some("{") => Command::Group(self.group()?),
+            Some(word) if self.at_function_definition(word) => {
+                return self.function().map(Command::Function)
+            }
+            _ if self.peek_operator() == Some("(") => Command::Subshell(self.subshell()?),
+            _ => return self.simple().map(Command::Simple),
+        };
+
+        let mut redirects = Vec::new();
+        while self.peek_operator().is_some_and(Redirect::is_redirection) {
+            redirects.push(self.redirection()?);
+        }
+        if redirects.is_empty() {
+            return Ok(command);
+        }
+        Ok(Command::Redirected(Box::new(command), redirects))
+    }
+
+    /// Reads a `( ... )` group
+    fn subshell(&mut self) -> Result<List, SyntaxError> {
+        let open = self.position();
+        self.pos += 1;
+        let list = self.list()?;
+        match self.peek() {
+            Some(token) if !token.is_operator(")") => return Err(self.unexpected(token)),
+            None => return Err(self.error_at(open, "syntax error: `(` is not closed".to_string())),
+            _ => {}
+        }
+        if list.items.is_empty() {
+            return Err(self.unexpected(&Token::Operator(")".to_string())));
+        }
+        self.pos += 1;
+        Ok(list)
+    }
+
+    /// Reads a `{ ... }` group
+    fn group(&mut self) -> Result<List, SyntaxError> {
+        let open = self.position();
+        self.pos += 1;
+        let list = self.nonempty_list()?;
+        self.expect("}", open, "{")?;
+        Ok(list)
+    }
+
+    /// Whether `word`, the next token, is the name of a function being
+    /// defined, as in `name() { ...; }`
+    fn at_function_definition(&self, word: &str) -> bool {
+        let operator = |offset: usize, operator: &str| {
+            self.tokens
+                .get(self.pos + offset)
+                .is_some_and(|next| next.token.is_operator(operator))
+        };
+        is_function_name(word) && operator(1, "(") && operator(2, ")")
+    }
+
+    /// Reads a function definition, whose body must be a compound command
+    fn function(&mut self) -> Result<Function, SyntaxError> {
+        let start = self.position();
+        let name = self.peek_word().unwrap_or_default().to_string();
+        self.pos += 3;
+        let body = match (self.peek_word(), self.peek()) {
+            (Some("{" | "if" | "case"), _) => self.command()?,
+            (_, Some(token)) if token.is_operator("(") => self.command()?,
+            _ => return Err(self.missing_command()),
+        };
+        let text = self.input[start..self.position()].trim().to_string();
+        Ok(Function {
+            name,
+            body: Box::new(body),
+            text,
+        })
+    }
+
+    /// Reads an `if` command, from the `if` up to its `fi`
+    fn if_clause(&mut self) -> Result<If, SyntaxError> {
+        let open = self.position();
+        let mut clause = If {
+            branches: Vec::new(),
+            otherwise: None,
+        };
+        loop {
+            // Past the `if` or `elif`
+            self.pos += 1;
+            let condition = self.nonempty_list()?;
+            self.expect("then", open, "if")?;
+            let body = self.nonempty_list()?;
+            clause.branches.push((condition, body));
+            match self.peek_word() {
+                Some("elif") => continue,
+                Some("else") => {
+                    self.pos += 1;
+                    clause.otherwise = Some(self.nonempty_list()?);
+                    self.expect("fi", open, "if")?;
+                }
+                _ => self.expect("fi", open, "if")?,
+            }
+            return Ok(clause);
+        }
+    }
+
+    /// Reads a `case` command, from the `case` up to its `esac`
+    fn case_clause(&mut self) -> Result<Case, SyntaxError> {
+        let open = self.position();
+        self.pos += 1;
+        let word = self.case_word(open)?;
+        self.expect("in", open, "case")?;
+        let mut items = Vec::new();
+        loop {
+            if self.peek_word() == Some("esac") {
+                self.pos += 1;
+                break;
+            }
+            // Patterns may have a `(` before them, as in `(*.rs)`
+            if self.peek_operator() == Some("(") {
+                self.pos += 1;
+            }
+            let mut patterns = vec![self.case_word(open)?];
+            while self.peek_operator() == Some("|") {
+                self.pos += 1;
+                patterns.push(self.case_word(open)?);
+            }
+            match self.peek() {
+                Some(token) if token.is_operator(")") => self.pos += 1,
+                Some(token) => return Err(self.unexpected(token)),
+                None => return Err(self.not_closed(open, "case")),
+            }
+            let body = self.list()?;
+            items.push(CaseItem { patterns, body });
+            match self.peek() {
+                Some(token) if token.is_operator(";;") => self.pos += 1,
+                _ => {
+                    self.expect("esac", open, "case")?;
+                    break;
+                }
+            }
+        }
+        Ok(Case { word, items })
+    }
+
+    /// Reads the word after `case`, or one of the patterns of an item
+    fn case_word(&mut self, open: usize) -> Result<String, SyntaxError> {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                let word = word.clone();
+                self.pos += 1;
+                Ok(word)
+            }
+            Some(token) => Err(self.unexpected(token)),
+            None => Err(self.not_closed(open, "case")),
+        }
+    }
+
+    /// A list that must have a command in it, as the parts of an `if` must
+    fn nonempty_list(&mut self) -> Result<List, SyntaxError> {
+        let list = self.list()?;
+        if list.items.is_empty() {
+            return Err(self.missing_command());
+        }
+        Ok(list)
+    }
+
+    fn simple(&mut self) -> Result<SimpleCommand, SyntaxError> {
+        let mut command = SimpleCommand::default();
+        while let Some(spanned) = self.tokens.get(self.pos) {
+            match &spanned.token {
+                Token::Word(word) => {
+                    let named = command
+                        .words
+                        .iter()
+                        .any(|word| !expand::is_assignment(word));
+                    let command_word = !named && !expand::is_assignment(word);
+                    if command_word || spanned.lookup {
+                        if let Some(value) = self.aliases.get(word) {
+                            if !spanned.aliases.contains(word) {
+                                self.expand_alias(self.pos, word.clone(), value)?;
+                                continue;
+                            }
+                        }
+                    }
+                    command.words.push(word.clone());
+                    self.pos += 1;
+                }
+                Token::Operator(operator) if Redirect::is_redirection(operator) => {
+                    command.redirects.push(self.redirection()?);
+                }
+                Token::Operator(_) => break,
+            }
+        }
+
+        if command.words.is_empty() && command.redirects.is_empty() {
+            return Err(self.missing_command());
+        }
+        Ok(command)
+    }
+
+    /// Reads a redirection operator and the word after it, if it takes one
+    fn redirection(&mut self) -> Result<Redirection, SyntaxError> {
+        let operator = self.peek_operator().unwrap_or_default().to_string();
+        self.pos += 1;
+        let target = match Redirect::takes_word(&operator) {
+            true => match self.peek() {
+                Some(Token::Word(word)) => {
+                    let word = word.clone();
+                    self.pos += 1;
+                    Some(word)
+                }
+                _ => return Err(self.missing_command()),
+            },
+            false => None,
+        };
+        if let Err(message) = Redirect::new(&operator, target.as_deref()) {
+            return Err(self.error_at(self.tokens[self.pos - 1].at, message));
+        }
+        Ok(Redirection { operator, target })
+    }
+
+    /// Replaces the word at `pos` with the tokens of the value of alias `name`
+    fn expand_alias(&mut self, pos: usize, name: String, value: &str) -> Result<(), SyntaxError> {
+        let at = self.tokens[pos].at;
+        let tokens =
+            lexer::tokenize_spanned(value).map_err(|message| self.error_at(at, message))?;
+        let count = tokens.len();
+        let mut aliases = self.tokens[pos].aliases.clone();
+        aliases.push(name);
+        let spliced = tokens
+            .into_iter()
+            .enumerate()
+            .map(|(i, (_, token))| Spanned {
+                at,
+                token,
+                aliases: aliases.clone(),
+                lookup: i == 0,
+            });
+        self.tokens.splice(pos..pos + 1, spliced);
+        if value.ends_with([' ', '\t']) {
+            if let Some(next) = self.tokens.get_mut(pos + count) {
+                next.lookup = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The function `source` starts by defining, for tests that need one
+#[cfg(test)]
+pub fn parse_function(source: &str) -> Function {
+    let list = parse(source, &HashMap::new()).unwrap();
+    let Command::Function(function) = &list.items[0].and_or.pipelines[0].1.commands[0] else {
+        panic!("not a function definition: {}", source);
+    };
+    function.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<List, SyntaxError> {
+        let aliases = HashMap::from([
+            ("ll".to_string(), "ls -l".to_string()),
+            ("count".to_string(), "sort | uniq -c".to_string()),
+            ("ls".to_string(), "ls -F".to_string()),
+            ("l".to_string(), "ll -a".to_string()),
+            ("ping".to_string(), "pong".to_string()),
+            ("pong".to_string(), "ping".to_string()),
+            ("sudo".to_string(), "sudo ".to_string()),
+            ("please".to_string(), "sudo".to_string()),
+        ]);
+        super::parse(input, &aliases)
+    }
+
+    /// The words of each simple command, with `|`, `(`, `)` and the list
+    /// operators between them
+    fn shape(list: &List) -> Vec<String> {
+        let mut out = Vec::new();
+        for item in &list.items {
+            for (and_or, pipeline) in &item.and_or.pipelines {
+                match and_or {
+                    Some(AndOr::And) => out.push("&&".to_string()),
+                    Some(AndOr::Or) => out.push("||".to_string()),
+                    None => {}
+                }
+                if pipeline.negated {
+                    out.push("!".to_string());
+                }
+                for (i, command) in pipeline.commands.iter().enumerate() {
+                    if i > 0 {
+                        out.push("|".to_string());
+                    }
+                    out.extend(shape_command(command));
+                }
+            }
+            out.push(if item.background { "&" } else { ";" }.to_string());
+        }
+        out
+    }
+
+    /// The words of a command for [`shape`], with the reserved words of a
+    /// compound command around the lists in it
+    fn shape_command(command: &Command) -> Vec<String> {
+        let mut out = Vec::new();
+        match command {
+            Command::Simple(simple) => {
+                out.extend(simple.tokens().iter().map(|t| t.text().to_string()))
+            }
+            Command::Subshell(list) => {
+                out.push("(".to_string());
+                out.extend(shape(list));
+                out.push(")".to_string());
+            }
+            Command::If(clause) => {
+                for (i, (condition, body)) in clause.branches.iter().enumerate() {
+                    out.push(if i == 0 { "if" } else { "elif" }.to_string());
+                    out.extend(shape(condition));
+                    out.push("then".to_string());
+                    out.extend(shape(body));
+                }
+                if let Some(otherwise) = &clause.otherwise {
+                    out.push("else".to_string());
+                    out.extend(shape(otherwise));
+                }
+                out.push("fi".to_string());
+            }
+            Command::Case(case) => {
+                out.extend(["case".to_string(), case.word.clone(), "in".to_string()]);
+                for item in &case.items {
+                    out.push(item.patterns.join("|"));
+                    out.push(")".to_string());
+                    out.extend(shape(&item.body));
+                    out.push(";;".to_string());
+                }
+                out.push("esac".to_string());
+            }
+            Command::Group(list) => {
+                out.push("{".to_string());
+                out.extend(shape(list));
+                out.push("}".to_string());
+            }
+            Command::Function(function) => {
+                out.push(format!("{}()", function.name));
+                out.extend(shape_command(&function.body));
+            }
+            Command::Redirected(command, redirects) => {
+                out.extend(shape_command(command));
+                for redirect in redirects {
+                    out.push(redirect.operator.clone());
+                    out.extend(redirect.target.clone());
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_lists_and_pipelines() {
+        let list = parse("a 1 && ! b|c 2>&1 >out; (d && e) || f &").unwrap();
+        assert_eq!(
+            shape(&list),
+            [
+                "a", "1", "&&", "!", "b", "|", "c", "2>&1", ">", "out", ";", "(", "d", "&&", "e",
+                ";", ")", "||", "f", "&"
+            ]
+        );
+        let pipeline = &list.items[0].and_or.pipelines[1].1;
+        assert_eq!(pipeline.text, "! b|c 2>&1 >out");
+        assert_eq!(list.items[1].and_or.pipelines[1].1.text, "f");
+
+        assert_eq!(shape(&parse("sleep 5&").unwrap()), ["sleep", "5", "&"]);
+        assert_eq!(
+            shape(&parse("echo 'a;b' \\&").unwrap()),
+            ["echo", "'a;b'", "\\&", ";"]
+        );
+        assert_eq!(shape(&parse("!").unwrap()), ["!", ";"]);
+        assert_eq!(parse("  ").unwrap(), List::default());
+    }
+
+    #[test]
+    fn test_aliases() {
+        assert_eq!(
+            shape(&parse("ll src").unwrap()),
+            ["ls", "-F", "-l", "src", ";"]
+        );
+        assert_eq!(shape(&parse("'ll' x").unwrap()), ["'ll'", "x", ";"]);
+        assert_eq!(
+            shape(&parse("X=1 ll").unwrap()),
+            ["X=1", "ls", "-F", "-l", ";"]
+        );
+        assert_eq!(shape(&parse("echo ll").unwrap()), ["echo", "ll", ";"]);
+        assert_eq!(
+            shape(&parse("cat f | count; ls").unwrap()),
+            ["cat", "f", "|", "sort", "|", "uniq", "-c", ";", "ls", "-F", ";"]
+        );
+        assert_eq!(
+            shape(&parse("l /").unwrap()),
+            ["ls", "-F", "-l", "-a", "/", ";"]
+        );
+        assert_eq!(shape(&parse("ping").unwrap()), ["ping", ";"]);
+        assert_eq!(
+            shape(&parse("sudo ll x").unwrap()),
+            ["sudo", "ls", "-F", "-l", "x", ";"]
+        );
+        assert_eq!(
+            shape(&parse("sudo ll ll").unwrap()),
+            ["sudo", "ls", "-F", "-l", "ll", ";"]
+        );
+        assert_eq!(
+            shape(&parse("sudo sudo ll").unwrap()),
+            ["sudo", "sudo", "ls", "-F", "-l", ";"]
+        );
+        assert_eq!(
+            shape(&parse("please ll").unwrap()),
+            ["sudo", "ls", "-F", "-l", ";"]
+        );
+    }
+
+    #[test]
+    fn test_if() {
+        assert_eq!(
+            shape(&parse("if a; then b; fi").unwrap()),
+            ["if", "a", ";", "then", "b", ";", "fi", ";"]
+        );
+        assert_eq!(
+            shape(
+                &parse("if a && b; then c | d; elif (e) then f& elif g; then h; else i; fi || j")
+                    .unwrap()
+            ),
+            [
+                "if", "a", "&&", "b", ";", "then", "c", "|", "d", ";", "elif", "(", "e", ";", ")",
+                ";", "then", "f", "&", "elif", "g", ";", "then", "h", ";", "else", "i", ";", "fi",
+                "||", "j", ";"
+            ]
+        );
+        assert_eq!(
+            shape(&parse("if if a; then b; fi; then ll; fi").unwrap()),
+            [
+                "if", "if", "a", ";", "then", "b", ";", "fi", ";", "then", "ls", "-F", "-l", ";",
+                "fi", ";"
+            ]
+        );
+        assert_eq!(
+            shape(&parse("echo if then fi; 'if' x").unwrap()),
+            ["echo", "if", "then", "fi", ";", "'if'", "x", ";"]
+        );
+    }
+
+    #[test]
+    fn test_case() {
+        assert_eq!(
+            shape(&parse("case $f in *.rs) a; b;; (*.toml|*.lock) ll;; esac").unwrap()),
+            [
+                "case",
+                "$f",
+                "in",
+                "*.rs",
+                ")",
+                "a",
+                ";",
+                "b",
+                ";",
+                ";;",
+                "*.toml|*.lock",
+                ")",
+                "ls",
+                "-F",
+                "-l",
+                ";",
+                ";;",
+                "esac",
+                ";"
+            ]
+        );
+        assert_eq!(
+            shape(&parse("case x in x) ;; *) a; esac && b").unwrap()),
+            ["case", "x", "in", "x", ")", ";;", "*", ")", "a", ";", ";;", "esac", "&&", "b", ";"]
+        );
+        assert_eq!(
+            shape(&parse("case x in esac").unwrap()),
+            ["case", "x", "in", "esac", ";"]
+        );
+        assert_eq!(
+            shape(&parse("case x in (a) if b; then c; fi;; esac").unwrap()),
+            [
+                "case", "x", "in", "a", ")", "if", "b", ";", "then", "c", ";", "fi", ";", ";;",
+                "esac", ";"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(
+            shape(&parse("{ a; b | c; } && d").unwrap()),
+            ["{", "a", ";", "b", "|", "c", ";", "}", "&&", "d", ";"]
+        );
+        assert_eq!(
+            shape(&parse("greet() { echo hi \"$1\"; }; greet x").unwrap()),
+            ["greet()", "{", "echo", "hi", "\"$1\"", ";", "}", ";", "greet", "x", ";"]
+        );
+        assert_eq!(
+            shape(&parse("f () (ll) ; g() if a; then b; fi").unwrap()),
+            [
+                "f()", "(", "ls", "-F", "-l", ";", ")", ";", "g()", "if", "a", ";", "then", "b",
+                ";", "fi", ";"
+            ]
+        );
+        assert_eq!(
+            shape(&parse("{ a; } > log 2>&1 | b; (c) <x").unwrap()),
+            [
+                "{", "a", ";", "}", ">", "log", "2>&1", "|", "b", ";", "(", "c", ";", ")", "<",
+                "x", ";"
+            ]
+        );
+        assert_eq!(
+            shape(&parse("echo { } }").unwrap()),
+            ["echo", "{", "}", "}", ";"]
+        );
+
+        let function = parse_function("my-fn() { a; }  ; b");
+        assert_eq!(function.name, "my-fn");
+        assert_eq!(function.text, "my-fn() { a; }");
+    }
+
+    #[test]
+    fn test_outline() {
+        let outline = |input| outline(input).unwrap();
+        assert_eq!(outline("a; b").separators, [1]);
+        assert_eq!(outline("(a; b); if c; then d; fi; e").separators, [6, 24]);
+        assert_eq!(outline("echo if; x").separators, [7]);
+        assert!(outline("echo 'if'").open.is_empty());
+        assert_eq!(outline("if a; then if b").open, ["if", "if"]);
+        assert!(outline("if a; then b; fi").open.is_empty());
+
+        assert!(outline("").continues);
+        assert!(outline("if a; then").continues);
+        assert!(outline("a &&").continues);
+        assert!(!outline("echo then").continues);
+        assert!(!outline("(a)").continues);
+
+        assert_eq!(outline("case x in a) b;; esac; c").separators, [21]);
+        assert_eq!(outline("a;; b").separators, [1, 2]);
+        assert_eq!(outline("case x in (a) (b)").open, ["case"]);
+        assert!(outline("case x in").continues);
+        assert!(outline("case x in a)").continues);
+        assert!(outline("case x in a) b;;").continues);
+        assert!(!outline("case x in a) b").continues);
+        assert!(!outline("case in").continues);
+
+        assert_eq!(outline("f() { a; b; }; c").separators, [13]);
+        assert_eq!(outline("f() { a").open, ["{"]);
+        assert!(outline("f()").continues);
+        assert!(outline("{").continues);
+        assert!(!outline("echo {").continues);
+        assert!(super::outline("a \"b").is_err());
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let error = |input| parse(input).unwrap_err().to_string();
+        assert_eq!(error("a && || b"), "syntax error near `||` at column 6");
+        assert_eq!(error("a &&"), "syntax error near `&&` at column 3");
+        assert_eq!(error("a | ;"), "syntax error near `;` at column 5");
+        assert_eq!(error("echo >"), "syntax error near `>` at column 6");
+        assert_eq!(error("cat >&x"), "syntax error near `>&` at column 5");
+        assert_eq!(error("(a"), "syntax error: `(` is not closed at column 1");
+        assert_eq!(error("a )"), "syntax error near `)` at column 3");
+        assert_eq!(error("()"), "syntax error near `)` at column 2");
+        assert_eq!(error("(a) b"), "syntax error near `b` at column 5");
+        assert_eq!(error("{ a; } >"), "syntax error near `>` at column 8");
+        assert_eq!(error("echo \"a"), "unterminated quoted string at column 8");
+        assert_eq!(
+            error("if a; then b"),
+            "syntax error: `if` is not closed at column 1"
+        );
+        assert_eq!(
+            error("x; if a; b; fi"),
+            "syntax error near `fi` at column 13"
+        );
+        assert_eq!(
+            error("if a; then fi"),
+            "syntax error near `fi` at column 12"
+        );
+        assert_eq!(
+            error("if a; then b; else fi"),
+            "syntax error near `fi` at column 20"
+        );
+        assert_eq!(
+            error("if a; then b; fi c"),
+            "syntax error near `c` at column 18"
+        );
+        assert_eq!(error("then"), "syntax error near `then` at column 1");
+        assert_eq!(error("(a; fi)"), "syntax error near `fi` at column 5");
+        assert_eq!(
+            error("case x in a) b;;"),
+            "syntax error: `case` is not closed at column 1"
+        );
+        assert_eq!(
+            error("case x a) b;; esac"),
+            "syntax error near `a` at column 8"
+        );
+        assert_eq!(
+            error("case x in a b) c;; esac"),
+            "syntax error near `b` at column 13"
+        );
+        assert_eq!(
+            error("case x in a) b; c) d;; esac"),
+            "syntax error near `)` at column 18"
+        );
+        assert_eq!(error("a;; b"), "syntax error near `;;` at column 2");
+        assert_eq!(
+            error("{ a; b"),
+            "syntax error: `{` is not closed at column 1"
+        );
+        assert_eq!(error("{ }"), "syntax error near `}` at column 3");
+        assert_eq!(
+            error("{ a }"),
+            "syntax error: `{` is not closed at column 1"
+        );
+        assert_eq!(error("f() echo hi"), "syntax error near `echo` at column 5");
+        assert_eq!(error("f()"), "syntax error near `)` at column 3");
+        assert_eq!(error("if() { a; }"), "syntax error near `)` at column 4");
+    }
+}
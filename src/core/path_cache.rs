@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Executables found on `$PATH`, scanned once and reused until `$PATH` changes.
+///
+/// Cloning is cheap, so the same table can be shared with the completer.
+#[derive(Debug, Clone, Default)]
+pub struct PathCache {
+    path: String,
+    executables: Arc<HashMap<String, PathBuf>>,
+    /// How often each command was run through the cache, as `hash` shows it
+    hits: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+impl PathCache {
+    /// Scans every directory in `path` for executables
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            executables: Arc::new(Self::scan(path)),
+            hits: Arc::default(),
+        }
+    }
+
+    /// Rescans if `path` differs from the one the cache was built from.
+    ///
+    /// Returns whether the cache was rebuilt.
+    pub fn refresh(&mut self, path: &str) -> bool {
+        if self.path == path {
+            return false;
+        }
+        *self = Self::new(path);
+        true
+    }
+
+    /// Forgets everything and scans the same `$PATH` again
+    pub fn rehash(&mut self) {
+        self.executables = Arc::new(Self::scan(&self.path));
+        self.hits = Arc::default();
+    }
+
+    /// Looks `name` up again in every `$PATH` directory, updating its entry
+    pub fn search(&mut self, name: &str) -> Option<&Path> {
+        let found = self
+            .path
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| Path::new(dir).join(name))
+            .find(|path| Self::is_executable(path));
+
+        let executables = Arc::make_mut(&mut self.executables);
+        match found {
+            Some(path) => {
+                executables.insert(name.to_string(), path);
+                self.hits.lock().ok()?.entry(name.to_string()).or_insert(0);
+            }
+            None => {
+                executables.remove(name);
+                self.hits.lock().ok()?.remove(name);
+            }
+        }
+        self.lookup(name)
+    }
+
+    /// Returns where to run `name` from, counting a hit, if it is still there
+    pub fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let path = self.lookup(name).filter(|path| path.is_file())?;
+        if let Ok(mut hits) = self.hits.lock() {
+            *hits.entry(name.to_string()).or_insert(0) += 1;
+        }
+        Some(path.to_path_buf())
+    }
+
+    /// The commands run so far with their paths and hit counts, sorted by name
+    pub fn hits(&self) -> Vec<(String, PathBuf, u32)> {
+        let Ok(hits) = self.hits.lock() else {
+            return Vec::new();
+        };
+        let mut hits: Vec<_> = hits
+            .iter()
+            .filter_map(|(name, &count)| {
+                let path = self.lookup(name)?.to_path_buf();
+                Some((name.clone(), path, count))
+            })
+            .collect();
+        hits.sort();
+        hits
+    }
+
+    /// Returns the full path of the first executable named `name` on `$PATH`
+    pub fn lookup(&self, name: &str) -> Option<&Path> {
+        self.executables.get(name).map(PathBuf::as_path)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.executables.keys()
+    }
+
+    fn scan(path: &str) -> HashMap<String, PathBuf> {
+        let mut executables = HashMap::new();
+
+        for dir in path.split(':').filter(|dir| !dir.is_empty()) {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+
+            for entry in entries.filter_map(Result::ok) {
+                let path = entry.path();
+                if !Self::is_executable(&path) {
+                    continue;
+                }
+
+                // Earlier directories take precedence, just like command lookup
+                let name = entry.file_name().to_string_lossy().into_owned();
+                executables.entry(name).or_insert(path);
+            }
+        }
+
+        executables
+    }
+
+    fn is_executable(path: &Path) -> bool {
+        fs::metadata(path)
+            .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_file(dir: &Path, name: &str, mode: u32) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_lookup_only_executables() {
+        let dir = TempDir::new().unwrap();
+        let tool = create_file(dir.path(), "tool", 0o755);
+        create_file(dir.path(), "notes", 0o644);
+
+        let cache = PathCache::new(dir.path().to_str().unwrap());
+        assert_eq!(cache.lookup("tool"), Some(tool.as_path()));
+        assert_eq!(cache.lookup("notes"), None);
+        assert_eq!(cache.names().count(), 1);
+    }
+
+    #[test]
+    fn test_earlier_directories_win() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let expected = create_file(first.path(), "tool", 0o755);
+        create_file(second.path(), "tool", 0o755);
+
+        let path = format!("{}:{}", first.path().display(), second.path().display());
+        let cache = PathCache::new(&path);
+        assert_eq!(cache.lookup("tool"), Some(expected.as_path()));
+    }
+
+    #[test]
+    fn test_refresh_and_rehash() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().to_str().unwrap().to_string();
+        let mut cache = PathCache::new(&path);
+        assert_eq!(cache.lookup("late"), None);
+
+        create_file(dir.path(), "late", 0o755);
+        assert!(!cache.refresh(&path));
+        assert_eq!(cache.lookup("late"), None);
+
+        cache.rehash();
+        assert!(cache.lookup("late").is_some());
+
+        assert!(cache.refresh(""));
+        assert_eq!(cache.names().count(), 0);
+    }
+
+    #[test]
+    fn test_hits_and_search() {
+        let dir = TempDir::new().unwrap();
+        let tool = create_file(dir.path(), "tool", 0o755);
+        let mut cache = PathCache::new(dir.path().to_str().unwrap());
+        assert!(cache.hits().is_empty());
+
+        assert_eq!(cache.resolve("tool"), Some(tool.clone()));
+        assert_eq!(cache.clone().resolve("tool"), Some(tool.clone()));
+        assert_eq!(cache.hits(), vec![("tool".to_string(), tool.clone(), 2)]);
+        assert_eq!(cache.resolve("missing"), None);
+
+        let late = create_file(dir.path(), "late", 0o755);
+        assert_eq!(cache.search("late"), Some(late.as_path()));
+        assert_eq!(cache.hits()[0], ("late".to_string(), late.clone(), 0));
+
+        fs::remove_file(&late).unwrap();
+        assert_eq!(cache.resolve("late"), None);
+        assert_eq!(cache.search("late"), None);
+
+        cache.rehash();
+        assert!(cache.hits().is_empty());
+    }
+}
@@ -0,0 +1,333 @@
+use serde_json::{json, Value};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    mem,
+    os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    path::{Path, PathBuf},
+    ptr,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::utils;
+
+/// Records a terminal session in the asciicast v2 format.
+///
+/// The file starts with a JSON header, followed by one `[seconds, kind, data]`
+/// event per line, where kind is `"o"` for output and `"i"` for typed input.
+/// Recordings play back with `record play` or any asciicast player.
+#[derive(Debug)]
+pub struct Recorder {
+    out: BufWriter<File>,
+    path: PathBuf,
+    start: Instant,
+}
+
+/// A recorder shared between the shell and the threads copying child output
+pub type SharedRecorder = Arc<Mutex<Recorder>>;
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let header = json!({
+            "version": 2,
+            "width": utils::term_width(),
+            "height": utils::term_height(),
+            "timestamp": timestamp,
+        });
+        writeln!(out, "{}", header)?;
+
+        Ok(Self {
+            out,
+            path: path.to_path_buf(),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records terminal output.
+    ///
+    /// Output captured through a pipe never passed the terminal's newline
+    /// translation, so bare `\n` becomes `\r\n` for players to show it right.
+    pub fn output(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut translated = Vec::with_capacity(data.len());
+        let mut previous = None;
+        for &byte in data {
+            if byte == b'\n' && previous != Some(b'\r') {
+                translated.push(b'\r');
+            }
+            translated.push(byte);
+            previous = Some(byte);
+        }
+        self.event("o", &translated)
+    }
+
+    pub fn input(&mut self, data: &[u8]) -> io::Result<()> {
+        self.event("i", data)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    fn event(&mut self, kind: &str, data: &[u8]) -> io::Result<()> {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let event = json!([elapsed, kind, String::from_utf8_lossy(data)]);
+        writeln!(self.out, "{}", event)
+    }
+}
+
+/// Copies `reader` to `writer` on a background thread, recording everything that passes
+pub fn tee(
+    mut reader: impl Read + Send + 'static,
+    mut writer: impl Write + Send + 'static,
+    recorder: SharedRecorder,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        while let Ok(n) = reader.read(&mut buffer) {
+            if n == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..n]).ok();
+            writer.flush().ok();
+            if let Ok(mut recorder) = recorder.lock() {
+                recorder.output(&buffer[..n]).ok();
+            }
+        }
+    })
+}
+
+/// Runs `f` with the shell's stdout and stderr passing through the recording.
+///
+/// The descriptors are swapped at the OS level, so builtins and the external
+/// commands they start, which inherit them, are all captured. On a terminal
+/// they are swapped for a pseudo-terminal of the same size and settings, so
+/// programs that check for a terminal, like `ls --color=auto` or an editor,
+/// behave as they would without the recording; otherwise for pipes.
+pub fn capture<T>(recorder: &SharedRecorder, f: impl FnOnce() -> T) -> io::Result<T> {
+    let (stdout, stderr) = (io::stdout().as_raw_fd(), io::stderr().as_raw_fd());
+    // SAFETY: isatty only inspects the descriptors
+    let terminal = unsafe { libc::isatty(stdout) == 1 && libc::isatty(stderr) == 1 };
+    let redirected = if terminal {
+        vec![Redirected::new(&[stdout, stderr], true, recorder)?]
+    } else {
+        vec![
+            Redirected::new(&[stdout], false, recorder)?,
+            Redirected::new(&[stderr], false, recorder)?,
+        ]
+    };
+    let result = f();
+
+    io::stdout().flush()?;
+    io::stderr().flush()?;
+    for redirected in redirected {
+        redirected.restore();
+    }
+    Ok(result)
+}
+
+/// Standard descriptors pointed at a pipe or pseudo-terminal whose contents
+/// are teed to where the first of them pointed before
+struct Redirected {
+    /// Each descriptor with a copy of what it was
+    saved: Vec<(RawFd, OwnedFd)>,
+    copier: JoinHandle<()>,
+}
+
+impl Redirected {
+    fn new(fds: &[RawFd], terminal: bool, recorder: &SharedRecorder) -> io::Result<Self> {
+        let (reader, writer) = if terminal {
+            open_pty(fds[0])?
+        } else {
+            let (reader, writer) = os_pipe::pipe()?;
+            (reader.into(), writer.into())
+        };
+        let original = duplicate(fds[0])?;
+
+        let mut redirected = Self {
+            saved: Vec::new(),
+            copier: tee(File::from(reader), File::from(original), recorder.clone()),
+        };
+        for &fd in fds {
+            let saved = duplicate(fd)?;
+            // SAFETY: dup2 only changes the descriptor table, and `saved`
+            // keeps what `fd` was for `restore`
+            if unsafe { libc::dup2(writer.as_raw_fd(), fd) } < 0 {
+                let error = io::Error::last_os_error();
+                redirected.restore();
+                return Err(error);
+            }
+            redirected.saved.push((fd, saved));
+        }
+        Ok(redirected)
+    }
+
+    /// Puts the original descriptors back, which closes the pipe or terminal
+    /// and lets the copier finish
+    fn restore(self) {
+        for (fd, saved) in self.saved {
+            // SAFETY: as in `new`
+            unsafe { libc::dup2(saved.as_raw_fd(), fd) };
+        }
+        self.copier.join().ok();
+    }
+}
+
+/// A copy of `fd` that is closed in the commands the shell runs
+fn duplicate(fd: RawFd) -> io::Result<OwnedFd> {
+    // SAFETY: the caller's descriptor is open for the length of the borrow
+    unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()
+}
+
+/// Opens a pseudo-terminal like the terminal `like`, returning its master
+/// and slave ends. Reading the master gives what was written to the slave.
+fn open_pty(like: RawFd) -> io::Result<(OwnedFd, OwnedFd)> {
+    // SAFETY: zeroed termios and winsize are valid values to be filled in
+    let (mut termios, mut size) = unsafe { (mem::zeroed::<libc::termios>(), mem::zeroed()) };
+    // SAFETY: both only fill in the structures passed
+    let has_termios = unsafe { libc::tcgetattr(like, &mut termios) } == 0;
+    let has_size = unsafe { libc::ioctl(like, libc::TIOCGWINSZ, &mut size) } == 0;
+    // The terminal it is copied to already turns `\n` into `\r\n`
+    termios.c_oflag &= !libc::OPOST;
+
+    let (mut master, mut slave) = (-1, -1);
+    // SAFETY: openpty writes the two descriptors it opens, which are owned below
+    let opened = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            ptr::null_mut(),
+            if has_termios { &termios } else { ptr::null() },
+            if has_size { &size } else { ptr::null() },
+        )
+    };
+    if opened < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: openpty succeeded, so both descriptors are open and nothing else owns them
+    let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+    for fd in [&master, &slave] {
+        // SAFETY: only sets a flag on a descriptor owned here
+        unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    Ok((master, slave))
+}
+
+/// Writes the output of a recording to `out`, keeping the pauses between events.
+///
+/// `speed` divides every pause, so 2.0 plays twice as fast.
+pub fn replay(path: &Path, out: &mut impl Write, speed: f64) -> io::Result<()> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut lines = BufReader::new(File::open(path)?).lines();
+
+    let header: Value = serde_json::from_str(&lines.next().ok_or_else(|| invalid("empty file"))??)
+        .map_err(|_| invalid("not an asciicast file"))?;
+    if header["version"] != 2 {
+        return Err(invalid("unsupported asciicast version"));
+    }
+
+    let start = Instant::now();
+    for line in lines {
+        let event: Value = serde_json::from_str(&line?).map_err(|_| invalid("malformed event"))?;
+        let (Some(time), Some("o"), Some(data)) =
+            (event[0].as_f64(), event[1].as_str(), event[2].as_str())
+        else {
+            continue;
+        };
+
+        let due = Duration::from_secs_f64(time / speed);
+        if let Some(wait) = due.checked_sub(start.elapsed()) {
+            thread::sleep(wait);
+        }
+        out.write_all(data.as_bytes())?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_and_replay() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("session.cast");
+
+        let mut recorder = Recorder::create(&path).unwrap();
+        recorder.output(b"$ ").unwrap();
+        recorder.input(b"echo \"hi\"\n").unwrap();
+        recorder.output(b"hi\none\r\n").unwrap();
+        recorder.flush().unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].contains("\"version\":2"));
+        assert!(lines[2].contains("\"i\",\"echo \\\"hi\\\"\\n\""));
+
+        let mut replayed = Vec::new();
+        replay(&path, &mut replayed, 100.0).unwrap();
+        assert_eq!(replayed, b"$ hi\r\none\r\n");
+    }
+
+    #[test]
+    fn test_tee_copies_and_records() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tee.cast");
+        let recorder = Arc::new(Mutex::new(Recorder::create(&path).unwrap()));
+
+        tee(&b"output"[..], io::sink(), recorder.clone())
+            .join()
+            .unwrap();
+        recorder.lock().unwrap().flush().unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("\"o\",\"output\""));
+    }
+
+    #[test]
+    fn test_commands_see_a_terminal() {
+        let dir = TempDir::new().unwrap();
+        let recorder = Arc::new(Mutex::new(
+            Recorder::create(&dir.path().join("tty.cast")).unwrap(),
+        ));
+        let shown = File::create(dir.path().join("shown")).unwrap();
+        let fd = shown.as_raw_fd();
+
+        let redirected = Redirected::new(&[fd], true, &recorder).unwrap();
+        let script = format!(
+            "if [ -t {0} ]; then echo tty; else echo notty; fi >&{0}",
+            fd
+        );
+        std::process::Command::new("sh")
+            .args(["-c", &script])
+            .status()
+            .unwrap();
+        redirected.restore();
+        recorder.lock().unwrap().flush().unwrap();
+
+        let shown = std::fs::read_to_string(dir.path().join("shown")).unwrap();
+        assert_eq!(shown.trim_end(), "tty");
+        let recording = std::fs::read_to_string(dir.path().join("tty.cast")).unwrap();
+        assert!(recording.contains("\"o\",\"tty\\r\\n\""));
+    }
+
+    #[test]
+    fn test_replay_rejects_other_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "hello\n").unwrap();
+        assert!(replay(&path, &mut io::sink(), 1.0).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Map};
+
+use super::flags::{FlagSpec, Flags};
+use crate::{system, utils};
+
+/// Options for builtins that can print their output as data for scripts
+pub const JSON_FLAG: FlagSpec =
+    FlagSpec::long("json", "Print the output as JSON, one object per row");
+pub const CSV_FLAG: FlagSpec = FlagSpec::long("csv", "Print the output as CSV with a header row");
+
+/// One cell of a table passed between builtins in a structured pipeline
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    /// A size in bytes
+    Size(u64),
+    /// Seconds since the Unix epoch
+    Time(i64),
+    Duration(Duration),
+    Text(String),
+}
+
+impl Value {
+    /// Reads `text` as a value of the same kind as `self`, so `1MB` means a size
+    /// when compared with sizes and `2024-01-31` or `2d` (ago) a time with times
+    pub fn parse_like(&self, text: &str) -> Option<Value> {
+        match self {
+            Value::Int(_) => text.parse().ok().map(Value::Int),
+            Value::Size(_) => parse_size(text).map(Value::Size),
+            Value::Time(_) => parse_time(text).map(Value::Time),
+            Value::Duration(_) => utils::parse_duration(text).map(Value::Duration),
+            Value::Text(_) => Some(Value::Text(text.to_string())),
+        }
+    }
+
+    /// Orders two values of the same kind; values of different kinds don't compare
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::Size(a), Value::Size(b)) => Some(a.cmp(b)),
+            (Value::Time(a), Value::Time(b)) => Some(a.cmp(b)),
+            (Value::Duration(a), Value::Duration(b)) => Some(a.cmp(b)),
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        !matches!(self, Value::Text(_))
+    }
+
+    /// Sizes in bytes, times in seconds since the epoch and durations in seconds
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Int(n) => json!(n),
+            Value::Size(bytes) => json!(bytes),
+            Value::Time(secs) => json!(secs),
+            Value::Duration(time) => json!(time.as_secs_f64()),
+            Value::Text(text) => json!(text),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Size(bytes) => write!(f, "{}", utils::format_size(*bytes)),
+            Value::Time(secs) => write!(f, "{}", format_time(*secs)),
+            Value::Duration(time) => write!(f, "{}", system::format_cpu_time(*time)),
+            Value::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// Rows with named columns, the data builtins hand each other in a structured pipeline
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(columns: &[&str]) -> Self {
+        Self {
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// The index of the column called `name`, ignoring case
+    pub fn column(&self, name: &str) -> Result<usize, String> {
+        self.columns
+            .iter()
+            .position(|column| column.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "no column '{}' (the columns are {})",
+                    name,
+                    self.columns.join(", ")
+                )
+            })
+    }
+
+    /// Whether `--json` or `--csv` asked for the output as data
+    pub fn wanted(flags: &Flags) -> bool {
+        flags.has_long("json") || flags.has_long("csv")
+    }
+
+    /// Prints the table as JSON or CSV, whichever `flags` asked for
+    pub fn print_data(&self, flags: &Flags) {
+        if flags.has_long("json") {
+            println!("{}", self.to_json());
+        } else {
+            print!("{}", self.to_csv());
+        }
+    }
+
+    /// An array with an object per row, keyed by column
+    fn to_json(&self) -> serde_json::Value {
+        self.rows
+            .iter()
+            .map(|row| {
+                let object: Map<String, serde_json::Value> = self
+                    .columns
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().map(Value::to_json))
+                    .collect();
+                serde_json::Value::Object(object)
+            })
+            .collect()
+    }
+
+    /// CSV quoted as RFC 4180 says, with the same units as the JSON
+    fn to_csv(&self) -> String {
+        let field = |text: String| {
+            if text.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            } else {
+                text
+            }
+        };
+        let line =
+            |fields: Vec<String>| fields.into_iter().map(field).collect::<Vec<_>>().join(",");
+
+        let mut out = line(self.columns.clone());
+        out.push('\n');
+        for row in &self.rows {
+            out.push_str(&line(
+                row.iter()
+                    .map(|value| match value.to_json() {
+                        serde_json::Value::String(text) => text,
+                        other => other.to_string(),
+                    })
+                    .collect(),
+            ));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Lays the table out in aligned columns, with numbers, sizes and times on the right
+    pub fn render(&self) -> String {
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(Value::to_string).collect())
+            .collect();
+        let widths: Vec<usize> = (0..self.columns.len())
+            .map(|i| {
+                cells
+                    .iter()
+                    .map(|row| utils::display_width(&row[i]))
+                    .chain([utils::display_width(&self.columns[i])])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let right = |i: usize| self.rows.first().is_some_and(|row| row[i].is_numeric());
+
+        let line = |row: Vec<&str>| {
+            let last = row.len() - 1;
+            let mut line = String::new();
+            for (i, cell) in row.into_iter().enumerate() {
+                let pad = " ".repeat(widths[i] - utils::display_width(cell));
+                if right(i) {
+                    line.push_str(&pad);
+                    line.push_str(cell);
+                } else {
+                    line.push_str(cell);
+                    if i != last {
+                        line.push_str(&pad);
+                    }
+                }
+                if i != last {
+                    line.push_str("  ");
+                }
+            }
+            line
+        };
+
+        let mut out = line(self.columns.iter().map(String::as_str).collect());
+        for row in &cells {
+            out.push('\n');
+            out.push_str(&line(row.iter().map(String::as_str).collect()));
+        }
+        out
+    }
+}
+
+/// Parses a size such as `512`, `4K` or `1.5MB`, in units of 1024 as `ls` shows them
+fn parse_size(text: &str) -> Option<u64> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let unit = unit.to_ascii_uppercase();
+    let exponent = match unit.trim_end_matches('B') {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return None,
+    };
+    Some((number * 1024_f64.powi(exponent)) as u64)
+}
+
+/// Parses a local date, `YYYY-MM-DD` with an optional ` HH:MM`, or a duration
+/// such as `2d` meaning that long ago
+fn parse_time(text: &str) -> Option<i64> {
+    if let Some(ago) = utils::parse_duration(text) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+        return Some(now.saturating_sub(ago).as_secs() as i64);
+    }
+
+    let (date, clock) = text.split_once([' ', 'T']).unwrap_or((text, "00:00"));
+    let date: Vec<i32> = date
+        .split('-')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let clock: Vec<i32> = clock
+        .split(':')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let (&[year, month, day], &[hour, minute, ..]) = (&date[..], &clock[..]) else {
+        return None;
+    };
+
+    // SAFETY: mktime only reads and normalises the struct it is given
+    let time = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        tm.tm_year = year - 1900;
+        tm.tm_mon = month - 1;
+        tm.tm_mday = day;
+        tm.tm_hour = hour;
+        tm.tm_min = minute;
+        tm.tm_sec = clock.get(2).copied().unwrap_or(0);
+        tm.tm_isdst = -1;
+        libc::mktime(&mut tm)
+    };
+    (time != -1).then_some(time as i64)
+}
+
+/// A timestamp as local `YYYY-MM-DD HH:MM`
+fn format_time(secs: i64) -> String {
+    let time = secs as libc::time_t;
+    // SAFETY: localtime_r only writes to the struct it is given
+    let tm = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&time, &mut tm);
+        tm
+    };
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_like() {
+        let size = Value::Size(0);
+        assert_eq!(size.parse_like("512"), Some(Value::Size(512)));
+        assert_eq!(size.parse_like("4k"), Some(Value::Size(4096)));
+        assert_eq!(size.parse_like("1.5MB"), Some(Value::Size(1572864)));
+        assert_eq!(size.parse_like("3 parsecs"), None);
+        assert_eq!(Value::Int(0).parse_like("-3"), Some(Value::Int(-3)));
+
+        let time = Value::Time(0);
+        let Some(Value::Time(date)) = time.parse_like("2024-01-31 12:30") else {
+            panic!("date did not parse");
+        };
+        assert_eq!(format_time(date), "2024-01-31 12:30");
+        let (day, hour) = (
+            time.parse_like("1d").unwrap(),
+            time.parse_like("1h").unwrap(),
+        );
+        assert_eq!(day.compare(&hour), Some(Ordering::Less));
+        assert_eq!(time.parse_like("Tuesday"), None);
+    }
+
+    #[test]
+    fn test_render_aligns_columns() {
+        let mut table = Table::new(&["name", "size"]);
+        table
+            .rows
+            .push(vec![Value::Text("a".into()), Value::Size(1024)]);
+        table
+            .rows
+            .push(vec![Value::Text("long name".into()), Value::Size(0)]);
+        assert_eq!(
+            table.render(),
+            "name        size\n\
+             a          1.0KB\n\
+             long name      0"
+        );
+        assert_eq!(table.column("SIZE"), Ok(1));
+        assert!(table.column("mode").is_err());
+    }
+
+    #[test]
+    fn test_data_output() {
+        let mut table = Table::new(&["name", "size", "time"]);
+        table.rows.push(vec![
+            Value::Text("a, \"b\"".into()),
+            Value::Size(1024),
+            Value::Duration(Duration::from_millis(1500)),
+        ]);
+        assert_eq!(
+            table.to_json(),
+            json!([{"name": "a, \"b\"", "size": 1024, "time": 1.5}])
+        );
+        assert_eq!(
+            table.to_csv(),
+            "name,size,time\n\"a, \"\"b\"\"\",1024,1.5\n"
+        );
+    }
+}
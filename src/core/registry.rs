@@ -1,58 +1,199 @@
-use rustyline::history::FileHistory;
-
 use crate::commands::{
-    ChangeDirectory, Echo, History, ListDirectory, PrintWorkingDirectory, TypeCommand,
+    Alias, Background, Base64, Builtin, ChangeDirectory, CommandBuiltin, ConfigCommand, Diff,
+    Doctor, Echo, Env, Export, Fetch, FileCommand, Foreground, Free, Functions, Hash, Help,
+    History, Jobs, Json, Kill, ListDirectory, Local, Md5Sum, Nice, OnChange, PagerCommand,
+    Parallel, PrintWorkingDirectory, Ps, Read, RealPath, Record, Rehash, Select, Serve, Set,
+    Sha256Sum, SortBy, Theme, Timeout, TypeCommand, Unalias, Unset, Uptime, UrlDecode, UrlEncode,
+    Wait, Where, Which,
 };
-use std::{collections::HashMap, error::Error, path::PathBuf};
+use std::collections::HashMap;
 
 use super::{
-    command::{Command, CommandContext},
-    flags::Flags,
+    command::{help_text, Command, CommandContext, ExitCode, Records},
+    error::{ShellError, ShellResult},
+    flags::{Flags, HELP_FLAG},
+    records::Table,
 };
 
 pub struct CommandRegistry {
     commands: HashMap<&'static str, Box<dyn Command>>,
-    context: CommandContext,
 }
 
+/// Builtins that POSIX sh also has; in posix mode every other name is looked up on `$PATH`
+pub const POSIX_BUILTINS: &[&str] = &[
+    "alias", "bg", "cd", "command", "echo", "export", "fg", "hash", "jobs", "kill", "pwd", "read",
+    "set", "type", "unalias", "unset", "wait",
+];
+
 impl CommandRegistry {
-    pub fn setup(history: &FileHistory) -> Self {
-        let commands: Vec<Box<dyn Command>> = vec![
+    /// Registers the builtins, plus any from plugins whose names are still free
+    pub fn setup(plugins: Vec<Box<dyn Command>>) -> Self {
+        let mut commands: Vec<Box<dyn Command>> = vec![
             Box::new(Echo),
             Box::new(ChangeDirectory),
             Box::new(ListDirectory),
             Box::new(PrintWorkingDirectory),
             Box::new(History),
             Box::new(TypeCommand),
+            Box::new(CommandBuiltin),
+            Box::new(Builtin),
+            Box::new(Export),
+            Box::new(Env),
+            Box::new(Unset),
+            Box::new(Alias),
+            Box::new(Unalias),
+            Box::new(Functions),
+            Box::new(Local),
+            Box::new(Which),
+            Box::new(RealPath),
+            Box::new(FileCommand),
+            Box::new(Sha256Sum),
+            Box::new(Md5Sum),
+            Box::new(Ps),
+            Box::new(Uptime),
+            Box::new(Free),
+            Box::new(Where),
+            Box::new(SortBy),
+            Box::new(Select),
+            Box::new(Fetch),
+            Box::new(Json),
+            Box::new(Base64),
+            Box::new(UrlEncode),
+            Box::new(UrlDecode),
+            Box::new(Rehash),
+            Box::new(Hash),
+            Box::new(PagerCommand),
+            Box::new(Theme),
+            Box::new(Set),
+            Box::new(Timeout),
+            Box::new(Nice),
+            Box::new(Parallel),
+            Box::new(OnChange),
+            Box::new(Serve),
+            Box::new(Diff),
+            Box::new(Jobs),
+            Box::new(Foreground),
+            Box::new(Background),
+            Box::new(Wait),
+            Box::new(Kill),
+            Box::new(Read),
+            Box::new(Record),
+            Box::new(Doctor),
+            Box::new(ConfigCommand),
         ];
+        for plugin in plugins {
+            if commands.iter().any(|cmd| cmd.name() == plugin.name()) {
+                eprintln!(
+                    "hermit: plugin builtin '{}' clashes with an existing one",
+                    plugin.name()
+                );
+                continue;
+            }
+            commands.push(plugin);
+        }
+
+        let help: Box<dyn Command> = Box::new(Help::new(&commands));
 
-        let command_names: Vec<&'static str> = commands.iter().map(|cmd| cmd.name()).collect();
         let mut command_map = HashMap::new();
-        for cmd in commands {
+        for cmd in commands.into_iter().chain([help]) {
             command_map.insert(cmd.name(), cmd);
         }
 
-        let context = CommandContext {
-            history: history.iter().map(|s| s.to_string()).collect(),
-            builtins: command_names,
-        };
-
         CommandRegistry {
             commands: command_map,
-            context,
         }
     }
 
-    pub fn execute(&mut self, command: &str, args: &[&str]) -> Result<bool, Box<dyn Error>> {
-        if let Some(cmd) = self.commands.get(command) {
-            let flags = Flags::new(args);
-            cmd.execute(args, &flags?, &self.context)?;
-            Ok(true)
-        } else {
-            Ok(false)
+    /// Runs a builtin, returning `None` when no builtin with that name exists
+    pub fn execute(
+        &self,
+        command: &str,
+        args: &[&str],
+        context: &mut CommandContext,
+    ) -> ShellResult<Option<ExitCode>> {
+        match self.lookup(command, context) {
+            Some(cmd) => {
+                if !cmd.parses_flags() {
+                    return cmd.execute(args, &Flags::default(), context).map(Some);
+                }
+
+                let flags = Self::parse_flags(cmd, args)?;
+                if flags.has_flag('?') {
+                    println!("{}", help_text(cmd));
+                    return Ok(Some(ExitCode::SUCCESS));
+                }
+                cmd.execute(args, &flags, context).map(Some)
+            }
+            None => Ok(None),
         }
     }
 
+    /// Runs a pipeline whose stages hand tables to each other, returning the last
+    /// table, or `None` when some stage isn't a builtin that can take part
+    pub fn execute_records(
+        &self,
+        pipeline: &[(&str, Vec<&str>)],
+        context: &mut CommandContext,
+    ) -> ShellResult<Option<Table>> {
+        let mut stages = Vec::new();
+        for (i, (command, _)) in pipeline.iter().enumerate() {
+            let expected = if i == 0 {
+                Records::Produces
+            } else {
+                Records::Transforms
+            };
+            match self.lookup(command, context) {
+                Some(cmd) if cmd.records() == expected => stages.push(cmd),
+                _ => return Ok(None),
+            }
+        }
+
+        let mut table = None;
+        for (cmd, (_, args)) in stages.into_iter().zip(pipeline) {
+            let flags = if cmd.parses_flags() {
+                Self::parse_flags(cmd, args)?
+            } else {
+                Flags::default()
+            };
+            table = Some(cmd.execute_records(table, args, &flags, context)?);
+        }
+        Ok(table)
+    }
+
+    /// The first command in `pipeline` that only works on tables, such as `where`
+    pub fn transforms_records<'a>(
+        &self,
+        pipeline: &[(&'a str, Vec<&str>)],
+        context: &CommandContext,
+    ) -> Option<&'a str> {
+        pipeline
+            .iter()
+            .map(|(command, _)| *command)
+            .find(|command| {
+                self.lookup(command, context)
+                    .is_some_and(|cmd| cmd.records() == Records::Transforms)
+            })
+    }
+
+    /// The builtin called `command`, if it is one in the current mode
+    fn lookup(&self, command: &str, context: &CommandContext) -> Option<&dyn Command> {
+        if context.options.posix && !POSIX_BUILTINS.contains(&command) {
+            return None;
+        }
+        self.commands.get(command).map(Box::as_ref)
+    }
+
+    /// Parses `args` against the command's declared options plus `-?`/`--help`
+    fn parse_flags(cmd: &dyn Command, args: &[&str]) -> ShellResult<Flags> {
+        let mut specs = cmd.flags().to_vec();
+        specs.push(HELP_FLAG);
+
+        Flags::parse(args, &specs).map_err(|e| ShellError::Builtin {
+            message: format!("{}: {} (see '{} --help')", cmd.name(), e, cmd.name()),
+            code: 2,
+        })
+    }
+
     pub fn get_commands(&self) -> Vec<&'static str> {
         self.commands.keys().copied().collect()
     }
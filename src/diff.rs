@@ -0,0 +1,250 @@
+/// One step of turning one sequence into another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Keep the next item of both
+    Equal,
+    /// Drop the next item of the old sequence
+    Delete,
+    /// Take the next item of the new sequence
+    Insert,
+}
+
+/// A line of a hunk, with what happened to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    Context(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A run of changes with the unchanged lines around them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk<'a> {
+    /// The first old line, counting from 0, and how many there are
+    pub old: (usize, usize),
+    /// The first new line, counting from 0, and how many there are
+    pub new: (usize, usize),
+    pub lines: Vec<Line<'a>>,
+}
+
+impl Hunk<'_> {
+    /// The `@@ -1,3 +1,4 @@` line that starts the hunk in a unified diff
+    pub fn header(&self) -> String {
+        // An empty range names the line before it, as in diff(1)
+        let range = |(start, len): (usize, usize)| match len {
+            0 => format!("{},0", start),
+            1 => format!("{}", start + 1),
+            _ => format!("{},{}", start + 1, len),
+        };
+        format!("@@ -{} +{} @@", range(self.old), range(self.new))
+    }
+}
+
+/// The shortest edit script from `a` to `b`, found with Myers' algorithm
+pub fn diff<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Op> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut ops = vec![Op::Equal; prefix];
+    ops.extend(middle(
+        &a[prefix..a.len() - suffix],
+        &b[prefix..b.len() - suffix],
+    ));
+    ops.extend(vec![Op::Equal; suffix]);
+    ops
+}
+
+/// Myers' O(ND) search, keeping each round's furthest reaching paths to walk
+/// back along
+fn middle<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Op> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    // v[k + offset] is how far along `a` the furthest path on diagonal k got
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let at = |k: isize| (k + offset) as usize;
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max {
+        trace.push(v[at(-d - 1)..=at(d + 1)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let get = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            ops.push(if x == prev_x { Op::Insert } else { Op::Delete });
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    ops.reverse();
+    ops
+}
+
+/// The changes from `a` to `b` grouped into hunks with `context` unchanged
+/// lines on either side, as a unified diff shows them
+pub fn hunks<'a>(a: &[&'a str], b: &[&'a str], context: usize) -> Vec<Hunk<'a>> {
+    let ops = diff(a, b);
+
+    // Where in each file every op starts
+    let mut positions = Vec::with_capacity(ops.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for op in &ops {
+        positions.push((i, j));
+        match op {
+            Op::Equal => (i, j) = (i + 1, j + 1),
+            Op::Delete => i += 1,
+            Op::Insert => j += 1,
+        }
+    }
+    positions.push((i, j));
+
+    let changes: Vec<usize> = (0..ops.len()).filter(|&i| ops[i] != Op::Equal).collect();
+    let mut hunks = Vec::new();
+    let mut group = 0;
+    while group < changes.len() {
+        let start = changes[group].saturating_sub(context);
+        let mut last = changes[group];
+        group += 1;
+        // Changes close enough to share their context go in the same hunk
+        while group < changes.len() && changes[group] - last <= 2 * context + 1 {
+            last = changes[group];
+            group += 1;
+        }
+        let end = (last + 1 + context).min(ops.len());
+
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        let (mut i, mut j) = (old_start, new_start);
+        let lines = ops[start..end]
+            .iter()
+            .map(|op| match op {
+                Op::Equal => {
+                    (i, j) = (i + 1, j + 1);
+                    Line::Context(a[i - 1])
+                }
+                Op::Delete => {
+                    i += 1;
+                    Line::Removed(a[i - 1])
+                }
+                Op::Insert => {
+                    j += 1;
+                    Line::Added(b[j - 1])
+                }
+            })
+            .collect();
+        hunks.push(Hunk {
+            old: (old_start, old_end - old_start),
+            new: (new_start, new_end - new_start),
+            lines,
+        });
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Applies `ops` to `a`, taking inserted items from `b`
+    fn apply(ops: &[Op], a: &[char], b: &[char]) -> Vec<char> {
+        let (mut i, mut j, mut out) = (0, 0, Vec::new());
+        for op in ops {
+            match op {
+                Op::Equal => {
+                    assert_eq!(a[i], b[j]);
+                    out.push(a[i]);
+                    i += 1;
+                    j += 1;
+                }
+                Op::Delete => i += 1,
+                Op::Insert => {
+                    out.push(b[j]);
+                    j += 1;
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_diff_is_shortest() {
+        let cases = [
+            ("ABCABBA", "CBABAC", 5),
+            ("", "abc", 3),
+            ("abc", "", 3),
+            ("same", "same", 0),
+            ("kitten", "sitting", 5),
+        ];
+        for (a, b, edits) in cases {
+            let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+            let ops = diff(&a, &b);
+            assert_eq!(apply(&ops, &a, &b), b);
+            assert_eq!(ops.iter().filter(|&&op| op != Op::Equal).count(), edits);
+        }
+    }
+
+    #[test]
+    fn test_hunks() {
+        let a: Vec<String> = (1..=20).map(|i| format!("{}\n", i)).collect();
+        let mut b = a.clone();
+        b[1] = "two\n".to_string();
+        b.remove(16);
+        let a: Vec<&str> = a.iter().map(String::as_str).collect();
+        let b: Vec<&str> = b.iter().map(String::as_str).collect();
+
+        let hunks = hunks(&a, &b, 3);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].header(), "@@ -1,5 +1,5 @@");
+        assert_eq!(
+            hunks[0].lines[..3],
+            [
+                Line::Context("1\n"),
+                Line::Removed("2\n"),
+                Line::Added("two\n")
+            ]
+        );
+        assert_eq!(hunks[1].header(), "@@ -14,7 +14,6 @@");
+        assert_eq!(hunks[1].lines[3], Line::Removed("17\n"));
+
+        let added = super::hunks(&[], &["x\n"], 3);
+        assert_eq!(added[0].header(), "@@ -0,0 +1 @@");
+    }
+}
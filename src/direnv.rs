@@ -0,0 +1,259 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+/// Names of the files that hold per-directory variables, in order of preference
+const FILES: &[&str] = &[".envrc", ".env"];
+
+/// Finds the env file that applies in `dir`: the nearest one in it or any parent
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .flat_map(|dir| FILES.iter().map(move |name| dir.join(name)))
+        .find(|file| file.is_file())
+}
+
+/// Reads the variable assignments out of an env file.
+///
+/// Lines look like `NAME=value` or `export NAME=value`, with the value
+/// optionally quoted. `$NAME` and `${NAME}` in a value are replaced by the
+/// variable, as set earlier in the file or else in `variables`, unless the
+/// value is in single quotes. Files are not run as scripts, so anything else,
+/// such as direnv's `layout` or `source_up`, is skipped.
+pub fn parse(contents: &str, variables: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut assignments: Vec<(String, String)> = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            continue;
+        }
+        let value = match unquote(value.trim()) {
+            (value, false) => value.to_string(),
+            (value, true) => expand(value, |name| {
+                assignments
+                    .iter()
+                    .rev()
+                    .find(|(assigned, _)| assigned == name)
+                    .map(|(_, value)| value)
+                    .or_else(|| variables.get(name))
+                    .cloned()
+            }),
+        };
+        assignments.push((name.to_string(), value));
+    }
+    assignments
+}
+
+/// The value without its quotes, and whether variables in it are expanded
+fn unquote(value: &str) -> (&str, bool) {
+    for (quote, expands) in [('"', true), ('\'', false)] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return (inner, expands);
+        }
+    }
+    // An unquoted value ends where a trailing comment starts
+    match value.find(" #") {
+        Some(end) => (value[..end].trim_end(), true),
+        None => (value, true),
+    }
+}
+
+/// Replaces `$NAME` and `${NAME}` in `value`, unset ones with nothing; `\$`
+/// is a literal dollar sign
+fn expand(mut value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::new();
+    while let Some(start) = value.find(['$', '\\']) {
+        expanded.push_str(&value[..start]);
+        let rest = &value[start + 1..];
+        if value[start..].starts_with('\\') {
+            match rest.strip_prefix('$') {
+                Some(after) => {
+                    expanded.push('$');
+                    value = after;
+                }
+                None => {
+                    expanded.push('\\');
+                    value = rest;
+                }
+            }
+            continue;
+        }
+
+        let (name, after) = match rest.strip_prefix('{') {
+            Some(braced) => braced.split_once('}').unwrap_or(("", rest)),
+            None => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                rest.split_at(end)
+            }
+        };
+        if name.is_empty() {
+            expanded.push('$');
+            value = rest;
+        } else {
+            expanded.push_str(&lookup(name).unwrap_or_default());
+            value = after;
+        }
+    }
+    expanded.push_str(value);
+    expanded
+}
+
+/// Variables (or aliases) loaded from a file, with the values they replaced
+#[derive(Debug)]
+pub struct LoadedEnv {
+    pub file: PathBuf,
+    /// Each variable's value before loading, `None` if it was unset
+    saved: Vec<(String, Option<String>)>,
+    /// Names the file exported that were not exported before
+    newly_exported: Vec<String>,
+}
+
+impl LoadedEnv {
+    pub fn apply(
+        file: PathBuf,
+        assignments: Vec<(String, String)>,
+        variables: &mut HashMap<String, String>,
+    ) -> Self {
+        let mut saved: Vec<(String, Option<String>)> = Vec::new();
+        for (name, value) in assignments {
+            let previous = variables.insert(name.clone(), value);
+            // A file setting the same name twice must restore the first original value
+            if !saved.iter().any(|(saved_name, _)| *saved_name == name) {
+                saved.push((name, previous));
+            }
+        }
+        Self {
+            file,
+            saved,
+            newly_exported: Vec::new(),
+        }
+    }
+
+    /// Exports the names the file set, remembering which were not exported yet
+    pub fn export(&mut self, exported: &mut HashSet<String>) {
+        for (name, _) in &self.saved {
+            if exported.insert(name.clone()) {
+                self.newly_exported.push(name.clone());
+            }
+        }
+    }
+
+    /// Puts back the values the file replaced
+    pub fn revert(self, variables: &mut HashMap<String, String>) {
+        for (name, previous) in self.saved {
+            match previous {
+                Some(value) => variables.insert(name, value),
+                None => variables.remove(&name),
+            };
+        }
+    }
+
+    /// Stops exporting the names `export` started exporting
+    pub fn unexport(&self, exported: &mut HashSet<String>) {
+        for name in &self.newly_exported {
+            exported.remove(name);
+        }
+    }
+
+    /// Names the file set
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.saved.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_assignments() {
+        let contents = "# project settings\n\
+                        export DATABASE_URL=\"postgres://localhost/app\"\n\
+                        DEBUG=1 # verbose\n\
+                        GREETING='hello world'\n\
+                        layout python\n\
+                        1BAD=x\n";
+        assert_eq!(
+            parse(contents, &HashMap::new()),
+            vec![
+                (
+                    "DATABASE_URL".to_string(),
+                    "postgres://localhost/app".to_string()
+                ),
+                ("DEBUG".to_string(), "1".to_string()),
+                ("GREETING".to_string(), "hello world".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_expands_variables() {
+        let variables = HashMap::from([
+            ("PWD".to_string(), "/app".to_string()),
+            ("PATH".to_string(), "/usr/bin".to_string()),
+        ]);
+        let contents = "export PATH=$PWD/bin:$PATH\n\
+                        TOOLS=\"${PATH}:/opt\"\n\
+                        LITERAL='$PWD'\n\
+                        PRICE=\\$5$UNSET\n\
+                        ALONE=a$ b\n";
+        assert_eq!(
+            parse(contents, &variables),
+            vec![
+                ("PATH".to_string(), "/app/bin:/usr/bin".to_string()),
+                ("TOOLS".to_string(), "/app/bin:/usr/bin:/opt".to_string()),
+                ("LITERAL".to_string(), "$PWD".to_string()),
+                ("PRICE".to_string(), "$5".to_string()),
+                ("ALONE".to_string(), "a$ b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_prefers_nearest_envrc() {
+        let dir = TempDir::new().unwrap();
+        let nested = dir.path().join("src").join("bin");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        std::fs::write(dir.path().join(".env"), "A=1").unwrap();
+        assert_eq!(find(&nested), Some(dir.path().join(".env")));
+
+        std::fs::write(dir.path().join(".envrc"), "A=1").unwrap();
+        assert_eq!(find(&nested), Some(dir.path().join(".envrc")));
+    }
+
+    #[test]
+    fn test_revert_restores_previous_values() {
+        let mut variables = HashMap::from([("PATH".to_string(), "/usr/bin".to_string())]);
+        let mut exported = HashSet::from(["PATH".to_string()]);
+        let mut loaded = LoadedEnv::apply(
+            PathBuf::from("/app/.env"),
+            parse("PATH=/app/bin:$PATH\nTOKEN=a\nTOKEN=b", &variables),
+            &mut variables,
+        );
+        loaded.export(&mut exported);
+        assert_eq!(variables["PATH"], "/app/bin:/usr/bin");
+        assert_eq!(variables["TOKEN"], "b");
+        assert_eq!(loaded.names().collect::<Vec<_>>(), ["PATH", "TOKEN"]);
+        assert!(exported.contains("TOKEN"));
+
+        loaded.unexport(&mut exported);
+        loaded.revert(&mut variables);
+        assert_eq!(variables["PATH"], "/usr/bin");
+        assert!(!variables.contains_key("TOKEN"));
+        assert_eq!(exported, HashSet::from(["PATH".to_string()]));
+    }
+}
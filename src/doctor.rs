@@ -0,0 +1,393 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::{self, IsTerminal},
+    os::fd::AsRawFd,
+    path::Path,
+};
+
+use crate::{
+    color::{ColorDepth, Themes},
+    config::Config,
+    plugin::Plugins,
+    utils,
+};
+
+/// How a check turned out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// The result of one diagnostic, with a suggested fix when something is wrong
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn problem(
+        name: &'static str,
+        status: Status,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mark = match self.status {
+            Status::Ok => "ok",
+            Status::Warning => "warn",
+            Status::Error => "FAIL",
+        };
+        write!(f, "[{:>4}] {}: {}", mark, self.name, self.detail)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n       fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs every check against the current environment
+pub fn run(vars: &HashMap<String, String>, history: &Path) -> Vec<Check> {
+    let mut checks = vec![check_config()];
+    checks.push(check_history(history));
+    checks.extend(check_path(vars.get("PATH").map_or("", String::as_str)));
+    checks.push(check_terminal(vars));
+    checks.push(check_git());
+    checks.push(check_plugins());
+    checks
+}
+
+fn check_config() -> Check {
+    const NAME: &str = "config";
+    let Some(path) = Config::path() else {
+        return Check::ok(NAME, "no config directory, using defaults");
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Check::ok(
+                NAME,
+                format!("{} not found, using defaults", path.display()),
+            )
+        }
+        Err(e) => {
+            return Check::problem(
+                NAME,
+                Status::Error,
+                format!("{}: {}", path.display(), e),
+                "make the file readable by your user",
+            )
+        }
+    };
+    validate_config(&path, &contents)
+}
+
+fn validate_config(path: &Path, contents: &str) -> Check {
+    const NAME: &str = "config";
+    let config = match Config::parse(contents) {
+        Ok(config) => config,
+        Err(e) => {
+            let line = e
+                .span()
+                .map(|span| contents[..span.start].lines().count().max(1));
+            let fix = match line {
+                Some(line) => format!("correct line {}; until then hermit uses the defaults", line),
+                None => "correct the file; until then hermit uses the defaults".to_string(),
+            };
+            return Check::problem(
+                NAME,
+                Status::Error,
+                format!("{} is invalid: {}", path.display(), e.message()),
+                fix,
+            );
+        }
+    };
+
+    if let Some(theme) = &config.theme {
+        let themes = Themes::new(&config.themes);
+        if themes.get(theme).is_none() {
+            return Check::problem(
+                NAME,
+                Status::Warning,
+                format!("unknown theme '{}'", theme),
+                format!(
+                    "use one of: {}",
+                    themes.names().collect::<Vec<_>>().join(", ")
+                ),
+            );
+        }
+    }
+    Check::ok(NAME, format!("{} is valid", path.display()))
+}
+
+fn check_history(path: &Path) -> Check {
+    const NAME: &str = "history";
+    let file = match fs::OpenOptions::new().read(true).append(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let parent = path.parent().unwrap_or(Path::new("."));
+            return if parent.is_dir() {
+                Check::ok(NAME, format!("{} will be created on exit", path.display()))
+            } else {
+                Check::problem(
+                    NAME,
+                    Status::Error,
+                    format!("{} does not exist", parent.display()),
+                    "set $HOME to an existing directory",
+                )
+            };
+        }
+        Err(e) => {
+            return Check::problem(
+                NAME,
+                Status::Error,
+                format!("{}: {}", path.display(), e),
+                format!("chmod u+rw {}", path.display()),
+            )
+        }
+    };
+
+    // The line editor takes this lock while it saves
+    // SAFETY: flock on a descriptor owned by `file`
+    let locked = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0;
+    if locked {
+        return Check::problem(
+            NAME,
+            Status::Warning,
+            format!("{} is locked by another process", path.display()),
+            "close other hermit sessions that are saving history; the lock clears when they exit",
+        );
+    }
+    // SAFETY: as above
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) };
+
+    let contents = fs::read(path).unwrap_or_default();
+    if std::str::from_utf8(&contents).is_err() {
+        return Check::problem(
+            NAME,
+            Status::Warning,
+            format!("{} contains invalid UTF-8", path.display()),
+            "remove the damaged lines, or move the file away to start fresh",
+        );
+    }
+    let entries = contents
+        .split(|&b| b == b'\n')
+        .filter(|l| !l.is_empty())
+        .count();
+    Check::ok(
+        NAME,
+        format!(
+            "{} ({} entries, {})",
+            path.display(),
+            entries,
+            utils::format_size(contents.len() as u64)
+        ),
+    )
+}
+
+fn check_path(path: &str) -> Vec<Check> {
+    const NAME: &str = "PATH";
+    if path.is_empty() {
+        return vec![Check::problem(
+            NAME,
+            Status::Error,
+            "$PATH is empty, only builtins will run",
+            "export PATH=/usr/local/bin:/usr/bin:/bin",
+        )];
+    }
+
+    let mut checks = Vec::new();
+    let mut seen = HashSet::new();
+    for entry in path.split(':') {
+        if entry.is_empty() || !entry.starts_with('/') {
+            let shown = if entry.is_empty() { "(empty)" } else { entry };
+            checks.push(Check::problem(
+                NAME,
+                Status::Warning,
+                format!(
+                    "relative entry {} resolves against the current directory",
+                    shown
+                ),
+                "remove it, or use an absolute path",
+            ));
+        } else if !seen.insert(entry) {
+            checks.push(Check::problem(
+                NAME,
+                Status::Warning,
+                format!("{} appears more than once", entry),
+                "remove the later copy",
+            ));
+        } else if !Path::new(entry).is_dir() {
+            checks.push(Check::problem(
+                NAME,
+                Status::Warning,
+                format!("{} is not a directory", entry),
+                "remove it from $PATH or create it",
+            ));
+        }
+    }
+
+    if checks.is_empty() {
+        checks.push(Check::ok(NAME, format!("{} directories", seen.len())));
+    }
+    checks
+}
+
+fn check_terminal(vars: &HashMap<String, String>) -> Check {
+    const NAME: &str = "terminal";
+    if !io::stdout().is_terminal() {
+        return Check::ok(NAME, "stdout is not a terminal, colors and paging are off");
+    }
+    let Some(term) = vars.get("TERM").filter(|term| !term.is_empty()) else {
+        return Check::problem(
+            NAME,
+            Status::Warning,
+            "$TERM is not set, the line editor falls back to basic mode",
+            "export TERM=xterm-256color",
+        );
+    };
+    if term == "dumb" {
+        return Check::problem(
+            NAME,
+            Status::Warning,
+            "TERM=dumb disables line editing and colors",
+            "run hermit from a terminal emulator, or set TERM to its real type",
+        );
+    }
+
+    let depth = match ColorDepth::current() {
+        ColorDepth::Basic => "16 colors",
+        ColorDepth::Ansi256 => "256 colors",
+        ColorDepth::TrueColor => "truecolor",
+    };
+    Check::ok(
+        NAME,
+        format!(
+            "{}, {}, {}x{}",
+            term,
+            depth,
+            utils::term_width(),
+            utils::term_height()
+        ),
+    )
+}
+
+fn check_git() -> Check {
+    const NAME: &str = "git";
+    let version = git2::Version::get();
+    let (major, minor, patch) = version.libgit2_version();
+    let detail = format!(
+        "libgit2 {}.{}.{} (git2 {}{})",
+        major,
+        minor,
+        patch,
+        version.crate_version(),
+        if version.vendored() { ", vendored" } else { "" }
+    );
+    if !version.threads() {
+        return Check::problem(
+            NAME,
+            Status::Warning,
+            format!("{} without thread support", detail),
+            "rebuild libgit2 with threads, or the prompt's background status may misbehave",
+        );
+    }
+    Check::ok(NAME, detail)
+}
+
+fn check_plugins() -> Check {
+    const NAME: &str = "plugins";
+    let config = Config::load();
+    let plugins = Plugins::load(&config.plugins);
+    if plugins.errors().is_empty() {
+        return Check::ok(NAME, "all plugins load");
+    }
+    Check::problem(
+        NAME,
+        Status::Error,
+        plugins.errors().join("\n       "),
+        "check the directories under [plugins], and rebuild or remove plugins that fail",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_problems() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let existing = dir.path().display().to_string();
+        let path = format!("{0}:bin::{0}:/definitely/missing", existing);
+
+        let checks = check_path(&path);
+        let details: Vec<&str> = checks.iter().map(|c| c.detail.as_str()).collect();
+        assert_eq!(checks.len(), 4);
+        assert!(details[0].contains("relative entry bin"));
+        assert!(details[1].contains("(empty)"));
+        assert!(details[2].contains("more than once"));
+        assert!(details[3].contains("not a directory"));
+
+        let checks = check_path(&existing);
+        assert_eq!(checks[0].status, Status::Ok);
+        assert_eq!(check_path("")[0].status, Status::Error);
+    }
+
+    #[test]
+    fn test_config_problems() {
+        let path = Path::new("config.toml");
+        let check = validate_config(path, "color = \"auto\"\n\n[prompt\n");
+        assert_eq!(check.status, Status::Error);
+        assert!(check.fix.unwrap().contains("line 3"));
+
+        let check = validate_config(path, "theme = \"neon\"\n");
+        assert_eq!(check.status, Status::Warning);
+        assert!(check.fix.unwrap().contains("solarized"));
+
+        assert_eq!(
+            validate_config(path, "theme = \"mono\"\n").status,
+            Status::Ok
+        );
+    }
+
+    #[test]
+    fn test_history_checks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("history");
+        assert_eq!(check_history(&path).status, Status::Ok);
+
+        fs::write(&path, "ls\ncd /tmp\n").unwrap();
+        let check = check_history(&path);
+        assert_eq!(check.status, Status::Ok);
+        assert!(check.detail.contains("2 entries"));
+
+        assert_eq!(
+            check_history(&dir.path().join("missing").join("history")).status,
+            Status::Error
+        );
+    }
+}
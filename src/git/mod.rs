@@ -1,26 +1,36 @@
-use colored::Colorize;
 use git2::Repository;
+use std::{
+    mem,
+    path::Path,
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{color::Palette, prompt::Segment};
 
 pub struct GitInfo {
     repo: Repository,
+    status: RepoStatus,
+    pending: Option<Receiver<RepoStatus>>,
+    /// A refresh was asked for while one was running, which may have read
+    /// the tree before the change it is for
+    rerun: bool,
 }
 
-#[derive(Default)]
+#[derive(Debug, Default, Clone, Copy)]
 struct RepoStatus {
     modified: usize,
     staged: usize,
     untracked: usize,
 }
 
-impl GitInfo {
-    pub fn new(repo: Repository) -> Self {
-        Self { repo }
-    }
-
-    fn get_status(&self) -> RepoStatus {
+impl RepoStatus {
+    /// Walks the working tree; this is the slow part in big repositories
+    fn compute(repo: &Repository) -> Self {
         let mut status = RepoStatus::default();
 
-        if let Ok(statuses) = self.repo.statuses(None) {
+        if let Ok(statuses) = repo.statuses(None) {
             for entry in statuses.iter() {
                 match entry.status() {
                     s if s.is_wt_modified() => status.modified += 1,
@@ -33,17 +43,89 @@ impl GitInfo {
 
         status
     }
+}
+
+impl GitInfo {
+    pub fn new(repo: Repository) -> Self {
+        let mut info = Self {
+            repo,
+            status: RepoStatus::default(),
+            pending: None,
+            rerun: false,
+        };
+        info.refresh_status();
+        info
+    }
+
+    /// Finds the repository containing `path`, if any
+    pub fn discover(path: &Path) -> Option<Self> {
+        Repository::discover(path).ok().map(Self::new)
+    }
 
-    pub fn get_info(&self) -> String {
-        let branch = self
-            .repo
+    /// The checked out branch, or `HEAD` when it is detached
+    fn branch(&self) -> String {
+        self.repo
             .head()
             .ok()
             .and_then(|h| h.shorthand().map(|s| s.to_string()))
-            .unwrap_or_else(|| String::from("HEAD"));
+            .unwrap_or_else(|| String::from("HEAD"))
+    }
+
+    /// The branch and last known status as variables for script functions
+    pub fn vars(&self) -> [(&'static str, String); 4] {
+        [
+            ("HERMIT_GIT_BRANCH", self.branch()),
+            ("HERMIT_GIT_MODIFIED", self.status.modified.to_string()),
+            ("HERMIT_GIT_STAGED", self.status.staged.to_string()),
+            ("HERMIT_GIT_UNTRACKED", self.status.untracked.to_string()),
+        ]
+    }
 
+    /// Starts recomputing the working tree status on a background thread.
+    ///
+    /// The previous status stays available until the new one arrives. While a
+    /// refresh is still running another is only queued, to start when it
+    /// finishes, so commands entered in quick succession don't pile up threads
+    /// walking the same tree.
+    pub fn refresh_status(&mut self) {
+        if self.pending.is_some() {
+            self.rerun = true;
+            return;
+        }
+        let path = self.repo.path().to_path_buf();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            if let Ok(repo) = Repository::open(&path) {
+                sender.send(RepoStatus::compute(&repo)).ok();
+            }
+        });
+
+        self.pending = Some(receiver);
+    }
+
+    /// Returns the branch name and working tree status as prompt segments.
+    ///
+    /// Waits at most `budget` for a status refresh in flight, and for the one
+    /// queued behind it; if it is not ready the last known status is shown
+    /// instead of holding up the prompt.
+    pub fn segments(&mut self, budget: Duration, palette: &Palette) -> Vec<Segment> {
+        let deadline = Instant::now() + budget;
+        while let Some(pending) = &self.pending {
+            match pending.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(status) => self.status = status,
+                Err(mpsc::RecvTimeoutError::Disconnected) => {}
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+            }
+            self.pending = None;
+            if mem::take(&mut self.rerun) {
+                self.refresh_status();
+            }
+        }
+
+        let branch = self.branch();
         let mut status_parts = Vec::new();
-        let status = self.get_status();
+        let status = self.status;
 
         if status.modified > 0 {
             status_parts.push(format!(" !{}", status.modified));
@@ -55,12 +137,29 @@ impl GitInfo {
             status_parts.push(format!(" ?{}", status.untracked));
         }
 
-        let status_str = if !status_parts.is_empty() {
-            status_parts.join("").red().to_string()
-        } else {
-            String::new()
-        };
+        let mut segments = vec![Segment::colored(branch, palette.git_branch)];
+        if !status_parts.is_empty() {
+            segments.push(Segment::colored(status_parts.join(""), palette.git_status));
+        }
+        segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_refresh_while_pending_reruns() {
+        let dir = TempDir::new().unwrap();
+        let mut info = GitInfo::new(Repository::init(dir.path()).unwrap());
+        std::fs::write(dir.path().join("new.txt"), "").unwrap();
+        info.refresh_status();
+        assert!(info.rerun);
 
-        format!("{}{}", branch.green(), status_str)
+        info.segments(Duration::from_secs(10), &Palette::default());
+        assert!(info.pending.is_none() && !info.rerun);
+        assert_eq!(info.status.untracked, 1);
     }
 }
@@ -0,0 +1,345 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// One piece of a pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`
+    Run,
+    /// `[...]`, holding inclusive ranges, or `[!...]` when negated
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+/// Reads a pattern, in which `*` is any run of characters, `?` any one,
+/// `[...]` any one of a set such as `[a-z_]` or `[!0-9]`, and a backslash
+/// makes the next character literal. A `[` without its `]` is literal too.
+fn parse(pattern: &str) -> Vec<Item> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut items = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let item = match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                Item::Char(chars[i])
+            }
+            '*' => Item::Run,
+            '?' => Item::Any,
+            '[' => match class(&chars[i + 1..]) {
+                Some((item, len)) => {
+                    i += len;
+                    item
+                }
+                None => Item::Char('['),
+            },
+            c => Item::Char(c),
+        };
+        items.push(item);
+        i += 1;
+    }
+    items
+}
+
+/// Reads the set after a `[`, returning it and how many characters it took
+/// including the `]`
+fn class(chars: &[char]) -> Option<(Item, usize)> {
+    let negated = matches!(chars.first(), Some('!' | '^'));
+    let mut i = usize::from(negated);
+    let mut ranges = Vec::new();
+    // A `]` right at the start is part of the set
+    let mut first = true;
+    loop {
+        let mut c = *chars.get(i)?;
+        if c == ']' && !first {
+            return Some((Item::Class { negated, ranges }, i + 1));
+        }
+        if c == '\\' {
+            i += 1;
+            c = *chars.get(i)?;
+        }
+        first = false;
+        match (chars.get(i + 1), chars.get(i + 2)) {
+            (Some('-'), Some(&end)) if end != ']' => {
+                ranges.push((c, end));
+                i += 3;
+            }
+            _ => {
+                ranges.push((c, c));
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Whether `word` has any characters that make it a pattern
+pub fn has_wildcards(word: &str) -> bool {
+    parse(word)
+        .iter()
+        .any(|item| !matches!(item, Item::Char(_)))
+}
+
+/// Whether the whole of `text` matches `pattern`, as a `case` pattern does
+pub fn matches(pattern: &str, text: &str) -> bool {
+    matches_items(&parse(pattern), text)
+}
+
+/// Matches a file name against a pattern as [`parse`] reads it
+fn matches_items(items: &[Item], name: &str) -> bool {
+    let text: Vec<char> = name.chars().collect();
+    let one = |item: &Item, c: char| match item {
+        Item::Char(expected) => *expected == c,
+        Item::Any => true,
+        Item::Run => false,
+        Item::Class { negated, ranges } => {
+            ranges.iter().any(|&(low, high)| (low..=high).contains(&c)) != *negated
+        }
+    };
+
+    // Position to resume from after the last `*`, for backtracking
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        match items.get(p) {
+            Some(Item::Run) => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(item) if one(item, text[t]) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    items[p..].iter().all(|item| *item == Item::Run)
+}
+
+/// Puts a backslash before each character that would be special in a pattern
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The paths matching `pattern`, sorted, relative to `cwd` unless the pattern is absolute.
+///
+/// Wildcards match within one path component and `**` matches any number of
+/// directories. As in other shells, wildcards only match a leading `.` when
+/// the pattern spells it out.
+pub fn expand(pattern: &str, cwd: &Path) -> Vec<PathBuf> {
+    let (root, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (PathBuf::from("/"), rest),
+        None => (PathBuf::new(), pattern),
+    };
+    let components: Vec<&str> = rest.split('/').filter(|c| !c.is_empty()).collect();
+
+    let mut matches = Vec::new();
+    walk(cwd, root, &components, &mut matches);
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+/// Every file under `dir`, skipping hidden files and directories
+pub fn files_under(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => pending.push(entry.path()),
+                Ok(_) => files.push(entry.path()),
+                Err(_) => {}
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Matches `components` below `path`, which is what the caller sees and
+/// `cwd.join(path)` what is on disk
+fn walk(cwd: &Path, path: PathBuf, components: &[&str], matches: &mut Vec<PathBuf>) {
+    let Some((&first, rest)) = components.split_first() else {
+        if !path.as_os_str().is_empty() {
+            matches.push(path);
+        }
+        return;
+    };
+
+    if first == "**" {
+        // Zero directories, then each directory below in turn; at the end of
+        // the pattern it takes files too
+        walk(cwd, path.clone(), rest, matches);
+        for (name, is_dir) in entries(cwd, &path) {
+            if name.starts_with('.') {
+                continue;
+            }
+            if is_dir {
+                walk(cwd, path.join(name), components, matches);
+            } else if rest.is_empty() {
+                matches.push(path.join(name));
+            }
+        }
+        return;
+    }
+
+    let items = parse(first);
+    let literal: Option<String> = items
+        .iter()
+        .map(|item| match item {
+            Item::Char(c) => Some(*c),
+            _ => None,
+        })
+        .collect();
+    if let Some(literal) = literal {
+        let next = path.join(literal);
+        if cwd.join(&next).symlink_metadata().is_ok() {
+            walk(cwd, next, rest, matches);
+        }
+        return;
+    }
+
+    for (name, is_dir) in entries(cwd, &path) {
+        if name.starts_with('.') && items.first() != Some(&Item::Char('.')) {
+            continue;
+        }
+        if (rest.is_empty() || is_dir) && matches_items(&items, &name) {
+            walk(cwd, path.join(name), rest, matches);
+        }
+    }
+}
+
+/// The names in a directory, and whether each is one
+fn entries(cwd: &Path, path: &Path) -> impl Iterator<Item = (String, bool)> {
+    let dir = if path.as_os_str().is_empty() {
+        cwd.to_path_buf()
+    } else {
+        cwd.join(path)
+    };
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| {
+            let is_dir = entry.path().is_dir();
+            (entry.file_name().to_string_lossy().into_owned(), is_dir)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::TempDir;
+
+    fn tree() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        for file in [
+            "a.rs",
+            "b.txt",
+            ".hidden.rs",
+            "src/main.rs",
+            "src/x/y.rs",
+            "src/x/z.md",
+        ] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            File::create(path).unwrap();
+        }
+        dir
+    }
+
+    fn expand_names(pattern: &str, cwd: &Path) -> Vec<String> {
+        expand(pattern, cwd)
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = tree();
+        assert_eq!(expand_names("*.rs", dir.path()), ["a.rs"]);
+        assert_eq!(expand_names(".*.rs", dir.path()), [".hidden.rs"]);
+        assert_eq!(expand_names("src/*/?.rs", dir.path()), ["src/x/y.rs"]);
+        assert_eq!(
+            expand_names("**/*.rs", dir.path()),
+            ["a.rs", "src/main.rs", "src/x/y.rs"]
+        );
+        assert_eq!(
+            expand_names("src/**", dir.path()),
+            ["src", "src/main.rs", "src/x", "src/x/y.rs", "src/x/z.md"]
+        );
+        assert_eq!(expand_names("*.none", dir.path()), Vec::<String>::new());
+        assert_eq!(expand_names("[ab].*", dir.path()), ["a.rs", "b.txt"]);
+        assert_eq!(expand_names("sr\\c/main.rs", dir.path()), ["src/main.rs"]);
+
+        let absolute = format!("{}/*.txt", dir.path().display());
+        assert_eq!(
+            expand(&absolute, Path::new("/nonexistent")),
+            [dir.path().join("b.txt")]
+        );
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(matches("*", ""));
+        assert!(matches("a*b*c", "aXXbYYc"));
+        assert!(!matches("a*b*c", "aXXbYY"));
+        assert!(matches("test_?.txt", "test_1.txt"));
+        assert!(!matches("test_?.txt", "test_10.txt"));
+        assert!(matches("[abc]*", "beta"));
+        assert!(!matches("[!abc]*", "beta"));
+        assert!(matches("file[0-9][0-9]", "file42"));
+        assert!(matches("[]x]", "]"));
+        assert!(matches("a[", "a["));
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "x"));
+        assert!(has_wildcards("[ab]"));
+        assert!(!has_wildcards("\\*.rs"));
+        assert!(!has_wildcards("a["));
+        assert_eq!(escape("a*[b]?\\"), "a\\*\\[b]\\?\\\\");
+    }
+
+    #[test]
+    fn test_files_under() {
+        let dir = tree();
+        let files = files_under(&dir.path().join("src"));
+        assert_eq!(
+            files,
+            [
+                dir.path().join("src/main.rs"),
+                dir.path().join("src/x/y.rs"),
+                dir.path().join("src/x/z.md"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,411 @@
+pub mod server;
+mod tls;
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use url::Url;
+
+use tls::TlsStream;
+
+const MAX_REDIRECTS: usize = 10;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Request {
+    pub method: String,
+    pub url: Url,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+}
+
+pub struct Response {
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Body,
+}
+
+impl Response {
+    /// The first header called `name`, ignoring case as HTTP does
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Request {
+    pub fn new(method: &str, url: Url) -> Self {
+        Self {
+            method: method.to_uppercase(),
+            url,
+            headers: Vec::new(),
+            body: None,
+        }
+    }
+
+    /// Sends the request, following redirects the way browsers do
+    pub fn send(mut self) -> io::Result<Response> {
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self.send_once()?;
+            let location = match (response.status, response.header("location")) {
+                (301 | 302 | 303 | 307 | 308, Some(location)) => location,
+                _ => return Ok(response),
+            };
+            let url = self
+                .url
+                .join(location)
+                .map_err(|e| io::Error::other(format!("bad redirect: {}", e)))?;
+            // Headers such as Authorization or Cookie were meant for the
+            // first server only, so like curl they are not sent elsewhere
+            let origin = |url: &Url| {
+                (
+                    url.scheme().to_string(),
+                    url.host_str().map(str::to_string),
+                    url.port_or_known_default(),
+                )
+            };
+            if origin(&url) != origin(&self.url) {
+                self.headers.clear();
+            }
+            self.url = url;
+            // Only 307 and 308 promise the method and body survive the move
+            if response.status == 303
+                || (response.status != 307 && response.status != 308 && self.method == "POST")
+            {
+                self.method = "GET".to_string();
+                self.body = None;
+            }
+        }
+        Err(io::Error::other(format!(
+            "more than {} redirects",
+            MAX_REDIRECTS
+        )))
+    }
+
+    fn send_once(&self) -> io::Result<Response> {
+        let host = self
+            .url
+            .host_str()
+            .ok_or_else(|| io::Error::other(format!("{}: no host in URL", self.url)))?;
+        let port = self
+            .url
+            .port_or_known_default()
+            .ok_or_else(|| io::Error::other(format!("{}: unknown port", self.url)))?;
+
+        let socket = TcpStream::connect((host, port))?;
+        socket.set_read_timeout(Some(TIMEOUT))?;
+        socket.set_write_timeout(Some(TIMEOUT))?;
+        let mut connection = match self.url.scheme() {
+            "http" => Connection::Plain(socket),
+            "https" => Connection::Tls(TlsStream::connect(socket, host)?),
+            scheme => return Err(io::Error::other(format!("unsupported scheme '{}'", scheme))),
+        };
+
+        connection.write_all(&self.head(host))?;
+        if let Some(body) = &self.body {
+            connection.write_all(body)?;
+        }
+        connection.flush()?;
+
+        read_response(BufReader::new(connection), &self.method)
+    }
+
+    fn head(&self, host: &str) -> Vec<u8> {
+        let mut target = self.url.path().to_string();
+        if let Some(query) = self.url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+        let host = match self.url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+
+        let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", self.method, target, host);
+        let defaults = [
+            ("User-Agent", concat!("hermit/", env!("CARGO_PKG_VERSION"))),
+            ("Accept", "*/*"),
+        ];
+        for (name, value) in defaults {
+            if !self.has_header(name) {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if let Some(body) = &self.body {
+            head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        // One request per connection, with the body as the server has it, so
+        // reading the response never needs to decompress or wait on keep-alive
+        head.push_str("Accept-Encoding: identity\r\nConnection: close\r\n\r\n");
+        head.into_bytes()
+    }
+
+    fn has_header(&self, name: &str) -> bool {
+        self.headers
+            .iter()
+            .any(|(key, _)| key.eq_ignore_ascii_case(name))
+    }
+}
+
+enum Connection {
+    Plain(TcpStream),
+    Tls(TlsStream),
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.read(buf),
+            Connection::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.write(buf),
+            Connection::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Plain(stream) => stream.flush(),
+            Connection::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(invalid("connection closed early"));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn read_response<R: BufRead + 'static>(mut reader: R, method: &str) -> io::Result<Response> {
+    let status_line = read_line(&mut reader)?;
+    let mut parts = status_line.splitn(3, ' ');
+    let status = match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/") => {
+            code.parse().map_err(|_| invalid("malformed status line"))?
+        }
+        _ => return Err(invalid("not an HTTP response")),
+    };
+    let reason = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let reader: Box<dyn BufRead> = Box::new(reader);
+    let mut response = Response {
+        status,
+        reason,
+        headers,
+        body: Body::Empty,
+    };
+    let bodyless = method == "HEAD" || status == 204 || status == 304 || status / 100 == 1;
+    response.body = if bodyless {
+        Body::Empty
+    } else if response
+        .header("transfer-encoding")
+        .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"))
+    {
+        Body::Chunked {
+            reader,
+            remaining: 0,
+            done: false,
+        }
+    } else if let Some(length) = response.header("content-length") {
+        let length = length
+            .parse()
+            .map_err(|_| invalid("malformed Content-Length"))?;
+        Body::Sized(reader.take(length))
+    } else {
+        Body::Sized(reader.take(u64::MAX))
+    };
+    Ok(response)
+}
+
+/// A response body, decoded from however the server framed it
+pub enum Body {
+    Empty,
+    Sized(io::Take<Box<dyn BufRead>>),
+    Chunked {
+        reader: Box<dyn BufRead>,
+        remaining: usize,
+        done: bool,
+    },
+}
+
+impl Read for Body {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Body::Empty => Ok(0),
+            Body::Sized(reader) => reader.read(buf),
+            Body::Chunked {
+                reader,
+                remaining,
+                done,
+            } => {
+                if *done {
+                    return Ok(0);
+                }
+                if *remaining == 0 {
+                    let line = read_line(reader)?;
+                    // Chunk extensions after ';' carry nothing we need
+                    let size = line.split(';').next().unwrap_or_default().trim();
+                    *remaining = usize::from_str_radix(size, 16)
+                        .map_err(|_| invalid("malformed chunk size"))?;
+                    if *remaining == 0 {
+                        // Skip any trailers up to the blank line ending the body
+                        while !read_line(reader)?.is_empty() {}
+                        *done = true;
+                        return Ok(0);
+                    }
+                }
+
+                let want = buf.len().min(*remaining);
+                let n = reader.read(&mut buf[..want])?;
+                if n == 0 {
+                    return Err(invalid("connection closed in the middle of a chunk"));
+                }
+                *remaining -= n;
+                if *remaining == 0 {
+                    read_line(reader)?;
+                }
+                Ok(n)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Cursor, net::TcpListener, thread};
+
+    fn response(raw: &str) -> Response {
+        read_response(Cursor::new(raw.as_bytes().to_vec()), "GET").unwrap()
+    }
+
+    #[test]
+    fn test_chunked_body() {
+        let mut res = response(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+             5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\nX-Trailer: 1\r\n\r\n",
+        );
+        let mut body = String::new();
+        res.body.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "hello, world");
+    }
+
+    #[test]
+    fn test_sized_body() {
+        let mut res = response("HTTP/1.1 404 Not Found\r\ncontent-length: 3\r\n\r\nabcdef");
+        assert_eq!(res.status, 404);
+        assert_eq!(res.reason, "Not Found");
+        assert_eq!(res.header("Content-Length"), Some("3"));
+        let mut body = String::new();
+        res.body.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "abc");
+    }
+
+    #[test]
+    fn test_send_follows_redirect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let replies = [
+                "HTTP/1.1 302 Found\r\nLocation: /final\r\nContent-Length: 0\r\n\r\n",
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\ndone",
+            ];
+            let mut targets = Vec::new();
+            for reply in replies {
+                let (mut socket, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(socket.try_clone().unwrap());
+                targets.push(read_line(&mut reader).unwrap());
+                while !read_line(&mut reader).unwrap().is_empty() {}
+                socket.write_all(reply.as_bytes()).unwrap();
+            }
+            targets
+        });
+
+        let url = Url::parse(&format!("http://127.0.0.1:{}/start?q=1", port)).unwrap();
+        let mut res = Request::new("get", url).send().unwrap();
+        let mut body = String::new();
+        res.body.read_to_string(&mut body).unwrap();
+        assert_eq!(res.status, 200);
+        assert_eq!(body, "done");
+        assert_eq!(
+            server.join().unwrap(),
+            ["GET /start?q=1 HTTP/1.1", "GET /final HTTP/1.1"]
+        );
+    }
+
+    #[test]
+    fn test_redirect_elsewhere_drops_headers() {
+        // Answers one request with `reply`, returning the header lines it was sent
+        fn serve(listener: TcpListener, reply: String) -> thread::JoinHandle<Vec<String>> {
+            thread::spawn(move || {
+                let (mut socket, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(socket.try_clone().unwrap());
+                let mut lines = Vec::new();
+                loop {
+                    let line = read_line(&mut reader).unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    lines.push(line);
+                }
+                socket.write_all(reply.as_bytes()).unwrap();
+                lines
+            })
+        }
+        let first = TcpListener::bind("127.0.0.1:0").unwrap();
+        let second = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = |listener: &TcpListener| listener.local_addr().unwrap().port();
+        let (first_port, second_port) = (port(&first), port(&second));
+
+        let moved = format!(
+            "HTTP/1.1 302 Found\r\nLocation: http://127.0.0.1:{}/next\r\n\
+             Content-Length: 0\r\n\r\n",
+            second_port
+        );
+        let first = serve(first, moved);
+        let second = serve(second, "HTTP/1.1 200 OK\r\n\r\n".to_string());
+
+        let url = Url::parse(&format!("http://127.0.0.1:{}/", first_port)).unwrap();
+        let mut request = Request::new("GET", url);
+        request
+            .headers
+            .push(("Authorization".to_string(), "Bearer secret".to_string()));
+        assert_eq!(request.send().unwrap().status, 200);
+
+        let sent = |lines: &[String]| lines.iter().any(|line| line.starts_with("Authorization"));
+        assert!(sent(&first.join().unwrap()));
+        assert!(!sent(&second.join().unwrap()));
+    }
+}
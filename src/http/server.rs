@@ -0,0 +1,314 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, BufReader, Write},
+    net::TcpStream,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use super::{read_line, TIMEOUT};
+use crate::encoding;
+
+/// What was asked for and how it was answered, for the request log
+pub struct Served {
+    pub method: String,
+    pub target: String,
+    pub status: u16,
+}
+
+/// Answers one request on `stream` with a file from `root`, or a listing when
+/// the target is a directory without an index.html
+pub fn handle(stream: TcpStream, root: &Path) -> io::Result<Served> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request_line = read_line(&mut reader)?;
+    while !read_line(&mut reader)?.is_empty() {}
+
+    let mut parts = request_line.split(' ');
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or("/").to_string();
+    let mut served = Served {
+        method,
+        target,
+        status: 200,
+    };
+
+    let mut stream = stream;
+    let head_only = served.method == "HEAD";
+    if served.method != "GET" && !head_only {
+        served.status = 405;
+        respond_text(&mut stream, 405, "Method Not Allowed", head_only)?;
+        return Ok(served);
+    }
+
+    let path = served.target.split(['?', '#']).next().unwrap_or_default();
+    let Some(file) = resolve(root, path) else {
+        served.status = 403;
+        respond_text(&mut stream, 403, "Forbidden", head_only)?;
+        return Ok(served);
+    };
+
+    if file.is_dir() {
+        if !path.ends_with('/') {
+            // Relative links in the page only work from inside the directory
+            served.status = 301;
+            let head = format!(
+                "HTTP/1.1 301 Moved Permanently\r\nLocation: {}/\r\n\
+                 Content-Length: 0\r\nConnection: close\r\n\r\n",
+                path
+            );
+            stream.write_all(head.as_bytes())?;
+            return Ok(served);
+        }
+        let index = file.join("index.html");
+        if !index.is_file() {
+            let page = listing(&file, path)?;
+            write_head(
+                &mut stream,
+                200,
+                "OK",
+                "text/html; charset=utf-8",
+                page.len(),
+            )?;
+            if !head_only {
+                stream.write_all(page.as_bytes())?;
+            }
+            return Ok(served);
+        }
+        return send_file(&mut stream, &index, head_only, served);
+    }
+    if file.is_file() {
+        return send_file(&mut stream, &file, head_only, served);
+    }
+
+    served.status = 404;
+    respond_text(&mut stream, 404, "Not Found", head_only)?;
+    Ok(served)
+}
+
+/// The file a request path names below `root`, or None if it would leave it.
+///
+/// A `%2f` decodes to a slash inside one component, which would let it start
+/// a new absolute path or hide a `..`, so such components are refused.
+fn resolve(root: &Path, path: &str) -> Option<PathBuf> {
+    let mut file = root.to_path_buf();
+    for component in path.split('/') {
+        let component = encoding::url_decode(component);
+        match &component[..] {
+            b"" | b"." => {}
+            b".." => return None,
+            name if name.contains(&0) || name.contains(&b'/') => return None,
+            name => file.push(OsStr::from_bytes(name)),
+        }
+    }
+    // A symlink below the root may still lead out of it
+    match (file.canonicalize(), root.canonicalize()) {
+        (Ok(real), Ok(root)) if !real.starts_with(&root) => None,
+        _ => Some(file),
+    }
+}
+
+fn send_file(
+    stream: &mut TcpStream,
+    path: &Path,
+    head_only: bool,
+    mut served: Served,
+) -> io::Result<Served> {
+    let Ok(mut file) = File::open(path) else {
+        served.status = 403;
+        respond_text(stream, 403, "Forbidden", head_only)?;
+        return Ok(served);
+    };
+    let length = file.metadata()?.len();
+    write_head(stream, 200, "OK", content_type(path), length as usize)?;
+    if !head_only {
+        io::copy(&mut file, stream)?;
+    }
+    Ok(served)
+}
+
+fn respond_text(
+    stream: &mut TcpStream,
+    status: u16,
+    reason: &str,
+    head_only: bool,
+) -> io::Result<()> {
+    let body = format!("{} {}\n", status, reason);
+    write_head(
+        stream,
+        status,
+        reason,
+        "text/plain; charset=utf-8",
+        body.len(),
+    )?;
+    if !head_only {
+        stream.write_all(body.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_head(
+    stream: &mut TcpStream,
+    status: u16,
+    reason: &str,
+    content_type: &str,
+    length: usize,
+) -> io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status, reason, content_type, length
+    );
+    stream.write_all(head.as_bytes())
+}
+
+/// The type browsers need to show a file rather than download it
+fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("txt" | "md" | "rs" | "toml" | "log" | "csv") => "text/plain; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("pdf") => "application/pdf",
+        Some("wasm") => "application/wasm",
+        Some("mp4") => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+/// An HTML page linking to each entry of `dir`, directories first
+fn listing(dir: &Path, path: &str) -> io::Result<String> {
+    let mut entries: Vec<(bool, String)> = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| {
+            let is_dir = entry.path().is_dir();
+            (!is_dir, entry.file_name().to_string_lossy().into_owned())
+        })
+        .collect();
+    entries.sort();
+
+    let title = escape(path);
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{0}</title></head>\n\
+         <body>\n<h1>{0}</h1>\n<ul>\n",
+        title
+    );
+    if path != "/" {
+        page.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for (is_file, name) in entries {
+        let slash = if is_file { "" } else { "/" };
+        page.push_str(&format!(
+            "<li><a href=\"{}{}\">{}{}</a></li>\n",
+            encoding::url_encode(&name),
+            slash,
+            escape(&name),
+            slash
+        ));
+    }
+    page.push_str("</ul>\n</body>\n</html>\n");
+    Ok(page)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Request;
+    use std::{io::Read, net::TcpListener, thread};
+    use tempfile::TempDir;
+    use url::Url;
+
+    #[test]
+    fn test_resolve() {
+        let root = Path::new("/srv");
+        assert_eq!(resolve(root, "/"), Some(PathBuf::from("/srv")));
+        assert_eq!(
+            resolve(root, "/a%20b/./c.txt"),
+            Some(PathBuf::from("/srv/a b/c.txt"))
+        );
+        assert_eq!(resolve(root, "/../etc/passwd"), None);
+        assert_eq!(resolve(root, "/%2e%2e/etc/passwd"), None);
+        assert_eq!(resolve(root, "/%2fetc%2fhostname"), None);
+        assert_eq!(resolve(root, "/..%2f..%2f..%2fetc%2fhostname"), None);
+        assert_eq!(resolve(root, "/docs%2F..%2F..%2Fetc"), None);
+    }
+
+    #[test]
+    fn test_resolve_symlinks() {
+        let dir = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        fs::write(outside.path().join("secret"), "").unwrap();
+        fs::write(dir.path().join("inside"), "").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("out")).unwrap();
+        std::os::unix::fs::symlink("inside", dir.path().join("link")).unwrap();
+
+        assert_eq!(resolve(dir.path(), "/out/secret"), None);
+        assert_eq!(resolve(dir.path(), "/link"), Some(dir.path().join("link")));
+        assert_eq!(resolve(dir.path(), "/new"), Some(dir.path().join("new")));
+    }
+
+    #[test]
+    fn test_handle() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/a <b>.txt"), "hello").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let root = dir.path().to_path_buf();
+        let server = thread::spawn(move || {
+            let mut statuses = Vec::new();
+            for _ in 0..6 {
+                let (socket, _) = listener.accept().unwrap();
+                statuses.push(handle(socket, &root).unwrap().status);
+            }
+            statuses
+        });
+
+        let get = |path: &str| {
+            let url = Url::parse(&format!("http://127.0.0.1:{}{}", port, path)).unwrap();
+            let mut res = Request::new("GET", url).send().unwrap();
+            let mut body = String::new();
+            res.body.read_to_string(&mut body).unwrap();
+            (
+                res.status,
+                res.header("content-type").map(str::to_string),
+                body,
+            )
+        };
+
+        // The redirect to docs/ is followed, so this takes two requests
+        let (status, _, page) = get("/docs");
+        assert_eq!(status, 200);
+        assert!(page.contains("<a href=\"a%20%3Cb%3E.txt\">a &lt;b&gt;.txt</a>"));
+
+        let (status, kind, body) = get("/docs/a%20%3Cb%3E.txt");
+        assert_eq!(
+            (status, kind.as_deref()),
+            (200, Some("text/plain; charset=utf-8"))
+        );
+        assert_eq!(body, "hello");
+
+        assert_eq!(get("/missing").0, 404);
+        assert_eq!(get("/%2fetc%2fhostname").0, 403);
+        assert_eq!(get("/..%2f..%2f..%2fetc%2fhostname").0, 403);
+        assert_eq!(server.join().unwrap(), [301, 200, 200, 404, 403, 403]);
+    }
+}
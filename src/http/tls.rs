@@ -0,0 +1,126 @@
+use std::{
+    ffi::{CStr, CString},
+    io::{self, Read, Write},
+    net::TcpStream,
+    os::fd::AsRawFd,
+    ptr,
+};
+
+use openssl_sys as ssl;
+
+// Not in openssl-sys's bindings, but part of libssl's API since the beginning
+extern "C" {
+    fn SSL_set_fd(ssl: *mut ssl::SSL, fd: libc::c_int) -> libc::c_int;
+}
+
+/// A TLS connection through the system's OpenSSL, verifying the server's
+/// certificate against the default trust store and the host name.
+pub struct TlsStream {
+    ssl: *mut ssl::SSL,
+    ctx: *mut ssl::SSL_CTX,
+    // Owned so the descriptor OpenSSL writes to stays open
+    _socket: TcpStream,
+}
+
+impl TlsStream {
+    pub fn connect(socket: TcpStream, host: &str) -> io::Result<Self> {
+        ssl::init();
+        let name = CString::new(host).map_err(|_| io::Error::other("invalid host name"))?;
+
+        // SAFETY: each pointer is checked before use and owned by the stream
+        // from the moment it exists, so `Drop` frees it on every path
+        unsafe {
+            let ctx = ssl::SSL_CTX_new(ssl::TLS_client_method());
+            if ctx.is_null() {
+                return Err(io::Error::other("could not set up TLS"));
+            }
+            let mut stream = Self {
+                ssl: ptr::null_mut(),
+                ctx,
+                _socket: socket,
+            };
+            ssl::SSL_CTX_set_default_verify_paths(ctx);
+            ssl::SSL_CTX_set_verify(ctx, ssl::SSL_VERIFY_PEER, None);
+
+            stream.ssl = ssl::SSL_new(ctx);
+            if stream.ssl.is_null() {
+                return Err(io::Error::other("could not set up TLS"));
+            }
+            // Servers hosting several names need it to pick the certificate
+            ssl::SSL_set_tlsext_host_name(stream.ssl, name.as_ptr() as *mut _);
+            let param = ssl::SSL_get0_param(stream.ssl);
+            ssl::X509_VERIFY_PARAM_set1_host(param, name.as_ptr(), host.len());
+            SSL_set_fd(stream.ssl, stream._socket.as_raw_fd());
+
+            if ssl::SSL_connect(stream.ssl) != 1 {
+                let verify = ssl::SSL_get_verify_result(stream.ssl);
+                if verify != ssl::X509_V_OK as libc::c_long {
+                    let reason = CStr::from_ptr(ssl::X509_verify_cert_error_string(verify));
+                    return Err(io::Error::other(format!(
+                        "certificate verification failed: {}",
+                        reason.to_string_lossy()
+                    )));
+                }
+                return Err(io::Error::other("TLS handshake failed"));
+            }
+            Ok(stream)
+        }
+    }
+
+    /// Turns the result of `SSL_read` or `SSL_write` into an error
+    fn error(&self, ret: libc::c_int) -> io::Error {
+        // SAFETY: the connection is alive for as long as `self`
+        match unsafe { ssl::SSL_get_error(self.ssl, ret) } {
+            ssl::SSL_ERROR_SYSCALL => io::Error::last_os_error(),
+            code => io::Error::other(format!("TLS error {}", code)),
+        }
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(libc::c_int::MAX as usize) as libc::c_int;
+        // SAFETY: OpenSSL writes at most `len` bytes into `buf`
+        let ret = unsafe { ssl::SSL_read(self.ssl, buf.as_mut_ptr().cast(), len) };
+        if ret > 0 {
+            return Ok(ret as usize);
+        }
+        // SAFETY: as above
+        match unsafe { ssl::SSL_get_error(self.ssl, ret) } {
+            // A clean close, or a server that just hung up after the response
+            ssl::SSL_ERROR_ZERO_RETURN => Ok(0),
+            ssl::SSL_ERROR_SYSCALL if ret == 0 => Ok(0),
+            _ => Err(self.error(ret)),
+        }
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(libc::c_int::MAX as usize) as libc::c_int;
+        // SAFETY: OpenSSL reads at most `len` bytes from `buf`
+        let ret = unsafe { ssl::SSL_write(self.ssl, buf.as_ptr().cast(), len) };
+        if ret > 0 {
+            Ok(ret as usize)
+        } else {
+            Err(self.error(ret))
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for TlsStream {
+    fn drop(&mut self) {
+        // SAFETY: both pointers are owned here and freed exactly once
+        unsafe {
+            if !self.ssl.is_null() {
+                ssl::SSL_shutdown(self.ssl);
+                ssl::SSL_free(self.ssl);
+            }
+            ssl::SSL_CTX_free(self.ctx);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::io::{self, IsTerminal, Write};
+use termion::input::TermRead;
+
+/// Reads one line from stdin for a builtin, without its line ending.
+///
+/// The prompt goes to stderr, and only when a user is typing. Returns `None`
+/// at the end of input.
+pub fn line(prompt: Option<&str>) -> io::Result<Option<String>> {
+    if let Some(prompt) = prompt.filter(|_| io::stdin().is_terminal()) {
+        eprint!("{}", prompt);
+        io::stderr().flush()?;
+    }
+
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+/// Reads one line without echoing it, for passwords and other secrets.
+///
+/// On a terminal the prompt and the typing go through `/dev/tty` directly, so
+/// neither ends up in a session recording, and nothing is added to the history.
+/// Returns `None` at the end of input or when the user gives up with Ctrl-C or Ctrl-D.
+pub fn secret(prompt: &str) -> io::Result<Option<String>> {
+    if !io::stdin().is_terminal() {
+        return line(None);
+    }
+
+    let mut tty = termion::get_tty()?;
+    write!(tty, "{}", prompt)?;
+    tty.flush()?;
+    let secret = tty.try_clone()?.read_passwd(&mut tty);
+    // Enter was not echoed either
+    writeln!(tty)?;
+    secret
+}
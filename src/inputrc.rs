@@ -0,0 +1,460 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rustyline::{
+    config::{BellStyle, CompletionType, EditMode},
+    At, Cmd, KeyCode, KeyEvent, Modifiers, Movement, Word,
+};
+
+/// Settings and key bindings read from a readline init file
+#[derive(Debug, Default)]
+pub struct Inputrc {
+    pub edit_mode: Option<EditMode>,
+    pub bell_style: Option<BellStyle>,
+    pub completion_type: Option<CompletionType>,
+    pub completion_ignore_case: Option<bool>,
+    pub completion_query_items: Option<usize>,
+    pub keyseq_timeout: Option<u16>,
+    pub bindings: Vec<(KeyEvent, Cmd)>,
+    /// Lines that could not be used, with their file and line number
+    pub errors: Vec<String>,
+}
+
+impl Inputrc {
+    /// The init file readline would read: `$INPUTRC`, or `~/.inputrc`
+    pub fn path() -> Option<PathBuf> {
+        match env::var_os("INPUTRC") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => dirs::home_dir().map(|home| home.join(".inputrc")),
+        }
+    }
+
+    /// Reads an init file; a missing file gives no settings at all
+    pub fn load(path: &Path) -> Self {
+        let mut inputrc = Self::default();
+        inputrc.read(path, 0);
+        inputrc
+    }
+
+    /// Reads settings from the contents of `file`, which `$include` may nest.
+    ///
+    /// Only part of readline's language is understood: `set` for the variables
+    /// that have a counterpart in the line editor, bindings of single keys to
+    /// functions or macros, `$if`/`$else`/`$endif` and `$include`. Bindings of
+    /// multi-key sequences such as `"\C-x\C-e"` are reported and skipped.
+    fn parse(&mut self, contents: &str, file: &Path, depth: usize) {
+        // Whether each enclosing `$if` branch applies
+        let mut conditions: Vec<bool> = Vec::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| format!("{}:{}: {}", file.display(), number + 1, message);
+
+            if let Some(directive) = line.strip_prefix('$') {
+                let (name, arg) = directive
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((directive, ""));
+                let active = conditions.iter().all(|&c| c);
+                match name {
+                    "if" => conditions.push(active && self.test(arg.trim())),
+                    "else" => match conditions.last_mut() {
+                        Some(condition) => *condition = !*condition,
+                        None => self.errors.push(error("$else without $if".to_string())),
+                    },
+                    "endif" => {
+                        if conditions.pop().is_none() {
+                            self.errors.push(error("$endif without $if".to_string()));
+                        }
+                    }
+                    "include" if active => {
+                        let path = expand_home(arg.trim());
+                        if depth < 10 {
+                            self.read(&path, depth + 1);
+                        }
+                    }
+                    "include" => {}
+                    other => self
+                        .errors
+                        .push(error(format!("unknown directive ${}", other))),
+                }
+                continue;
+            }
+            if !conditions.iter().all(|&c| c) {
+                continue;
+            }
+
+            let result = match line.strip_prefix("set") {
+                Some(rest) if rest.starts_with(char::is_whitespace) => self.set(rest.trim()),
+                _ => self.bind(line),
+            };
+            if let Err(message) = result {
+                self.errors.push(error(message));
+            }
+        }
+    }
+
+    fn read(&mut self, path: &Path, depth: usize) {
+        match fs::read_to_string(path) {
+            Ok(contents) => self.parse(&contents, path, depth),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && depth == 0 => {}
+            Err(e) => self.errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /// Evaluates the test of an `$if`: `mode=`, `term=`, or an application name
+    fn test(&self, test: &str) -> bool {
+        if let Some(mode) = test.strip_prefix("mode=") {
+            let vi = matches!(self.edit_mode, Some(EditMode::Vi));
+            return mode == if vi { "vi" } else { "emacs" };
+        }
+        if let Some(term) = test.strip_prefix("term=") {
+            let current = env::var("TERM").unwrap_or_default();
+            let base = current.split('-').next().unwrap_or_default();
+            return current == term || base == term;
+        }
+        test.eq_ignore_ascii_case("hermit")
+    }
+
+    fn set(&mut self, setting: &str) -> Result<(), String> {
+        let (name, value) = setting
+            .split_once(char::is_whitespace)
+            .unwrap_or((setting, ""));
+        let value = value.trim();
+        // Readline turns a variable on for an empty value, "on" or "1"
+        let on = value.is_empty() || value.eq_ignore_ascii_case("on") || value == "1";
+
+        match name.to_ascii_lowercase().as_str() {
+            "editing-mode" => {
+                self.edit_mode = Some(match value {
+                    "vi" => EditMode::Vi,
+                    "emacs" => EditMode::Emacs,
+                    _ => return Err(format!("editing-mode: unknown mode '{}'", value)),
+                })
+            }
+            "bell-style" => {
+                self.bell_style = Some(match value {
+                    "none" => BellStyle::None,
+                    "visible" => BellStyle::Visible,
+                    "audible" => BellStyle::Audible,
+                    _ => return Err(format!("bell-style: unknown style '{}'", value)),
+                })
+            }
+            "completion-ignore-case" => self.completion_ignore_case = Some(on),
+            "show-all-if-ambiguous" | "show-all-if-unmodified" => {
+                self.completion_type = Some(if on {
+                    CompletionType::List
+                } else {
+                    CompletionType::Circular
+                })
+            }
+            "completion-query-items" => self.completion_query_items = Some(number(name, value)?),
+            "keyseq-timeout" => self.keyseq_timeout = Some(number(name, value)?),
+            // Like readline, ignore variables it does not know; most have no
+            // counterpart in the line editor
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Reads a `KEY: function` or `KEY: "macro"` binding.
+    ///
+    /// Bindings the line editor cannot carry out, of multi-key sequences or to
+    /// functions it lacks, are skipped without complaint.
+    fn bind(&mut self, line: &str) -> Result<(), String> {
+        let (key, action) =
+            split_binding(line).ok_or_else(|| format!("cannot parse '{}'", line))?;
+        let key = match key.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+            Some(sequence) => parse_sequence(sequence)?,
+            None => parse_key_name(key).map(Some)?,
+        };
+
+        let action = action.trim();
+        let cmd = match action.strip_prefix('"').and_then(|a| a.strip_suffix('"')) {
+            Some(text) => Some(Cmd::Insert(1, unescape(text)?.into_iter().collect())),
+            None => command(action),
+        };
+        if let (Some(key), Some(cmd)) = (key, cmd) {
+            self.bindings.push((key, cmd));
+        }
+        Ok(())
+    }
+}
+
+fn number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{}: expected a number, got '{}'", name, value))
+}
+
+/// Splits a binding at the `:` that ends the key, which may itself be a quoted `:`
+fn split_binding(line: &str) -> Option<(&str, &str)> {
+    if line.starts_with('"') {
+        let mut escaped = false;
+        for (i, c) in line.char_indices().skip(1) {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    let rest = line[i + 1..].trim_start().strip_prefix(':')?;
+                    return Some((&line[..=i], rest));
+                }
+                _ => escaped = false,
+            }
+        }
+        return None;
+    }
+    line.split_once(':')
+}
+
+/// Turns a quoted key sequence into the single key it stands for, if it is one.
+///
+/// Handles `\C-`, `\M-`, the usual backslash escapes, and the escape sequences
+/// terminals send for arrows, Home, End and the editing keys.
+fn parse_sequence(sequence: &str) -> Result<Option<KeyEvent>, String> {
+    let keys = unescape(sequence)?;
+    let key = match keys.as_slice() {
+        [c] => Some(KeyEvent::new(*c, Modifiers::NONE)),
+        ['\x1b', '[' | 'O', rest @ ..] => terminal_key(rest),
+        ['\x1b', c] => Some(KeyEvent::new(*c, Modifiers::ALT)),
+        _ => None,
+    };
+    Ok(key)
+}
+
+fn terminal_key(sequence: &[char]) -> Option<KeyEvent> {
+    let code = match sequence.iter().collect::<String>().as_str() {
+        "A" => KeyCode::Up,
+        "B" => KeyCode::Down,
+        "C" => KeyCode::Right,
+        "D" => KeyCode::Left,
+        "H" | "1~" | "7~" => KeyCode::Home,
+        "F" | "4~" | "8~" => KeyCode::End,
+        "2~" => KeyCode::Insert,
+        "3~" => KeyCode::Delete,
+        "5~" => KeyCode::PageUp,
+        "6~" => KeyCode::PageDown,
+        "Z" => KeyCode::BackTab,
+        _ => return None,
+    };
+    Some(KeyEvent(code, Modifiers::NONE))
+}
+
+/// Reads a key written out by name, such as `Control-a`, `M-b` or `Rubout`
+fn parse_key_name(name: &str) -> Result<KeyEvent, String> {
+    let mut modifiers = Modifiers::NONE;
+    let mut rest = name.trim();
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        if let Some(prefix) = ["control-", "c-"].iter().find(|p| lower.starts_with(*p)) {
+            modifiers |= Modifiers::CTRL;
+            rest = &rest[prefix.len()..];
+        } else if let Some(prefix) = ["meta-", "m-"].iter().find(|p| lower.starts_with(*p)) {
+            modifiers |= Modifiers::ALT;
+            rest = &rest[prefix.len()..];
+        } else {
+            break;
+        }
+    }
+
+    let c = match rest.to_ascii_lowercase().as_str() {
+        "rubout" | "del" => '\x7f',
+        "escape" | "esc" => '\x1b',
+        "return" | "ret" => '\r',
+        "newline" | "lfd" => '\n',
+        "space" | "spc" => ' ',
+        "tab" => '\t',
+        _ => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(format!("unknown key name '{}'", name)),
+            }
+        }
+    };
+    Ok(KeyEvent::new(c, modifiers))
+}
+
+/// Resolves the escapes in a quoted key sequence or macro
+fn unescape(text: &str) -> Result<Vec<char>, String> {
+    let mut chars = text.chars().peekable();
+    let mut keys = Vec::new();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            keys.push(c);
+            continue;
+        }
+        let escaped = chars.next().ok_or("trailing backslash")?;
+        let key = match escaped {
+            'C' | 'M' if chars.peek() == Some(&'-') => {
+                chars.next();
+                let target = match chars.next() {
+                    Some('\\') => match chars.next() {
+                        Some('e') => '\x1b',
+                        Some(c) => c,
+                        None => return Err("trailing backslash".to_string()),
+                    },
+                    Some(c) => c,
+                    None => return Err(format!("\\{}- without a key", escaped)),
+                };
+                if escaped == 'M' {
+                    keys.push('\x1b');
+                    target
+                } else if target == '?' {
+                    '\x7f'
+                } else {
+                    char::from(target.to_ascii_uppercase() as u8 & 0x1f)
+                }
+            }
+            'e' => '\x1b',
+            'a' => '\x07',
+            'b' => '\x08',
+            'd' => '\x7f',
+            'f' => '\x0c',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'v' => '\x0b',
+            other => other,
+        };
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+/// The line editor's command for a readline function name
+fn command(function: &str) -> Option<Cmd> {
+    let emacs_word = Word::Emacs;
+    let cmd = match function.to_ascii_lowercase().as_str() {
+        "beginning-of-line" => Cmd::Move(Movement::BeginningOfLine),
+        "end-of-line" => Cmd::Move(Movement::EndOfLine),
+        "forward-char" => Cmd::Move(Movement::ForwardChar(1)),
+        "backward-char" => Cmd::Move(Movement::BackwardChar(1)),
+        "forward-word" => Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, emacs_word)),
+        "backward-word" => Cmd::Move(Movement::BackwardWord(1, emacs_word)),
+        "previous-history" => Cmd::PreviousHistory,
+        "next-history" => Cmd::NextHistory,
+        "beginning-of-history" => Cmd::BeginningOfHistory,
+        "end-of-history" => Cmd::EndOfHistory,
+        "history-search-backward" | "history-substring-search-backward" => {
+            Cmd::HistorySearchBackward
+        }
+        "history-search-forward" | "history-substring-search-forward" => Cmd::HistorySearchForward,
+        "reverse-search-history" => Cmd::ReverseSearchHistory,
+        "forward-search-history" => Cmd::ForwardSearchHistory,
+        "kill-line" => Cmd::Kill(Movement::EndOfLine),
+        "backward-kill-line" | "unix-line-discard" => Cmd::Kill(Movement::BeginningOfLine),
+        "kill-whole-line" => Cmd::Kill(Movement::WholeLine),
+        "kill-word" => Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, emacs_word)),
+        "backward-kill-word" => Cmd::Kill(Movement::BackwardWord(1, emacs_word)),
+        "unix-word-rubout" => Cmd::Kill(Movement::BackwardWord(1, Word::Big)),
+        "delete-char" => Cmd::Kill(Movement::ForwardChar(1)),
+        "backward-delete-char" => Cmd::Kill(Movement::BackwardChar(1)),
+        "yank" => Cmd::Yank(1, rustyline::Anchor::Before),
+        "yank-pop" => Cmd::YankPop,
+        "transpose-chars" => Cmd::TransposeChars,
+        "transpose-words" => Cmd::TransposeWords(1),
+        "upcase-word" => Cmd::UpcaseWord,
+        "downcase-word" => Cmd::DowncaseWord,
+        "capitalize-word" => Cmd::CapitalizeWord,
+        "clear-screen" => Cmd::ClearScreen,
+        "redraw-current-line" => Cmd::Repaint,
+        "complete" | "menu-complete" => Cmd::Complete,
+        "menu-complete-backward" => Cmd::CompleteBackward,
+        "undo" => Cmd::Undo(1),
+        "accept-line" => Cmd::AcceptLine,
+        "abort" => Cmd::Abort,
+        "quoted-insert" => Cmd::QuotedInsert,
+        "tab-insert" => Cmd::Insert(1, "\t".to_string()),
+        _ => return None,
+    };
+    Some(cmd)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Inputrc {
+        let mut inputrc = Inputrc::default();
+        inputrc.parse(contents, Path::new("inputrc"), 0);
+        inputrc
+    }
+
+    #[test]
+    fn test_settings() {
+        let inputrc = parse(
+            "# comment\n\
+             set editing-mode vi\n\
+             set completion-ignore-case on\n\
+             set bell-style none\n\
+             set show-all-if-ambiguous on\n\
+             set mark-symlinked-directories on\n\
+             set bell-style loud\n",
+        );
+        assert_eq!(inputrc.edit_mode, Some(EditMode::Vi));
+        assert_eq!(inputrc.completion_ignore_case, Some(true));
+        assert_eq!(inputrc.bell_style, Some(BellStyle::None));
+        assert_eq!(inputrc.completion_type, Some(CompletionType::List));
+        assert_eq!(inputrc.errors.len(), 1);
+        assert!(inputrc.errors[0].starts_with("inputrc:7:"));
+    }
+
+    #[test]
+    fn test_bindings() {
+        let inputrc = parse(
+            "\"\\e[A\": history-search-backward\n\
+             \"\\C-p\": previous-history\n\
+             Meta-b: backward-word\n\
+             \"\\M-l\": \"ls -la\"\n\
+             Control-x: unknown-function\n\
+             \"\\C-x\\C-e\": edit-and-execute-command\n\
+             \"\\C-o: oops\n",
+        );
+        assert_eq!(
+            inputrc.bindings,
+            vec![
+                (
+                    KeyEvent(KeyCode::Up, Modifiers::NONE),
+                    Cmd::HistorySearchBackward
+                ),
+                (KeyEvent::ctrl('P'), Cmd::PreviousHistory),
+                (
+                    KeyEvent::alt('b'),
+                    Cmd::Move(Movement::BackwardWord(1, Word::Emacs))
+                ),
+                (KeyEvent::alt('l'), Cmd::Insert(1, "ls -la".to_string())),
+            ]
+        );
+        assert_eq!(inputrc.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let inputrc = parse(
+            "$if Bash\n\
+             set editing-mode vi\n\
+             $else\n\
+             set bell-style visible\n\
+             $endif\n\
+             $if mode=emacs\n\
+             \"\\C-n\": next-history\n\
+             $endif\n",
+        );
+        assert_eq!(inputrc.edit_mode, None);
+        assert_eq!(inputrc.bell_style, Some(BellStyle::Visible));
+        assert_eq!(inputrc.bindings.len(), 1);
+        assert!(inputrc.errors.is_empty());
+    }
+}
@@ -0,0 +1,305 @@
+use std::{collections::HashMap, env, fmt, fs, sync::OnceLock};
+
+use crate::config::Config;
+
+/// A message the shell prints in the user's language.
+///
+/// The English text is built in. Other languages come from the catalogs
+/// compiled into the shell, or from `~/.config/hermit/locale/<lang>.toml`
+/// files mapping each message's key to its translation, which take precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    Error,
+    DidYouMean,
+    CommandNotFound,
+    PermissionDenied,
+    ParseError,
+    NotRun,
+    NeedsConfirmation,
+    ConfirmRun,
+    NotTrusted,
+    LoadUntrusted,
+    TrustNotSaved,
+    ConfigReloading,
+    Usage,
+    Options,
+    Goodbye,
+}
+
+impl Message {
+    pub const ALL: &'static [Message] = &[
+        Self::Error,
+        Self::DidYouMean,
+        Self::CommandNotFound,
+        Self::PermissionDenied,
+        Self::ParseError,
+        Self::NotRun,
+        Self::NeedsConfirmation,
+        Self::ConfirmRun,
+        Self::NotTrusted,
+        Self::LoadUntrusted,
+        Self::TrustNotSaved,
+        Self::ConfigReloading,
+        Self::Usage,
+        Self::Options,
+        Self::Goodbye,
+    ];
+
+    /// The name translations use for this message
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::DidYouMean => "did_you_mean",
+            Self::CommandNotFound => "command_not_found",
+            Self::PermissionDenied => "permission_denied",
+            Self::ParseError => "parse_error",
+            Self::NotRun => "not_run",
+            Self::NeedsConfirmation => "needs_confirmation",
+            Self::ConfirmRun => "confirm_run",
+            Self::NotTrusted => "not_trusted",
+            Self::LoadUntrusted => "load_untrusted",
+            Self::TrustNotSaved => "trust_not_saved",
+            Self::ConfigReloading => "config_reloading",
+            Self::Usage => "usage",
+            Self::Options => "options",
+            Self::Goodbye => "goodbye",
+        }
+    }
+
+    /// The English text, where each `{}` is filled in by [`Message::format`]
+    fn english(self) -> &'static str {
+        match self {
+            Self::Error => "Error:",
+            Self::DidYouMean => "Did you mean '{}'?",
+            Self::CommandNotFound => "command not found: {}",
+            Self::PermissionDenied => "permission denied: {}",
+            Self::ParseError => "parse error: {}",
+            Self::NotRun => "hermit: {}: not run",
+            Self::NeedsConfirmation => "hermit: '{}' needs confirmation",
+            Self::ConfirmRun => "'{}' matches '{}'. Run it? [y/N/a] ",
+            Self::NotTrusted => "hermit: {} is not trusted, not loading it",
+            Self::LoadUntrusted => "hermit: {} is not trusted. Load it? [y/N] ",
+            Self::TrustNotSaved => "hermit: could not save trust: {}",
+            Self::ConfigReloading => "hermit: config changed, reloading",
+            Self::Usage => "Usage:",
+            Self::Options => "Options:",
+            Self::Goodbye => "Goodbye!",
+        }
+    }
+
+    /// The text in the user's language
+    pub fn text(self) -> &'static str {
+        Catalog::current().text(self)
+    }
+
+    /// The text in the user's language with each `{}` replaced by the next argument
+    pub fn format(self, args: &[&dyn fmt::Display]) -> String {
+        fill(self.text(), args)
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.text())
+    }
+}
+
+/// Translations compiled into the shell, by language code
+const BUILTIN: &[(&str, &[(&str, &str)])] = &[(
+    "de",
+    &[
+        ("error", "Fehler:"),
+        ("did_you_mean", "Meinten Sie '{}'?"),
+        ("command_not_found", "Befehl nicht gefunden: {}"),
+        ("permission_denied", "Zugriff verweigert: {}"),
+        ("parse_error", "Syntaxfehler: {}"),
+        ("not_run", "hermit: {}: nicht ausgeführt"),
+        ("needs_confirmation", "hermit: '{}' muss bestätigt werden"),
+        ("confirm_run", "'{}' passt auf '{}'. Ausführen? [y/N/a] "),
+        (
+            "not_trusted",
+            "hermit: {} ist nicht vertrauenswürdig und wird nicht geladen",
+        ),
+        (
+            "load_untrusted",
+            "hermit: {} ist nicht vertrauenswürdig. Laden? [y/N] ",
+        ),
+        (
+            "trust_not_saved",
+            "hermit: Vertrauen konnte nicht gespeichert werden: {}",
+        ),
+        (
+            "config_reloading",
+            "hermit: Konfiguration geändert, wird neu geladen",
+        ),
+        ("usage", "Aufruf:"),
+        ("options", "Optionen:"),
+        ("goodbye", "Auf Wiedersehen!"),
+    ],
+)];
+
+/// The translations for one language; messages it lacks are shown in English
+#[derive(Debug, Default)]
+struct Catalog {
+    messages: HashMap<&'static str, String>,
+}
+
+impl Catalog {
+    /// The catalog for `$LC_ALL`, `$LC_MESSAGES` or `$LANG`, picked once
+    fn current() -> &'static Self {
+        static CATALOG: OnceLock<Catalog> = OnceLock::new();
+        CATALOG.get_or_init(|| {
+            let var = |name: &str| env::var(name).ok();
+            match language(var) {
+                Some((language, region)) => Self::load(&language, region.as_deref()),
+                None => Self::default(),
+            }
+        })
+    }
+
+    /// Builds the catalog for a language, preferring the most specific source.
+    ///
+    /// For `de_AT` that is `locale/de_AT.toml`, then `locale/de.toml`, then the
+    /// built-in `de` catalog.
+    fn load(language: &str, region: Option<&str>) -> Self {
+        let mut names = vec![language.to_string()];
+        if let Some(region) = region {
+            names.insert(0, format!("{}_{}", language, region));
+        }
+
+        let mut catalog = Self::default();
+        for (name, messages) in BUILTIN {
+            if names.iter().any(|n| n == name) {
+                catalog.extend(messages.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+            }
+        }
+        for name in names.iter().rev() {
+            let Some(path) = Config::file(&format!("locale/{}.toml", name)) else {
+                continue;
+            };
+            let Ok(contents) = fs::read_to_string(&path) else {
+                continue;
+            };
+            match toml::from_str::<HashMap<String, String>>(&contents) {
+                Ok(messages) => catalog.extend(messages),
+                Err(e) => eprintln!("hermit: ignoring {}: {}", path.display(), e),
+            }
+        }
+        catalog
+    }
+
+    /// Adds translations, skipping unknown keys and texts whose `{}` do not match the English
+    fn extend(&mut self, messages: impl IntoIterator<Item = (String, String)>) {
+        for (key, text) in messages {
+            let Some(message) = Message::ALL.iter().find(|m| m.key() == key) else {
+                continue;
+            };
+            if text.matches("{}").count() == message.english().matches("{}").count() {
+                self.messages.insert(message.key(), text);
+            }
+        }
+    }
+
+    fn text(&self, message: Message) -> &str {
+        self.messages
+            .get(message.key())
+            .map_or(message.english(), String::as_str)
+    }
+}
+
+/// The language and region of the first locale variable that is set, like `de` and `AT`.
+///
+/// `C` and `POSIX` mean untranslated messages.
+fn language(var: impl Fn(&str) -> Option<String>) -> Option<(String, Option<String>)> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| var(name))
+        .find(|value| !value.is_empty())?;
+    let locale = locale.split(['.', '@']).next().unwrap_or_default();
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return None;
+    }
+    let (language, region) = match locale.split_once('_') {
+        Some((language, region)) => (language, Some(region.to_string())),
+        None => (locale, None),
+    };
+    Some((language.to_string(), region))
+}
+
+/// Replaces each `{}` in `text` with the next argument
+fn fill(text: &str, args: &[&dyn fmt::Display]) -> String {
+    let mut parts = text.split("{}");
+    let mut filled = parts.next().unwrap_or_default().to_string();
+    let mut args = args.iter();
+    for part in parts {
+        if let Some(arg) = args.next() {
+            filled.push_str(&arg.to_string());
+        }
+        filled.push_str(part);
+    }
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_from_variables() {
+        let vars = |pairs: &'static [(&str, &str)]| {
+            move |name: &str| {
+                pairs
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        assert_eq!(
+            language(vars(&[("LANG", "de_AT.UTF-8")])),
+            Some(("de".to_string(), Some("AT".to_string())))
+        );
+        assert_eq!(
+            language(vars(&[("LANG", "de_DE.UTF-8"), ("LC_ALL", "fr")])),
+            Some(("fr".to_string(), None))
+        );
+        assert_eq!(language(vars(&[("LC_ALL", ""), ("LANG", "C.UTF-8")])), None);
+        assert_eq!(language(vars(&[])), None);
+    }
+
+    #[test]
+    fn test_catalog_falls_back_to_english() {
+        let catalog = Catalog::load("de", None);
+        assert_eq!(catalog.text(Message::Goodbye), "Auf Wiedersehen!");
+
+        let mut catalog = Catalog::default();
+        catalog.extend([
+            ("goodbye".to_string(), "Au revoir !".to_string()),
+            (
+                "did_you_mean".to_string(),
+                "Vouliez-vous dire ?".to_string(),
+            ),
+            ("unknown".to_string(), "?".to_string()),
+        ]);
+        assert_eq!(catalog.text(Message::Goodbye), "Au revoir !");
+        assert_eq!(catalog.text(Message::DidYouMean), "Did you mean '{}'?");
+        assert_eq!(catalog.text(Message::Usage), "Usage:");
+    }
+
+    #[test]
+    fn test_builtin_catalogs_match_placeholders() {
+        for (name, messages) in BUILTIN {
+            let mut catalog = Catalog::default();
+            catalog.extend(messages.iter().map(|(k, v)| (k.to_string(), v.to_string())));
+            assert_eq!(catalog.messages.len(), Message::ALL.len(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(
+            fill("'{}' matches '{}'.", &[&"rm -rf /", &"rm -rf *"]),
+            "'rm -rf /' matches 'rm -rf *'."
+        );
+        assert_eq!(fill("no placeholders", &[&1]), "no placeholders");
+    }
+}
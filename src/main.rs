@@ -1,22 +1,152 @@
-use std::error::Error;
+use std::{
+    env,
+    error::Error,
+    io::{self, Write},
+    path::PathBuf,
+};
 
+mod cloud;
+mod color;
 mod commands;
 mod config;
+mod confirm;
 mod core;
+mod diff;
+mod digest;
+mod direnv;
+mod doctor;
+mod encoding;
 mod git;
+mod glob;
+mod http;
+mod input;
+mod inputrc;
+mod locale;
+mod pager;
+mod platform;
+mod plugin;
+mod project;
+mod prompt;
+mod scripts;
 mod shell;
+mod signals;
+mod system;
+mod trust;
 mod utils;
 
+use crate::{core::options::ShellOptions, locale::Message};
 use shell::Shell;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let mut shell = Shell::new().map_err(|e| format!("Failed to initialize shell: {}", e))?;
+    if env::args().nth(1).as_deref() == Some("doctor") {
+        let vars = env::vars().collect();
+        let checks = doctor::run(&vars, &Shell::get_history_file_path());
+        for check in &checks {
+            println!("{}", check);
+        }
+        let failed = checks.iter().any(|c| c.status == doctor::Status::Error);
+        std::process::exit(i32::from(failed));
+    }
+
+    if env::args().nth(1).as_deref() == Some("exec") {
+        let args: Vec<String> = env::args().skip(2).collect();
+        let Some((name, args)) = args.split_first() else {
+            eprintln!("hermit: usage: hermit exec BUILTIN [ARG...]");
+            std::process::exit(2);
+        };
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let code = match Shell::exec_builtin(name, &args) {
+            Ok(Some(status)) => status.code(),
+            Ok(None) => {
+                eprintln!("hermit: {}: not a builtin", name);
+                127
+            }
+            Err(e) => {
+                eprintln!("hermit: {}", e);
+                e.exit_code()
+            }
+        };
+        io::stdout().flush().ok();
+        std::process::exit(code);
+    }
 
-    if let Err(e) = shell.run() {
-        eprintln!("Shell error: {}", e);
-        std::process::exit(1);
+    let mut options = ShellOptions {
+        posix: env::var_os("POSIXLY_CORRECT").is_some(),
+        ..ShellOptions::default()
+    };
+    let mut record = None;
+    let mut script = None;
+    let mut command = None;
+    // `login` and friends start login shells with a dash before the name
+    let mut login = env::args().next().is_some_and(|name| name.starts_with('-'));
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--record" => match args.next() {
+                Some(file) => record = Some(PathBuf::from(file)),
+                None => {
+                    eprintln!("hermit: --record needs a file");
+                    std::process::exit(2);
+                }
+            },
+            // `-c COMMAND [NAME [ARG...]]` runs COMMAND with NAME as `$0`
+            "-c" => match args.next() {
+                Some(string) => command = Some((string, args.by_ref().collect())),
+                None => {
+                    eprintln!("hermit: -c needs a command");
+                    std::process::exit(2);
+                }
+            },
+            "-l" | "--login" => login = true,
+            "-n" | "--dry-run" => options.noexec = true,
+            "--no-confirm" => options.noconfirm = true,
+            "--posix" => options.posix = true,
+            // The first argument that is not an option names a script, and
+            // the rest are its positional parameters
+            _ if !arg.starts_with('-') => {
+                script = Some((PathBuf::from(arg), args.by_ref().collect()));
+            }
+            _ => {
+                eprintln!("hermit: unknown option: {}", arg);
+                std::process::exit(2);
+            }
+        }
     }
 
-    println!("Goodbye!");
-    Ok(())
+    let shell = if command.is_some() || script.is_some() {
+        Shell::non_interactive(options)
+    } else {
+        Shell::with_options(options)
+    };
+    let mut shell = shell.map_err(|e| format!("Failed to initialize shell: {}", e))?;
+    if login {
+        shell.login();
+    }
+    if let Some(path) = record {
+        shell
+            .record_to(&path)
+            .map_err(|e| format!("Failed to record to {}: {}", path.display(), e))?;
+    }
+
+    let status = match (command, script) {
+        (Some((command, args)), _) => Some(shell.run_command_string(&command, args)),
+        (None, Some((path, args))) => Some(shell.run_script(&path, args)),
+        (None, None) => None,
+    };
+    if let Some(status) = status {
+        io::stdout().flush().ok();
+        std::process::exit(status.code());
+    }
+
+    match shell.run() {
+        Ok(status) => {
+            println!("{}", Message::Goodbye);
+            io::stdout().flush().ok();
+            std::process::exit(status.code());
+        }
+        Err(e) => {
+            eprintln!("Shell error: {}", e);
+            std::process::exit(e.exit_code());
+        }
+    }
 }
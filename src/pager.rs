@@ -0,0 +1,315 @@
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal, Write},
+    process::{Command, Stdio},
+};
+use termion::{
+    clear, cursor, event::Key, input::TermRead, raw::IntoRawMode, screen::IntoAlternateScreen,
+    style,
+};
+
+use crate::{signals::ResizeGuard, utils};
+
+/// Prints `text`, paging it when it does not fit on the terminal.
+///
+/// `$PAGER` is used when it is set and can be started; otherwise the built-in
+/// pager takes over, so paging works in containers without `less`. With
+/// `plain`, the text is printed as it is instead of taking over the screen.
+pub fn page(text: &str, vars: &HashMap<String, String>, plain: bool) -> io::Result<()> {
+    let fits = text.lines().count() < utils::term_height();
+    if fits || !io::stdout().is_terminal() {
+        print!("{}", text);
+        return io::stdout().flush();
+    }
+
+    if let Some(pager) = vars.get("PAGER") {
+        if run_external(pager, text).is_ok() {
+            return Ok(());
+        }
+    }
+    if plain {
+        print!("{}", text);
+        return io::stdout().flush();
+    }
+    run(text)
+}
+
+fn run_external(pager: &str, text: &str) -> io::Result<()> {
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().ok_or(io::ErrorKind::NotFound)?;
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may quit before reading everything
+        stdin.write_all(text.as_bytes()).ok();
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Shows `text` in the built-in pager until the user quits
+pub fn run(text: &str) -> io::Result<()> {
+    // Keys come from the terminal itself, so the text may have been piped in
+    let tty = termion::get_tty()?;
+    let (mut width, height) = termion::terminal_size()?;
+    let mut screen = io::stdout().into_raw_mode()?.into_alternate_screen()?;
+    let resize = ResizeGuard::new();
+
+    let mut pager = Pager::new(text, rows_for_text(height));
+    pager.render(&mut screen, width as usize)?;
+    for key in tty.keys() {
+        match key {
+            // A resize interrupts the wait for a key
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            key => {
+                if !pager.handle_key(key?) {
+                    break;
+                }
+            }
+        }
+        if resize.resized() {
+            let height;
+            (width, height) = termion::terminal_size()?;
+            pager.resize(rows_for_text(height));
+        }
+        pager.render(&mut screen, width as usize)?;
+    }
+
+    write!(screen, "{}", cursor::Show)?;
+    screen.flush()
+}
+
+/// The rows left for text on a terminal `height` rows tall, below which the status line goes
+fn rows_for_text(height: u16) -> usize {
+    (height as usize).saturating_sub(1).max(1)
+}
+
+/// Scroll position and search state of the built-in pager
+#[derive(Debug)]
+struct Pager {
+    lines: Vec<String>,
+    top: usize,
+    height: usize,
+    pattern: Option<String>,
+    last_match: Option<usize>,
+    /// Text typed after `/` that has not been submitted yet
+    input: Option<String>,
+    message: Option<String>,
+}
+
+impl Pager {
+    fn new(text: &str, height: usize) -> Self {
+        Self {
+            lines: text.lines().map(String::from).collect(),
+            top: 0,
+            height,
+            pattern: None,
+            last_match: None,
+            input: None,
+            message: None,
+        }
+    }
+
+    fn max_top(&self) -> usize {
+        self.lines.len().saturating_sub(self.height)
+    }
+
+    /// Fits the view to a resized terminal, keeping the top line where it is
+    fn resize(&mut self, height: usize) {
+        self.height = height;
+        self.top = self.top.min(self.max_top());
+    }
+
+    fn scroll_by(&mut self, delta: isize) {
+        self.top = self.top.saturating_add_signed(delta).min(self.max_top());
+    }
+
+    /// Moves to the next line matching the pattern, wrapping around neither end
+    fn find(&mut self, forward: bool) {
+        let Some(pattern) = &self.pattern else {
+            self.message = Some("No previous pattern".to_string());
+            return;
+        };
+
+        let found = match (forward, self.last_match) {
+            (true, Some(line)) => {
+                (line + 1..self.lines.len()).find(|&i| self.lines[i].contains(pattern))
+            }
+            (true, None) => (self.top..self.lines.len()).find(|&i| self.lines[i].contains(pattern)),
+            (false, Some(line)) => (0..line).rev().find(|&i| self.lines[i].contains(pattern)),
+            (false, None) => (0..self.top)
+                .rev()
+                .find(|&i| self.lines[i].contains(pattern)),
+        };
+
+        match found {
+            Some(line) => {
+                self.last_match = Some(line);
+                self.top = line.min(self.max_top());
+            }
+            None => self.message = Some("Pattern not found".to_string()),
+        }
+    }
+
+    /// Applies one key press, returning `false` once the user quits
+    fn handle_key(&mut self, key: Key) -> bool {
+        self.message = None;
+        let page = self.height as isize;
+
+        if let Some(input) = &mut self.input {
+            match key {
+                Key::Char('\n') => {
+                    let pattern = std::mem::take(input);
+                    self.input = None;
+                    if !pattern.is_empty() {
+                        self.pattern = Some(pattern);
+                    }
+                    self.last_match = None;
+                    self.find(true);
+                }
+                Key::Esc => self.input = None,
+                Key::Backspace if input.is_empty() => self.input = None,
+                Key::Backspace => {
+                    input.pop();
+                }
+                Key::Char(c) => input.push(c),
+                _ => {}
+            }
+            return true;
+        }
+
+        match key {
+            Key::Char('q') | Key::Esc | Key::Ctrl('c') => return false,
+            Key::Char('j') | Key::Char('\n') | Key::Down => self.scroll_by(1),
+            Key::Char('k') | Key::Up => self.scroll_by(-1),
+            Key::Char(' ') | Key::Char('f') | Key::PageDown => self.scroll_by(page),
+            Key::Char('b') | Key::PageUp => self.scroll_by(-page),
+            Key::Char('d') => self.scroll_by(page / 2),
+            Key::Char('u') => self.scroll_by(-page / 2),
+            Key::Char('g') | Key::Home => self.top = 0,
+            Key::Char('G') | Key::End => self.top = self.max_top(),
+            Key::Char('/') => self.input = Some(String::new()),
+            Key::Char('n') => self.find(true),
+            Key::Char('N') => self.find(false),
+            _ => {}
+        }
+        true
+    }
+
+    fn status(&self) -> String {
+        match (&self.input, &self.message) {
+            (Some(input), _) => format!("/{}", input),
+            (None, Some(message)) => message.clone(),
+            (None, None) if self.top >= self.max_top() => "(END)".to_string(),
+            (None, None) => ":".to_string(),
+        }
+    }
+
+    fn render(&self, out: &mut impl Write, width: usize) -> io::Result<()> {
+        write!(out, "{}", clear::All)?;
+
+        for (row, line) in self
+            .lines
+            .iter()
+            .skip(self.top)
+            .take(self.height)
+            .enumerate()
+        {
+            let line: String = line.chars().take(width).collect();
+            let line = match &self.pattern {
+                Some(pattern) if !pattern.is_empty() => line.replace(
+                    pattern.as_str(),
+                    &format!("{}{}{}", style::Invert, pattern, style::NoInvert),
+                ),
+                _ => line,
+            };
+            write!(out, "{}{}", cursor::Goto(1, row as u16 + 1), line)?;
+        }
+
+        write!(
+            out,
+            "{}{}{}{}",
+            cursor::Goto(1, self.height as u16 + 1),
+            style::Invert,
+            self.status(),
+            style::Reset
+        )?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pager() -> Pager {
+        let text: Vec<String> = (1..=20).map(|i| format!("line {}", i)).collect();
+        Pager::new(&text.join("\n"), 5)
+    }
+
+    fn type_keys(pager: &mut Pager, keys: &str) {
+        for c in keys.chars() {
+            pager.handle_key(Key::Char(c));
+        }
+    }
+
+    #[test]
+    fn test_scrolling_stays_in_bounds() {
+        let mut pager = pager();
+        pager.handle_key(Key::Up);
+        assert_eq!(pager.top, 0);
+
+        pager.handle_key(Key::Char(' '));
+        assert_eq!(pager.top, 5);
+        pager.handle_key(Key::Char('G'));
+        assert_eq!(pager.top, 15);
+        assert_eq!(pager.status(), "(END)");
+        pager.handle_key(Key::Down);
+        assert_eq!(pager.top, 15);
+
+        pager.handle_key(Key::Char('g'));
+        assert_eq!(pager.top, 0);
+        assert_eq!(pager.status(), ":");
+    }
+
+    #[test]
+    fn test_resize_keeps_view_in_bounds() {
+        let mut pager = pager();
+        pager.handle_key(Key::Char('G'));
+        pager.resize(10);
+        assert_eq!((pager.top, pager.height), (10, 10));
+        pager.resize(30);
+        assert_eq!(pager.top, 0);
+        assert_eq!(pager.status(), "(END)");
+    }
+
+    #[test]
+    fn test_search_and_repeat() {
+        let mut pager = pager();
+        type_keys(&mut pager, "/line 1\n");
+        assert_eq!(pager.last_match, Some(0));
+
+        pager.handle_key(Key::Char('n'));
+        assert_eq!(pager.last_match, Some(9));
+        assert_eq!(pager.top, 9);
+
+        pager.handle_key(Key::Char('N'));
+        assert_eq!(pager.last_match, Some(0));
+
+        type_keys(&mut pager, "/missing\n");
+        assert_eq!(pager.status(), "Pattern not found");
+    }
+
+    #[test]
+    fn test_quit_and_cancelled_search() {
+        let mut pager = pager();
+        pager.handle_key(Key::Char('/'));
+        assert_eq!(pager.status(), "/");
+        assert!(pager.handle_key(Key::Esc));
+        assert!(pager.input.is_none());
+        assert!(!pager.handle_key(Key::Char('q')));
+    }
+}
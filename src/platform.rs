@@ -0,0 +1,94 @@
+use std::{env, process};
+
+/// Facts about the machine shown in the prompt.
+///
+/// Each OS has its own backend for finding them; anything a backend cannot
+/// answer falls back to `uname`, then to compile-time defaults.
+#[derive(Debug, Clone)]
+pub struct PlatformInfo {
+    pub os_name: String,
+    pub hostname: String,
+}
+
+impl PlatformInfo {
+    pub fn detect() -> Self {
+        let os_name = backend::os_name()
+            .or_else(|| uname("-s"))
+            .unwrap_or_else(|| env::consts::OS.to_string());
+
+        // Only the first label, as in `\h` of other shells' prompts
+        let hostname = backend::hostname()
+            .or_else(|| uname("-n"))
+            .and_then(|name| name.split('.').next().map(String::from))
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "localhost".to_string());
+
+        Self { os_name, hostname }
+    }
+}
+
+fn uname(flag: &str) -> Option<String> {
+    let output = process::Command::new("uname").arg(flag).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(target_os = "linux")]
+mod backend {
+    use os_release::OsRelease;
+    use std::fs;
+
+    /// Reads `/etc/os-release`, which is missing on some minimal and non-systemd systems
+    pub fn os_name() -> Option<String> {
+        OsRelease::new()
+            .ok()
+            .map(|os| os.name)
+            .filter(|name| !name.is_empty())
+    }
+
+    pub fn hostname() -> Option<String> {
+        fs::read_to_string("/proc/sys/kernel/hostname")
+            .ok()
+            .map(|name| name.trim().to_string())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod backend {
+    use std::process;
+
+    /// `uname` would only say "Darwin"; `sw_vers` knows the product name
+    pub fn os_name() -> Option<String> {
+        let output = process::Command::new("sw_vers")
+            .arg("-productName")
+            .output()
+            .ok()?;
+        let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (!name.is_empty()).then_some(name)
+    }
+
+    pub fn hostname() -> Option<String> {
+        let output = process::Command::new("scutil")
+            .args(["--get", "LocalHostName"])
+            .output()
+            .ok()?;
+        let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !name.is_empty()).then_some(name)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod backend {
+    // The BSDs and others report something sensible through `uname`
+
+    pub fn os_name() -> Option<String> {
+        None
+    }
+
+    pub fn hostname() -> Option<String> {
+        None
+    }
+}
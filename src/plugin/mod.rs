@@ -0,0 +1,101 @@
+mod native;
+mod stdio;
+mod wasm;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{config::PluginConfig, core::command::Command, prompt::Segment};
+
+/// A prompt segment contributed by a plugin
+pub trait SegmentSource {
+    /// Text for the segment in `cwd`, or `None` to leave it out
+    fn render(&self, cwd: &Path) -> Option<String>;
+}
+
+/// Builtins and prompt segments loaded from plugins at startup.
+///
+/// A plugin that fails to load is skipped, with the reason kept in
+/// [`Plugins::errors`]; the shell starts without it.
+#[derive(Default)]
+pub struct Plugins {
+    builtins: Vec<Box<dyn Command>>,
+    segments: Vec<Box<dyn SegmentSource>>,
+    errors: Vec<String>,
+}
+
+impl Plugins {
+    pub fn load(config: &PluginConfig) -> Self {
+        let mut plugins = Self::default();
+
+        for dir in &config.directories {
+            let dir = expand_home(dir);
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    plugins.errors.push(format!("{}: {}", dir.display(), e));
+                    continue;
+                }
+            };
+            let mut files: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+            files.sort();
+
+            for file in files {
+                let loaded = match file.extension().and_then(|ext| ext.to_str()) {
+                    Some(std::env::consts::DLL_EXTENSION) => native::load(&file),
+                    Some("wasm") => wasm::load(&file),
+                    _ => continue,
+                };
+                match loaded {
+                    Ok(plugin) => plugins.add(plugin),
+                    Err(e) => plugins.errors.push(format!("{}: {}", file.display(), e)),
+                }
+            }
+        }
+
+        plugins.add(stdio::load(config));
+        plugins
+    }
+
+    fn add(&mut self, plugin: Self) {
+        self.builtins.extend(plugin.builtins);
+        self.segments.extend(plugin.segments);
+        self.errors.extend(plugin.errors);
+    }
+
+    /// Why plugins that were found could not be loaded
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// Hands the builtins over to the command registry
+    pub fn take_builtins(&mut self) -> Vec<Box<dyn Command>> {
+        std::mem::take(&mut self.builtins)
+    }
+
+    pub fn segments(&self, cwd: &Path) -> Vec<Segment> {
+        self.segments
+            .iter()
+            .filter_map(|source| source.render(cwd))
+            .filter(|text| !text.is_empty())
+            .map(Segment::plain)
+            .collect()
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Turns a string from a plugin into the `'static` one [`Command`] expects.
+///
+/// Plugins are loaded once and live as long as the shell, so this leaks only
+/// a few names and descriptions.
+fn leak(text: String) -> &'static str {
+    Box::leak(text.into_boxed_str())
+}
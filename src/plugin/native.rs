@@ -0,0 +1,325 @@
+//! Plugins compiled to dynamic libraries.
+//!
+//! A plugin is a `cdylib` exporting two C functions:
+//!
+//! ```c
+//! uint32_t hermit_plugin_abi_version(void);
+//! const PluginDeclaration *hermit_plugin_declare(void);
+//! ```
+//!
+//! The version is checked before anything else is touched, so a plugin built
+//! against another ABI is refused instead of misread. The declaration and the
+//! strings it points to must stay valid while the library is loaded. Builtins
+//! get the shell's directory and exported variables along with their
+//! arguments, since the process's own are not kept in step with the shell's;
+//! they write to stdout and stderr themselves and return their exit code.
+//! Segment text is allocated by the plugin and handed back to its `free`
+//! function.
+
+use libloading::Library;
+use std::{
+    error::Error,
+    ffi::{c_char, CStr, CString},
+    io::{self, Write},
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    ptr,
+    sync::Arc,
+};
+
+use super::{leak, Plugins, SegmentSource};
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::Flags,
+};
+
+/// Version of the interface below; bump it on any change to these types
+pub const ABI_VERSION: u32 = 2;
+
+#[repr(C)]
+pub struct PluginDeclaration {
+    pub name: *const c_char,
+    pub builtins: *const BuiltinDeclaration,
+    pub builtin_count: usize,
+    pub segments: *const SegmentDeclaration,
+    pub segment_count: usize,
+}
+
+#[repr(C)]
+pub struct BuiltinDeclaration {
+    pub name: *const c_char,
+    pub description: *const c_char,
+    /// Runs the builtin with its arguments, not including its name, in the
+    /// shell's directory `cwd` with the `NAME=value` strings of `envp`, which
+    /// ends with a null pointer
+    pub run: BuiltinFn,
+}
+
+pub type BuiltinFn = extern "C" fn(
+    argc: usize,
+    argv: *const *const c_char,
+    cwd: *const c_char,
+    envp: *const *const c_char,
+) -> i32;
+
+#[repr(C)]
+pub struct SegmentDeclaration {
+    /// Returns the segment text for the directory, or null to leave it out
+    pub render: extern "C" fn(cwd: *const c_char) -> *mut c_char,
+    pub free: extern "C" fn(text: *mut c_char),
+}
+
+type AbiVersionFn = extern "C" fn() -> u32;
+type DeclareFn = extern "C" fn() -> *const PluginDeclaration;
+
+/// Loads a plugin library, checking that it speaks this version of the ABI
+pub fn load(path: &Path) -> Result<Plugins, String> {
+    // SAFETY: loading runs the library's initialisers; plugins are code the
+    // user chose to install, the same trust as anything else on $PATH
+    let library = unsafe { Library::new(path) }.map_err(|e| match e.source() {
+        Some(cause) => format!("{}: {}", e, cause),
+        None => e.to_string(),
+    })?;
+
+    // SAFETY: the symbols are declared with the signatures the ABI documents
+    let declaration = unsafe {
+        let version = library
+            .get::<AbiVersionFn>(b"hermit_plugin_abi_version")
+            .map_err(|_| "not a hermit plugin".to_string())?;
+        if version() != ABI_VERSION {
+            return Err(format!(
+                "built for plugin ABI {}, this shell uses {}",
+                version(),
+                ABI_VERSION
+            ));
+        }
+        let declare = library
+            .get::<DeclareFn>(b"hermit_plugin_declare")
+            .map_err(|e| e.to_string())?;
+        declare()
+    };
+    if declaration.is_null() {
+        return Err("plugin declared nothing".to_string());
+    }
+
+    // SAFETY: checked for null, and the ABI requires it to outlive the library
+    unsafe { from_declaration(&*declaration, Some(Arc::new(library))) }
+}
+
+/// Wraps a plugin's declarations in shell types.
+///
+/// # Safety
+///
+/// Every pointer in `declaration` must be valid as the ABI describes, for as
+/// long as `library` is loaded.
+unsafe fn from_declaration(
+    declaration: &PluginDeclaration,
+    library: Option<Arc<Library>>,
+) -> Result<Plugins, String> {
+    let plugin = string(declaration.name).ok_or("plugin has no name")?;
+    let mut plugins = Plugins::default();
+
+    for builtin in slice(declaration.builtins, declaration.builtin_count) {
+        let name = string(builtin.name).ok_or(format!("{}: builtin has no name", plugin))?;
+        let description = string(builtin.description)
+            .unwrap_or_else(|| format!("Provided by the {} plugin", plugin));
+        plugins.builtins.push(Box::new(NativeBuiltin {
+            name: leak(name),
+            description: leak(description),
+            run: builtin.run,
+            _library: library.clone(),
+        }));
+    }
+
+    for segment in slice(declaration.segments, declaration.segment_count) {
+        plugins.segments.push(Box::new(NativeSegment {
+            render: segment.render,
+            free: segment.free,
+            _library: library.clone(),
+        }));
+    }
+
+    Ok(plugins)
+}
+
+unsafe fn string(text: *const c_char) -> Option<String> {
+    (!text.is_null()).then(|| CStr::from_ptr(text).to_string_lossy().into_owned())
+}
+
+unsafe fn slice<'a, T>(items: *const T, count: usize) -> &'a [T] {
+    if items.is_null() || count == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(items, count)
+    }
+}
+
+/// A builtin implemented by a plugin library
+struct NativeBuiltin {
+    name: &'static str,
+    description: &'static str,
+    run: BuiltinFn,
+    /// Keeps the code behind `run` loaded
+    _library: Option<Arc<Library>>,
+}
+
+impl Command for NativeBuiltin {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    /// The plugin sees its arguments exactly as typed
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let nul = |what: &str| ShellError::builtin(format!("{}: {} contains NUL", self.name, what));
+        let args: Vec<CString> = args
+            .iter()
+            .map(|arg| CString::new(*arg))
+            .collect::<Result<_, _>>()
+            .map_err(|_| nul("argument"))?;
+        let argv: Vec<*const c_char> = args.iter().map(|arg| arg.as_ptr()).collect();
+        let cwd = CString::new(context.cwd.as_os_str().as_bytes()).map_err(|_| nul("directory"))?;
+        // Variables that cannot be passed are left out, as for other commands
+        let env: Vec<CString> = context
+            .environment()
+            .into_iter()
+            .filter_map(|(name, value)| CString::new(format!("{}={}", name, value)).ok())
+            .collect();
+        let envp: Vec<*const c_char> = env
+            .iter()
+            .map(|var| var.as_ptr())
+            .chain(std::iter::once(ptr::null()))
+            .collect();
+
+        // Anything the shell buffered must come out before the plugin writes
+        io::stdout().flush()?;
+        let code = (self.run)(argv.len(), argv.as_ptr(), cwd.as_ptr(), envp.as_ptr());
+        Ok(ExitCode::new(code))
+    }
+}
+
+struct NativeSegment {
+    render: extern "C" fn(*const c_char) -> *mut c_char,
+    free: extern "C" fn(*mut c_char),
+    _library: Option<Arc<Library>>,
+}
+
+impl SegmentSource for NativeSegment {
+    fn render(&self, cwd: &Path) -> Option<String> {
+        let cwd = CString::new(cwd.to_string_lossy().as_bytes()).ok()?;
+        let text = (self.render)(cwd.as_ptr());
+        if text.is_null() {
+            return None;
+        }
+
+        // SAFETY: non-null results are NUL-terminated strings owned by the plugin
+        let rendered = unsafe { CStr::from_ptr(text) }
+            .to_string_lossy()
+            .into_owned();
+        (self.free)(text);
+        Some(rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The argument count, if the first argument is `ok`, the directory is
+    /// `/work` and `GREETING` is the last variable
+    extern "C" fn count_args(
+        argc: usize,
+        argv: *const *const c_char,
+        cwd: *const c_char,
+        envp: *const *const c_char,
+    ) -> i32 {
+        let args = unsafe { std::slice::from_raw_parts(argv, argc) };
+        let first = unsafe { CStr::from_ptr(args[0]) };
+        let cwd = unsafe { CStr::from_ptr(cwd) };
+        let env = unsafe { std::slice::from_raw_parts(envp, 2) };
+        let var = unsafe { CStr::from_ptr(env[0]) };
+        let ok = first.to_bytes() == b"ok"
+            && cwd.to_bytes() == b"/work"
+            && var.to_bytes() == b"GREETING=hi"
+            && env[1].is_null();
+        if ok {
+            argc as i32
+        } else {
+            -1
+        }
+    }
+
+    extern "C" fn render_dir(cwd: *const c_char) -> *mut c_char {
+        let cwd = unsafe { CStr::from_ptr(cwd) }.to_string_lossy();
+        if cwd == "/" {
+            return ptr::null_mut();
+        }
+        CString::new(format!("in {}", cwd)).unwrap().into_raw()
+    }
+
+    extern "C" fn free_text(text: *mut c_char) {
+        drop(unsafe { CString::from_raw(text) });
+    }
+
+    #[test]
+    fn test_declaration_becomes_builtins_and_segments() {
+        let builtins = [BuiltinDeclaration {
+            name: c"count".as_ptr(),
+            description: ptr::null(),
+            run: count_args,
+        }];
+        let segments = [SegmentDeclaration {
+            render: render_dir,
+            free: free_text,
+        }];
+        let declaration = PluginDeclaration {
+            name: c"example".as_ptr(),
+            builtins: builtins.as_ptr(),
+            builtin_count: builtins.len(),
+            segments: segments.as_ptr(),
+            segment_count: segments.len(),
+        };
+
+        let mut plugins = unsafe { from_declaration(&declaration, None) }.unwrap();
+        let builtins = plugins.take_builtins();
+        assert_eq!(builtins[0].name(), "count");
+        assert_eq!(builtins[0].description(), "Provided by the example plugin");
+
+        let mut context = CommandContext::default();
+        context.cwd = "/work".into();
+        context.set_var("GREETING", "hi");
+        context.set_var("LOCAL", "unexported");
+        context.exported.insert("GREETING".to_string());
+        let status = builtins[0]
+            .execute(&["ok", "two", "three"], &Flags::default(), &mut context)
+            .unwrap();
+        assert_eq!(status, ExitCode::new(3));
+
+        assert_eq!(
+            plugins.segments(Path::new("/work")),
+            vec![crate::prompt::Segment::plain("in /work")]
+        );
+        assert!(plugins.segments(Path::new("/")).is_empty());
+    }
+
+    #[test]
+    fn test_load_rejects_other_libraries() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("broken.so");
+        std::fs::write(&path, "not a library").unwrap();
+        assert!(load(&path).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+//! Plugins that are ordinary executables speaking JSON over stdin and stdout.
+//!
+//! For every call the shell starts the program and writes one request object
+//! to its stdin:
+//!
+//! ```json
+//! {"protocol": 1, "type": "builtin", "name": "todo", "args": ["add", "milk"],
+//!  "cwd": "/home/me", "env": {"HOME": "/home/me", ...}}
+//! ```
+//!
+//! A builtin answers on stdout with a single object, every field optional:
+//!
+//! ```json
+//! {"output": "added\n", "status": 0, "cwd": "/tmp",
+//!  "env": {"TODO_COUNT": "3", "OLD": null}, "aliases": {"t": "todo list"}}
+//! ```
+//!
+//! `null` removes a variable or alias. Segments get `"type": "segment"` and
+//! reply `{"text": "..."}`; they have a moment to answer before they are
+//! killed, so a slow plugin cannot hold up the prompt. Stderr is left alone
+//! for the plugin's own messages.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command as Process, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{expand_home, leak, Plugins, SegmentSource};
+use crate::{
+    config::PluginConfig,
+    core::{
+        command::{Command, CommandContext, ExitCode},
+        error::{ShellError, ShellResult},
+        flags::Flags,
+    },
+};
+
+const PROTOCOL: u32 = 1;
+
+/// How long a segment plugin may take before the prompt goes on without it
+const SEGMENT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Registers the executables listed in the config
+pub fn load(config: &PluginConfig) -> Plugins {
+    let mut plugins = Plugins::default();
+    for builtin in &config.builtins {
+        plugins.builtins.push(Box::new(StdioBuiltin {
+            name: leak(builtin.name.clone()),
+            description: leak(
+                builtin
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| format!("Runs {}", builtin.command)),
+            ),
+            command: builtin.command.clone(),
+        }));
+    }
+    for segment in &config.segments {
+        plugins.segments.push(Box::new(StdioSegment {
+            command: segment.command.clone(),
+        }));
+    }
+    plugins
+}
+
+/// Starts `command` in `cwd` and hands it the request.
+///
+/// `env` replaces the environment the shell was started with, when given.
+fn start(
+    command: &str,
+    cwd: &Path,
+    env: Option<&HashMap<String, String>>,
+    request: &Value,
+) -> io::Result<Child> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().ok_or(io::ErrorKind::NotFound)?;
+    let mut process = Process::new(expand_home(Path::new(program)));
+    process
+        .args(parts)
+        .current_dir(cwd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    if let Some(env) = env {
+        process.env_clear().envs(env);
+    }
+
+    let mut child = process.spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A plugin that ignores the request and exits is not an error
+        stdin.write_all(request.to_string().as_bytes()).ok();
+    }
+    Ok(child)
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Reply {
+    output: String,
+    status: i32,
+    cwd: Option<PathBuf>,
+    env: HashMap<String, Option<String>>,
+    aliases: HashMap<String, Option<String>>,
+}
+
+impl Reply {
+    /// Carries out the state changes the plugin asked for
+    fn apply(self, context: &mut CommandContext) -> Result<(), String> {
+        for (name, value) in self.env {
+            match value {
+                Some(value) => context.set_var(&name, &value),
+                None => {
+                    context.unset_var(&name);
+                }
+            }
+        }
+        for (name, value) in self.aliases {
+            match value {
+                Some(value) => context.aliases.insert(name, value),
+                None => context.aliases.remove(&name),
+            };
+        }
+        if let Some(dir) = self.cwd {
+            let dir = context
+                .resolve_path(&dir)
+                .canonicalize()
+                .map_err(|e| format!("{}: {}", dir.display(), e))?;
+            if !dir.is_dir() {
+                return Err(format!("{}: Not a directory", dir.display()));
+            }
+            context.set_cwd(dir);
+        }
+        Ok(())
+    }
+}
+
+/// A builtin backed by an executable
+struct StdioBuiltin {
+    name: &'static str,
+    description: &'static str,
+    command: String,
+}
+
+impl StdioBuiltin {
+    fn request(&self, args: &[&str], context: &CommandContext) -> Value {
+        json!({
+            "protocol": PROTOCOL,
+            "type": "builtin",
+            "name": self.name,
+            "args": args,
+            "cwd": context.cwd,
+            "env": context.variables,
+        })
+    }
+}
+
+impl Command for StdioBuiltin {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    /// The plugin sees its arguments exactly as typed
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let fail = |message: String| ShellError::builtin(format!("{}: {}", self.name, message));
+
+        let request = self.request(args, context);
+        let child = start(
+            &self.command,
+            &context.cwd,
+            Some(&context.environment()),
+            &request,
+        )
+        .map_err(|e| fail(format!("{}: {}", self.command, e)))?;
+        let output = child.wait_with_output()?;
+        if output.stdout.iter().all(u8::is_ascii_whitespace) {
+            // Nothing to apply, so the plugin's own exit status is the answer
+            return Ok(output.status.into());
+        }
+
+        let reply: Reply = serde_json::from_slice(&output.stdout)
+            .map_err(|e| fail(format!("invalid reply: {}", e)))?;
+        print!("{}", reply.output);
+        io::stdout().flush()?;
+        let status = ExitCode::new(reply.status);
+        reply.apply(context).map_err(fail)?;
+        Ok(status)
+    }
+}
+
+struct StdioSegment {
+    command: String,
+}
+
+#[derive(Deserialize)]
+struct SegmentReply {
+    text: String,
+}
+
+impl SegmentSource for StdioSegment {
+    fn render(&self, cwd: &Path) -> Option<String> {
+        let request = json!({"protocol": PROTOCOL, "type": "segment", "cwd": cwd});
+        let mut child = start(&self.command, cwd, None, &request).ok()?;
+
+        let mut stdout = child.stdout.take()?;
+        let reader = thread::spawn(move || {
+            let mut reply = Vec::new();
+            stdout.read_to_end(&mut reply).map(|_| reply)
+        });
+
+        let deadline = Instant::now() + SEGMENT_TIMEOUT;
+        while child.try_wait().ok()?.is_none() {
+            if Instant::now() >= deadline {
+                child.kill().ok();
+                child.wait().ok();
+                return None;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let reply = reader.join().ok()?.ok()?;
+        let reply: SegmentReply = serde_json::from_slice(&reply).ok()?;
+        Some(reply.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    fn script(dir: &Path, name: &str, body: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.display().to_string()
+    }
+
+    #[test]
+    fn test_builtin_reply_changes_state() {
+        let dir = TempDir::new().unwrap();
+        let reply = r#"{"status": 3, "cwd": "..", "env": {"ADDED": "1", "GONE": null}, "aliases": {"t": "todo list"}}"#;
+        let command = script(
+            dir.path(),
+            "todo",
+            &format!("cat >/dev/null; echo '{}'", reply),
+        );
+        let plugins = load(&PluginConfig {
+            builtins: vec![crate::config::ExternalBuiltin {
+                name: "todo".to_string(),
+                command,
+                description: None,
+            }],
+            ..PluginConfig::default()
+        });
+
+        let mut context = CommandContext::new(dir.path().join("sub"));
+        std::fs::create_dir(&context.cwd).unwrap();
+        context.set_var("GONE", "x");
+        let status = plugins.builtins[0]
+            .execute(&["list"], &Flags::default(), &mut context)
+            .unwrap();
+
+        assert_eq!(status, ExitCode::new(3));
+        assert_eq!(context.var("ADDED"), Some("1"));
+        assert_eq!(context.var("GONE"), None);
+        assert_eq!(context.aliases["t"], "todo list");
+        assert_eq!(context.cwd, dir.path().canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_builtin_receives_request() {
+        let dir = TempDir::new().unwrap();
+        let builtin = StdioBuiltin {
+            name: "todo",
+            description: "",
+            command: script(dir.path(), "todo", "exit 0"),
+        };
+        let mut context = CommandContext::new(dir.path().to_path_buf());
+        context.set_var("HOME", "/home/me");
+
+        let request = builtin.request(&["add", "milk"], &context);
+        assert_eq!(request["type"], "builtin");
+        assert_eq!(request["args"], json!(["add", "milk"]));
+        assert_eq!(request["env"]["HOME"], "/home/me");
+
+        let status = builtin
+            .execute(&[], &Flags::default(), &mut context)
+            .unwrap();
+        assert_eq!(status, ExitCode::SUCCESS);
+    }
+
+    #[test]
+    fn test_segment_reply_and_timeout() {
+        let dir = TempDir::new().unwrap();
+        let segment = StdioSegment {
+            command: script(
+                dir.path(),
+                "kube",
+                r#"cat >/dev/null; echo '{"text": "prod"}'"#,
+            ),
+        };
+        assert_eq!(segment.render(dir.path()), Some("prod".to_string()));
+
+        let slow = StdioSegment {
+            command: script(dir.path(), "slow", "sleep 5"),
+        };
+        let started = Instant::now();
+        assert_eq!(slow.render(dir.path()), None);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,460 @@
+//! Plugins compiled to WebAssembly for WASI.
+//!
+//! A module exporting `_start`, as WASI commands do, becomes a builtin named
+//! after its file; one exporting `hermit_segment` adds a prompt segment made of
+//! whatever that function writes to stdout. The host shim provides only what a
+//! command needs: arguments, environment, stdout, stderr, clocks, randomness
+//! and exit. Everything else, files and sockets included, fails with `ENOSYS`,
+//! so a plugin cannot touch anything outside its own memory.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use wasmi::{Caller, Config, Engine, Error, Linker, Memory, Module, Store, Val, ValType};
+
+use super::{leak, Plugins, SegmentSource};
+use crate::core::{
+    command::{Command, CommandContext, ExitCode},
+    error::{ShellError, ShellResult},
+    flags::Flags,
+};
+
+const WASI: &str = "wasi_snapshot_preview1";
+
+/// The WASI functions the shim implements
+const PROVIDED: &[&str] = &[
+    "args_sizes_get",
+    "args_get",
+    "environ_sizes_get",
+    "environ_get",
+    "fd_write",
+    "clock_time_get",
+    "random_get",
+    "proc_exit",
+];
+
+const ERRNO_SUCCESS: i32 = 0;
+const ERRNO_BADF: i32 = 8;
+const ERRNO_FAULT: i32 = 21;
+const ERRNO_NOSYS: i32 = 52;
+
+/// Instructions a segment may run before it is cut off, so a plugin cannot stall the prompt
+const SEGMENT_FUEL: u64 = 10_000_000;
+
+/// Loads a module, refusing ones that import anything but WASI
+pub fn load(path: &Path) -> Result<Plugins, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let plugin = WasmPlugin::new(&bytes).map_err(|e| e.to_string())?;
+    let module = &plugin.module;
+
+    if let Some(import) = module.imports().find(|import| import.module() != WASI) {
+        return Err(format!(
+            "imports {}.{}, which hermit does not provide",
+            import.module(),
+            import.name()
+        ));
+    }
+
+    let exports = |name: &str| {
+        module
+            .get_export(name)
+            .is_some_and(|export| export.func().is_some())
+    };
+    let (is_command, has_segment) = (exports("_start"), exports("hermit_segment"));
+    let file = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    let plugin = Arc::new(plugin);
+
+    let mut plugins = Plugins::default();
+    if is_command {
+        plugins.builtins.push(Box::new(WasmBuiltin {
+            name: leak(name.into_owned()),
+            description: leak(format!("WebAssembly plugin {}", file)),
+            plugin: plugin.clone(),
+        }));
+    }
+    if has_segment {
+        plugins.segments.push(Box::new(WasmSegment { plugin }));
+    }
+    if !is_command && !has_segment {
+        return Err("exports neither _start nor hermit_segment".to_string());
+    }
+    Ok(plugins)
+}
+
+struct WasmPlugin {
+    engine: Engine,
+    module: Module,
+}
+
+impl WasmPlugin {
+    fn new(bytes: &[u8]) -> Result<Self, Error> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, bytes)?;
+        Ok(Self { engine, module })
+    }
+
+    /// Calls the exported function `entry` in a fresh instance, returning its exit status
+    fn run(&self, entry: &str, host: Host, fuel: u64) -> Result<(i32, Host), Error> {
+        let mut store = Store::new(&self.engine, host);
+        store.set_fuel(fuel)?;
+        let instance = self
+            .linker()?
+            .instantiate_and_start(&mut store, &self.module)?;
+        let func = instance.get_typed_func::<(), ()>(&store, entry)?;
+
+        let status = match func.call(&mut store, ()) {
+            Ok(()) => 0,
+            Err(e) => e.i32_exit_status().ok_or(e)?,
+        };
+        Ok((status, store.into_data()))
+    }
+
+    fn linker(&self) -> Result<Linker<Host>, Error> {
+        let mut linker = Linker::new(&self.engine);
+        linker.func_wrap(
+            WASI,
+            "args_sizes_get",
+            |caller: Caller<Host>, count: i32, size: i32| {
+                let args = caller.data().args.clone();
+                strings_sizes(caller, &args, count, size)
+            },
+        )?;
+        linker.func_wrap(
+            WASI,
+            "args_get",
+            |caller: Caller<Host>, ptrs: i32, buffer: i32| {
+                let args = caller.data().args.clone();
+                strings_get(caller, &args, ptrs, buffer)
+            },
+        )?;
+        linker.func_wrap(
+            WASI,
+            "environ_sizes_get",
+            |caller: Caller<Host>, count: i32, size: i32| {
+                let env = caller.data().env.clone();
+                strings_sizes(caller, &env, count, size)
+            },
+        )?;
+        linker.func_wrap(
+            WASI,
+            "environ_get",
+            |caller: Caller<Host>, ptrs: i32, buffer: i32| {
+                let env = caller.data().env.clone();
+                strings_get(caller, &env, ptrs, buffer)
+            },
+        )?;
+        linker.func_wrap(WASI, "fd_write", fd_write)?;
+        linker.func_wrap(
+            WASI,
+            "clock_time_get",
+            |mut caller: Caller<Host>, _clock: i32, _precision: i64, time: i32| {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_nanos() as u64);
+                with_memory(&mut caller, |data, _| {
+                    write_bytes(data, time, &now.to_le_bytes())
+                })
+            },
+        )?;
+        linker.func_wrap(
+            WASI,
+            "random_get",
+            |mut caller: Caller<Host>, buffer: i32, len: i32| {
+                with_memory(&mut caller, |data, host| {
+                    let bytes: Vec<u8> = (0..len).map(|_| host.next_random()).collect();
+                    write_bytes(data, buffer, &bytes)
+                })
+            },
+        )?;
+        linker.func_wrap(
+            WASI,
+            "proc_exit",
+            |_: Caller<Host>, code: i32| -> Result<(), Error> { Err(Error::i32_exit(code)) },
+        )?;
+
+        // The rest of WASI exists so modules link, but does nothing
+        for import in self.module.imports() {
+            let Some(ty) = import.ty().func() else {
+                continue;
+            };
+            if PROVIDED.contains(&import.name()) {
+                continue;
+            }
+            let returns_errno = ty.results() == [ValType::I32];
+            linker.func_new(WASI, import.name(), ty.clone(), move |_, _, results| {
+                if !returns_errno {
+                    return Err(Error::new("unsupported WASI call"));
+                }
+                results[0] = Val::I32(ERRNO_NOSYS);
+                Ok(())
+            })?;
+        }
+        Ok(linker)
+    }
+}
+
+/// What a plugin instance can see of the shell
+#[derive(Debug, Default)]
+struct Host {
+    args: Vec<String>,
+    /// `NAME=value` pairs
+    env: Vec<String>,
+    /// Collects stdout instead of printing it, for segments
+    captured: Option<Vec<u8>>,
+    random: u64,
+}
+
+impl Host {
+    fn write(&mut self, fd: i32, bytes: &[u8]) -> i32 {
+        let result = match (fd, &mut self.captured) {
+            (1, Some(captured)) => {
+                captured.extend_from_slice(bytes);
+                Ok(())
+            }
+            (1, None) => io::stdout().write_all(bytes),
+            (2, _) => io::stderr().write_all(bytes),
+            _ => return ERRNO_BADF,
+        };
+        result.map_or(ERRNO_FAULT, |_| ERRNO_SUCCESS)
+    }
+
+    /// Xorshift seeded from the clock; good enough for hash maps, not for secrets
+    fn next_random(&mut self) -> u8 {
+        if self.random == 0 {
+            self.random = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |d| d.as_nanos() as u64)
+                | 1;
+        }
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        self.random as u8
+    }
+}
+
+fn with_memory(
+    caller: &mut Caller<Host>,
+    f: impl FnOnce(&mut [u8], &mut Host) -> Option<()>,
+) -> i32 {
+    let Some(memory) = caller.get_export("memory").and_then(|e| e.into_memory()) else {
+        return ERRNO_FAULT;
+    };
+    let (data, host) = Memory::data_and_store_mut(&memory, caller);
+    f(data, host).map_or(ERRNO_FAULT, |_| ERRNO_SUCCESS)
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn write_bytes(data: &mut [u8], at: i32, bytes: &[u8]) -> Option<()> {
+    let at = at as u32 as usize;
+    data.get_mut(at..at + bytes.len())?.copy_from_slice(bytes);
+    Some(())
+}
+
+fn strings_sizes(mut caller: Caller<Host>, strings: &[String], count: i32, size: i32) -> i32 {
+    let total: usize = strings.iter().map(|s| s.len() + 1).sum();
+    with_memory(&mut caller, |data, _| {
+        write_bytes(data, count, &(strings.len() as u32).to_le_bytes())?;
+        write_bytes(data, size, &(total as u32).to_le_bytes())
+    })
+}
+
+/// Copies NUL-terminated strings to `buffer` and their addresses to `ptrs`
+fn strings_get(mut caller: Caller<Host>, strings: &[String], ptrs: i32, buffer: i32) -> i32 {
+    with_memory(&mut caller, |data, _| {
+        let mut offset = buffer as u32;
+        for (i, string) in strings.iter().enumerate() {
+            write_bytes(data, ptrs + 4 * i as i32, &offset.to_le_bytes())?;
+            let mut bytes = string.as_bytes().to_vec();
+            bytes.push(0);
+            write_bytes(data, offset as i32, &bytes)?;
+            offset += bytes.len() as u32;
+        }
+        Some(())
+    })
+}
+
+fn fd_write(mut caller: Caller<Host>, fd: i32, iovs: i32, iovs_len: i32, written: i32) -> i32 {
+    let mut errno = ERRNO_SUCCESS;
+    let status = with_memory(&mut caller, |data, host| {
+        let mut bytes = Vec::new();
+        for i in 0..iovs_len as usize {
+            let iov = iovs as u32 as usize + i * 8;
+            let (ptr, len) = (
+                read_u32(data, iov)? as usize,
+                read_u32(data, iov + 4)? as usize,
+            );
+            bytes.extend_from_slice(data.get(ptr..ptr + len)?);
+        }
+        errno = host.write(fd, &bytes);
+        write_bytes(data, written, &(bytes.len() as u32).to_le_bytes())
+    });
+    if status == ERRNO_SUCCESS {
+        errno
+    } else {
+        status
+    }
+}
+
+/// A builtin run in a fresh sandbox each time
+struct WasmBuiltin {
+    name: &'static str,
+    description: &'static str,
+    plugin: Arc<WasmPlugin>,
+}
+
+impl Command for WasmBuiltin {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    /// The plugin sees its arguments exactly as typed
+    fn parses_flags(&self) -> bool {
+        false
+    }
+
+    fn execute(
+        &self,
+        args: &[&str],
+        _flags: &Flags,
+        context: &mut CommandContext,
+    ) -> ShellResult<ExitCode> {
+        let mut env: Vec<String> = context
+            .environment()
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        env.push(format!("PWD={}", context.cwd.display()));
+        let host = Host {
+            args: std::iter::once(self.name)
+                .chain(args.iter().copied())
+                .map(String::from)
+                .collect(),
+            env,
+            ..Host::default()
+        };
+
+        io::stdout().flush()?;
+        let (status, _) = self
+            .plugin
+            .run("_start", host, u64::MAX)
+            .map_err(|e| ShellError::builtin(format!("{}: {}", self.name, e)))?;
+        Ok(ExitCode::new(status))
+    }
+}
+
+struct WasmSegment {
+    plugin: Arc<WasmPlugin>,
+}
+
+impl SegmentSource for WasmSegment {
+    fn render(&self, cwd: &Path) -> Option<String> {
+        let host = Host {
+            env: vec![format!("PWD={}", cwd.display())],
+            captured: Some(Vec::new()),
+            ..Host::default()
+        };
+        let (_, host) = self.plugin.run("hermit_segment", host, SEGMENT_FUEL).ok()?;
+        let text = String::from_utf8_lossy(&host.captured?).into_owned();
+        Some(text.trim_end().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: &str = r#"
+        (module
+          (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "args_sizes_get" (func $args_sizes_get (param i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "args_get" (func $args_get (param i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+          (import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 100) "seg")
+
+          (func $print (param $ptr i32) (param $len i32)
+            (i32.store (i32.const 8) (local.get $ptr))
+            (i32.store (i32.const 12) (local.get $len))
+            (drop (call $fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 16))))
+
+          ;; Prints the first three bytes of argv[1] and exits with argc plus path_open's errno
+          (func (export "_start")
+            (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+            (drop (call $args_get (i32.const 200) (i32.const 300)))
+            (call $print (i32.load (i32.const 204)) (i32.const 3))
+            (call $proc_exit
+              (i32.add
+                (i32.load (i32.const 0))
+                (call $path_open (i32.const 0) (i32.const 0) (i32.const 0) (i32.const 0)
+                  (i32.const 0) (i64.const 0) (i64.const 0) (i32.const 0) (i32.const 0)))))
+
+          (func (export "hermit_segment")
+            (call $print (i32.const 100) (i32.const 3))))
+    "#;
+
+    fn write_module(dir: &Path, name: &str, wat: &str) -> std::path::PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_command_gets_args_and_exits() {
+        let plugin = WasmPlugin::new(&wat::parse_str(MODULE).unwrap()).unwrap();
+        let host = Host {
+            args: vec!["hello".to_string(), "abcdef".to_string()],
+            captured: Some(Vec::new()),
+            ..Host::default()
+        };
+        let (status, host) = plugin.run("_start", host, u64::MAX).unwrap();
+        assert_eq!(host.captured.unwrap(), b"abc");
+        assert_eq!(status, 2 + ERRNO_NOSYS);
+    }
+
+    #[test]
+    fn test_load_builtin_and_segment() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut plugins = load(&write_module(dir.path(), "hello.wasm", MODULE)).unwrap();
+        assert_eq!(plugins.take_builtins()[0].name(), "hello");
+        assert_eq!(
+            plugins.segments(Path::new("/")),
+            vec![crate::prompt::Segment::plain("seg")]
+        );
+    }
+
+    #[test]
+    fn test_segment_runs_out_of_fuel() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let spin = r#"(module (func (export "hermit_segment") (loop (br 0))))"#;
+        let plugins = load(&write_module(dir.path(), "spin.wasm", spin)).unwrap();
+        assert!(plugins.segments(Path::new("/")).is_empty());
+    }
+
+    #[test]
+    fn test_load_rejects_foreign_imports() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let foreign = r#"(module (import "env" "system" (func)) (func (export "_start")))"#;
+        let error = load(&write_module(dir.path(), "bad.wasm", foreign))
+            .err()
+            .unwrap();
+        assert!(error.contains("env.system"));
+    }
+}
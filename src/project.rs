@@ -0,0 +1,161 @@
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{color::Color, direnv::LoadedEnv, prompt::Segment};
+
+/// Name of the file that marks a project root
+const FILE: &str = ".hermit.toml";
+
+/// Settings a project carries in its `.hermit.toml`.
+///
+/// ```toml
+/// path = ["bin", "scripts"]
+///
+/// [aliases]
+/// t = "cargo test"
+///
+/// [prompt]
+/// label = "api"
+/// color = "magenta"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProjectConfig {
+    pub aliases: HashMap<String, String>,
+    /// Directories to put in front of `$PATH`, relative to the project root
+    pub path: Vec<PathBuf>,
+    pub prompt: ProjectPrompt,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProjectPrompt {
+    /// Shown in brackets before the prompt while inside the project
+    pub label: Option<String>,
+    pub color: Option<Color>,
+}
+
+impl ProjectConfig {
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+}
+
+/// Finds the `.hermit.toml` of the project containing `dir`, if any
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(FILE))
+        .find(|file| file.is_file())
+}
+
+/// A project config in effect, with what it replaced so leaving can undo it
+#[derive(Debug)]
+pub struct LoadedProject {
+    pub file: PathBuf,
+    variables: LoadedEnv,
+    aliases: LoadedEnv,
+    prompt: ProjectPrompt,
+}
+
+impl LoadedProject {
+    pub fn apply(
+        file: PathBuf,
+        config: ProjectConfig,
+        variables: &mut HashMap<String, String>,
+        aliases: &mut HashMap<String, String>,
+    ) -> Self {
+        let root = file.parent().unwrap_or(Path::new("/"));
+        let mut path: Vec<String> = config
+            .path
+            .iter()
+            .map(|dir| root.join(dir).display().to_string())
+            .collect();
+        let mut assignments = Vec::new();
+        if !path.is_empty() {
+            path.extend(variables.get("PATH").cloned());
+            assignments.push(("PATH".to_string(), path.join(":")));
+        }
+
+        Self {
+            variables: LoadedEnv::apply(file.clone(), assignments, variables),
+            aliases: LoadedEnv::apply(file.clone(), config.aliases.into_iter().collect(), aliases),
+            prompt: config.prompt,
+            file,
+        }
+    }
+
+    pub fn revert(
+        self,
+        variables: &mut HashMap<String, String>,
+        aliases: &mut HashMap<String, String>,
+    ) {
+        self.variables.revert(variables);
+        self.aliases.revert(aliases);
+    }
+
+    /// The prompt label, if the project sets one
+    pub fn segment(&self) -> Option<Segment> {
+        let label = format!("[{}]", self.prompt.label.as_ref()?);
+        Some(match self.prompt.color {
+            Some(color) => Segment::colored(label, color),
+            None => Segment::plain(label),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_revert() {
+        let config = ProjectConfig::parse(
+            "path = [\"bin\"]\n\
+             [aliases]\n\
+             t = \"cargo test\"\n\
+             ls = \"ls -a\"\n\
+             [prompt]\n\
+             label = \"api\"\n\
+             color = \"magenta\"\n",
+        )
+        .unwrap();
+
+        let mut variables = HashMap::from([("PATH".to_string(), "/usr/bin".to_string())]);
+        let mut aliases = HashMap::from([("ls".to_string(), "ls -l".to_string())]);
+        let project = LoadedProject::apply(
+            PathBuf::from("/work/api/.hermit.toml"),
+            config,
+            &mut variables,
+            &mut aliases,
+        );
+        assert_eq!(variables["PATH"], "/work/api/bin:/usr/bin");
+        assert_eq!(aliases["t"], "cargo test");
+        assert_eq!(aliases["ls"], "ls -a");
+        assert_eq!(
+            project.segment(),
+            Some(Segment::colored("[api]", Color::Magenta))
+        );
+
+        project.revert(&mut variables, &mut aliases);
+        assert_eq!(variables["PATH"], "/usr/bin");
+        assert_eq!(aliases["ls"], "ls -l");
+        assert!(!aliases.contains_key("t"));
+    }
+
+    #[test]
+    fn test_empty_config_changes_nothing() {
+        let mut variables = HashMap::new();
+        let mut aliases = HashMap::new();
+        let project = LoadedProject::apply(
+            PathBuf::from("/work/.hermit.toml"),
+            ProjectConfig::parse("").unwrap(),
+            &mut variables,
+            &mut aliases,
+        );
+        assert!(variables.is_empty() && aliases.is_empty());
+        assert_eq!(project.segment(), None);
+    }
+}
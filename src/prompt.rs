@@ -0,0 +1,247 @@
+use std::{
+    env,
+    sync::{Arc, RwLock},
+};
+
+use crate::{color::Color, config::PromptConfig, platform::PlatformInfo, utils};
+
+/// A piece of the prompt with its color.
+///
+/// Text and color are kept apart so the prompt can be measured and shortened
+/// on the plain text, without escape sequences throwing off the column count.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    text: String,
+    color: Option<Color>,
+    shrinkable: bool,
+}
+
+impl Segment {
+    pub fn plain(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            color: None,
+            shrinkable: false,
+        }
+    }
+
+    pub fn colored(text: impl Into<String>, color: Color) -> Self {
+        Self {
+            color: Some(color),
+            ..Self::plain(text)
+        }
+    }
+
+    /// Marks the segment as one that may lose leading characters when the prompt is too wide
+    pub fn shrinkable(mut self) -> Self {
+        self.shrinkable = true;
+        self
+    }
+
+    fn styled(&self) -> String {
+        match self.color {
+            Some(color) => color.paint(&self.text),
+            None => self.text.clone(),
+        }
+    }
+}
+
+/// Prompt pieces that only change with the config, looked up at startup and on reload
+#[derive(Debug, Clone)]
+pub struct StaticSegments {
+    pub user: String,
+    /// The OS name, or the hostname when the config asks for it
+    pub host: String,
+}
+
+impl StaticSegments {
+    pub fn detect(config: &PromptConfig) -> Self {
+        let platform = PlatformInfo::detect();
+        Self {
+            user: env::var("USER").unwrap_or_else(|_| "user".to_string()),
+            host: if config.show_hostname {
+                platform.hostname
+            } else {
+                platform.os_name
+            },
+        }
+    }
+}
+
+/// Shortens every directory but the last `full_dirs` to `length` characters,
+/// turning `~/work/hermit/src/commands` into `~/w/h/s/commands`.
+///
+/// Hidden directories keep their dot, so `.config` becomes `.c`. A `length` of
+/// 0 leaves the path as it is.
+pub fn abbreviate_dir(path: &str, length: usize, full_dirs: usize) -> String {
+    if length == 0 {
+        return path.to_string();
+    }
+    let parts: Vec<&str> = path.split('/').collect();
+    let keep_from = parts.len().saturating_sub(full_dirs.max(1));
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            if i >= keep_from || *part == "~" {
+                return part.to_string();
+            }
+            let dot = usize::from(part.starts_with('.'));
+            part.chars().take(length + dot).collect()
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// The prompt as rustyline sees it: plain text for cursor math, styled text for display
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderedPrompt {
+    pub plain: String,
+    pub styled: String,
+}
+
+impl RenderedPrompt {
+    /// Puts `below` on the next line, where the cursor ends up
+    pub fn above(self, below: RenderedPrompt) -> Self {
+        Self {
+            plain: format!("{}\n{}", self.plain, below.plain),
+            styled: format!("{}\n{}", self.styled, below.styled),
+        }
+    }
+}
+
+/// The rendered prompt shared with the line editor helper, which draws it on every refresh
+pub type SharedPrompt = Arc<RwLock<RenderedPrompt>>;
+
+/// Builds the prompt line out of segments
+#[derive(Debug, Clone, Default)]
+pub struct Prompt {
+    segments: Vec<Segment>,
+}
+
+impl Prompt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, segment: Segment) {
+        self.segments.push(segment);
+    }
+
+    pub fn extend(&mut self, segments: impl IntoIterator<Item = Segment>) {
+        self.segments.extend(segments);
+    }
+
+    /// Number of terminal columns the prompt occupies
+    pub fn width(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| utils::display_width(&segment.text))
+            .sum()
+    }
+
+    /// Shortens shrinkable segments from the left until the prompt fits in `max_width` columns
+    pub fn fit(mut self, max_width: usize) -> Self {
+        const ELLIPSIS: char = '…';
+
+        if self.width() <= max_width {
+            return self;
+        }
+
+        for i in 0..self.segments.len() {
+            if !self.segments[i].shrinkable {
+                continue;
+            }
+
+            let others: usize = self
+                .segments
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, segment)| utils::display_width(&segment.text))
+                .sum();
+            let available = max_width.saturating_sub(others);
+            let segment = &mut self.segments[i];
+            if utils::display_width(&segment.text) <= available {
+                continue;
+            }
+
+            let mut kept = String::new();
+            let mut kept_width = utils::display_width(&ELLIPSIS.to_string());
+            for c in segment.text.chars().rev() {
+                let char_width = utils::display_width(&c.to_string());
+                if kept_width + char_width > available {
+                    break;
+                }
+                kept.insert(0, c);
+                kept_width += char_width;
+            }
+            segment.text = format!("{}{}", ELLIPSIS, kept);
+        }
+
+        self
+    }
+
+    pub fn render(&self) -> RenderedPrompt {
+        RenderedPrompt {
+            plain: self.segments.iter().map(|s| s.text.as_str()).collect(),
+            styled: self.segments.iter().map(Segment::styled).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Prompt {
+        let mut prompt = Prompt::new();
+        prompt.push(Segment::colored("user", Color::Green));
+        prompt.push(Segment::plain(" "));
+        prompt.push(Segment::colored("~/projects/hermit/src", Color::Blue).shrinkable());
+        prompt.push(Segment::plain(" > "));
+        prompt
+    }
+
+    #[test]
+    fn test_render_plain_and_styled() {
+        let rendered = sample().render();
+        assert_eq!(rendered.plain, "user ~/projects/hermit/src > ");
+        assert!(rendered.styled.contains("~/projects/hermit/src"));
+        assert_eq!(sample().width(), rendered.plain.chars().count());
+    }
+
+    #[test]
+    fn test_fit_shrinks_from_the_left() {
+        let prompt = sample().fit(20);
+        assert_eq!(prompt.width(), 20);
+        assert_eq!(prompt.render().plain, "user …/hermit/src > ");
+    }
+
+    #[test]
+    fn test_two_line_prompt() {
+        let mut marker = Prompt::new();
+        marker.push(Segment::colored("> ", Color::Green));
+        let rendered = sample().render().above(marker.render());
+        assert_eq!(rendered.plain, "user ~/projects/hermit/src > \n> ");
+        assert!(rendered.styled.ends_with(&Color::Green.paint("> ")));
+    }
+
+    #[test]
+    fn test_abbreviate_dir() {
+        let path = "~/work/hermit/src/commands";
+        assert_eq!(abbreviate_dir(path, 1, 1), "~/w/h/s/commands");
+        assert_eq!(abbreviate_dir(path, 2, 2), "~/wo/he/src/commands");
+        assert_eq!(abbreviate_dir(path, 0, 1), path);
+        assert_eq!(abbreviate_dir("~/.config/hermit", 1, 1), "~/.c/hermit");
+        assert_eq!(abbreviate_dir("/usr/local/bin", 1, 1), "/u/l/bin");
+        assert_eq!(abbreviate_dir("~", 1, 1), "~");
+        assert_eq!(abbreviate_dir("/", 1, 1), "/");
+    }
+
+    #[test]
+    fn test_fit_keeps_short_prompts() {
+        let prompt = sample().fit(80);
+        assert_eq!(prompt.render().plain, "user ~/projects/hermit/src > ");
+    }
+}
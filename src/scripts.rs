@@ -0,0 +1,304 @@
+//! Scripts that extend the shell, written in hermit's own language and sourced
+//! from `~/.config/hermit/scripts/*.hsh` when an interactive shell starts.
+//!
+//! A script can set variables and aliases like any sourced file. Functions it
+//! defines with these names are called by the shell:
+//!
+//! - `hermit_segment_NAME` prints a prompt segment, left out when empty. The
+//!   segments are worked out in the background after each command, and the
+//!   prompt shows the last ones if they take too long
+//! - `hermit_complete_COMMAND` prints candidates for COMMAND's arguments, one
+//!   per line, when they are completed
+//! - `hermit_on_chpwd` and `hermit_on_error` run after the `[hooks]` of the
+//!   same name, with the same variables
+//!
+//! Segment and completion functions run in a `hermit -c` of their own, with
+//! the shell's functions, exported variables and directory, so they can't
+//! change the shell. `$HERMIT_GIT_BRANCH`, `$HERMIT_GIT_MODIFIED`,
+//! `$HERMIT_GIT_STAGED` and `$HERMIT_GIT_UNTRACKED` describe the repository
+//! the shell is in, if any.
+//!
+//! ```sh
+//! hermit_segment_node() { [ -f package.json ] && node --version; }
+//! hermit_complete_make() { sed -n 's/^\([a-z_-]*\):.*/\1/p' Makefile 2>/dev/null; }
+//! ```
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{config::Config, core::parser::Function};
+
+/// Functions that print a prompt segment
+pub const SEGMENT_PREFIX: &str = "hermit_segment_";
+/// Functions that print the completions for a command's arguments
+pub const COMPLETE_PREFIX: &str = "hermit_complete_";
+/// Run after the current directory changes
+pub const CHPWD_HOOK: &str = "hermit_on_chpwd";
+/// Run after a command fails
+pub const ERROR_HOOK: &str = "hermit_on_error";
+
+/// The scripts to source at startup, in name order
+pub fn files() -> Vec<PathBuf> {
+    Config::file("scripts").map_or_else(Vec::new, |dir| files_in(&dir))
+}
+
+/// The `.hsh` files in `dir`, sorted; a missing directory has none
+fn files_in(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "hsh") && path.is_file())
+        .collect();
+    files.sort();
+    files
+}
+
+/// The functions whose names start with `prefix`, sorted, each with the rest
+/// of its name
+pub fn named<'a>(
+    functions: &'a HashMap<String, Rc<Function>>,
+    prefix: &str,
+) -> Vec<(&'a str, &'a str)> {
+    let mut named: Vec<(&str, &str)> = functions
+        .keys()
+        .filter_map(|name| {
+            let rest = name.strip_prefix(prefix)?;
+            (!rest.is_empty()).then_some((name.as_str(), rest))
+        })
+        .collect();
+    named.sort();
+    named
+}
+
+/// The hook's command lines from the config, followed by the script function
+/// `name` if one is defined
+pub fn hook_lines(
+    lines: &[String],
+    name: &str,
+    functions: &HashMap<String, Rc<Function>>,
+) -> Vec<String> {
+    let mut lines = lines.to_vec();
+    if functions.contains_key(name) {
+        lines.push(name.to_string());
+    }
+    lines
+}
+
+/// Runs script functions in a process of their own, where neither the
+/// completer nor a thread working out the prompt can reach the shell
+#[derive(Debug, Clone, Default)]
+pub struct Runner {
+    /// The shell to run them with, this one
+    exe: PathBuf,
+    /// Every function's definition, so the one called can use the others
+    definitions: String,
+    env: HashMap<String, String>,
+    cwd: PathBuf,
+}
+
+impl Runner {
+    pub fn new(
+        exe: PathBuf,
+        functions: &HashMap<String, Rc<Function>>,
+        env: HashMap<String, String>,
+        cwd: PathBuf,
+    ) -> Self {
+        let mut definitions: Vec<&str> = functions
+            .values()
+            .map(|function| function.text.as_str())
+            .collect();
+        definitions.sort();
+        Self {
+            exe,
+            definitions: definitions.join("\n"),
+            env,
+            cwd,
+        }
+    }
+
+    /// What the function `name` prints; its errors would garble the line
+    /// being edited, so they are dropped
+    pub fn output(&self, name: &str) -> io::Result<String> {
+        let output = Command::new(&self.exe)
+            .arg("-c")
+            .arg(format!("{}\n{}", self.definitions, name))
+            .env_clear()
+            .envs(&self.env)
+            .current_dir(&self.cwd)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// The output of the segment functions, worked out on a background thread
+#[derive(Default)]
+pub struct Segments {
+    texts: Vec<String>,
+    pending: Option<Receiver<Vec<String>>>,
+    /// A run asked for while one was going, to start when it finishes
+    queued: Option<(Runner, Vec<String>)>,
+}
+
+impl Segments {
+    /// Starts running the functions `names` again. The last output stays
+    /// available until theirs arrives.
+    pub fn refresh(&mut self, runner: Runner, names: Vec<String>) {
+        if self.pending.is_some() {
+            self.queued = Some((runner, names));
+            return;
+        }
+        if names.is_empty() {
+            self.texts.clear();
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let texts = names
+                .iter()
+                .map(|name| runner.output(name).unwrap_or_default().trim().to_string())
+                .filter(|text| !text.is_empty())
+                .collect();
+            sender.send(texts).ok();
+        });
+
+        self.pending = Some(receiver);
+    }
+
+    /// The segments that came out non-empty, waiting at most `budget` for
+    /// a run in flight and the one queued behind it
+    pub fn texts(&mut self, budget: Duration) -> &[String] {
+        let deadline = Instant::now() + budget;
+        while let Some(pending) = &self.pending {
+            match pending.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(texts) => self.texts = texts,
+                Err(mpsc::RecvTimeoutError::Disconnected) => {}
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+            }
+            self.pending = None;
+            if let Some((runner, names)) = self.queued.take() {
+                self.refresh(runner, names);
+            }
+        }
+        &self.texts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser;
+
+    fn functions(names: &[&str]) -> HashMap<String, Rc<Function>> {
+        names
+            .iter()
+            .map(|name| {
+                let function = parser::parse_function(&format!("{}() {{ true; }}", name));
+                (name.to_string(), Rc::new(function))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_files_in() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        for name in ["b.hsh", "a.hsh", "notes.txt"] {
+            fs::write(tmp_dir.path().join(name), "").unwrap();
+        }
+        fs::create_dir(tmp_dir.path().join("dir.hsh")).unwrap();
+
+        let names: Vec<_> = files_in(tmp_dir.path())
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, ["a.hsh", "b.hsh"]);
+        assert!(files_in(&tmp_dir.path().join("missing")).is_empty());
+    }
+
+    #[test]
+    fn test_named() {
+        let functions = functions(&[
+            "hermit_segment_node",
+            "hermit_segment_",
+            "hermit_complete_make",
+            "hermit_segment_aws",
+            "greet",
+        ]);
+        assert_eq!(
+            named(&functions, SEGMENT_PREFIX),
+            [
+                ("hermit_segment_aws", "aws"),
+                ("hermit_segment_node", "node")
+            ]
+        );
+        assert_eq!(
+            named(&functions, COMPLETE_PREFIX),
+            [("hermit_complete_make", "make")]
+        );
+    }
+
+    fn sh_runner(definitions: &str, dir: &Path) -> Runner {
+        // The definitions are also valid sh, which stands in for hermit here
+        Runner {
+            exe: PathBuf::from("sh"),
+            definitions: definitions.to_string(),
+            env: HashMap::from([("GREETING".to_string(), "hi".to_string())]),
+            cwd: dir.to_path_buf(),
+        }
+    }
+
+    #[test]
+    fn test_runner_output() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let runner = sh_runner(
+            "where() { pwd; }\ngreet() { echo $GREETING; where; echo oops >&2; }",
+            tmp_dir.path(),
+        );
+        let output = runner.output("greet").unwrap();
+        let dir = tmp_dir.path().canonicalize().unwrap();
+        assert_eq!(output, format!("hi\n{}\n", dir.display()));
+    }
+
+    #[test]
+    fn test_segments() {
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let runner = sh_runner("a() { echo ' one '; }\nb() { :; }", tmp_dir.path());
+        let mut segments = Segments::default();
+        assert!(segments.texts(Duration::ZERO).is_empty());
+
+        segments.refresh(runner.clone(), vec!["a".to_string(), "b".to_string()]);
+        segments.refresh(runner, vec!["b".to_string()]);
+        assert!(segments.queued.is_some());
+        assert!(segments.texts(Duration::from_secs(10)).is_empty());
+
+        let runner = sh_runner("a() { echo one; }", tmp_dir.path());
+        segments.refresh(runner, vec!["a".to_string()]);
+        assert_eq!(segments.texts(Duration::from_secs(10)), ["one"]);
+    }
+
+    #[test]
+    fn test_hook_lines() {
+        let config = vec!["echo moved".to_string()];
+        assert_eq!(
+            hook_lines(&config, CHPWD_HOOK, &functions(&[])),
+            ["echo moved"]
+        );
+        assert_eq!(
+            hook_lines(&config, CHPWD_HOOK, &functions(&[CHPWD_HOOK])),
+            ["echo moved", CHPWD_HOOK]
+        );
+    }
+}
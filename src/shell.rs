@@ -1,66 +1,561 @@
-use colored::Colorize;
-use git2::Repository;
-use os_release::OsRelease;
-use rustyline::{error::ReadlineError, history::FileHistory, Editor};
+use rustyline::{
+    config::{CompletionType, Configurer},
+    error::ReadlineError,
+    history::{FileHistory, History},
+    Editor,
+};
 use std::{
-    env,
-    error::Error,
-    io::{self, Write},
-    path::PathBuf,
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::{self, IsTerminal, Write},
+    os::fd::{AsRawFd, OwnedFd, RawFd},
+    path::{Path, PathBuf},
+    process::Child,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
 };
 
 use crate::{
-    core::{completer::CommandCompleter, external::ExternalCommand, registry::CommandRegistry},
+    cloud,
+    color::Themes,
+    commands,
+    config::{Config, HooksConfig, PromptConfig, PromptLayout},
+    confirm::{self, Guard},
+    core::{
+        command::{CommandContext, ExitCode, Frame},
+        completer::{CommandCompleter, CompletionState, SharedCompletionState},
+        error::{ShellError, ShellResult},
+        expand,
+        external::{ExternalCommand, Priority, Redirect},
+        history,
+        lexer::{self, Quoting, Scanner, Token},
+        options::ShellOptions,
+        parser::{self, AndOr, Case, Command, Function, If, List, Pipeline, Redirection},
+        recorder::{self, Recorder},
+        registry::CommandRegistry,
+    },
+    direnv::{self, LoadedEnv},
     git::GitInfo,
+    glob,
+    inputrc::Inputrc,
+    locale::Message,
+    plugin::Plugins,
+    project::{self, LoadedProject, ProjectConfig},
+    prompt::{self, Prompt, Segment, SharedPrompt, StaticSegments},
+    scripts,
+    trust::TrustStore,
+    utils,
 };
 
-type ShellResult<T> = Result<T, Box<dyn Error>>;
+/// A command and its arguments, with the redirections taken out
+type Redirected<'a> = (&'a str, Vec<&'a str>, Vec<Redirect>);
+
+/// A variable as it was before [`Shell::set_vars`] set it for a command
+struct SavedVar {
+    name: String,
+    value: Option<String>,
+    exported: bool,
+}
+
+/// A command of a pipeline, ready to run
+enum Stage<'a> {
+    /// The expanded words and redirections of a simple command, whether
+    /// `command` or `builtin` in front of it skips functions, and the
+    /// `NAME=value` words before it, as written
+    Simple {
+        tokens: Vec<Token>,
+        skips_functions: bool,
+        assignments: Vec<String>,
+    },
+    Compound(&'a Command),
+}
+
+/// How a command of a pipeline runs
+enum Runs<'a> {
+    /// As a child process
+    External,
+    Builtin,
+    Function(Rc<Function>),
+    Source,
+    Compound(&'a Command),
+}
 
 /// Shell represents an interactive command-line interface that handles both built-in
 /// and external commands, with support for command history, git integration, and tab completion.
 pub struct Shell {
-    current_dir: PathBuf,
+    context: CommandContext,
+    registry: CommandRegistry,
+    completion: SharedCompletionState,
+    prompt: SharedPrompt,
     editor: Editor<CommandCompleter, FileHistory>,
+    static_segments: StaticSegments,
+    prompt_config: PromptConfig,
     git_info: Option<GitInfo>,
+    git_dir: PathBuf,
     history_path: PathBuf,
+    /// Whether background jobs write straight to the terminal, from `[jobs]`
+    jobs_pass_through: bool,
+    guard: Guard,
+    /// Time limits for commands matching each pattern, from `[[timeouts]]`
+    timeouts: Vec<(String, Duration)>,
+    /// Scheduling priorities for commands matching each pattern, from `[[priorities]]`
+    priorities: Vec<(String, Priority)>,
+    /// When the config file was last changed, to notice edits made outside the shell
+    config_modified: Option<SystemTime>,
+    /// Prompt segments contributed by plugins
+    plugins: Plugins,
+    trust: TrustStore,
+    /// Variables from the `.envrc` or `.env` that applies in the current directory
+    dir_env: Option<LoadedEnv>,
+    /// The `.hermit.toml` of the project the current directory is in
+    project: Option<LoadedProject>,
+    /// Env and project files the user declined to load this session
+    declined: HashSet<PathBuf>,
+    /// Whether this is a login shell, which runs the logout file on exit
+    login: bool,
+    /// Whether commands come from the user at a prompt, rather than from a
+    /// script or `-c`
+    interactive: bool,
+    /// How many `( ... )` groups are running, where `exit` only leaves the group
+    subshells: usize,
+    /// How many files `source` is running, where `return` leaves the file
+    sourced: usize,
+    /// How many `if` conditions and `!` pipelines are running, whose failures
+    /// neither `set -e` nor the error hook acts on
+    conditions: usize,
+    /// The completion style to go back to when screen-reader mode is turned off
+    saved_completion_type: Option<CompletionType>,
+    hooks: HooksConfig,
+    /// Whether a hook is running, so that hooks do not set each other off
+    in_hook: bool,
+    /// The shell's end of each `<(...)` and `>(...)` pipe of the command being
+    /// run, with the commands at the other end
+    substitutions: Vec<(OwnedFd, Vec<Child>)>,
+    /// The shell that runs script functions for the prompt and completion
+    script_exe: PathBuf,
+    /// The prompt segments printed by script functions
+    script_segments: scripts::Segments,
 }
 
 impl Shell {
     /// Creates a new Shell instance with initialized command completion, history, and git information.
+    #[cfg(test)]
     pub fn new() -> ShellResult<Self> {
-        let mut editor = Editor::new()?;
+        Self::with_options(ShellOptions::default())
+    }
+
+    /// Creates a shell with options from the command line already in effect
+    pub fn with_options(options: ShellOptions) -> ShellResult<Self> {
+        let config = Config::load();
+        let mut plugins = Plugins::load(&config.plugins);
+        for error in plugins.errors() {
+            eprintln!("hermit: plugin {}", error);
+        }
+        let registry = CommandRegistry::setup(plugins.take_builtins());
+        let mut shell = Self::build(options, &config, registry)?;
+        shell.plugins = plugins;
+        shell.interactive = true;
+
+        Self::setup_editor(
+            &mut shell.editor,
+            &shell.history_path,
+            shell.completion.clone(),
+            shell.prompt.clone(),
+        )?;
+        if let Some(path) = Inputrc::path() {
+            Self::apply_inputrc(&mut shell.editor, &Inputrc::load(&path));
+        }
+        shell.context.history = shell
+            .editor
+            .history()
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        shell.git_info = GitInfo::discover(&shell.context.cwd);
+        shell.trust = TrustStore::load();
+        shell.update_directory_files();
+        shell.load_scripts();
+        shell.refresh_script_segments();
+        shell.sync_completion_state();
+
+        Ok(shell)
+    }
+
+    /// Creates a shell to run a script or `-c` command. The config applies,
+    /// but history, the inputrc, plugins and env files are left alone.
+    pub fn non_interactive(options: ShellOptions) -> ShellResult<Self> {
+        Self::build(options, &Config::load(), CommandRegistry::setup(Vec::new()))
+    }
+
+    /// The parts of a shell that every kind of shell needs
+    fn build(
+        options: ShellOptions,
+        config: &Config,
+        registry: CommandRegistry,
+    ) -> ShellResult<Self> {
         let current_dir = env::current_dir()?;
         let history_path = Self::get_history_file_path();
+        let mut context = CommandContext::new(current_dir);
+        context.builtins = registry.get_commands();
+        context.options = options;
+        let level = context
+            .var("SHLVL")
+            .and_then(|level| level.parse::<u32>().ok())
+            .unwrap_or(0);
+        context.set_var("SHLVL", &(level + 1).to_string());
+        // `env diff` compares against the environment the shell starts with
+        context.env_snapshot = context.environment();
+        context.history_path = history_path.clone();
+        let git_dir = context.cwd.clone();
+
+        let mut shell = Self {
+            context,
+            registry,
+            completion: SharedCompletionState::default(),
+            prompt: SharedPrompt::default(),
+            editor: Editor::new()?,
+            static_segments: StaticSegments::detect(&config.prompt),
+            prompt_config: config.prompt.clone(),
+            git_info: None,
+            git_dir,
+            history_path,
+            jobs_pass_through: false,
+            guard: Guard::new(config.confirm.patterns.clone()),
+            timeouts: Vec::new(),
+            priorities: Vec::new(),
+            config_modified: Config::modified(),
+            plugins: Plugins::default(),
+            trust: TrustStore::default(),
+            dir_env: None,
+            project: None,
+            declined: HashSet::new(),
+            login: false,
+            interactive: false,
+            subshells: 0,
+            sourced: 0,
+            conditions: 0,
+            saved_completion_type: None,
+            hooks: HooksConfig::default(),
+            in_hook: false,
+            substitutions: Vec::new(),
+            script_exe: env::current_exe().unwrap_or_else(|_| PathBuf::from("hermit")),
+            script_segments: scripts::Segments::default(),
+        };
+        shell.apply_config(config);
+        Ok(shell)
+    }
 
-        Self::setup_editor(&mut editor, &history_path)?;
+    /// Runs a single builtin with the shell's config but none of its interactive
+    /// state, for `hermit exec`. Plugin builtins are left out so it starts quickly.
+    ///
+    /// Returns `None` if there is no builtin called `name`.
+    pub fn exec_builtin(name: &str, args: &[&str]) -> ShellResult<Option<ExitCode>> {
+        let config = Config::load();
+        let registry = CommandRegistry::setup(Vec::new());
+        let mut context = CommandContext::new(env::current_dir()?);
+        context.builtins = registry.get_commands();
+        context.history_path = Self::get_history_file_path();
+        context.color = config.color;
+        context.options.accessible = config.accessible;
+        context.themes = Themes::new(&config.themes);
+        if let Some(palette) = config.theme.and_then(|name| context.themes.select(&name)) {
+            context.palette = palette;
+        }
+        context.color_choice().apply(&context.variables);
 
-        let repo = Repository::discover(&current_dir).ok();
-        let git_info = repo.map(GitInfo::new);
+        registry.execute(name, args, &mut context)
+    }
 
-        Ok(Self {
-            current_dir,
-            editor,
-            git_info,
-            history_path,
-        })
+    fn reload_config(&mut self) {
+        self.config_modified = Config::modified();
+        self.apply_config(&Config::load());
+    }
+
+    /// Puts the settings from the config file into effect.
+    ///
+    /// Plugins are only loaded at startup; everything else can change while
+    /// the shell runs.
+    fn apply_config(&mut self, config: &Config) {
+        self.context.color = config.color;
+        self.context.options.accessible = config.accessible;
+        self.context.themes = Themes::new(&config.themes);
+        if let Some(name) = &config.theme {
+            match self.context.themes.select(name) {
+                Some(palette) => self.context.palette = palette,
+                None => eprintln!("hermit: unknown theme '{}'", name),
+            }
+        }
+        self.static_segments = StaticSegments::detect(&config.prompt);
+        self.prompt_config = config.prompt.clone();
+        self.guard.set_patterns(config.confirm.patterns.clone());
+        self.jobs_pass_through = config.jobs.pass_through;
+        self.hooks = config.hooks.clone();
+        self.timeouts = config
+            .timeouts
+            .iter()
+            .filter_map(|rule| match utils::parse_duration(&rule.after) {
+                Some(limit) => Some((rule.pattern.clone(), limit)),
+                None => {
+                    eprintln!("hermit: timeouts: invalid duration '{}'", rule.after);
+                    None
+                }
+            })
+            .collect();
+        self.priorities = config
+            .priorities
+            .iter()
+            .map(|rule| {
+                let priority = Priority {
+                    nice: rule.nice,
+                    idle_io: rule.idle_io,
+                };
+                (rule.pattern.clone(), priority)
+            })
+            .collect();
+        if let Err(e) = self.editor.set_max_history_size(config.history.max_size) {
+            eprintln!("hermit: history.max_size: {}", e);
+        }
+    }
+
+    /// Sets up a login shell: points `$SHELL` here and runs the profile.
+    ///
+    /// The profile is `~/.config/hermit/profile`, and the file run on exit is
+    /// `logout` next to it. Both hold one command line per line.
+    pub fn login(&mut self) {
+        self.login = true;
+        if let Ok(exe) = env::current_exe() {
+            self.context.set_var("SHELL", &exe.to_string_lossy());
+        }
+        if let Some(profile) = Config::file("profile") {
+            self.run_file(&profile);
+        }
+    }
+
+    /// Sources the scripts in the config directory, which extend the prompt,
+    /// completion and hooks; see [`scripts`]
+    fn load_scripts(&mut self) {
+        for file in scripts::files() {
+            self.source(&[file.to_string_lossy().to_string()]);
+        }
+        self.context.last_status = ExitCode::SUCCESS;
+    }
+
+    /// Runs script functions where the shell is, with the git variables set
+    fn script_runner(&self) -> scripts::Runner {
+        let mut env = self.context.environment();
+        if let Some(git) = &self.git_info {
+            env.extend(git.vars().map(|(name, value)| (name.to_string(), value)));
+        }
+        scripts::Runner::new(
+            self.script_exe.clone(),
+            &self.context.functions,
+            env,
+            self.context.cwd.clone(),
+        )
+    }
+
+    /// Starts working out the prompt segments script functions print
+    fn refresh_script_segments(&mut self) {
+        let names = scripts::named(&self.context.functions, scripts::SEGMENT_PREFIX)
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        let runner = self.script_runner();
+        self.script_segments.refresh(runner, names);
+    }
+
+    /// Runs the logout file if this is a login shell, only the first time it is called
+    fn logout(&mut self) {
+        if !std::mem::take(&mut self.login) {
+            return;
+        }
+        if let Some(logout) = Config::file("logout") {
+            self.run_file(&logout);
+        }
+    }
+
+    /// Runs each line of a file as if it had been typed; a missing file is skipped
+    fn run_file(&mut self, path: &Path) {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) => {
+                eprintln!("hermit: {}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        self.run_lines(&path.to_string_lossy(), &contents);
+        if !self.interactive {
+            return;
+        }
+        if let Err(e) = self.update_state() {
+            eprintln!("hermit: {}: {}", path.display(), e);
+        }
+    }
+
+    /// Runs a script without the prompt or line editor, with `args` as its
+    /// positional parameters, returning the status of its last command.
+    ///
+    /// A script that cannot be read fails with 127 if it is missing and 126
+    /// otherwise, as in other shells.
+    pub fn run_script(&mut self, path: &Path, args: Vec<String>) -> ExitCode {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("hermit: {}: {}", path.display(), e);
+                let code = if e.kind() == io::ErrorKind::NotFound {
+                    127
+                } else {
+                    126
+                };
+                return ExitCode::new(code);
+            }
+        };
+
+        let name = path.to_string_lossy().into_owned();
+        self.context.frame = Frame {
+            name: name.clone(),
+            args,
+        };
+        self.run_lines(&name, &contents);
+        self.context.last_status
+    }
+
+    /// Runs a command string, as `hermit -c` does, returning its status.
+    ///
+    /// The first of `args` becomes `$0` and the rest `$1` and on.
+    pub fn run_command_string(&mut self, command: &str, args: Vec<String>) -> ExitCode {
+        let mut args = args.into_iter();
+        if let Some(name) = args.next() {
+            self.context.frame.name = name;
+        }
+        self.context.frame.args = args.collect();
+        self.run_lines("-c", command);
+        self.context.last_status
+    }
+
+    /// Runs the lines of a file as commands, joining the lines of a construct
+    /// left open as the continuation prompt would. Errors are reported with
+    /// `source`, the file's name.
+    ///
+    /// Stops at a `return`, or an `exit` in a subshell, handing it on.
+    fn run_lines(&mut self, source: &str, contents: &str) -> Flow {
+        let posix = self.context.options.posix;
+        let mut lines = contents.lines();
+        while let Some(line) = lines.next() {
+            let mut unfinished = None;
+            let input = complete_input(line.to_string(), posix, |open| {
+                unfinished = Some(open);
+                lines.next().map(String::from)
+            });
+            let Some(input) = input else {
+                let open = unfinished.map_or("construct", Open::name);
+                eprintln!(
+                    "hermit: {}: unexpected end of file in an open {}",
+                    source, open
+                );
+                self.context.last_status = ExitCode::new(2);
+                return Flow::Continue;
+            };
+
+            let commands = self.transform_input(input);
+            match self.process_commands(&commands) {
+                Ok(Flow::Continue) => {}
+                Ok(flow) => return flow,
+                Err(e) => eprintln!("hermit: {}: {}", source, e),
+            }
+        }
+        Flow::Continue
+    }
+
+    /// Runs a file's commands in the shell itself, as `source` and `.` do,
+    /// with any arguments after its name as the positional parameters while
+    /// it runs. A `return` in the file ends it.
+    fn source(&mut self, args: &[String]) -> Flow {
+        let Some((file, args)) = args.split_first() else {
+            let message = "source: a file name is needed".to_string();
+            self.context.last_status = self.finish(Err(ShellError::Builtin { message, code: 2 }));
+            return Flow::Continue;
+        };
+        if self.too_deep(file) {
+            return Flow::Continue;
+        }
+        let contents = match fs::read_to_string(self.context.resolve_path(file)) {
+            Ok(contents) => contents,
+            Err(e) => {
+                let error = ShellError::builtin(format!("source: {}: {}", file, e));
+                self.context.last_status = self.finish(Err(error));
+                return Flow::Continue;
+            }
+        };
+
+        let saved = (!args.is_empty())
+            .then(|| std::mem::replace(&mut self.context.frame.args, args.to_vec()));
+        self.context.last_status = ExitCode::SUCCESS;
+        self.sourced += 1;
+        let flow = self.run_lines(file, &contents);
+        self.sourced -= 1;
+        if let Some(args) = saved {
+            self.context.frame.args = args;
+        }
+        match flow {
+            Flow::Return => Flow::Continue,
+            flow => flow,
+        }
     }
 
     fn setup_editor(
         editor: &mut Editor<CommandCompleter, FileHistory>,
         history_path: &PathBuf,
+        completion: SharedCompletionState,
+        prompt: SharedPrompt,
     ) -> ShellResult<()> {
-        let builtin = CommandRegistry::setup(editor.history());
-        let commands = builtin.get_commands();
-        let completer = CommandCompleter::new(commands);
+        let completer = CommandCompleter::new(completion, prompt);
 
         editor.set_helper(Some(completer));
-        editor.load_history(history_path)?;
+        if history_path.exists() {
+            editor.load_history(history_path)?;
+        }
 
         Ok(())
     }
 
+    /// Applies the settings and key bindings from a readline init file
+    fn apply_inputrc(editor: &mut Editor<CommandCompleter, FileHistory>, inputrc: &Inputrc) {
+        for error in &inputrc.errors {
+            eprintln!("hermit: {}", error);
+        }
+        if let Some(mode) = inputrc.edit_mode {
+            editor.set_edit_mode(mode);
+        }
+        if let Some(style) = inputrc.bell_style {
+            editor.set_bell_style(style);
+        }
+        if let Some(completion_type) = inputrc.completion_type {
+            editor.set_completion_type(completion_type);
+        }
+        if let Some(limit) = inputrc.completion_query_items {
+            editor.set_completion_prompt_limit(limit);
+        }
+        if let Some(timeout) = inputrc.keyseq_timeout {
+            editor.set_keyseq_timeout(Some(timeout));
+        }
+        if let (Some(ignore_case), Some(completer)) =
+            (inputrc.completion_ignore_case, editor.helper_mut())
+        {
+            completer.set_ignore_case(ignore_case);
+        }
+        for (key, cmd) in &inputrc.bindings {
+            editor.bind_sequence(*key, cmd.clone());
+        }
+    }
+
     /// Starts the main shell loop, processing user input until exit command is received.
-    pub fn run(&mut self) -> ShellResult<()> {
+    ///
+    /// Returns the status of the last command, for the shell to exit with.
+    pub fn run(&mut self) -> ShellResult<ExitCode> {
         while let Some(input) = self.read_input() {
             if input.is_empty() {
                 continue;
@@ -70,297 +565,2060 @@ impl Shell {
             self.update_state()?;
         }
 
+        let status = self.context.last_status;
+        self.logout();
         self.editor.save_history(&self.history_path)?;
-        Ok(())
+        Ok(status)
     }
 
-    fn process_commands(&mut self, commands: &[String]) -> ShellResult<()> {
+    /// Parses and runs each command in turn, stopping early for `exit` in a
+    /// subshell or `return`
+    fn process_commands(&mut self, commands: &[String]) -> ShellResult<Flow> {
         for command in commands {
-            let parts = self.parse_args(command);
-            if parts.is_empty() {
-                continue;
-            }
-
-            let (cmd, args) = parts.split_first().unwrap();
-            let expanded_args: Vec<String> =
-                args.iter().map(|arg| self.expand_tilde(arg)).collect();
-
-            if *cmd == "exit" {
-                return self.handle_exit();
+            let list = match parser::parse(command, &self.context.aliases) {
+                Ok(list) => list,
+                Err(e) => {
+                    let e = ShellError::from(e);
+                    self.report_error(&e);
+                    if self.context.options.posix {
+                        self.context.last_status = ExitCode::new(e.exit_code());
+                    }
+                    continue;
+                }
+            };
+            let flow = self.run_list(&list)?;
+            if flow != Flow::Continue {
+                return Ok(flow);
             }
+        }
+        Ok(Flow::Continue)
+    }
 
-            if let Err(e) = self.execute(cmd, &expanded_args) {
-                eprintln!("Error: {}", e);
+    /// Runs the and-or lists of a command line in turn
+    fn run_list(&mut self, list: &List) -> ShellResult<Flow> {
+        for item in &list.items {
+            let pipelines = &item.and_or.pipelines;
+            for (i, (and_or, pipeline)) in pipelines.iter().enumerate() {
+                // `a && b` runs b only if a succeeded, `a || b` only if it failed
+                let succeeded = self.context.last_status == ExitCode::SUCCESS;
+                match and_or {
+                    Some(AndOr::And) if !succeeded => continue,
+                    Some(AndOr::Or) if succeeded => continue,
+                    _ => {}
+                }
+                // `&` sends the last pipeline of the and-or list to the background
+                let background = item.background && i == pipelines.len() - 1;
+                let flow = self.run_pipeline(pipeline, background)?;
+                if flow != Flow::Continue {
+                    return Ok(flow);
+                }
+                // With `set -e` a failure stops everything, unless it was
+                // tested by `if`, `&&`, `||` or `!`
+                let tested = self.conditions > 0 || pipeline.negated || i < pipelines.len() - 1;
+                if self.context.options.errexit
+                    && !tested
+                    && self.context.last_status != ExitCode::SUCCESS
+                {
+                    return Ok(Flow::Exit);
+                }
             }
         }
-        Ok(())
+        Ok(Flow::Continue)
     }
 
-    fn handle_exit(&mut self) -> ShellResult<()> {
-        self.editor.save_history(&self.history_path)?;
-        std::process::exit(0);
+    /// Runs a pipeline, inverting its status if `!` leads it
+    fn run_pipeline(&mut self, pipeline: &Pipeline, background: bool) -> ShellResult<Flow> {
+        if !pipeline.negated {
+            return self.run_commands(pipeline, background);
+        }
+        // A failure that is turned into success is not an error
+        self.conditions += 1;
+        let result = self.run_commands(pipeline, background);
+        self.conditions -= 1;
+        let flow = result?;
+        self.context.last_status = self.context.last_status.negate();
+        Ok(flow)
     }
 
-    fn update_state(&mut self) -> ShellResult<()> {
-        self.current_dir = env::current_dir()?;
-        self.git_info = Repository::discover(&self.current_dir)
-            .ok()
-            .map(GitInfo::new);
-        Ok(())
-    }
+    /// Expands the commands of a pipeline and runs them
+    fn run_commands(&mut self, pipeline: &Pipeline, background: bool) -> ShellResult<Flow> {
+        // Substitutions of a command that ended up not running are done with too
+        self.finish_substitutions();
+        let compound = pipeline
+            .commands
+            .iter()
+            .find(|command| !matches!(command, Command::Simple(_)));
+        match (compound, pipeline.commands.as_slice()) {
+            (Some(command), _) if background => {
+                let message = format!("{} cannot run in the background", compound_name(command));
+                self.report_error(&ShellError::builtin(message));
+                self.context.last_status = ExitCode::FAILURE;
+                return Ok(Flow::Continue);
+            }
+            (Some(_), [command]) => return self.run_compound(command),
+            _ => {}
+        }
 
-    /// Expands the tilde (~) character in paths to the user's home directory.
-    fn expand_tilde(&self, path: &str) -> String {
-        if path.starts_with('~') {
-            if let Ok(home) = env::var("HOME") {
-                return path.replacen('~', &home, 1);
+        // `NAME=value command` sets NAME for that command alone, and a
+        // command of nothing but assignments sets them in the shell
+        let mut stages = Vec::with_capacity(pipeline.commands.len());
+        for command in &pipeline.commands {
+            let Command::Simple(command) = command else {
+                stages.push(Stage::Compound(command));
+                continue;
+            };
+            let assigned = command
+                .words
+                .iter()
+                .take_while(|word| expand::is_assignment(word))
+                .count();
+            let words = match self.command_words(&command.words[assigned..]) {
+                Ok(words) => words,
+                Err(e) => {
+                    self.report_error(&e);
+                    self.context.last_status = ExitCode::new(e.exit_code());
+                    return Ok(Flow::Continue);
+                }
+            };
+            // `command NAME` and `builtin NAME` run NAME even if a function has that name
+            let skips_functions = words.len() < command.words.len() - assigned;
+            let mut tokens: Vec<Token> = words.iter().cloned().map(Token::Word).collect();
+            tokens.extend(command.tokens().into_iter().skip(command.words.len()));
+            stages.push(Stage::Simple {
+                tokens,
+                skips_functions,
+                assignments: command.words[..assigned].to_vec(),
+            });
+        }
+        if let [Stage::Simple {
+            tokens,
+            assignments,
+            ..
+        }] = stages.as_slice()
+        {
+            if tokens.is_empty() {
+                self.context.last_status = match self.assign(assignments) {
+                    Ok(saved) => {
+                        // On their own they set shell variables, exporting nothing new
+                        for var in saved.into_iter().filter(|var| !var.exported) {
+                            self.context.exported.remove(&var.name);
+                        }
+                        self.context.refresh_path_cache();
+                        ExitCode::SUCCESS
+                    }
+                    Err(e) => self.finish(Err(e)),
+                };
+                return Ok(Flow::Continue);
             }
         }
-        path.to_string()
-    }
 
-    /// Returns the path to the shell history file.
-    fn get_history_file_path() -> PathBuf {
-        env::var("HOME")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| PathBuf::from("."))
-            .join(".hermit_history")
-    }
+        let mut expanded = Vec::with_capacity(stages.len());
+        for stage in stages {
+            let Stage::Simple {
+                tokens,
+                skips_functions,
+                assignments,
+            } = stage
+            else {
+                expanded.push(stage);
+                continue;
+            };
+            match self.expand_tokens(tokens) {
+                Ok(tokens) => expanded.push(Stage::Simple {
+                    tokens,
+                    skips_functions,
+                    assignments,
+                }),
+                Err(e) => {
+                    self.report_error(&e);
+                    self.context.last_status = ExitCode::new(e.exit_code());
+                    return Ok(Flow::Continue);
+                }
+            }
+        }
+        let stages = expanded;
+        // The words of the simple commands, for tracing and confirmation;
+        // the commands inside compound ones are traced as they run
+        let parts: Vec<String> = stages
+            .iter()
+            .filter_map(|stage| match stage {
+                Stage::Simple { tokens, .. } => Some(tokens),
+                Stage::Compound(_) => None,
+            })
+            .enumerate()
+            .flat_map(|(i, tokens)| {
+                let pipe = (i > 0).then(|| "|".to_string());
+                pipe.into_iter()
+                    .chain(tokens.iter().map(|t| t.text().to_string()))
+            })
+            .collect();
+        let (cmd, expanded_args) = match parts.split_first() {
+            Some((cmd, args)) => (cmd.as_str(), args),
+            None if compound.is_some() => ("", &[][..]),
+            None => {
+                self.context.last_status = ExitCode::SUCCESS;
+                return Ok(Flow::Continue);
+            }
+        };
+        let command = pipeline.text.as_str();
+        let leading = matches!(stages[0], Stage::Simple { .. });
 
-    /// Displays the shell prompt with username, distribution, current directory, and git information.
-    fn display_prompt(&self) {
-        print!("{}", self.get_prompt_info());
-        io::stdout().flush().unwrap();
-    }
+        if cmd == "exit" && leading {
+            let status = self.status_argument(cmd, expanded_args);
+            if self.subshells > 0 {
+                self.context.last_status = status;
+                return Ok(Flow::Exit);
+            }
+            self.handle_exit(status)?;
+            return Ok(Flow::Exit);
+        }
+        if cmd == "return" && leading {
+            if self.context.scopes.is_empty() && self.sourced == 0 {
+                let error =
+                    ShellError::builtin("return: can only be used in a function or sourced file");
+                self.context.last_status = self.finish(Err(error));
+                return Ok(Flow::Continue);
+            }
+            self.context.last_status = self.status_argument(cmd, expanded_args);
+            return Ok(Flow::Return);
+        }
 
-    /// Reads a line of input from the user, handling special cases like Ctrl-C and Ctrl-D.
-    fn read_input(&mut self) -> Option<Vec<String>> {
-        self.display_prompt();
+        if self.context.options.xtrace && !cmd.is_empty() {
+            self.trace(cmd, expanded_args);
+        }
+        // `set` still runs so that `set +n` can end a dry run
+        if self.context.options.noexec && cmd != "set" {
+            println!("would run: {}", Self::format_command(cmd, expanded_args));
+            self.context.last_status = ExitCode::SUCCESS;
+            return Ok(Flow::Continue);
+        }
 
-        match self.editor.readline(&self.get_prompt_info()) {
-            Ok(line) => {
-                self.editor.add_history_entry(&line).ok();
-                Some(self.transform_input(line))
+        let options = &self.context.options;
+        if !options.noconfirm && !options.posix && !self.confirm(cmd, expanded_args) {
+            eprintln!("{}", Message::NotRun.format(&[&cmd]));
+            self.context.last_status = ExitCode::FAILURE;
+            return Ok(Flow::Continue);
+        }
+
+        // Functions and sourced files run in the shell itself, which only
+        // one command at a time can
+        let in_shell = |stage: &Stage| match stage {
+            Stage::Simple {
+                tokens,
+                skips_functions,
+                ..
+            } => tokens.first().is_some_and(|name| {
+                let name = name.text();
+                let function = !skips_functions && self.context.function(name).is_some();
+                function || matches!(name, "source" | ".")
+            }),
+            Stage::Compound(_) => false,
+        };
+        if background && stages.iter().any(in_shell) {
+            let message = "functions and `source` cannot run in the background";
+            self.report_error(&ShellError::builtin(message));
+            self.context.last_status = ExitCode::FAILURE;
+            return Ok(Flow::Continue);
+        }
+        let call = match stages.as_slice() {
+            [stage @ Stage::Simple {
+                tokens,
+                skips_functions,
+                ..
+            }] if in_shell(stage) => Some((tokens, *skips_functions)),
+            _ => None,
+        };
+
+        // A command of a pipeline gets its assignments as it starts
+        let assignments = match stages.as_slice() {
+            [Stage::Simple { assignments, .. }] => assignments.as_slice(),
+            _ => &[],
+        };
+        let saved = match self.assign(assignments) {
+            Ok(saved) => saved,
+            Err(e) => {
+                self.report_error(&e);
+                self.context.last_status = ExitCode::new(e.exit_code());
+                return Ok(Flow::Continue);
             }
-            Err(ReadlineError::Interrupted) => Some(vec![]),
-            Err(ReadlineError::Eof) => None,
-            Err(_) => Some(vec![]),
+        };
+        if !saved.is_empty() {
+            self.context.refresh_path_cache();
+        }
+        if let Some((tokens, skips_functions)) = call {
+            let flow = match self.call_redirected(tokens, skips_functions) {
+                Ok(flow) => flow,
+                Err(e) => {
+                    self.report_error(&e);
+                    self.context.last_status = ExitCode::new(e.exit_code());
+                    Ok(Flow::Continue)
+                }
+            };
+            self.restore_vars(saved);
+            self.context.refresh_path_cache();
+            return flow;
+        }
+        // Variables like NO_COLOR may have changed since the last command
+        self.context.color_choice().apply(&self.context.variables);
+        let cwd = self.context.cwd.clone();
+        self.context.last_status = if background {
+            let result = match stages.as_slice() {
+                [Stage::Simple { tokens, .. }]
+                    if !tokens
+                        .iter()
+                        .any(|token| matches!(token, Token::Operator(_))) =>
+                {
+                    self.execute_background(cmd, expanded_args, command)
+                }
+                _ => Err(ShellError::builtin(
+                    "only simple commands can run in the background",
+                )),
+            };
+            self.finish(result)
+        } else {
+            match self.context.recorder.clone() {
+                Some(recorder) => recorder::capture(&recorder, || {
+                    let result = self.execute(&stages);
+                    self.finish(result)
+                })?,
+                None => {
+                    let result = self.execute(&stages);
+                    self.finish(result)
+                }
+            }
+        };
+        self.restore_vars(saved);
+        self.finish_substitutions();
+        self.context.refresh_path_cache();
+        if std::mem::take(&mut self.context.config_changed) {
+            self.reload_config();
         }
+        if self.context.cwd != cwd {
+            let lines = scripts::hook_lines(
+                &self.hooks.chpwd,
+                scripts::CHPWD_HOOK,
+                &self.context.functions,
+            );
+            self.run_hook(lines, &[])?;
+        }
+        if self.context.last_status != ExitCode::SUCCESS && self.conditions == 0 {
+            let status = self.context.last_status.code().to_string();
+            let vars = [("HERMIT_COMMAND", command), ("HERMIT_STATUS", &status)];
+            let lines = scripts::hook_lines(
+                &self.hooks.error,
+                scripts::ERROR_HOOK,
+                &self.context.functions,
+            );
+            self.run_hook(lines, &vars)?;
+        }
+        Ok(Flow::Continue)
     }
 
-    /// Generates the shell prompt string with colored components.
-    fn get_prompt_info(&self) -> String {
-        let username = env::var("USER").unwrap_or_else(|_| "user".to_string());
-        let distro = OsRelease::new()
-            .map(|os| os.name)
-            .unwrap_or_else(|_| "unknown".to_string());
-
-        let current_dir = self.format_current_dir();
-        let git_info = self
-            .git_info
-            .as_ref()
-            .map(|git| format!(" {}", git.get_info()))
-            .unwrap_or_default();
+    /// Runs the function or sourced file a simple command names, with its
+    /// redirections applied around it.
+    ///
+    /// The outer result is whether the redirections could be made, the inner
+    /// one that of the call.
+    fn call_redirected(
+        &mut self,
+        tokens: &[Token],
+        skips_functions: bool,
+    ) -> ShellResult<ShellResult<Flow>> {
+        let (words, redirects) = Redirect::extract(tokens).map_err(ShellError::Parse)?;
+        let Some((cmd, args)) = words.split_first() else {
+            return Ok(Ok(Flow::Continue));
+        };
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let function = self.context.function(cmd).filter(|_| !skips_functions);
+        self.redirected(&redirects, None, None, |shell| {
+            Ok(match function {
+                Some(function) => shell.call(&function, &args),
+                None => Ok(shell.source(&args)),
+            })
+        })
+    }
 
-        format!(
-            "{}@{} {}{} > ",
-            username.bright_green(),
-            distro.green(),
-            current_dir.bright_blue(),
-            git_info
-        )
+    /// Drops the `command` or `builtin` in front of a command's name, which
+    /// run the name itself rather than an alias; `builtin` also refuses
+    /// anything but a builtin
+    fn command_words<'a>(&self, words: &'a [String]) -> ShellResult<&'a [String]> {
+        match words {
+            [first, name, ..] if first == "command" && !name.starts_with('-') => Ok(&words[1..]),
+            [first, dashes, _, ..] if first == "command" && dashes == "--" => Ok(&words[2..]),
+            [first, name, ..] if first == "builtin" => {
+                commands::Builtin::check(&lexer::unquote(name), &self.context)?;
+                Ok(&words[1..])
+            }
+            _ => Ok(words),
+        }
     }
 
-    fn format_current_dir(&self) -> String {
-        if let Ok(home) = env::var("HOME") {
-            self.current_dir.display().to_string().replace(&home, "~")
-        } else {
-            self.current_dir.display().to_string()
+    /// Runs the command lines of a hook with `vars` set, leaving `$?` and the
+    /// variables as they were before.
+    ///
+    /// Commands run by the hook itself do not trigger further hooks.
+    fn run_hook(&mut self, lines: Vec<String>, vars: &[(&str, &str)]) -> ShellResult<()> {
+        if self.in_hook || lines.is_empty() {
+            return Ok(());
         }
+
+        let status = self.context.last_status;
+        let saved = self.set_vars(vars);
+        self.in_hook = true;
+        let result = lines.into_iter().try_for_each(|line| {
+            let commands = self.transform_input(line);
+            self.process_commands(&commands).map(|_| ())
+        });
+        self.in_hook = false;
+        self.restore_vars(saved);
+        self.context.last_status = status;
+        result
     }
 
-    /// Transforms raw input by removing comments and splitting into multiple commands.
-    fn transform_input(&self, input: String) -> Vec<String> {
-        input
-            .split('#')
-            .next() // Return the uncommented section
-            .unwrap_or("")
-            .split(';')
-            .map(str::trim)
-            .filter(|cmd| !cmd.is_empty())
-            .map(String::from)
+    /// Sets and exports `vars` for the commands about to run, returning what
+    /// they were before for [`Shell::restore_vars`]
+    fn set_vars(&mut self, vars: &[(&str, &str)]) -> Vec<SavedVar> {
+        vars.iter()
+            .map(|&(name, value)| {
+                let saved = SavedVar {
+                    name: name.to_string(),
+                    value: self.context.var(name).map(String::from),
+                    exported: !self.context.exported.insert(name.to_string()),
+                };
+                self.context.set_var(name, value);
+                saved
+            })
             .collect()
     }
 
-    /// Executes a command with its arguments, handling pipelines, redirections, and built-in commands.
-    fn execute(&mut self, command: &str, args: &[String]) -> ShellResult<()> {
-        if command.is_empty() {
-            return Ok(());
+    /// Puts back variables as [`Shell::set_vars`] found them, last set first
+    fn restore_vars(&mut self, saved: Vec<SavedVar>) {
+        for var in saved.into_iter().rev() {
+            match var.value {
+                Some(value) => self.context.set_var(&var.name, &value),
+                None => {
+                    self.context.unset_var(&var.name);
+                }
+            }
+            if !var.exported {
+                self.context.exported.remove(&var.name);
+            }
         }
+    }
 
-        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    /// Expands and sets the `NAME=value` words before a command in turn, so
+    /// each sees the ones before it, returning what they replaced
+    fn assign(&mut self, words: &[String]) -> ShellResult<Vec<SavedVar>> {
+        let mut saved = Vec::new();
+        for word in words {
+            match self.expand_word(word) {
+                Ok(assignment) => {
+                    let (name, value) = assignment.split_once('=').unwrap_or_default();
+                    saved.extend(self.set_vars(&[(name, value)]));
+                }
+                Err(e) => {
+                    self.restore_vars(saved);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(saved)
+    }
 
-        if let Some(pipeline) = self.try_parse_pipeline(command, &args) {
-            return self.execute_pipeline(&pipeline);
+    /// Runs `f` with `assignments` set, as `NAME=value command` sets them for
+    /// one command, and puts the variables back afterwards
+    fn with_assignments<T>(
+        &mut self,
+        assignments: &[String],
+        f: impl FnOnce(&mut Self) -> ShellResult<T>,
+    ) -> ShellResult<T> {
+        if assignments.is_empty() {
+            return f(self);
         }
+        let saved = self.assign(assignments)?;
+        self.context.refresh_path_cache();
+        let result = f(self);
+        self.restore_vars(saved);
+        self.context.refresh_path_cache();
+        result
+    }
 
-        if let Some((cmd, args, output)) = self.try_parse_redirects(command, &args) {
-            return self.execute_redirect(cmd, &args, &output);
+    /// Runs a command other than a simple one
+    fn run_compound(&mut self, command: &Command) -> ShellResult<Flow> {
+        match command {
+            Command::Simple(_) => Ok(Flow::Continue),
+            Command::Subshell(list) => {
+                self.context.last_status = self.run_subshell(list)?;
+                Ok(Flow::Continue)
+            }
+            Command::If(clause) => self.run_if(clause),
+            Command::Case(case) => self.run_case(case),
+            Command::Group(list) => self.run_list(list),
+            Command::Function(function) => {
+                let function = Rc::new(function.clone());
+                self.context
+                    .functions
+                    .insert(function.name.clone(), function);
+                self.context.last_status = ExitCode::SUCCESS;
+                Ok(Flow::Continue)
+            }
+            Command::Redirected(command, redirections) => {
+                let result = self
+                    .expand_redirections(redirections)
+                    .and_then(|redirects| {
+                        self.redirected(&redirects, None, None, |shell| {
+                            Ok(shell.run_compound(command))
+                        })
+                    });
+                match result {
+                    Ok(flow) => flow,
+                    Err(e) => {
+                        self.report_error(&e);
+                        self.context.last_status = ExitCode::new(e.exit_code());
+                        Ok(Flow::Continue)
+                    }
+                }
+            }
         }
+    }
 
-        self.execute_command(command, &args)
+    /// Expands the target words of the redirections after a compound command
+    fn expand_redirections(&mut self, redirections: &[Redirection]) -> ShellResult<Vec<Redirect>> {
+        let tokens = redirections.iter().flat_map(|redirection| {
+            let target = redirection.target.clone().map(Token::Word);
+            std::iter::once(Token::Operator(redirection.operator.clone())).chain(target)
+        });
+        let tokens = self.expand_tokens(tokens.collect())?;
+        let (_, redirects) = Redirect::extract(&tokens).map_err(ShellError::Parse)?;
+        Ok(redirects)
     }
 
-    fn execute_pipeline(&self, pipeline: &[(&str, Vec<&str>)]) -> ShellResult<()> {
-        let external = ExternalCommand::new(self.current_dir.clone());
-        Ok(external.execute_pipeline(pipeline)?)
+    /// Whether functions and sourced files are already nested as deep as they
+    /// may go, in which case `name` is not run and fails
+    fn too_deep(&mut self, name: &str) -> bool {
+        if self.context.scopes.len() + self.sourced < MAX_DEPTH {
+            return false;
+        }
+        let error = ShellError::builtin(format!("{}: nested more than {} deep", name, MAX_DEPTH));
+        self.context.last_status = self.finish(Err(error));
+        true
     }
 
-    fn execute_redirect(&self, cmd: &str, args: &[&str], output: &str) -> ShellResult<()> {
-        let external = ExternalCommand::new(self.current_dir.clone());
-        Ok(external.execute_redirect(cmd, args, output)?)
+    /// Runs a function with `args` as its positional parameters, putting
+    /// back the variables it made `local` when it returns
+    fn call(&mut self, function: &Function, args: &[String]) -> ShellResult<Flow> {
+        if self.too_deep(&function.name) {
+            return Ok(Flow::Continue);
+        }
+        let frame = Frame {
+            name: self.context.frame.name.clone(),
+            args: args.to_vec(),
+        };
+        let frame = std::mem::replace(&mut self.context.frame, frame);
+        self.context.scopes.push(HashMap::new());
+        let result = self.run_compound(&function.body);
+        for (name, value) in self.context.scopes.pop().unwrap_or_default() {
+            match value {
+                Some(value) => self.context.set_var(&name, &value),
+                None => {
+                    self.context.unset_var(&name);
+                }
+            }
+        }
+        self.context.frame = frame;
+        match result? {
+            Flow::Return => Ok(Flow::Continue),
+            flow => Ok(flow),
+        }
     }
 
-    fn execute_command(&mut self, command: &str, args: &[&str]) -> ShellResult<()> {
-        if self.execute_builtin(command, args)? {
-            Ok(())
-        } else {
-            self.execute_external(command, args)
+    /// Runs the list after the first condition of an `if` that holds, or its
+    /// `else` part if none does.
+    ///
+    /// A failing condition is not an error, so neither the error hook nor
+    /// `set -e` acts on it.
+    fn run_if(&mut self, clause: &If) -> ShellResult<Flow> {
+        for (condition, body) in &clause.branches {
+            self.conditions += 1;
+            let result = self.run_list(condition);
+            self.conditions -= 1;
+            let flow = result?;
+            if flow != Flow::Continue {
+                return Ok(flow);
+            }
+            if self.context.last_status == ExitCode::SUCCESS {
+                return self.run_list(body);
+            }
+        }
+        match &clause.otherwise {
+            Some(list) => self.run_list(list),
+            None => {
+                self.context.last_status = ExitCode::SUCCESS;
+                Ok(Flow::Continue)
+            }
         }
     }
 
-    /// Parses command line for output redirection.
-    fn try_parse_redirects<'a>(
-        &self,
-        command: &'a str,
-        args: &'a [&'a str],
-    ) -> Option<(&'a str, Vec<&'a str>, String)> {
-        let commands = std::iter::once(command)
-            .chain(args.iter().copied())
-            .collect::<Vec<_>>();
+    /// Runs the list of the first `case` item with a pattern matching the
+    /// expanded word. With no match the status is success.
+    fn run_case(&mut self, case: &Case) -> ShellResult<Flow> {
+        let body = match self.matching_item(case) {
+            Ok(body) => body,
+            Err(e) => {
+                self.report_error(&e);
+                self.context.last_status = ExitCode::new(e.exit_code());
+                return Ok(Flow::Continue);
+            }
+        };
+        match body {
+            Some(list) => self.run_list(list),
+            None => {
+                self.context.last_status = ExitCode::SUCCESS;
+                Ok(Flow::Continue)
+            }
+        }
+    }
 
-        if let Some(pos) = commands.iter().position(|&x| x == ">") {
-            if pos + 1 < commands.len() {
-                let output = commands[pos + 1].to_string();
-                let command = commands[0];
-                let args = commands[1..pos].to_vec();
-                return Some((command, args, output));
+    /// The list of the first `case` item that matches, expanding its patterns
+    /// only as far as needed
+    fn matching_item<'a>(&mut self, case: &'a Case) -> ShellResult<Option<&'a List>> {
+        let word = self.expand_word(&case.word)?;
+        for item in &case.items {
+            for pattern in &item.patterns {
+                if glob::matches(&expand::pattern(pattern, &mut self.context)?, &word) {
+                    return Ok(Some(&item.body));
+                }
             }
         }
-        None
+        Ok(None)
     }
 
-    /// Parses a command line into a pipeline of commands if pipe operators are present.
-    fn try_parse_pipeline<'a>(
-        &self,
-        command: &'a str,
-        args: &'a [&'a str],
-    ) -> Option<Vec<(&'a str, Vec<&'a str>)>> {
-        let commands = std::iter::once(command)
-            .chain(args.iter().copied())
-            .collect::<Vec<_>>();
+    /// Runs the commands of a `( ... )` group in a copy of the shell's state.
+    ///
+    /// Directory, variable, alias and option changes made inside are undone
+    /// afterwards, as if the group had run in a child process.
+    fn run_subshell(&mut self, group: &List) -> ShellResult<ExitCode> {
+        self.isolated(|shell| shell.run_list(group))
+            .map(|_| self.context.last_status)
+    }
 
-        if !commands.contains(&"|") {
-            return None;
-        }
+    /// Runs `f` as if in a child process: `exit` ends only what it runs,
+    /// and directory, variable, alias and option changes are undone after
+    fn isolated<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let cwd = self.context.cwd.clone();
+        let variables = self.context.variables.clone();
+        let exported = self.context.exported.clone();
+        let aliases = self.context.aliases.clone();
+        let functions = self.context.functions.clone();
+        let options = self.context.options.clone();
 
-        let mut pipeline = Vec::new();
-        let mut current_cmd = Vec::new();
+        self.subshells += 1;
+        let result = f(self);
+        self.subshells -= 1;
 
-        for &arg in &commands {
-            if arg == "|" {
-                if !current_cmd.is_empty() {
-                    pipeline.push((current_cmd[0], current_cmd[1..].to_vec()));
-                    current_cmd.clear();
-                }
-            } else {
-                current_cmd.push(arg);
+        self.context.cwd = cwd;
+        self.context.variables = variables;
+        self.context.exported = exported;
+        self.context.aliases = aliases;
+        self.context.functions = functions;
+        self.context.options = options;
+        self.context.refresh_path_cache();
+        result
+    }
+
+    /// Turns a command result into its exit status, reporting any error
+    fn finish(&self, result: ShellResult<ExitCode>) -> ExitCode {
+        match result {
+            Ok(status) => status,
+            Err(e) => {
+                self.report_error(&e);
+                ExitCode::new(e.exit_code())
             }
         }
+    }
+
+    /// Asks before running a command that matches a confirmation pattern.
+    ///
+    /// Answering `a` trusts that exact command for the rest of the session.
+    /// Without a terminal to ask on, matching commands are refused.
+    fn confirm(&mut self, cmd: &str, args: &[String]) -> bool {
+        let words: Vec<&str> = std::iter::once(cmd)
+            .chain(args.iter().map(String::as_str))
+            .collect();
+        let Some(pattern) = self.guard.check(&words) else {
+            return true;
+        };
+        if !io::stdin().is_terminal() {
+            eprintln!("{}", Message::NeedsConfirmation.format(&[&pattern]));
+            return false;
+        }
 
-        if !current_cmd.is_empty() {
-            pipeline.push((current_cmd[0], current_cmd[1..].to_vec()));
+        let question = Message::ConfirmRun.format(&[&Self::format_command(cmd, args), &pattern]);
+        match self.editor.readline(&question).as_deref().map(str::trim) {
+            Ok("y" | "Y" | "yes") => true,
+            Ok("a" | "A" | "always") => {
+                self.guard.trust(&words);
+                true
+            }
+            _ => false,
         }
+    }
 
-        Some(pipeline)
+    /// Prints a command as it is about to run, for `set -x`
+    fn trace(&self, cmd: &str, args: &[String]) {
+        eprintln!("{}", self.format_trace(cmd, args));
     }
 
-    fn execute_builtin(&mut self, command: &str, args: &[&str]) -> ShellResult<bool> {
-        let mut builtin = CommandRegistry::setup(self.editor.history());
-        builtin.execute(command, args)
+    fn format_trace(&self, cmd: &str, args: &[String]) -> String {
+        let ps4 = self.context.var("PS4").unwrap_or("+ ");
+        format!("{}{}", ps4, Self::format_command(cmd, args))
     }
 
-    fn execute_external(&self, command: &str, args: &[&str]) -> ShellResult<()> {
-        let external = ExternalCommand::new(self.current_dir.clone());
-        external.execute(command, args).map_err(|e| {
-            if e.kind() == io::ErrorKind::NotFound {
-                format!("command not found: {}", command).into()
-            } else {
-                e.into()
-            }
-        })
+    /// Joins a command back into one line, quoting words that need it
+    fn format_command(cmd: &str, args: &[String]) -> String {
+        let words: Vec<String> = std::iter::once(cmd)
+            .chain(args.iter().map(String::as_str))
+            .map(utils::quote)
+            .collect();
+        words.join(" ")
     }
 
-    /// Parses input string into command arguments, handling quoted strings.
-    pub fn parse_args(&self, input: &str) -> Vec<String> {
-        let mut parts = Vec::new();
-        let mut current_part = String::new();
-        let mut in_quotes = false;
+    /// Prints an error, suggesting a similarly named command when one could not be found.
+    fn report_error(&self, error: &ShellError) {
+        let label = self.context.palette.error.paint(Message::Error.text());
+        eprintln!("{} {}", label, error);
 
-        for c in input.chars() {
-            match c {
-                '"' => in_quotes = !in_quotes,
-                ' ' if !in_quotes => {
-                    if !current_part.is_empty() {
-                        parts.push(std::mem::take(&mut current_part));
-                    }
-                }
-                _ => current_part.push(c),
+        if let ShellError::CommandNotFound(name) = error {
+            let candidates = self
+                .context
+                .builtins
+                .iter()
+                .copied()
+                .filter(|name| self.context.is_builtin(name))
+                .chain(self.context.aliases.keys().map(String::as_str))
+                .chain(self.context.path_cache.names().map(String::as_str));
+            if let Some(suggestion) = utils::closest_match(name, candidates) {
+                eprintln!("{}", Message::DidYouMean.format(&[&suggestion]));
             }
         }
+    }
 
-        if !current_part.is_empty() {
-            parts.push(current_part);
+    /// Prints a notice for every background job that finished since the last prompt.
+    fn report_finished_jobs(&mut self) {
+        match self.context.jobs.reap() {
+            Ok(finished) => {
+                for job in finished {
+                    println!("{}", job);
+                    // Replay what the job printed that nobody asked to see yet
+                    let mut stdout = io::stdout();
+                    stdout.write_all(&job.output).ok();
+                    stdout.flush().ok();
+                }
+            }
+            Err(e) => {
+                let message = format!("failed to check background jobs: {}", e);
+                self.report_error(&ShellError::builtin(message));
+            }
         }
+    }
 
-        parts
+    /// Leaves the shell with `status`, after the logout file and saving history
+    fn handle_exit(&mut self, status: ExitCode) -> ShellResult<()> {
+        self.logout();
+        if self.interactive {
+            self.editor.save_history(&self.history_path)?;
+        }
+        io::stdout().flush().ok();
+        std::process::exit(status.code());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// The status `exit` or `return` was given, or the last one without an
+    /// argument. One that is not a number is an error, and gives 2.
+    fn status_argument(&self, cmd: &str, args: &[String]) -> ExitCode {
+        let Some(arg) = args.first() else {
+            return self.context.last_status;
+        };
+        match arg.parse() {
+            Ok(code) => ExitCode::new(code),
+            Err(_) => self.finish(Err(ShellError::Builtin {
+                message: format!("{}: {}: numeric argument required", cmd, arg),
+                code: 2,
+            })),
+        }
+    }
 
-    #[test]
-    fn test_shell_initialization() -> ShellResult<()> {
-        let shell = Shell::new()?;
-        assert!(shell.current_dir.is_absolute());
-        assert!(shell.history_path.ends_with(".hermit_history"));
+    /// Refreshes state derived from the working directory after a command has run.
+    fn update_state(&mut self) -> ShellResult<()> {
+        if self.git_dir != self.context.cwd {
+            self.git_info = GitInfo::discover(&self.context.cwd);
+            self.git_dir = self.context.cwd.clone();
+            self.update_directory_files();
+        } else if let Some(git) = &mut self.git_info {
+            // The command may have touched the working tree
+            git.refresh_status();
+        }
+        if Config::modified() != self.config_modified {
+            eprintln!("{}", Message::ConfigReloading);
+            self.reload_config();
+        }
+        self.refresh_script_segments();
+        self.sync_completion_state();
         Ok(())
     }
 
-    #[test]
-    fn test_parse_args() {
-        let shell = Shell::new().unwrap();
+    /// Loads the project and env files for the new directory, unloading those that applied before.
+    ///
+    /// A file is only read after the user trusts it, and is asked about again
+    /// whenever its contents change. The env file is loaded last and unloaded
+    /// first, so values both of them set are restored correctly.
+    fn update_directory_files(&mut self) {
+        // POSIX sh knows nothing of these files, so any loaded before are unloaded
+        let (project_file, env_file) = if self.context.options.posix {
+            (None, None)
+        } else {
+            (
+                project::find(&self.context.cwd),
+                direnv::find(&self.context.cwd),
+            )
+        };
+        let project_changed =
+            self.project.as_ref().map(|project| &project.file) != project_file.as_ref();
+        let env_changed =
+            project_changed || self.dir_env.as_ref().map(|env| &env.file) != env_file.as_ref();
 
-        assert_eq!(
-            shell.parse_args(r#"command "quoted arg" unquoted"#),
-            vec!["command", "quoted arg", "unquoted"]
-        );
+        if env_changed {
+            if let Some(env) = self.dir_env.take() {
+                eprintln!("hermit: unloading {}", env.file.display());
+                env.unexport(&mut self.context.exported);
+                env.revert(&mut self.context.variables);
+            }
+        }
+        if project_changed {
+            if let Some(project) = self.project.take() {
+                project.revert(&mut self.context.variables, &mut self.context.aliases);
+            }
+            if let Some(file) = project_file {
+                self.load_project(file);
+            }
+        }
+        if env_changed {
+            if let Some(file) = env_file {
+                self.load_dir_env(file);
+            }
+        }
+        self.context.refresh_path_cache();
+    }
 
-        assert_eq!(
-            shell.parse_args("command with multiple    spaces"),
-            vec!["command", "with", "multiple", "spaces"]
+    fn load_dir_env(&mut self, file: PathBuf) {
+        let Ok(contents) = fs::read_to_string(&file) else {
+            return;
+        };
+        if !self.trust.is_trusted(&file, &contents) && !self.ask_trust(&file, &contents) {
+            return;
+        }
+
+        let assignments = direnv::parse(&contents, &self.context.variables);
+        let mut env = LoadedEnv::apply(file, assignments, &mut self.context.variables);
+        env.export(&mut self.context.exported);
+        let names: Vec<&str> = env.names().collect();
+        eprintln!(
+            "hermit: loading {}: {}",
+            env.file.display(),
+            names.join(" ")
         );
+        self.dir_env = Some(env);
     }
 
-    #[test]
-    fn test_expand_tilde() {
+    fn load_project(&mut self, file: PathBuf) {
+        let Ok(contents) = fs::read_to_string(&file) else {
+            return;
+        };
+        if !self.trust.is_trusted(&file, &contents) && !self.ask_trust(&file, &contents) {
+            return;
+        }
+
+        match ProjectConfig::parse(&contents) {
+            Ok(config) => {
+                self.project = Some(LoadedProject::apply(
+                    file,
+                    config,
+                    &mut self.context.variables,
+                    &mut self.context.aliases,
+                ));
+            }
+            Err(e) => eprintln!("hermit: ignoring {}: {}", file.display(), e),
+        }
+    }
+
+    /// Asks whether to load an env or project file seen for the first time or since it changed
+    fn ask_trust(&mut self, file: &Path, contents: &str) -> bool {
+        if self.declined.contains(file) {
+            return false;
+        }
+        if !io::stdin().is_terminal() {
+            eprintln!("{}", Message::NotTrusted.format(&[&file.display()]));
+            self.declined.insert(file.to_path_buf());
+            return false;
+        }
+
+        let question = Message::LoadUntrusted.format(&[&file.display()]);
+        match self.editor.readline(&question).as_deref().map(str::trim) {
+            Ok("y" | "Y" | "yes") => {
+                if let Err(e) = self.trust.trust(file, contents) {
+                    eprintln!("{}", Message::TrustNotSaved.format(&[&e]));
+                }
+                true
+            }
+            _ => {
+                self.declined.insert(file.to_path_buf());
+                false
+            }
+        }
+    }
+
+    /// Publishes the names and directory the completer needs from the current context.
+    fn sync_completion_state(&self) {
+        let mut aliases: Vec<String> = self.context.aliases.keys().cloned().collect();
+        aliases.sort();
+        let mut functions: Vec<String> = self.context.functions.keys().cloned().collect();
+        functions.sort();
+
+        let mut state = self.completion.write().unwrap_or_else(|e| e.into_inner());
+        *state = CompletionState {
+            builtins: self
+                .context
+                .builtins
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            aliases,
+            functions,
+            arguments: scripts::named(&self.context.functions, scripts::COMPLETE_PREFIX)
+                .into_iter()
+                .map(|(name, command)| (command.to_string(), name.to_string()))
+                .collect(),
+            scripts: self.script_runner(),
+            executables: self.context.path_cache.clone(),
+            cwd: self.context.cwd.clone(),
+            accessible: self.context.options.accessible,
+        };
+    }
+
+    /// Expands a word as written into the argument a command receives; see [`expand::word`]
+    fn expand_word(&mut self, word: &str) -> ShellResult<String> {
+        expand::word(word, &mut self.context)
+    }
+
+    /// Expands each word of a command, letting patterns grow into several
+    /// words except where a redirection needs exactly one
+    fn expand_tokens(&mut self, tokens: Vec<Token>) -> ShellResult<Vec<Token>> {
+        let mut expanded = Vec::with_capacity(tokens.len());
+        let mut after_redirect = false;
+        for token in tokens {
+            let substituted = match &token {
+                Token::Word(word) => self.substitute_process(word)?,
+                Token::Operator(_) => None,
+            };
+            match token {
+                _ if substituted.is_some() => expanded.extend(substituted.map(Token::Word)),
+                Token::Word(word) if after_redirect => {
+                    expanded.push(Token::Word(self.expand_word(&word)?));
+                }
+                Token::Word(word) => expanded.extend(
+                    expand::fields(&word, &mut self.context)?
+                        .into_iter()
+                        .map(Token::Word),
+                ),
+                Token::Operator(_) => expanded.push(token),
+            }
+            // `|` and the `>&N` kind take no word after them
+            after_redirect = matches!(
+                expanded.last(),
+                Some(Token::Operator(op)) if op != "|" && !op.contains('&')
+            );
+        }
+        Ok(expanded)
+    }
+
+    /// Starts the commands of a `<(...)` or `>(...)` word on a pipe, returning
+    /// the `/dev/fd` path through which to read their output or write their input.
+    ///
+    /// Other words give `None`, as does everything under `set -n`, where
+    /// nothing is run.
+    fn substitute_process(&mut self, word: &str) -> ShellResult<Option<String>> {
+        let reading = word.starts_with("<(");
+        let inner = match word.strip_suffix(')') {
+            Some(rest) if reading || rest.starts_with(">(") => &rest[2..],
+            _ => return Ok(None),
+        };
+        if self.context.options.noexec {
+            return Ok(None);
+        }
+
+        let list = parser::parse(inner, &self.context.aliases)?;
+        let commands = match list.items.as_slice() {
+            [item] if !item.background && item.and_or.pipelines.len() == 1 => {
+                &item.and_or.pipelines[0].1.commands
+            }
+            _ => {
+                return Err(ShellError::Parse(format!(
+                    "{}: only a pipeline can be substituted",
+                    word
+                )))
+            }
+        };
+        let mut expanded = Vec::with_capacity(commands.len());
+        for command in commands {
+            match command {
+                Command::Simple(command) => expanded.push(self.expand_tokens(command.tokens())?),
+                other => {
+                    return Err(ShellError::Parse(format!(
+                        "{}: {} cannot be substituted",
+                        word,
+                        compound_name(other)
+                    )))
+                }
+            }
+        }
+        let mut stages: Vec<Redirected> = Vec::with_capacity(expanded.len());
+        for tokens in &expanded {
+            let (words, redirects) = Redirect::extract(tokens).map_err(ShellError::Parse)?;
+            let Some((command, args)) = words.split_first() else {
+                return Err(ShellError::Parse(
+                    "redirection without a command".to_string(),
+                ));
+            };
+            stages.push((command, args.to_vec(), redirects));
+        }
+
+        let (reader, writer) = os_pipe::pipe()?;
+        let external = self.context.external_command();
+        let (ours, spawned): (OwnedFd, _) = if reading {
+            let spawned = external.spawn_pipeline(&stages, None, Some(writer.into()));
+            (reader.into(), spawned)
+        } else {
+            let spawned = external.spawn_pipeline(&stages, Some(reader.into()), None);
+            (writer.into(), spawned)
+        };
+        let children = spawned.map_err(|e| ShellError::from_spawn(stages[0].0, e))?;
+        let path = format!("/dev/fd/{}", ours.as_raw_fd());
+        self.substitutions.push((ours, children));
+        Ok(Some(path))
+    }
+
+    /// Closes the shell's end of the process substitutions once their command
+    /// is done, so the commands on the other end see the pipe close, and reaps
+    /// them without waiting
+    fn finish_substitutions(&mut self) {
+        for (fd, children) in self.substitutions.drain(..) {
+            drop(fd);
+            for mut child in children {
+                std::thread::spawn(move || child.wait());
+            }
+        }
+    }
+
+    /// Returns the path to the shell history file.
+    pub fn get_history_file_path() -> PathBuf {
+        env::var("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("."))
+            .join(".hermit_history")
+    }
+
+    /// Lists completions as plain text while screen-reader mode is on, instead of cycling through them
+    fn sync_completion_type(&mut self) {
+        match (self.context.options.accessible, self.saved_completion_type) {
+            (true, None) => {
+                self.saved_completion_type = Some(self.editor.config_mut().completion_type());
+                self.editor.set_completion_type(CompletionType::List);
+            }
+            (false, Some(completion_type)) => {
+                self.saved_completion_type = None;
+                self.editor.set_completion_type(completion_type);
+            }
+            _ => {}
+        }
+    }
+
+    /// Reads a line of input from the user, handling special cases like Ctrl-C and Ctrl-D.
+    fn read_input(&mut self) -> Option<Vec<String>> {
+        self.report_finished_jobs();
+        self.sync_completion_type();
+        let prompt = self.refresh_prompt();
+
+        match self.editor.readline(&prompt) {
+            Ok(line) => {
+                self.record_input(&line);
+                let Some(line) = self.read_continuation(line) else {
+                    return Some(vec![]);
+                };
+                let line = match history::expand(&line, &self.context.history) {
+                    Ok(Some(expanded)) => {
+                        println!("{}", expanded);
+                        expanded
+                    }
+                    Ok(None) => line,
+                    Err(message) => {
+                        self.report_error(&ShellError::builtin(message));
+                        return Some(vec![]);
+                    }
+                };
+                self.record_history(&line);
+                Some(self.transform_input(line))
+            }
+            Err(ReadlineError::Interrupted) => Some(vec![]),
+            Err(ReadlineError::Eof) => None,
+            Err(_) => Some(vec![]),
+        }
+    }
+
+    /// Keeps reading lines under the continuation prompt until the input is complete.
+    ///
+    /// Returns `None` when the input is abandoned with Ctrl-C or ends in the middle
+    /// of a construct.
+    fn read_continuation(&mut self, input: String) -> Option<String> {
+        let posix = self.context.options.posix;
+        complete_input(input, posix, |open| {
+            let prompt = self.continuation_prompt(open);
+            match self.editor.readline(&prompt) {
+                Ok(line) => {
+                    self.record_input(&line);
+                    Some(line)
+                }
+                Err(ReadlineError::Eof) => {
+                    let message = format!("unexpected end of input in an open {}", open.name());
+                    self.report_error(&ShellError::Parse(message));
+                    None
+                }
+                Err(_) => None,
+            }
+        })
+    }
+
+    /// Renders the continuation prompt for an unfinished construct and publishes it
+    /// to the line editor, returning the plain text for `readline`.
+    ///
+    /// `$PS2` takes precedence over the prompt config, like `$PS4` does for traces.
+    fn continuation_prompt(&mut self, open: Open) -> String {
+        let template = match self.context.var("PS2") {
+            Some(ps2) => ps2.to_string(),
+            None => self.prompt_config.continuation.clone(),
+        };
+        let text = template.replace("{open}", open.name());
+
+        let mut prompt = Prompt::new();
+        prompt.push(match self.prompt_config.continuation_color {
+            Some(color) if !self.context.options.accessible => Segment::colored(text, color),
+            _ => Segment::plain(text),
+        });
+        let rendered = prompt.render();
+        let plain = rendered.plain.clone();
+        *self.prompt.write().unwrap_or_else(|e| e.into_inner()) = rendered;
+        plain
+    }
+
+    /// Adds the prompt and the typed line to the session recording, if one is running
+    fn record_input(&self, line: &str) {
+        let Some(recorder) = &self.context.recorder else {
+            return;
+        };
+        let Ok(mut recorder) = recorder.lock() else {
+            return;
+        };
+
+        // The line editor echoed the prompt and the typing straight to the terminal
+        let styled = self
+            .prompt
+            .read()
+            .map(|p| p.styled.clone())
+            .unwrap_or_default();
+        recorder
+            .output(format!("{}{}\r\n", styled, line).as_bytes())
+            .ok();
+        recorder.input(format!("{}\n", line).as_bytes()).ok();
+    }
+
+    /// Starts recording the session to `path`, as `record start` would
+    pub fn record_to(&mut self, path: &Path) -> ShellResult<()> {
+        let recorder = Recorder::create(path)?;
+        self.context.recorder = Some(Arc::new(Mutex::new(recorder)));
+        Ok(())
+    }
+
+    /// Adds a line to the editor history and mirrors it into the builtin context.
+    fn record_history(&mut self, line: &str) {
+        if !self.editor.add_history_entry(line).unwrap_or(false) {
+            return;
+        }
+
+        let history = &mut self.context.history;
+        history.push(line.to_string());
+        // The editor drops its oldest entries once it is full
+        let excess = history.len().saturating_sub(self.editor.history().len());
+        history.drain(..excess);
+    }
+
+    /// Renders the prompt for the current state and publishes it to the line editor.
+    ///
+    /// Returns the plain prompt text to hand to `readline`; the editor helper swaps in
+    /// the styled version whenever it draws, so there is exactly one prompt on screen.
+    fn refresh_prompt(&mut self) -> String {
+        self.context.color_choice().apply(&self.context.variables);
+        let mut prompt = self.build_prompt();
+        // Shortening would change the prompt from one line to the next
+        let accessible = self.context.options.accessible;
+        let fit = |prompt: Prompt, max_width| {
+            if accessible {
+                prompt
+            } else {
+                prompt.fit(max_width)
+            }
+        };
+
+        let rendered = match self.prompt_config.layout {
+            PromptLayout::OneLine => {
+                prompt.push(Segment::plain(" > "));
+                // Leave at least a third of the line for the command itself
+                fit(prompt, (utils::term_width() * 2 / 3).max(20)).render()
+            }
+            PromptLayout::TwoLine => {
+                // The command has the second line to itself; stopping short of the
+                // last column keeps the terminal from wrapping the first
+                let mut marker = Prompt::new();
+                marker.push(Segment::plain("> "));
+                let max_width = utils::term_width().saturating_sub(1).max(20);
+                fit(prompt, max_width).render().above(marker.render())
+            }
+        };
+        let plain = rendered.plain.clone();
+        *self.prompt.write().unwrap_or_else(|e| e.into_inner()) = rendered;
+        plain
+    }
+
+    /// Builds the prompt with username, distribution, current directory, and git information,
+    /// leaving the input marker to the layout.
+    ///
+    /// Static pieces come from the startup cache and the git status is computed in the
+    /// background, so building the prompt never walks the working tree itself.
+    fn build_prompt(&mut self) -> Prompt {
+        // Long enough for small repositories to show a fresh status right away
+        const GIT_STATUS_BUDGET: Duration = Duration::from_millis(5);
+        // Enough for a segment function that runs a quick command or two
+        const SCRIPT_SEGMENT_BUDGET: Duration = Duration::from_millis(50);
+
+        let mut prompt = Prompt::new();
+        let palette = self.context.palette;
+        // Screen readers get the same few pieces every time, without the
+        // git status that fills in later or output from plugins
+        if self.context.options.accessible {
+            prompt.push(Segment::plain(format!(
+                "{}@{} {}",
+                self.static_segments.user,
+                self.static_segments.host,
+                self.format_current_dir()
+            )));
+            return prompt;
+        }
+        if let Some(label) = self.project.as_ref().and_then(LoadedProject::segment) {
+            prompt.push(label);
+            prompt.push(Segment::plain(" "));
+        }
+        prompt.push(Segment::colored(&self.static_segments.user, palette.user));
+        prompt.push(Segment::plain("@"));
+        prompt.push(Segment::colored(&self.static_segments.host, palette.host));
+        prompt.push(Segment::plain(" "));
+        prompt.push(Segment::colored(self.format_current_dir(), palette.cwd).shrinkable());
+        if let Some(git) = &mut self.git_info {
+            prompt.push(Segment::plain(" "));
+            prompt.extend(git.segments(GIT_STATUS_BUDGET, &palette));
+        }
+        let cloud = cloud::segments(&self.context.variables, &self.prompt_config.cloud, &palette);
+        for segment in cloud {
+            prompt.push(Segment::plain(" "));
+            prompt.push(segment);
+        }
+        for segment in self.plugins.segments(&self.context.cwd) {
+            prompt.push(Segment::plain(" "));
+            prompt.push(segment);
+        }
+        for text in self.script_segments.texts(SCRIPT_SEGMENT_BUDGET) {
+            prompt.push(Segment::plain(" "));
+            prompt.push(Segment::plain(text));
+        }
+        prompt
+    }
+
+    /// The current directory as the prompt shows it, with `~` for home and
+    /// directories shortened as `[prompt]` asks
+    fn format_current_dir(&self) -> String {
+        let cwd = &self.context.cwd;
+        let dir = match self
+            .context
+            .var("HOME")
+            .and_then(|home| cwd.strip_prefix(home).ok())
+        {
+            Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+            Some(rest) => format!("~/{}", rest.display()),
+            None => cwd.display().to_string(),
+        };
+        let config = &self.prompt_config;
+        prompt::abbreviate_dir(&dir, config.dir_length, config.full_dirs)
+    }
+
+    /// Transforms raw input by moving here-document bodies onto their commands,
+    /// removing comments and splitting into multiple commands.
+    ///
+    /// In posix mode a `#` only starts a comment at the beginning of a word,
+    /// so `echo a#b` keeps its argument.
+    fn transform_input(&self, input: String) -> Vec<String> {
+        let (input, _) = lexer::inline_heredocs(&input);
+        let comment = lexer::comment_start(&input, self.context.options.posix);
+        Self::split_commands(&input[..comment.unwrap_or(input.len())])
+            .into_iter()
+            .map(str::trim)
+            .filter(|cmd| !cmd.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// Splits a line at `;`, except inside quotes, a `( ... )` group or a
+    /// compound command such as `if ... fi`
+    fn split_commands(line: &str) -> Vec<&str> {
+        // A line that does not tokenize stays whole, for the parser to report
+        let separators = parser::outline(line).map_or(Vec::new(), |outline| outline.separators);
+        let mut commands = Vec::new();
+        let mut start = 0;
+        for i in separators {
+            commands.push(&line[start..i]);
+            start = i + 1;
+        }
+        commands.push(&line[start..]);
+        commands
+    }
+
+    /// Executes the expanded commands of a pipeline, handling pipes, redirections,
+    /// and built-in commands.
+    fn execute(&mut self, stages: &[Stage]) -> ShellResult<ExitCode> {
+        let mut commands: Vec<Redirected> = Vec::with_capacity(stages.len());
+        let mut runs = Vec::with_capacity(stages.len());
+        let mut assigned: Vec<&[String]> = Vec::with_capacity(stages.len());
+        for stage in stages {
+            let (tokens, skips_functions, assignments) = match stage {
+                Stage::Simple {
+                    tokens,
+                    skips_functions,
+                    assignments,
+                } => (tokens, *skips_functions, assignments),
+                Stage::Compound(command) => {
+                    commands.push(("", Vec::new(), Vec::new()));
+                    runs.push(Runs::Compound(command));
+                    assigned.push(&[]);
+                    continue;
+                }
+            };
+            let (words, redirects) = Redirect::extract(tokens).map_err(ShellError::Parse)?;
+            match words.split_first() {
+                Some((command, args)) => {
+                    runs.push(self.runs(command, skips_functions));
+                    commands.push((command, args.to_vec(), redirects));
+                    assigned.push(assignments);
+                }
+                None if redirects.is_empty() => {}
+                None => {
+                    return Err(ShellError::Parse(
+                        "redirection without a command".to_string(),
+                    ))
+                }
+            }
+        }
+
+        match commands.as_slice() {
+            [] => Ok(ExitCode::SUCCESS),
+            [("", _, _)] => Ok(ExitCode::SUCCESS),
+            [(command, args, redirects)] if redirects.is_empty() => {
+                self.execute_command(command, args)
+            }
+            [(command, args, redirects)] if self.context.is_builtin(command) => {
+                self.redirected(redirects, None, None, |shell| {
+                    shell.execute_command(command, args)
+                })
+            }
+            [(command, args, redirects)] => self.execute_redirect(command, args, redirects),
+            _ => self.execute_pipeline(&commands, &runs, &assigned),
+        }
+    }
+
+    /// How the command `name` runs as part of a pipeline
+    fn runs(&self, name: &str, skips_functions: bool) -> Runs<'static> {
+        match self.context.function(name) {
+            Some(function) if !skips_functions => Runs::Function(function),
+            _ if matches!(name, "source" | ".") => Runs::Source,
+            _ if self.context.is_builtin(name) => Runs::Builtin,
+            _ => Runs::External,
+        }
+    }
+
+    /// Runs a command of a pipeline in the shell itself. `exit` ends only
+    /// that command, and all but the last command of the pipeline run in a
+    /// copy of the shell's state, as if in a child process.
+    fn run_stage(
+        &mut self,
+        runs: &Runs,
+        cmd: &str,
+        args: &[&str],
+        last: bool,
+    ) -> ShellResult<ExitCode> {
+        if !last {
+            return self.isolated(|shell| shell.run_stage(runs, cmd, args, true));
+        }
+        let owned: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        self.subshells += 1;
+        let result = match runs {
+            Runs::External | Runs::Builtin => self.execute_command(cmd, args),
+            Runs::Function(function) => self
+                .call(function, &owned)
+                .map(|_| self.context.last_status),
+            Runs::Source => {
+                self.source(&owned);
+                Ok(self.context.last_status)
+            }
+            Runs::Compound(command) => self.run_compound(command).map(|_| self.context.last_status),
+        };
+        self.subshells -= 1;
+        result
+    }
+
+    /// Runs `f` with the shell's own descriptors pointed where `redirects`
+    /// say, on top of `stdin` and `stdout` when they are given, so that the
+    /// builtins it runs read and write through them
+    fn redirected<T>(
+        &mut self,
+        redirects: &[Redirect],
+        stdin: Option<OwnedFd>,
+        stdout: Option<OwnedFd>,
+        f: impl FnOnce(&mut Self) -> ShellResult<T>,
+    ) -> ShellResult<T> {
+        let external = self
+            .context
+            .external_command()
+            .with_no_clobber(self.context.options.noclobber);
+        let _swapped = external.redirect_shell(redirects, stdin, stdout)?;
+        f(self)
+    }
+
+    /// Starts an external command in the background and registers it in the job table.
+    fn execute_background(
+        &mut self,
+        command: &str,
+        args: &[String],
+        command_line: &str,
+    ) -> ShellResult<ExitCode> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let external = self
+            .context
+            .external_command()
+            .with_inherited_fds(self.substituted_fds());
+        let child = if self.jobs_pass_through {
+            external.spawn_background(command, &args)
+        } else {
+            external.spawn_background_piped(command, &args)
+        }
+        .map_err(|e| ShellError::from_spawn(command, e))?;
+
+        let pid = child.id();
+        self.context.last_background = Some(pid);
+        let id = self.context.jobs.add(child, command_line);
+        println!("[{}] {}", id, pid);
+        Ok(ExitCode::SUCCESS)
+    }
+
+    /// Creates an executor for `command`, following the first `[[timeouts]]` and
+    /// `[[priorities]]` rules it matches
+    fn external_command(&self, command: &str, args: &[&str]) -> ExternalCommand {
+        let words: Vec<&str> = std::iter::once(command)
+            .chain(args.iter().copied())
+            .collect();
+        let timeout = self
+            .timeouts
+            .iter()
+            .find(|(pattern, _)| confirm::matches(pattern, &words))
+            .map(|(_, limit)| *limit);
+        let priority = self
+            .priorities
+            .iter()
+            .find(|(pattern, _)| confirm::matches(pattern, &words))
+            .map(|(_, priority)| *priority)
+            .unwrap_or_default();
+        self.context
+            .external_command()
+            .with_timeout(timeout)
+            .with_priority(priority)
+            .with_inherited_fds(self.substituted_fds())
+    }
+
+    /// The shell's descriptors that `/dev/fd` arguments of the current command name
+    fn substituted_fds(&self) -> Vec<RawFd> {
+        self.substitutions
+            .iter()
+            .map(|(fd, _)| fd.as_raw_fd())
+            .collect()
+    }
+
+    /// Runs the commands of a pipeline, each reading the output of the one
+    /// before it.
+    ///
+    /// Programs are all started first. The builtins, functions and compound
+    /// commands among the commands then run in turn, with the shell's own
+    /// descriptors pointed at their pipes.
+    fn execute_pipeline(
+        &mut self,
+        stages: &[Redirected],
+        runs: &[Runs],
+        assignments: &[&[String]],
+    ) -> ShellResult<ExitCode> {
+        let plain = runs
+            .iter()
+            .all(|runs| matches!(runs, Runs::External | Runs::Builtin));
+        if plain && stages.iter().all(|(_, _, redirects)| redirects.is_empty()) {
+            let pipeline: Vec<(&str, Vec<&str>)> = stages
+                .iter()
+                .map(|(cmd, args, _)| (*cmd, args.clone()))
+                .collect();
+            let pipeline = pipeline.as_slice();
+            if self.context.options.structured {
+                if let Some(table) = self.registry.execute_records(pipeline, &mut self.context)? {
+                    println!("{}", table.render());
+                    return Ok(ExitCode::SUCCESS);
+                }
+            } else if let Some(command) = self.registry.transforms_records(pipeline, &self.context)
+            {
+                return Err(ShellError::builtin(format!(
+                    "{}: needs structured pipelines, which `set -o structured` turns on",
+                    command
+                )));
+            }
+        }
+
+        let builtins: Vec<bool> = runs
+            .iter()
+            .map(|runs| !matches!(runs, Runs::External))
+            .collect();
+        let mut inputs: Vec<Option<OwnedFd>> = vec![None];
+        let mut outputs: Vec<Option<OwnedFd>> = Vec::new();
+        for i in 1..stages.len() {
+            let (reader, writer) = os_pipe::pipe()?;
+            let mut reader = OwnedFd::from(reader);
+            // Builtins run one at a time, so a command writing to one that
+            // has not started would wait for it forever, and a builtin
+            // writing to a reader that is gone would fail
+            if builtins[i - 1] || (builtins[i] && builtins[..i].contains(&true)) {
+                reader = ExternalCommand::relay(reader, builtins[i - 1])?;
+            }
+            outputs.push(Some(writer.into()));
+            inputs.push(Some(reader));
+        }
+        outputs.push(None);
+
+        let (first, first_args, _) = &stages[0];
+        let external = self
+            .external_command(first, first_args)
+            .with_no_clobber(self.context.options.noclobber);
+        let mut statuses = vec![ExitCode::SUCCESS; stages.len()];
+        let mut children = Vec::new();
+        for (i, (cmd, args, redirects)) in stages.iter().enumerate() {
+            if builtins[i] {
+                continue;
+            }
+            let result = self.with_assignments(assignments[i], |shell| {
+                // The environment is taken as the command is made
+                let external = shell
+                    .external_command(first, first_args)
+                    .with_no_clobber(shell.context.options.noclobber);
+                external
+                    .spawn(cmd, args, redirects, inputs[i].take(), outputs[i].take())
+                    .map_err(|e| ShellError::from_spawn(cmd, e))
+            });
+            match result {
+                Ok(child) => children.push((i, child)),
+                Err(e) => statuses[i] = self.finish(Err(e)),
+            }
+        }
+        let last = stages.len() - 1;
+        for (i, (cmd, args, redirects)) in stages.iter().enumerate() {
+            if builtins[i] {
+                let (input, output) = (inputs[i].take(), outputs[i].take());
+                let result = self.with_assignments(assignments[i], |shell| {
+                    shell.redirected(redirects, input, output, |shell| {
+                        shell.run_stage(&runs[i], cmd, args, i == last)
+                    })
+                });
+                statuses[i] = self.finish(result);
+            }
+        }
+
+        let (indices, processes): (Vec<usize>, Vec<Child>) = children.into_iter().unzip();
+        for (i, status) in indices.into_iter().zip(external.wait(first, processes)?) {
+            statuses[i] = status;
+        }
+        Ok(external.pipeline_status(&statuses))
+    }
+
+    /// Runs `cmd` with its file descriptors redirected
+    fn execute_redirect(
+        &self,
+        cmd: &str,
+        args: &[&str],
+        redirects: &[Redirect],
+    ) -> ShellResult<ExitCode> {
+        let external = self
+            .external_command(cmd, args)
+            .with_no_clobber(self.context.options.noclobber);
+        external
+            .execute_redirect(cmd, args, redirects)
+            .map_err(|e| ShellError::from_spawn(cmd, e))
+    }
+
+    fn execute_command(&mut self, command: &str, args: &[&str]) -> ShellResult<ExitCode> {
+        match self.execute_builtin(command, args)? {
+            Some(status) => Ok(status),
+            None => self.execute_external(command, args),
+        }
+    }
+
+    fn execute_builtin(&mut self, command: &str, args: &[&str]) -> ShellResult<Option<ExitCode>> {
+        self.registry.execute(command, args, &mut self.context)
+    }
+
+    fn execute_external(&self, command: &str, args: &[&str]) -> ShellResult<ExitCode> {
+        let external = self.external_command(command, args);
+        external
+            .execute(command, args)
+            .map_err(|e| ShellError::from_spawn(command, e))
+    }
+
+    /// Splits a command into words and operators, honouring quotes and backslashes.
+    ///
+    /// The words are left as written; [`Shell::expand_word`] turns them into arguments.
+    #[cfg(test)]
+    pub fn parse_args(&self, input: &str) -> ShellResult<Vec<Token>> {
+        lexer::tokenize(input).map_err(ShellError::Parse)
+    }
+}
+
+/// How deep functions and sourced files may nest before the shell gives up on them
+const MAX_DEPTH: usize = 100;
+
+/// What to call a command other than a simple one in error messages
+fn compound_name(command: &Command) -> &'static str {
+    match command {
+        Command::Simple(_) => "commands",
+        Command::Subshell(_) => "subshells",
+        Command::If(_) => "`if` commands",
+        Command::Case(_) => "`case` commands",
+        Command::Group(_) => "`{ }` groups",
+        Command::Function(_) => "function definitions",
+        Command::Redirected(command, _) => compound_name(command),
+    }
+}
+
+/// Whether to go on with the commands after the one just run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Continue,
+    /// `exit` ran in a subshell, or a command failed with `set -e`, which
+    /// ends the subshell or script there
+    Exit,
+    /// `return` ran in a function, which ends there
+    Return,
+}
+
+/// A construct left open at the end of a line, which continues on the next one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Open {
+    Quote,
+    HereDoc,
+    Pipe,
+    And,
+    Or,
+    Group,
+    /// A compound command, by the reserved word opening it
+    Compound(&'static str),
+    /// A function definition whose body is still to come, as after `name()`
+    Function,
+    Backslash,
+}
+
+impl Open {
+    /// The name `{open}` expands to in the continuation prompt
+    fn name(self) -> &'static str {
+        match self {
+            Self::Quote => "quote",
+            Self::HereDoc => "heredoc",
+            Self::Pipe => "pipe",
+            Self::And => "and",
+            Self::Or => "or",
+            Self::Group => "subshell",
+            Self::Compound(word) => word,
+            Self::Function => "function",
+            Self::Backslash => "backslash",
+        }
+    }
+}
+
+/// Adds lines from `next_line` to `input` until it leaves no construct open,
+/// or returns `None` once `next_line` has no line for the construct it is given.
+///
+/// Comments are dropped from the lines joined, except in quotes and
+/// here-documents, since the line they end up in goes on after them.
+fn complete_input(
+    mut input: String,
+    posix: bool,
+    mut next_line: impl FnMut(Open) -> Option<String>,
+) -> Option<String> {
+    let without_comment =
+        |line: &str| line[..lexer::comment_start(line, posix).unwrap_or(line.len())].to_string();
+    if open_construct(&input).is_some() {
+        input = without_comment(&input);
+    }
+    while let Some(open) = open_construct(&input) {
+        let mut line = next_line(open)?;
+        if !matches!(open, Open::Quote | Open::HereDoc) {
+            line = without_comment(&line);
+        }
+
+        // What follows a here-document must not run into its delimiter line
+        let after_heredoc = lexer::inline_heredocs(&input).0 != input;
+        match open {
+            Open::Quote | Open::HereDoc => input.push('\n'),
+            Open::Backslash => {
+                input.pop();
+            }
+            Open::Pipe | Open::And | Open::Or if after_heredoc => input.push('\n'),
+            Open::Pipe | Open::And | Open::Or | Open::Function => input.push(' '),
+            Open::Group | Open::Compound(_) => {
+                // No `;` where a command has yet to come, as after `(` or `then`
+                let separate = !parser::outline(&input).is_ok_and(|outline| outline.continues);
+                if after_heredoc {
+                    input.push('\n');
+                }
+                if separate {
+                    input.push(';');
+                }
+                input.push(' ');
+            }
+        }
+        input.push_str(&line);
+    }
+    Some(input)
+}
+
+/// The construct `input` leaves unfinished, if any: a here-document without
+/// its delimiter line, an unclosed quote or `(`, a trailing `|`, `&&` or `||`,
+/// a trailing backslash, a function without its body, or a compound command
+/// such as `if` without its end.
+fn open_construct(input: &str) -> Option<Open> {
+    let (input, awaited) = lexer::inline_heredocs(input);
+    if awaited.is_some() {
+        return Some(Open::HereDoc);
+    }
+    let mut depth = 0usize;
+    // The last two characters that are not unquoted whitespace
+    let mut last = [None; 2];
+    let mut scanner = Scanner::new(&input);
+    for (_, c, quoting) in scanner.by_ref() {
+        if quoting == Quoting::Unquoted {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        if !c.is_whitespace() || quoting != Quoting::Unquoted {
+            last = [last[1], Some((c, quoting))];
+        }
+    }
+
+    if scanner.in_quotes() {
+        Some(Open::Quote)
+    } else if scanner.in_escape() {
+        Some(Open::Backslash)
+    } else {
+        let unquoted = |c| Some((c, Quoting::Unquoted));
+        match last {
+            [a, b] if a == unquoted('&') && b == a => Some(Open::And),
+            [a, b] if a == unquoted('|') && b == a => Some(Open::Or),
+            [_, b] if b == unquoted('|') => Some(Open::Pipe),
+            [a, b] if a == unquoted('(') && b == unquoted(')') => Some(Open::Function),
+            _ if depth > 0 => Some(Open::Group),
+            _ => match parser::outline(&input) {
+                Ok(outline) => outline.open.last().map(|&word| Open::Compound(word)),
+                Err(_) => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_initialization() -> ShellResult<()> {
+        let shell = Shell::new()?;
+        assert!(shell.context.cwd.is_absolute());
+        assert!(shell.history_path.ends_with(".hermit_history"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_construct() {
+        assert_eq!(open_construct("echo hi"), None);
+        assert_eq!(open_construct(r#"echo "one"#), Some(Open::Quote));
+        assert_eq!(open_construct(r#"echo "(|" ok"#), None);
+        assert_eq!(open_construct("ls |  "), Some(Open::Pipe));
+        assert_eq!(open_construct("make &&"), Some(Open::And));
+        assert_eq!(open_construct("test -f x || "), Some(Open::Or));
+        assert_eq!(open_construct("echo '&&'"), None);
+        assert_eq!(open_construct("(cd /tmp; ls"), Some(Open::Group));
+        assert_eq!(open_construct("make \\"), Some(Open::Backslash));
+        assert_eq!(open_construct("echo \\\\"), None);
+        assert_eq!(open_construct("echo 'it\\"), Some(Open::Quote));
+        assert_eq!(open_construct("echo '|'"), None);
+        assert_eq!(open_construct("echo '(' ok"), None);
+        assert_eq!(open_construct("if true"), Some(Open::Compound("if")));
+        assert_eq!(open_construct("case x in"), Some(Open::Compound("case")));
+        assert_eq!(
+            open_construct("case x in a) b;;"),
+            Some(Open::Compound("case"))
+        );
+        assert_eq!(
+            open_construct("if a; then if b; then c; fi"),
+            Some(Open::Compound("if"))
+        );
+        assert_eq!(open_construct("if a; then b; fi"), None);
+        assert_eq!(open_construct("echo if"), None);
+        assert_eq!(open_construct("greet ( )"), Some(Open::Function));
+        assert_eq!(open_construct("greet() {"), Some(Open::Compound("{")));
+        assert_eq!(open_construct("greet() { a; }"), None);
+        assert_eq!(open_construct("echo '()'"), None);
+    }
+
+    /// The first pipeline of a line as the shell parses it, and whether it
+    /// goes to the background
+    fn first_pipeline(shell: &Shell, line: &str) -> (Pipeline, bool) {
+        let list = parser::parse(line, &shell.context.aliases).unwrap();
+        let item = &list.items[0];
+        (item.and_or.pipelines[0].1.clone(), item.background)
+    }
+
+    fn first_command(shell: &Shell, line: &str) -> Vec<Token> {
+        match &first_pipeline(shell, line).0.commands[0] {
+            Command::Simple(command) => command.tokens(),
+            _ => panic!("{} starts with a compound command", line),
+        }
+    }
+
+    /// The words of `tokens` as a command receives them, operators in brackets
+    fn words(tokens: Vec<Token>) -> Vec<String> {
+        tokens
+            .iter()
+            .map(|token| match token {
+                Token::Word(word) => lexer::unquote(word),
+                Token::Operator(operator) => format!("<{}>", operator),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
         let shell = Shell::new().unwrap();
+
+        assert_eq!(
+            words(
+                shell
+                    .parse_args(r#"command "quoted arg" unquoted"#)
+                    .unwrap()
+            ),
+            vec!["command", "quoted arg", "unquoted"]
+        );
+
+        assert_eq!(
+            words(shell.parse_args("command with multiple    spaces").unwrap()),
+            vec!["command", "with", "multiple", "spaces"]
+        );
+
+        assert_eq!(
+            words(shell.parse_args(r#"echo 'it''s' a\ b "x|y"|wc"#).unwrap()),
+            vec!["echo", "its", "a b", "x|y", "<|>", "wc"]
+        );
+
+        assert!(matches!(
+            shell.parse_args(r#"echo "unterminated"#),
+            Err(ShellError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let mut shell = Shell::new().unwrap();
         let home = env::var("HOME").unwrap();
 
-        assert_eq!(shell.expand_tilde("~/test"), format!("{}/test", home));
-        assert_eq!(shell.expand_tilde("/absolute/path"), "/absolute/path");
+        assert_eq!(
+            shell.expand_word("~/test").unwrap(),
+            format!("{}/test", home)
+        );
+        assert_eq!(
+            shell.expand_word("/absolute/path").unwrap(),
+            "/absolute/path"
+        );
+    }
+
+    #[test]
+    fn test_error_hook() {
+        let mut shell = Shell::new().unwrap();
+        shell.hooks.error = vec!["cd /".to_string(), "false".to_string()];
+        let start = shell.context.cwd.clone();
+
+        shell.process_commands(&["! false".to_string()]).unwrap();
+        assert_eq!(shell.context.cwd, start);
+
+        shell.process_commands(&["false".to_string()]).unwrap();
+        assert_eq!(shell.context.cwd, PathBuf::from("/"));
+        assert_eq!(shell.context.last_status, ExitCode::FAILURE);
+        assert_eq!(shell.context.var("HERMIT_STATUS"), None);
+    }
+
+    #[test]
+    fn test_script_functions() {
+        let mut shell = Shell::new().unwrap();
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        shell.context.cwd = tmp_dir.path().to_path_buf();
+        // The functions are also valid sh, which stands in for hermit here
+        shell.script_exe = PathBuf::from("sh");
+        let script = "hermit_complete_make() { printf 'build\\ntest\\n'; false; }\n\
+                      hermit_segment_mark() { printf ' marked\\n'; }\n\
+                      hermit_on_error() { R=$R$HERMIT_STATUS; }\n\
+                      hermit_on_chpwd() { R=moved; }\n";
+        fs::write(tmp_dir.path().join("script.hsh"), script).unwrap();
+        let mut run = |line: &str| {
+            let commands = shell.transform_input(line.to_string());
+            shell.process_commands(&commands).unwrap();
+            shell.context.var("R").unwrap_or_default().to_string()
+        };
+
+        run("source script.hsh");
+        assert_eq!(run("R=; sh -c 'exit 3'"), "3");
+        assert_eq!(run("R=; if false; then :; fi; ! false; ! true"), "");
+        assert_eq!(run("cd /"), "moved");
+
+        run("false");
+        shell.update_state().unwrap();
+        let arguments = shell.completion.read().unwrap().arguments.clone();
+        assert_eq!(arguments["make"], "hermit_complete_make");
+        shell.script_segments.texts(Duration::from_secs(10));
+        let prompt = shell.build_prompt().render();
+        assert!(prompt.plain.ends_with(" marked"));
+        assert_eq!(shell.context.last_status, ExitCode::FAILURE);
+    }
+
+    #[test]
+    fn test_last_status() {
+        let mut shell = Shell::new().unwrap();
+
+        shell.process_commands(&["false".to_string()]).unwrap();
+        assert_eq!(shell.context.last_status, ExitCode::FAILURE);
+
+        shell.process_commands(&["true".to_string()]).unwrap();
+        assert_eq!(shell.context.last_status, ExitCode::SUCCESS);
+
+        shell
+            .process_commands(&["definitely-not-a-command".to_string()])
+            .unwrap();
+        assert_eq!(shell.context.last_status, ExitCode::new(127));
+
+        shell
+            .process_commands(&["R=$?; (exit 4); R=$R-$?".to_string()])
+            .unwrap();
+        assert_eq!(shell.context.var("R"), Some("127-4"));
+        shell
+            .process_commands(&["(false; exit)".to_string()])
+            .unwrap();
+        assert_eq!(shell.context.last_status, ExitCode::FAILURE);
+        shell.process_commands(&["(exit x)".to_string()]).unwrap();
+        assert_eq!(shell.context.last_status, ExitCode::new(2));
+    }
+
+    #[test]
+    fn test_cd_changes_shell_directory() {
+        let mut shell = Shell::new().unwrap();
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let target = tmp_dir.path().canonicalize().unwrap();
+
+        let command = format!("cd {}", target.display());
+        shell.process_commands(&[command]).unwrap();
+        assert_eq!(shell.context.cwd, target);
+        assert_eq!(
+            shell
+                .external_command("true", &[])
+                .execute("true", &[])
+                .unwrap(),
+            ExitCode::SUCCESS
+        );
+    }
+
+    #[test]
+    fn test_format_trace() {
+        let mut shell = Shell::new().unwrap();
+        let args = vec!["-l".to_string(), "my file".to_string()];
+        assert_eq!(shell.format_trace("ls", &args), "+ ls -l 'my file'");
+
+        shell.context.set_var("PS4", ">> ");
+        assert_eq!(shell.format_trace("pwd", &[]), ">> pwd");
+    }
+
+    #[test]
+    fn test_expand_alias() {
+        let mut shell = Shell::new().unwrap();
+        shell
+            .context
+            .aliases
+            .insert("ll".to_string(), "ls -l".to_string());
+
+        assert_eq!(
+            words(first_command(&shell, "ll src")),
+            vec!["ls", "-l", "src"]
+        );
+
+        assert_eq!(words(first_command(&shell, "pwd")), vec!["pwd"]);
+
+        assert_eq!(words(first_command(&shell, "'ll'")), vec!["ll"]);
+    }
+
+    #[test]
+    fn test_record_history() {
+        let mut shell = Shell::new().unwrap();
+        let before = shell.context.history.len();
+
+        shell.record_history("echo recorded");
+        assert_eq!(shell.context.history.len(), before + 1);
+        assert_eq!(shell.context.history.last().unwrap(), "echo recorded");
+
+        // Consecutive duplicates are ignored by the editor and the mirror alike
+        shell.record_history("echo recorded");
+        assert_eq!(shell.context.history.len(), before + 1);
+    }
+
+    #[test]
+    fn test_split_background() {
+        let shell = Shell::new().unwrap();
+        let split = |line| {
+            let (pipeline, background) = first_pipeline(&shell, line);
+            (pipeline.text, background)
+        };
+        assert_eq!(split("sleep 5 &"), ("sleep 5".to_string(), true));
+        assert_eq!(split("sleep 5&"), ("sleep 5".to_string(), true));
+        assert_eq!(split("sleep 5"), ("sleep 5".to_string(), false));
     }
 
     #[test]
@@ -376,5 +2634,598 @@ mod tests {
             shell.transform_input("cmd1;; cmd2".to_string()),
             vec!["cmd1", "cmd2"]
         );
+
+        assert_eq!(
+            shell.transform_input("(cd /tmp; ls); echo \"a;b\"".to_string()),
+            vec!["(cd /tmp; ls)", "echo \"a;b\""]
+        );
+
+        assert_eq!(
+            shell.transform_input("echo \"issue #42\"; echo 'a;b' # c;d".to_string()),
+            vec!["echo \"issue #42\"", "echo 'a;b'"]
+        );
+
+        assert_eq!(
+            shell.transform_input("if a; then b; else c; fi; d".to_string()),
+            vec!["if a; then b; else c; fi", "d"]
+        );
+    }
+
+    #[test]
+    fn test_negation() {
+        let mut shell = Shell::new().unwrap();
+        shell.process_commands(&["! false".to_string()]).unwrap();
+        assert_eq!(shell.context.last_status, ExitCode::SUCCESS);
+        shell
+            .process_commands(&["! true | true".to_string()])
+            .unwrap();
+        assert_eq!(shell.context.last_status, ExitCode::FAILURE);
+        shell.process_commands(&["! (exit 3)".to_string()]).unwrap();
+        assert_eq!(shell.context.last_status, ExitCode::SUCCESS);
+    }
+
+    #[test]
+    fn test_and_or() {
+        let mut shell = Shell::new().unwrap();
+        let run = |shell: &mut Shell, line: &str| {
+            shell.process_commands(&[line.to_string()]).unwrap();
+            shell.context.var("X").map(str::to_string)
+        };
+
+        assert_eq!(run(&mut shell, "true && export X=1"), Some("1".into()));
+        assert_eq!(run(&mut shell, "false && export X=2"), Some("1".into()));
+        assert_eq!(shell.context.last_status, ExitCode::FAILURE);
+        assert_eq!(run(&mut shell, "false || export X=3"), Some("3".into()));
+        assert_eq!(run(&mut shell, "true || export X=4"), Some("3".into()));
+        assert_eq!(shell.context.last_status, ExitCode::SUCCESS);
+        // A skipped command leaves the status for the next operator to test
+        assert_eq!(
+            run(&mut shell, "false && export X=5 || export X=6"),
+            Some("6".into())
+        );
+        assert_eq!(run(&mut shell, "export X='a&&b'"), Some("a&&b".into()));
+
+        let split_and_or = |line| {
+            parser::parse(line, &shell.context.aliases).map(|list| {
+                let pipelines = list.items[0].and_or.pipelines.clone();
+                pipelines
+                    .into_iter()
+                    .map(|(and_or, pipeline)| (and_or, pipeline.text))
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(
+            split_and_or("(a && b) || c").unwrap(),
+            [(None, "(a && b)".into()), (Some(AndOr::Or), "c".into())]
+        );
+        assert!(split_and_or("a && || b").is_err());
+        assert!(split_and_or("a &&").is_err());
+    }
+
+    #[test]
+    fn test_heredoc() {
+        let mut shell = Shell::new().unwrap();
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let out = tmp_dir.path().join("out");
+
+        let input = format!(
+            "cat <<EOF >{0}; cat <<'EOF' >>{0}\none; two\nEOF\n'three'\nEOF",
+            out.display()
+        );
+        let commands = shell.transform_input(input);
+        assert_eq!(commands.len(), 2);
+        shell.process_commands(&commands).unwrap();
+        assert_eq!(fs::read_to_string(&out).unwrap(), "one; two\n'three'\n");
+
+        assert_eq!(open_construct("cat <<EOF\nbody"), Some(Open::HereDoc));
+        assert_eq!(open_construct("cat <<EOF |\nbody\nEOF"), Some(Open::Pipe));
+        assert_eq!(open_construct("cat <<EOF\n'\nEOF"), None);
+    }
+
+    #[test]
+    fn test_builtins_in_pipelines() {
+        let mut shell = Shell::new().unwrap();
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        shell.context.cwd = tmp_dir.path().to_path_buf();
+        let run = |shell: &mut Shell, line: &str| {
+            shell.process_commands(&[line.to_string()]).unwrap();
+            shell.context.last_status
+        };
+        let read = |name: &str| fs::read_to_string(tmp_dir.path().join(name)).unwrap();
+
+        assert_eq!(run(&mut shell, "base64 hello > out"), ExitCode::SUCCESS);
+        assert_eq!(read("out"), "aGVsbG8=\n");
+        run(&mut shell, "printf hi | base64 | base64 -d > out");
+        assert_eq!(read("out"), "hi");
+        run(&mut shell, "urlencode 'a b' 2>&1 | cat > out");
+        assert_eq!(read("out"), "a%20b\n");
+
+        run(&mut shell, "read X Y <<< 'p q'");
+        assert_eq!(shell.context.var("X"), Some("p"));
+        run(&mut shell, "printf 'r s\\n' | read X Y");
+        assert_eq!(shell.context.var("Y"), Some("s"));
+        assert_eq!(run(&mut shell, "read X < missing"), ExitCode::FAILURE);
+        // Without a terminal, -s reads like a plain read
+        run(&mut shell, "printf 'pass\\\\word\\n' | read -s -r X");
+        assert_eq!(shell.context.var("X"), Some("pass\\word"));
+        assert_eq!(run(&mut shell, "read -s X < /dev/null"), ExitCode::FAILURE);
+
+        run(&mut shell, "printf 'true\\nfalse\\nfalse\\n' > cmds");
+        assert_eq!(run(&mut shell, "cat cmds | parallel"), ExitCode::new(2));
+        assert_eq!(run(&mut shell, "parallel -j 1 < cmds"), ExitCode::new(2));
+
+        let status = run(&mut shell, "true | hermit-no-such-command");
+        assert_eq!(status, ExitCode::new(127));
+        let status = run(&mut shell, "hermit-no-such-command | true");
+        assert_eq!(status, ExitCode::SUCCESS);
+    }
+
+    #[test]
+    fn test_compound_commands_in_pipelines() {
+        let mut shell = Shell::new().unwrap();
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        shell.context.cwd = tmp_dir.path().to_path_buf();
+        let run = |shell: &mut Shell, line: &str| {
+            shell.process_commands(&[line.to_string()]).unwrap();
+            shell.context.last_status
+        };
+        let read = |name: &str| fs::read_to_string(tmp_dir.path().join(name)).unwrap();
+
+        run(&mut shell, "twice() { printf \"$1$1\"; }");
+        assert_eq!(run(&mut shell, "twice a > out"), ExitCode::SUCCESS);
+        assert_eq!(read("out"), "aa");
+        run(
+            &mut shell,
+            "twice b | base64 -d 2> /dev/null; twice c | cat > out",
+        );
+        assert_eq!(read("out"), "cc");
+        run(&mut shell, "printf hi | { read X; printf \"[$X]\"; } > out");
+        assert_eq!(read("out"), "[hi]");
+        run(&mut shell, "if true; then twice d; fi | cat > out");
+        assert_eq!(read("out"), "dd");
+        run(&mut shell, "(twice e) | base64 | base64 -d > out");
+        assert_eq!(read("out"), "ee");
+
+        // `exit` in a stage ends that stage alone
+        run(&mut shell, "quit() { exit 3; }");
+        assert_eq!(run(&mut shell, "quit | cat"), ExitCode::SUCCESS);
+        assert_eq!(run(&mut shell, "true | quit"), ExitCode::new(3));
+
+        assert_eq!(run(&mut shell, "{ true; } < missing"), ExitCode::FAILURE);
+        assert_eq!(run(&mut shell, "twice f &"), ExitCode::FAILURE);
+    }
+
+    #[test]
+    fn test_expand_tokens_globs() {
+        let mut shell = Shell::new().unwrap();
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        for name in ["a.rs", "b.rs", "c.txt"] {
+            fs::write(tmp_dir.path().join(name), "").unwrap();
+        }
+        shell.context.cwd = tmp_dir.path().to_path_buf();
+
+        let mut expand = |line: &str| {
+            let tokens = shell.parse_args(line).unwrap();
+            words(shell.expand_tokens(tokens).unwrap())
+        };
+        assert_eq!(expand("ls *.rs"), ["ls", "a.rs", "b.rs"]);
+        assert_eq!(
+            expand("ls '*'.rs [bc].* ?.md"),
+            ["ls", "*.rs", "b.rs", "c.txt", "?.md"]
+        );
+        assert_eq!(
+            expand("cat 2>&1 *.txt > *.rs"),
+            ["cat", "<2>&1>", "c.txt", "<>>", "*.rs"]
+        );
+    }
+
+    #[test]
+    fn test_assignments() {
+        let mut shell = Shell::new().unwrap();
+        shell.context.set_var("OUTER", "old");
+        let run =
+            |shell: &mut Shell, line: &str| shell.process_commands(&[line.to_string()]).unwrap();
+
+        run(&mut shell, "A=1 B=\"$A two\"");
+        assert_eq!(shell.context.var("B"), Some("1 two"));
+
+        run(
+            &mut shell,
+            "OUTER=new NEW=x sh -c 'test \"$OUTER$NEW\" = newx'",
+        );
+        assert_eq!(shell.context.last_status, ExitCode::SUCCESS);
+        assert_eq!(shell.context.var("OUTER"), Some("old"));
+        assert_eq!(shell.context.var("NEW"), None);
+
+        run(&mut shell, "'A=2' true");
+        assert_eq!(shell.context.last_status, ExitCode::new(127));
+        assert_eq!(shell.context.var("A"), Some("1"));
+
+        // Only exported variables and those set for the command reach it
+        let exported = |shell: &mut Shell, name: &str| {
+            run(shell, &format!("sh -c 'test \"${{{}+set}}\" = set'", name));
+            shell.context.last_status == ExitCode::SUCCESS
+        };
+        assert!(!exported(&mut shell, "A"));
+        run(&mut shell, "A=3 true");
+        assert!(!exported(&mut shell, "A"));
+        run(&mut shell, "export A");
+        assert!(exported(&mut shell, "A"));
+        run(&mut shell, "unset A; A=4");
+        assert!(!exported(&mut shell, "A"));
+        run(&mut shell, "export C=5");
+        assert!(exported(&mut shell, "C"));
+
+        // Each command of a pipeline gets only its own assignments
+        run(&mut shell, "set -o pipefail");
+        run(&mut shell, "P=1 true | sh -c 'test -z \"$P\"'");
+        assert_eq!(shell.context.last_status, ExitCode::SUCCESS);
+        run(&mut shell, "true | P=2 sh -c 'test \"$P\" = 2' | Q=3 cat");
+        assert_eq!(shell.context.last_status, ExitCode::SUCCESS);
+        run(&mut shell, "P=4 sh -c 'test \"$P\" = 4' | true");
+        assert_eq!(shell.context.last_status, ExitCode::SUCCESS);
+        run(&mut shell, "true | P=5 export");
+        assert_eq!(shell.context.var("P"), None);
+        run(&mut shell, "set +o pipefail");
+    }
+
+    #[test]
+    fn test_if() {
+        let mut shell = Shell::new().unwrap();
+        let mut run = |line: &str| {
+            let commands = shell.transform_input(line.to_string());
+            shell.process_commands(&commands).unwrap();
+            (
+                shell.context.var("R").unwrap_or_default().to_string(),
+                shell.context.last_status,
+            )
+        };
+
+        assert_eq!(run("if true; then R=then; else R=else; fi").0, "then");
+        assert_eq!(run("if false; then R=then; else R=else; fi").0, "else");
+        assert_eq!(
+            run("if false; then R=1; elif false; then R=2; elif true; then R=3; fi").0,
+            "3"
+        );
+        assert_eq!(
+            run("R=; if false; then R=x; fi"),
+            (String::new(), ExitCode::SUCCESS)
+        );
+        assert_eq!(run("if true; then false; fi").1, ExitCode::FAILURE);
+        assert_eq!(
+            run("if if false; then true; fi; then R=inner; fi; R=$R-after").0,
+            "inner-after"
+        );
+        assert_eq!(
+            run("! if false; then true; else false; fi").1,
+            ExitCode::SUCCESS
+        );
+    }
+
+    #[test]
+    fn test_case() {
+        let mut shell = Shell::new().unwrap();
+        let mut run = |line: &str| {
+            let commands = shell.transform_input(line.to_string());
+            shell.process_commands(&commands).unwrap();
+            (
+                shell.context.var("R").unwrap_or_default().to_string(),
+                shell.context.last_status,
+            )
+        };
+
+        let kind = "case $F in *.rs) R=rust;; *.toml|*.lock) R=cargo;; *) R=other;; esac";
+        assert_eq!(run(&format!("F=main.rs; {}", kind)).0, "rust");
+        assert_eq!(run(&format!("F=Cargo.lock; {}", kind)).0, "cargo");
+        assert_eq!(run(&format!("F=README; {}", kind)).0, "other");
+        assert_eq!(
+            run("R=; case x in y) R=y;; esac"),
+            (String::new(), ExitCode::SUCCESS)
+        );
+        assert_eq!(
+            run("case ab in '*') R=quoted;; a?) R=wild;; esac").0,
+            "wild"
+        );
+        assert_eq!(run("P='a*'; case ab in $P) R=1;; *) R=2;; esac").0, "2");
+        assert_eq!(run("case 'a*' in $P) R=literal;; esac").0, "literal");
+        assert_eq!(run("case a in a) false;; esac").1, ExitCode::FAILURE);
+        assert_eq!(
+            run("case a in a) R=first;; a) R=second;; esac; R=$R-after").0,
+            "first-after"
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut shell = Shell::new().unwrap();
+        let mut run = |line: &str| {
+            let commands = shell.transform_input(line.to_string());
+            shell.process_commands(&commands).unwrap();
+            (
+                shell.context.var("R").unwrap_or_default().to_string(),
+                shell.context.last_status,
+            )
+        };
+
+        run("set -- outer; greet() { R=\"hi $1 ($#)\"; }");
+        assert_eq!(run("greet world again").0, "hi world (2)");
+        assert_eq!(run("R=$1").0, "outer");
+
+        run("setx() { local X=inner Y; R=$X-${Y:-unset}; }");
+        assert_eq!(run("X=outer; Y=y; setx").0, "inner-unset");
+        assert_eq!(run("R=$X-$Y").0, "outer-y");
+
+        run("check() { if [ \"$1\" = yes ]; then return 0; fi; R=fell; return 3; R=after; }");
+        assert_eq!(run("R=; check yes"), (String::new(), ExitCode::SUCCESS));
+        assert_eq!(run("check no"), ("fell".to_string(), ExitCode::new(3)));
+        assert_eq!(run("return").1, ExitCode::FAILURE);
+        assert_eq!(run("local X=1").1, ExitCode::FAILURE);
+
+        run("count() { if [ $1 -gt 0 ]; then R=$R$1; count $(($1 - 1)); fi; }");
+        assert_eq!(run("R=; count 3").0, "321");
+        run("forever() { forever; }");
+        assert_eq!(run("forever").1, ExitCode::FAILURE);
+
+        // Builtins come first unless `funcfirst` is on
+        run("pwd() { R=function; }");
+        assert_eq!(run("R=; pwd > /dev/null").0, "");
+        assert_eq!(run("set -o funcfirst; R=; pwd").0, "function");
+        run("set +o funcfirst");
+
+        run("true() { R=$R$1; command true \"$@\"; }");
+        assert_eq!(run("R=; true x"), ("x".to_string(), ExitCode::SUCCESS));
+        assert_eq!(run("R=; command true x").0, "");
+        assert_eq!(run("R=; command true x | cat").1, ExitCode::SUCCESS);
+        assert_eq!(
+            run("command true x > /dev/null"),
+            (String::new(), ExitCode::SUCCESS)
+        );
+        run("unset -f true");
+
+        assert_eq!(run("{ R=group; false; }").1, ExitCode::FAILURE);
+        // Stages before the last of a pipeline run in a copy of the shell
+        assert_eq!(run("R=; greet x | cat"), (String::new(), ExitCode::SUCCESS));
+        assert_eq!(run("R=; cat < /dev/null | greet x").0, "hi x (1)");
+        assert_eq!(run("unset -f greet; R=; greet").0, "");
+        assert!(!shell.context.functions.contains_key("greet"));
+    }
+
+    #[test]
+    fn test_builtin_command() {
+        let mut shell = Shell::new().unwrap();
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        shell.context.cwd = tmp_dir.path().to_path_buf();
+        let mut run = |line: &str| {
+            let commands = shell.transform_input(line.to_string());
+            shell.process_commands(&commands).unwrap();
+            (
+                shell.context.var("R").unwrap_or_default().to_string(),
+                shell.context.last_status,
+            )
+        };
+
+        run("alias pwd='R=alias'; set -o funcfirst; pwd() { R=function; }");
+        assert_eq!(run("R=; pwd").0, "alias");
+        assert_eq!(
+            run("R=; builtin pwd > /dev/null"),
+            (String::new(), ExitCode::SUCCESS)
+        );
+        assert_eq!(run("R=; builtin 'pwd' | cat > /dev/null").0, "");
+        assert_eq!(run("builtin sh -c true").1, ExitCode::FAILURE);
+        assert_eq!(run("builtin hermit-no-such-command").1, ExitCode::FAILURE);
+        assert_eq!(run("builtin").1, ExitCode::SUCCESS);
+
+        run("check() { builtin return 4; R=after; }");
+        assert_eq!(run("R=; check"), (String::new(), ExitCode::new(4)));
+    }
+
+    #[test]
+    fn test_complete_input() {
+        let complete = |lines: &[&str]| {
+            let mut rest = lines[1..].iter().map(|line| line.to_string());
+            complete_input(lines[0].to_string(), false, |_| rest.next())
+        };
+        assert_eq!(
+            complete(&["if a; then # why", "b # because", "fi"]).as_deref(),
+            Some("if a; then  b ; fi")
+        );
+        assert_eq!(
+            complete(&["echo 'one", "# two'"]).as_deref(),
+            Some("echo 'one\n# two'")
+        );
+        assert_eq!(
+            complete(&["cat <<END", "# body", "END"]).as_deref(),
+            Some("cat <<END\n# body\nEND")
+        );
+        assert_eq!(complete(&["ls # all"]).as_deref(), Some("ls # all"));
+        assert_eq!(complete(&["f() {", "a"]), None);
+    }
+
+    #[test]
+    fn test_run_script() {
+        let mut shell = Shell::non_interactive(ShellOptions::default()).unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let script = dir.path().join("script.hsh");
+        fs::write(
+            &script,
+            "#!/usr/bin/env hermit\n\
+             R=\"$# $1\"\n\
+             if [ \"$2\" = two ]; then\n  R=\"$R two\" # matched\nfi\n\
+             false\n",
+        )
+        .unwrap();
+        let args = vec!["one".to_string(), "two".to_string()];
+        assert_eq!(shell.run_script(&script, args), ExitCode::FAILURE);
+        assert_eq!(shell.context.var("R"), Some("2 one two"));
+        assert_eq!(shell.context.frame.name, script.to_string_lossy());
+
+        let missing = dir.path().join("missing.hsh");
+        assert_eq!(shell.run_script(&missing, Vec::new()), ExitCode::new(127));
+    }
+
+    #[test]
+    fn test_run_command_string() {
+        let mut shell = Shell::non_interactive(ShellOptions::default()).unwrap();
+        let args = ["name", "a", "b"].map(String::from).to_vec();
+        let status = shell.run_command_string("R=\"$0 $# $2\"; [ $1 = a ]", args);
+        assert_eq!(status, ExitCode::SUCCESS);
+        assert_eq!(shell.context.var("R"), Some("name 2 b"));
+
+        let status = shell.run_command_string("if false; then\n  true\nfi; false", Vec::new());
+        assert_eq!(status, ExitCode::FAILURE);
+        assert_eq!(shell.context.frame.name, "name");
+        assert!(shell.context.frame.args.is_empty());
+        assert!(!shell.interactive && shell.git_info.is_none());
+    }
+
+    #[test]
+    fn test_source() {
+        let mut shell = Shell::new().unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = dir.path().join("setup.hsh");
+        fs::write(
+            &file,
+            "alias ll='ls -l'\n\
+             f() {\n  R=function\n}\n\
+             if [ $# -gt 0 ]; then\n  R=\"args $*\"\n  return 3\nfi\n\
+             R=end\n",
+        )
+        .unwrap();
+        let loop_file = dir.path().join("loop.hsh");
+        fs::write(&loop_file, "source loop.hsh\n").unwrap();
+        let mut run = |line: String| {
+            let commands = shell.transform_input(line);
+            shell.process_commands(&commands).unwrap();
+            (
+                shell.context.var("R").unwrap_or_default().to_string(),
+                shell.context.last_status,
+            )
+        };
+
+        let file = file.display();
+        assert_eq!(run(format!("source {}", file)).0, "end");
+        assert_eq!(run("f".into()).0, "function");
+        assert_eq!(
+            run(format!("set -- x; . {} a b; R=\"$R/$1\"", file)),
+            ("args a b/x".to_string(), ExitCode::SUCCESS)
+        );
+        assert_eq!(run(format!(". {} a", file)).1, ExitCode::new(3));
+        assert_eq!(run("source".into()).1, ExitCode::new(2));
+        assert_eq!(run("source missing.hsh".into()).1, ExitCode::FAILURE);
+        assert_eq!(
+            run(format!("cd {}; source loop.hsh", dir.path().display())).1,
+            ExitCode::FAILURE
+        );
+        assert!(shell.context.aliases.contains_key("ll"));
+    }
+
+    #[test]
+    fn test_errexit() {
+        let mut shell = Shell::new().unwrap();
+        let mut run = |script: &str| {
+            shell.context.set_var("R", "");
+            let status = shell.run_command_string(script, Vec::new());
+            let r = shell.context.var("R").unwrap_or_default().to_string();
+            (r, status)
+        };
+
+        assert_eq!(
+            run("set -e\nR=a; false; R=b"),
+            ("a".to_string(), ExitCode::FAILURE)
+        );
+        assert_eq!(
+            run("false || R=or; false && R=and; ! true; if false; then :; elif false; then :; fi; R=$R-end"),
+            ("or-end".to_string(), ExitCode::SUCCESS)
+        );
+        assert_eq!(
+            run("f() { R=f; (exit 3); R=after; }\nf; R=never"),
+            ("f".to_string(), ExitCode::new(3))
+        );
+        assert_eq!(
+            run("if { false; R=cond; }; then R=$R-then; fi"),
+            ("cond-then".to_string(), ExitCode::SUCCESS)
+        );
+        assert_eq!(run("set +e; false; R=off").0, "off");
+
+        // At the prompt only the rest of the line is skipped
+        shell.context.options.errexit = true;
+        let commands = shell.transform_input("R=x; false; R=y".to_string());
+        shell.process_commands(&commands).unwrap();
+        shell.process_commands(&["R=$R-z".to_string()]).unwrap();
+        assert_eq!(shell.context.var("R"), Some("x-z"));
+    }
+
+    #[test]
+    fn test_process_substitution() {
+        let mut shell = Shell::new().unwrap();
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let out = tmp_dir.path().join("out");
+        let mut run = |line: String| {
+            shell.process_commands(&[line]).unwrap();
+            shell.context.last_status
+        };
+
+        assert_eq!(run("diff <(echo a) <(echo a)".into()), ExitCode::SUCCESS);
+        assert_eq!(
+            run("diff <(echo a) <(echo b) > /dev/null".into()),
+            ExitCode::FAILURE
+        );
+
+        let line = format!("cat <(printf 'x y\\n' | tr ' ' -) > {}", out.display());
+        assert_eq!(run(line), ExitCode::SUCCESS);
+        assert_eq!(fs::read_to_string(&out).unwrap(), "x-y\n");
+        assert!(shell.substitutions.is_empty());
+    }
+
+    #[test]
+    fn test_subshell_keeps_parent_state() {
+        let mut shell = Shell::new().unwrap();
+        let tmp_dir = tempfile::TempDir::new().unwrap();
+        let target = tmp_dir.path().canonicalize().unwrap();
+        let start = shell.context.cwd.clone();
+
+        let command = format!(
+            "(cd {}; export INNER=1; alias x=y; f() {{ :; }}; exit 4)",
+            target.display()
+        );
+        shell.process_commands(&[command]).unwrap();
+        assert_eq!(shell.context.cwd, start);
+        assert_eq!(shell.context.var("INNER"), None);
+        assert!(!shell.context.aliases.contains_key("x"));
+        assert!(!shell.context.functions.contains_key("f"));
+        assert_eq!(shell.context.last_status, ExitCode::new(4));
+
+        // Nor do the commands of a pipeline before the last
+        shell
+            .process_commands(&["g() { :; } | true; g".to_string()])
+            .unwrap();
+        assert!(!shell.context.functions.contains_key("g"));
+        assert_eq!(shell.context.last_status, ExitCode::new(127));
+
+        shell.process_commands(&["(true)".to_string()]).unwrap();
+        assert_eq!(shell.context.last_status, ExitCode::SUCCESS);
+    }
+
+    #[test]
+    fn test_posix_comments_start_words() {
+        let mut shell = Shell::with_options(ShellOptions {
+            posix: true,
+            ..ShellOptions::default()
+        })
+        .unwrap();
+        assert_eq!(
+            shell.transform_input("echo a#b #c".to_string()),
+            vec!["echo a#b"]
+        );
+        assert_eq!(
+            shell.transform_input("# all".to_string()),
+            Vec::<String>::new()
+        );
+
+        shell.context.options.posix = false;
+        assert_eq!(
+            shell.transform_input("echo a#b".to_string()),
+            vec!["echo a"]
+        );
     }
 }
@@ -0,0 +1,93 @@
+use std::{
+    mem, ptr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn on_resize(_: libc::c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+}
+
+/// Points `signal` at `handler`, returning the action it replaces.
+///
+/// Without SA_RESTART, a blocking read the signal arrives during fails with
+/// EINTR, so the code waiting on it gets a chance to look at the flag.
+fn install(signal: libc::c_int, handler: extern "C" fn(libc::c_int)) -> libc::sigaction {
+    // SAFETY: the handlers only store to an atomic, which is async-signal-safe
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut previous = mem::zeroed();
+        libc::sigaction(signal, &action, &mut previous);
+        previous
+    }
+}
+
+fn restore(signal: libc::c_int, previous: &libc::sigaction) {
+    // SAFETY: puts back the action that was in place before
+    unsafe { libc::sigaction(signal, previous, ptr::null_mut()) };
+}
+
+/// Catches Ctrl-C for as long as it is alive, so a builtin that runs until
+/// interrupted can stop cleanly instead of taking the shell down with it.
+///
+/// Children started meanwhile still get the default action, as exec resets
+/// caught signals.
+pub struct InterruptGuard {
+    previous: libc::sigaction,
+}
+
+impl InterruptGuard {
+    pub fn new() -> Self {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+        Self {
+            previous: install(libc::SIGINT, on_interrupt),
+        }
+    }
+
+    /// Whether Ctrl-C was pressed since the guard was created
+    pub fn interrupted(&self) -> bool {
+        INTERRUPTED.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        restore(libc::SIGINT, &self.previous);
+    }
+}
+
+/// Notices the terminal being resized for as long as it is alive, for code
+/// that draws to the whole screen itself.
+///
+/// The line editor has a handler of its own, which is put back on drop.
+pub struct ResizeGuard {
+    previous: libc::sigaction,
+}
+
+impl ResizeGuard {
+    pub fn new() -> Self {
+        RESIZED.store(false, Ordering::SeqCst);
+        Self {
+            previous: install(libc::SIGWINCH, on_resize),
+        }
+    }
+
+    /// Whether the terminal was resized since the last call
+    pub fn resized(&self) -> bool {
+        RESIZED.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Drop for ResizeGuard {
+    fn drop(&mut self) {
+        restore(libc::SIGWINCH, &self.previous);
+    }
+}
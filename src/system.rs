@@ -0,0 +1,259 @@
+use std::time::Duration;
+
+/// A running process, as `ps` lists it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Process {
+    pub pid: u32,
+    pub uid: u32,
+    /// One letter, like `R` for running or `S` for sleeping
+    pub state: char,
+    /// Resident memory in bytes
+    pub rss: u64,
+    /// CPU time used so far, user and system together
+    pub cpu_time: Duration,
+    pub command: String,
+}
+
+/// Memory and swap in bytes, as `free` shows them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Memory {
+    pub total: u64,
+    pub free: u64,
+    pub available: u64,
+    pub shared: u64,
+    pub buffers_cache: u64,
+    pub swap_total: u64,
+    pub swap_free: u64,
+}
+
+impl Memory {
+    /// Memory programs hold on to, which caches the kernel can reclaim do not count toward
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.available)
+    }
+
+    pub fn swap_used(&self) -> u64 {
+        self.swap_total.saturating_sub(self.swap_free)
+    }
+}
+
+pub use backend::{load_average, memory, processes, uptime};
+
+#[cfg(target_os = "linux")]
+mod backend {
+    use std::{fs, io, os::unix::fs::MetadataExt, time::Duration};
+
+    use super::{Memory, Process};
+
+    /// Every process whose `/proc` entry could be read; ones that exit meanwhile are skipped
+    pub fn processes() -> io::Result<Vec<Process>> {
+        // SAFETY: sysconf only reads system configuration
+        let (ticks, page_size) = unsafe {
+            (
+                libc::sysconf(libc::_SC_CLK_TCK).max(1) as u64,
+                libc::sysconf(libc::_SC_PAGESIZE).max(1) as u64,
+            )
+        };
+
+        let mut processes = Vec::new();
+        for entry in fs::read_dir("/proc")?.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse().ok())
+            else {
+                continue;
+            };
+            let dir = entry.path();
+            let (Ok(meta), Ok(stat)) = (entry.metadata(), fs::read_to_string(dir.join("stat")))
+            else {
+                continue;
+            };
+            let Some(mut process) = parse_stat(pid, &stat, ticks, page_size) else {
+                continue;
+            };
+            process.uid = meta.uid();
+            // Kernel threads have no command line and keep their bracketed name
+            if let Ok(cmdline) = fs::read(dir.join("cmdline")) {
+                let args: Vec<String> = cmdline
+                    .split(|&byte| byte == 0)
+                    .filter(|arg| !arg.is_empty())
+                    .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                    .collect();
+                if !args.is_empty() {
+                    process.command = args.join(" ");
+                }
+            }
+            processes.push(process);
+        }
+        processes.sort_by_key(|process| process.pid);
+        Ok(processes)
+    }
+
+    /// Reads `/proc/<pid>/stat`, whose second field is the name in parentheses
+    pub(super) fn parse_stat(pid: u32, stat: &str, ticks: u64, page_size: u64) -> Option<Process> {
+        let open = stat.find('(')?;
+        // The name may itself contain parentheses and spaces
+        let close = stat.rfind(')')?;
+        let name = &stat[open + 1..close];
+        let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+
+        // Numbered from the state, the third field in proc(5)
+        let field = |n: usize| fields.get(n - 3)?.parse::<u64>().ok();
+        let cpu_ticks = field(14)? + field(15)?;
+        Some(Process {
+            pid,
+            uid: 0,
+            state: fields.first()?.chars().next()?,
+            rss: field(24)? * page_size,
+            cpu_time: Duration::from_millis(cpu_ticks * 1000 / ticks),
+            command: format!("[{}]", name),
+        })
+    }
+
+    pub fn memory() -> io::Result<Memory> {
+        Ok(parse_meminfo(&fs::read_to_string("/proc/meminfo")?))
+    }
+
+    /// Reads the `Name:  1234 kB` lines of `/proc/meminfo`
+    pub(super) fn parse_meminfo(meminfo: &str) -> Memory {
+        let value = |name: &str| {
+            meminfo
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .and_then(|value| value.split_whitespace().next()?.parse::<u64>().ok())
+                .map_or(0, |kib| kib * 1024)
+        };
+        Memory {
+            total: value("MemTotal"),
+            free: value("MemFree"),
+            available: value("MemAvailable"),
+            shared: value("Shmem"),
+            buffers_cache: value("Buffers") + value("Cached") + value("SReclaimable"),
+            swap_total: value("SwapTotal"),
+            swap_free: value("SwapFree"),
+        }
+    }
+
+    pub fn uptime() -> io::Result<Duration> {
+        let uptime = fs::read_to_string("/proc/uptime")?;
+        uptime
+            .split_whitespace()
+            .next()
+            .and_then(|seconds| seconds.parse::<f64>().ok())
+            .map(Duration::from_secs_f64)
+            .ok_or_else(|| io::Error::other("unreadable /proc/uptime"))
+    }
+
+    /// The 1, 5 and 15 minute load averages
+    pub fn load_average() -> io::Result<[f64; 3]> {
+        let loadavg = fs::read_to_string("/proc/loadavg")?;
+        let mut values = loadavg
+            .split_whitespace()
+            .map(|value| value.parse::<f64>().unwrap_or(0.0));
+        Ok([(); 3].map(|_| values.next().unwrap_or(0.0)))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod backend {
+    // Only Linux has `/proc` in a form worth reading without extra libraries
+
+    use std::{io, time::Duration};
+
+    use super::{unsupported, Memory, Process};
+
+    pub fn processes() -> io::Result<Vec<Process>> {
+        Err(unsupported())
+    }
+
+    pub fn memory() -> io::Result<Memory> {
+        Err(unsupported())
+    }
+
+    pub fn uptime() -> io::Result<Duration> {
+        Err(unsupported())
+    }
+
+    pub fn load_average() -> io::Result<[f64; 3]> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unsupported() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "not supported on this system",
+    )
+}
+
+/// Formats a duration like `3 days, 4:05` or `12 min`, as `uptime` does
+pub fn format_uptime(uptime: Duration) -> String {
+    let minutes = uptime.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    let clock = if hours == 0 {
+        format!("{} min", minutes)
+    } else {
+        format!("{}:{:02}", hours, minutes)
+    };
+    match days {
+        0 => clock,
+        1 => format!("1 day, {}", clock),
+        _ => format!("{} days, {}", days, clock),
+    }
+}
+
+/// Formats CPU time as `MM:SS`, or `H:MM:SS` past an hour
+pub fn format_cpu_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::{backend::*, *};
+
+    #[test]
+    fn test_parse_stat() {
+        let stat = "42 (tmux: server) S 1 42 42 0 -1 4194560 1 0 0 0 250 50 0 0 20 0 1 0 \
+                    100 5000000 300 18446744073709551615 0 0 0 0 0 0 0 0 0 0 0 0 17 0 0 0 0 0 0";
+        let process = parse_stat(42, stat, 100, 4096).unwrap();
+        assert_eq!(process.state, 'S');
+        assert_eq!(process.cpu_time, Duration::from_secs(3));
+        assert_eq!(process.rss, 300 * 4096);
+        assert_eq!(process.command, "[tmux: server]");
+
+        assert_eq!(parse_stat(1, "1 (init) S 0", 100, 4096), None);
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let meminfo = "MemTotal:       16000 kB\nMemFree:         4000 kB\n\
+                       MemAvailable:    9000 kB\nBuffers:          500 kB\n\
+                       Cached:          3000 kB\nSwapCached:         0 kB\n\
+                       SwapTotal:       2000 kB\nSwapFree:        1500 kB\n\
+                       Shmem:            200 kB\nSReclaimable:     500 kB\n";
+        let memory = parse_meminfo(meminfo);
+        assert_eq!(memory.total, 16000 * 1024);
+        assert_eq!(memory.buffers_cache, 4000 * 1024);
+        assert_eq!(memory.used(), 7000 * 1024);
+        assert_eq!(memory.swap_used(), 500 * 1024);
+    }
+
+    #[test]
+    fn test_format_durations() {
+        assert_eq!(format_uptime(Duration::from_secs(12 * 60)), "12 min");
+        assert_eq!(
+            format_uptime(Duration::from_secs(3 * 86400 + 4 * 3600 + 5 * 60)),
+            "3 days, 4:05"
+        );
+        assert_eq!(format_cpu_time(Duration::from_secs(75)), "1:15");
+        assert_eq!(format_cpu_time(Duration::from_secs(3725)), "1:02:05");
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Files the user has allowed the shell to load, remembered across sessions.
+///
+/// Each entry pairs a file with a hash of the contents that were approved, so
+/// editing a trusted file asks again before it is loaded.
+#[derive(Debug, Default)]
+pub struct TrustStore {
+    /// Where entries are saved; `None` keeps them for this session only
+    path: Option<PathBuf>,
+    entries: HashMap<PathBuf, u64>,
+}
+
+impl TrustStore {
+    /// Reads the store from the data directory, starting empty if there is none yet
+    pub fn load() -> Self {
+        match dirs::data_dir() {
+            Some(dir) => Self::open(dir.join("hermit").join("trusted")),
+            None => Self::default(),
+        }
+    }
+
+    fn open(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (hash, file) = line.split_once('\t')?;
+                Some((PathBuf::from(file), u64::from_str_radix(hash, 16).ok()?))
+            })
+            .collect();
+
+        Self {
+            path: Some(path),
+            entries,
+        }
+    }
+
+    pub fn is_trusted(&self, file: &Path, contents: &str) -> bool {
+        self.entries.get(file) == Some(&hash(contents))
+    }
+
+    /// Trusts `file` as long as it keeps its current `contents`
+    pub fn trust(&mut self, file: &Path, contents: &str) -> io::Result<()> {
+        self.entries.insert(file.to_path_buf(), hash(contents));
+        self.save()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut out = fs::File::create(path)?;
+        for (file, hash) in &self.entries {
+            writeln!(out, "{:016x}\t{}", hash, file.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// FNV-1a, which unlike the standard hasher gives the same value in every build
+fn hash(contents: &str) -> u64 {
+    contents.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_trust_survives_reload_until_contents_change() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("hermit").join("trusted");
+        let file = Path::new("/projects/app/.envrc");
+
+        let mut store = TrustStore::open(path.clone());
+        assert!(!store.is_trusted(file, "export A=1"));
+        store.trust(file, "export A=1").unwrap();
+
+        let store = TrustStore::open(path);
+        assert!(store.is_trusted(file, "export A=1"));
+        assert!(!store.is_trusted(file, "export A=2"));
+        assert!(!store.is_trusted(Path::new("/other/.envrc"), "export A=1"));
+    }
+}
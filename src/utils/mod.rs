@@ -1,20 +1,17 @@
-use colored::{ColoredString, Colorize};
-use std::{
-    fs::Metadata,
-    time::{SystemTime, UNIX_EPOCH},
-};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use unicode_width::UnicodeWidthStr;
 
 pub fn term_width() -> usize {
     term_size::dimensions().map_or(80, |(w, _)| w)
 }
 
-pub fn colorize_file_name(file_name: &str, metadata: &Metadata) -> ColoredString {
-    match (metadata.is_dir(), file_name.starts_with('.')) {
-        (true, true) => file_name.blue().dimmed(),
-        (true, false) => file_name.blue(),
-        (false, true) => file_name.dimmed(),
-        (false, false) => file_name.normal(),
-    }
+pub fn term_height() -> usize {
+    term_size::dimensions().map_or(24, |(_, h)| h)
+}
+
+/// Number of terminal columns `text` occupies; `text` must not contain escape sequences
+pub fn display_width(text: &str) -> usize {
+    text.width()
 }
 
 pub fn format_size(size: u64) -> String {
@@ -48,3 +45,122 @@ pub fn format_time(time: u64) -> String {
         format!("{}d", time_diff / 86400)
     }
 }
+
+/// Parses a duration such as `30s`, `1.5m`, `250ms` or `2h`; a bare number is seconds
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        "d" => number * 86400.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// Number of single-character edits (including swapping neighbours) needed to turn `a` into `b`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            dist[i][j] = (dist[i - 1][j] + 1)
+                .min(dist[i][j - 1] + 1)
+                .min(dist[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                dist[i][j] = dist[i][j].min(dist[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    dist[a.len()][b.len()]
+}
+
+/// Quotes `word` so it reads back as a single shell word, leaving plain words as they are
+pub fn quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_./=:,+@%~".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Finds the candidate closest to `name`, if any is close enough to be a likely typo.
+///
+/// `name` itself is never suggested: a command that exists but failed to run,
+/// like a broken link on the `PATH`, is still listed among the candidates.
+pub fn closest_match<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).clamp(1, 2);
+    candidates
+        .filter(|&candidate| candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("1.5m"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(parse_duration("5x"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("ls"), "ls");
+        assert_eq!(quote("~/src/main.rs"), "~/src/main.rs");
+        assert_eq!(quote("two words"), "'two words'");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("git", "git"), 0);
+        assert_eq!(edit_distance("gti", "git"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("carg", "cargo"), 1);
+        assert_eq!(edit_distance("", "ls"), 2);
+    }
+
+    #[test]
+    fn test_closest_match() {
+        let candidates = ["cargo", "cat", "cd", "git"];
+        assert_eq!(closest_match("carg", candidates.into_iter()), Some("cargo"));
+        assert_eq!(closest_match("gti", candidates.into_iter()), Some("git"));
+        assert_eq!(closest_match("python", candidates.into_iter()), None);
+        assert_eq!(closest_match("cat", candidates.into_iter()), None);
+        assert_eq!(closest_match("cd", ["cd", "cp"].into_iter()), Some("cp"));
+    }
+}